@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::email::Email;
+use crate::state;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityKind {
+    Read,
+    Replied,
+}
+
+/// One thing the user did with an email during a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub at: DateTime<Utc>,
+    pub kind: ActivityKind,
+    pub account: String,
+    pub sender: String,
+    pub subject: String,
+}
+
+/// Append-only log of session activity, written only when enabled in the config.
+pub struct ActivityLog {
+    enabled: bool,
+    path: Option<PathBuf>,
+}
+
+fn log_path() -> Option<PathBuf> {
+    state::state_dir().map(|dir| dir.join("activity.jsonl"))
+}
+
+impl ActivityLog {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            path: log_path(),
+        }
+    }
+
+    pub fn record(&self, kind: ActivityKind, email: &Email) -> Result<()> {
+        let (true, Some(path)) = (self.enabled, &self.path) else {
+            return Ok(());
+        };
+
+        let entry = ActivityEntry {
+            at: Utc::now(),
+            kind,
+            account: email.account.clone(),
+            sender: email.sender.clone(),
+            subject: email.subject.clone(),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// Reads every recorded entry, skipping lines that can't be parsed.
+pub fn read_entries() -> Result<Vec<ActivityEntry>> {
+    let Some(path) = log_path().filter(|path| path.exists()) else {
+        return Ok(Vec::new());
+    };
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Writes one CSV row per local day with the first and last activity, counts,
+/// and the subjects worked on, for reconstructing timesheets.
+pub fn export_csv(entries: &[ActivityEntry], out: &mut impl Write) -> Result<()> {
+    let mut days: BTreeMap<NaiveDate, Vec<&ActivityEntry>> = BTreeMap::new();
+    for entry in entries {
+        let day = entry.at.with_timezone(&Local).date_naive();
+        days.entry(day).or_default().push(entry);
+    }
+
+    writeln!(out, "date,first,last,read,replied,subjects")?;
+    for (day, mut day_entries) in days {
+        day_entries.sort_by_key(|entry| entry.at);
+        let first = day_entries[0].at.with_timezone(&Local);
+        let last = day_entries[day_entries.len() - 1].at.with_timezone(&Local);
+        let count = |kind| day_entries.iter().filter(|e| e.kind == kind).count();
+
+        let mut subjects: Vec<&str> = Vec::new();
+        for entry in &day_entries {
+            if !subjects.contains(&entry.subject.as_str()) {
+                subjects.push(&entry.subject);
+            }
+        }
+
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            day.format("%Y-%m-%d"),
+            first.format("%H:%M"),
+            last.format("%H:%M"),
+            count(ActivityKind::Read),
+            count(ActivityKind::Replied),
+            csv_field(&subjects.join("; "))
+        )?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field when it contains separators or quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(at: DateTime<Local>, kind: ActivityKind, subject: &str) -> ActivityEntry {
+        ActivityEntry {
+            at: at.with_timezone(&Utc),
+            kind,
+            account: "work".to_string(),
+            sender: "boss@company.com".to_string(),
+            subject: subject.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_export_csv_groups_by_day() {
+        let entries = vec![
+            entry(
+                Local.with_ymd_and_hms(2024, 4, 2, 9, 15, 0).unwrap(),
+                ActivityKind::Read,
+                "Budget, final",
+            ),
+            entry(
+                Local.with_ymd_and_hms(2024, 4, 1, 16, 30, 0).unwrap(),
+                ActivityKind::Replied,
+                "Kickoff",
+            ),
+            entry(
+                Local.with_ymd_and_hms(2024, 4, 1, 8, 5, 0).unwrap(),
+                ActivityKind::Read,
+                "Kickoff",
+            ),
+        ];
+
+        let mut out = Vec::new();
+        export_csv(&entries, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+
+        assert_eq!(
+            csv,
+            "date,first,last,read,replied,subjects\n\
+             2024-04-01,08:05,16:30,1,1,Kickoff\n\
+             2024-04-02,09:15,09:15,1,0,\"Budget, final\"\n"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+/// Keyring service and user the generated cache key is stored under.
+const KEYRING_SERVICE: &str = "mail-tui";
+const KEYRING_USER: &str = "cache-key";
+
+/// Bytes of the nonce stored in front of every ciphertext.
+const NONCE_LEN: usize = 12;
+
+/// Encrypts cached values with ChaCha20-Poly1305, storing them as base64 text.
+pub struct Cipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// Derives the key from a passphrase with Argon2id.
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow!("Failed to derive the cache key: {}", e))?;
+        Ok(Self {
+            cipher: ChaCha20Poly1305::new(&key),
+        })
+    }
+
+    /// Uses the key kept in the OS keyring, generating and storing one the first time.
+    /// Returns whether the key is new, so data encrypted before can't be read.
+    pub fn from_keyring() -> Result<(Self, bool)> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+            .context("Failed to open the OS keyring")?;
+        let (key, new) = match entry.get_password() {
+            Ok(encoded) => {
+                let bytes = STANDARD
+                    .decode(encoded)
+                    .context("The cache key in the OS keyring is corrupt")?;
+                if bytes.len() != Key::default().len() {
+                    return Err(anyhow!("The cache key in the OS keyring is corrupt"));
+                }
+                (*Key::from_slice(&bytes), false)
+            }
+            Err(keyring::Error::NoEntry) => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                entry
+                    .set_password(&STANDARD.encode(key))
+                    .context("Failed to store the cache key in the OS keyring")?;
+                (key, true)
+            }
+            Err(e) => return Err(e).context("Failed to read the cache key from the OS keyring"),
+        };
+        Ok((
+            Self {
+                cipher: ChaCha20Poly1305::new(&key),
+            },
+            new,
+        ))
+    }
+
+    pub fn generate_salt() -> Vec<u8> {
+        ChaCha20Poly1305::generate_nonce(&mut OsRng).to_vec()
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| anyhow!("Failed to encrypt a cached value"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    pub fn decrypt(&self, encoded: &str) -> Result<String> {
+        let sealed = STANDARD
+            .decode(encoded)
+            .context("A cached value is not encrypted")?;
+        if sealed.len() < NONCE_LEN {
+            return Err(anyhow!("A cached value is not encrypted"));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt a cached value, the key is wrong"))?;
+        String::from_utf8(plaintext).context("A decrypted cached value is not text")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passphrase_round_trip() {
+        let salt = Cipher::generate_salt();
+        let cipher = Cipher::from_passphrase("correct horse", &salt).unwrap();
+        let sealed = cipher.encrypt("Quarterly numbers").unwrap();
+
+        assert_ne!(sealed, "Quarterly numbers");
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), "Quarterly numbers");
+        let wrong = Cipher::from_passphrase("wrong horse", &salt).unwrap();
+        assert!(wrong.decrypt(&sealed).is_err());
+    }
+}
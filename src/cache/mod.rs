@@ -0,0 +1,664 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{CacheConfig, CacheEncryption};
+use crate::email::{Attachment, BodyFormat, BodyHandle, Email, Header, Importance, Meeting};
+
+mod crypto;
+
+use crypto::Cipher;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS emails (
+        account TEXT NOT NULL,
+        id TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        sender TEXT NOT NULL,
+        recipients TEXT NOT NULL,
+        cc TEXT NOT NULL,
+        date INTEGER NOT NULL,
+        read INTEGER NOT NULL,
+        meeting_start INTEGER,
+        meeting_end INTEGER,
+        meeting_location TEXT,
+        meeting_organizer TEXT,
+        PRIMARY KEY (account, id)
+    );
+    CREATE TABLE IF NOT EXISTS bodies (
+        account TEXT NOT NULL,
+        email_id TEXT NOT NULL,
+        format TEXT NOT NULL,
+        content TEXT NOT NULL,
+        PRIMARY KEY (account, email_id, format),
+        FOREIGN KEY (account, email_id) REFERENCES emails (account, id) ON DELETE CASCADE
+    );
+    CREATE TABLE IF NOT EXISTS attachments (
+        account TEXT NOT NULL,
+        email_id TEXT NOT NULL,
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        content_type TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        PRIMARY KEY (account, email_id, id),
+        FOREIGN KEY (account, email_id) REFERENCES emails (account, id) ON DELETE CASCADE
+    );
+";
+
+/// Changes to caches created by earlier versions, applied in order. The cache's
+/// `user_version` counts how many have been applied.
+const MIGRATIONS: &[&str] = &[
+    "ALTER TABLE emails ADD COLUMN flagged INTEGER NOT NULL DEFAULT 0;",
+    "CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+    "ALTER TABLE emails ADD COLUMN content_type TEXT NOT NULL DEFAULT 'text/plain';",
+    "ALTER TABLE emails ADD COLUMN headers TEXT;",
+    "ALTER TABLE emails ADD COLUMN thread TEXT;",
+    "ALTER TABLE emails ADD COLUMN size INTEGER NOT NULL DEFAULT 0;
+     ALTER TABLE emails ADD COLUMN importance INTEGER NOT NULL DEFAULT 1;",
+    // Encrypted caches kept their envelopes in plain text, and refill sealed
+    "DELETE FROM emails WHERE EXISTS
+         (SELECT 1 FROM meta WHERE key = 'encryption' AND value != 'none');",
+];
+
+/// Value encrypted into the `meta` table to tell a wrong key from a right one.
+const KEY_CHECK: &str = "mail-tui";
+
+/// Environment variable holding the passphrase of a passphrase-encrypted cache.
+pub const PASSPHRASE_VAR: &str = "MAIL_TUI_CACHE_PASSPHRASE";
+
+/// Emails evicted at a time while the cache is over its size limit.
+const EVICTION_BATCH: i64 = 50;
+
+/// Directory for cache files, `~/.cache/mail-tui` on Linux.
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("mail-tui"))
+}
+
+/// Fetched emails kept in SQLite, so mail can be read before a sync finishes or
+/// without a network at all.
+pub struct Cache {
+    conn: Connection,
+    config: CacheConfig,
+    /// Set when the mail is encrypted at rest
+    cipher: Option<Cipher>,
+}
+
+impl Cache {
+    /// Opens the cache in the cache directory, or returns `None` if there is none.
+    pub fn open(config: &CacheConfig) -> Result<Option<Self>> {
+        let Some(dir) = cache_dir() else {
+            return Ok(None);
+        };
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        Self::open_at(&dir.join("cache.sqlite"), config).map(Some)
+    }
+
+    pub fn open_at(path: &Path, config: &CacheConfig) -> Result<Self> {
+        let conn =
+            Connection::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Self::init(conn, config.clone())
+    }
+
+    fn init(mut conn: Connection, config: CacheConfig) -> Result<Self> {
+        // The TUI and `sync --daemon` may write at the same time
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create the cache tables")?;
+
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        // Each step lands whole with its version, so a failed one is retried next time
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)
+                .and_then(|_| tx.execute_batch(&format!("PRAGMA user_version = {}", i + 1)))
+                .and_then(|_| tx.commit())
+                .context("Failed to upgrade the cache")?;
+        }
+
+        let mut cache = Self {
+            conn,
+            config,
+            cipher: None,
+        };
+        cache.setup_encryption()?;
+        Ok(cache)
+    }
+
+    /// Sets up the cipher for the configured encryption. Switching encryption on
+    /// or off, or to a new key, empties the cache as it can't be read any more.
+    fn setup_encryption(&mut self) -> Result<()> {
+        let mode = self.config.encryption;
+        if self.meta("encryption")?.as_deref() != Some(mode.as_str()) {
+            self.clear()?;
+            self.conn
+                .execute("DELETE FROM meta WHERE key IN ('salt', 'key_check')", [])?;
+            self.set_meta("encryption", mode.as_str())?;
+        }
+
+        let cipher = match mode {
+            CacheEncryption::None => return Ok(()),
+            CacheEncryption::Passphrase => {
+                let passphrase = std::env::var(PASSPHRASE_VAR)
+                    .map_err(|_| anyhow!("Set {} to open the encrypted cache", PASSPHRASE_VAR))?;
+                let salt = match self.meta("salt")? {
+                    Some(salt) => STANDARD.decode(salt).context("The cache salt is corrupt")?,
+                    None => {
+                        let salt = Cipher::generate_salt();
+                        self.set_meta("salt", &STANDARD.encode(&salt))?;
+                        salt
+                    }
+                };
+                Cipher::from_passphrase(&passphrase, &salt)?
+            }
+            CacheEncryption::Keyring => {
+                let (cipher, new) = Cipher::from_keyring()?;
+                if new {
+                    self.clear()?;
+                }
+                cipher
+            }
+        };
+
+        match self.meta("key_check")? {
+            Some(check) if cipher.decrypt(&check).ok().as_deref() == Some(KEY_CHECK) => {}
+            Some(_) if mode == CacheEncryption::Passphrase => {
+                bail!("Wrong passphrase for the encrypted cache")
+            }
+            _ => {
+                self.clear()?;
+                self.set_meta("key_check", &cipher.encrypt(KEY_CHECK)?)?;
+            }
+        }
+        self.cipher = Some(cipher);
+        Ok(())
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Removes every cached email, along with their bodies and attachments.
+    fn clear(&self) -> Result<()> {
+        self.conn.execute("DELETE FROM emails", [])?;
+        Ok(())
+    }
+
+    /// Every cached email with its attachments and loaded bodies, in the order
+    /// they were first cached.
+    pub fn load(&self) -> Result<Vec<Email>> {
+        let mut attachments: HashMap<(String, String), Vec<Attachment>> = HashMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT account, email_id, id, name, content_type, size FROM attachments
+             ORDER BY rowid",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                (row.get(0)?, row.get(1)?),
+                Attachment {
+                    id: row.get(2)?,
+                    name: row.get(3)?,
+                    content_type: row.get(4)?,
+                    size: row.get::<_, i64>(5)? as u64,
+                },
+            ))
+        })?;
+        for row in rows {
+            let (key, mut attachment) = row?;
+            attachment.name = unseal(self.cipher.as_ref(), attachment.name)?;
+            attachments.entry(key).or_default().push(attachment);
+        }
+
+        let mut bodies: HashMap<(String, String), BodyHandle> = HashMap::new();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT account, email_id, format, content FROM bodies")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                (row.get(0)?, row.get(1)?),
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        for row in rows {
+            let (key, format, content) = row?;
+            if let Some(format) = BodyFormat::parse(&format) {
+                let content = unseal(self.cipher.as_ref(), content)?;
+                bodies.entry(key).or_default().insert(format, content);
+            }
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT account, id, subject, sender, recipients, cc, date, read, flagged,
+                    meeting_start, meeting_end, meeting_location, meeting_organizer, content_type,
+                    headers, thread, size, importance
+             FROM emails ORDER BY rowid",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let meeting = match (
+                row.get::<_, Option<i64>>(9)?,
+                row.get::<_, Option<i64>>(10)?,
+            ) {
+                (Some(start), Some(end)) => Some(Meeting {
+                    start: timestamp(start),
+                    end: timestamp(end),
+                    location: row.get::<_, Option<String>>(11)?.unwrap_or_default(),
+                    organizer: row.get::<_, Option<String>>(12)?.unwrap_or_default(),
+                }),
+                _ => None,
+            };
+            // The recipients and headers are filled in once decrypted
+            let sealed: (String, String, Option<String>) = (row.get(4)?, row.get(5)?, row.get(14)?);
+            let email = Email {
+                account: row.get(0)?,
+                id: row.get(1)?,
+                subject: row.get(2)?,
+                sender: row.get(3)?,
+                to: Vec::new(),
+                cc: Vec::new(),
+                date: timestamp(row.get(6)?),
+                body: BodyHandle::default(),
+                attachments: Vec::new(),
+                read: row.get(7)?,
+                flagged: row.get(8)?,
+                content_type: row.get(13)?,
+                headers: None,
+                size: row.get::<_, i64>(16)? as u64,
+                importance: importance(row.get(17)?),
+                thread: row
+                    .get::<_, Option<String>>(15)?
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+                meeting,
+            };
+            Ok((email, sealed))
+        })?;
+
+        let cipher = self.cipher.as_ref();
+        let mut emails = Vec::new();
+        for row in rows {
+            let (mut email, (to, cc, headers)) = row?;
+            email.subject = unseal(cipher, email.subject)?;
+            email.sender = unseal(cipher, email.sender)?;
+            email.to = split_addresses(&unseal(cipher, to)?);
+            email.cc = split_addresses(&unseal(cipher, cc)?);
+            if let Some(headers) = headers {
+                email.headers = serde_json::from_str(&unseal(cipher, headers)?).ok();
+            }
+            if let Some(meeting) = email.meeting.as_mut() {
+                meeting.location = unseal(cipher, std::mem::take(&mut meeting.location))?;
+                meeting.organizer = unseal(cipher, std::mem::take(&mut meeting.organizer))?;
+            }
+            let key = (email.account.clone(), email.id.clone());
+            email.attachments = attachments.remove(&key).unwrap_or_default();
+            email.body = bodies.remove(&key).unwrap_or_default();
+            emails.push(email);
+        }
+        Ok(emails)
+    }
+
+    /// Makes the cached mail of `account` match what was just fetched from the
+    /// server, keeping bodies cached earlier for emails that are still there, then
+    /// evicts mail past the cache limits.
+    pub fn store_account(&mut self, account: &str, emails: &[Email]) -> Result<()> {
+        let cutoff = self.age_cutoff();
+        let emails: Vec<&Email> = emails
+            .iter()
+            .filter(|email| email.flagged || cutoff.is_none_or(|cutoff| email.date >= cutoff))
+            .collect();
+        let cipher = self.cipher.as_ref();
+        let tx = self.conn.transaction()?;
+        {
+            let mut cached_ids = tx.prepare("SELECT id FROM emails WHERE account = ?1")?;
+            let stale: Vec<String> = cached_ids
+                .query_map([account], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|id| !emails.iter().any(|email| &email.id == id))
+                .collect();
+            for id in stale {
+                tx.execute(
+                    "DELETE FROM emails WHERE account = ?1 AND id = ?2",
+                    params![account, id],
+                )?;
+            }
+
+            for email in &emails {
+                let meeting = email.meeting.as_ref();
+                tx.execute(
+                    "INSERT INTO emails (account, id, subject, sender, recipients, cc, date, read,
+                         flagged, meeting_start, meeting_end, meeting_location, meeting_organizer,
+                         content_type, headers, thread, size, importance)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                             ?16, ?17, ?18)
+                     ON CONFLICT (account, id) DO UPDATE SET
+                         subject = excluded.subject, sender = excluded.sender,
+                         recipients = excluded.recipients, cc = excluded.cc,
+                         date = excluded.date, read = excluded.read, flagged = excluded.flagged,
+                         meeting_start = excluded.meeting_start,
+                         meeting_end = excluded.meeting_end,
+                         meeting_location = excluded.meeting_location,
+                         meeting_organizer = excluded.meeting_organizer,
+                         content_type = excluded.content_type,
+                         headers = COALESCE(excluded.headers, emails.headers),
+                         thread = excluded.thread, size = excluded.size,
+                         importance = excluded.importance",
+                    params![
+                        account,
+                        email.id,
+                        seal(cipher, &email.subject)?,
+                        seal(cipher, &email.sender)?,
+                        seal(cipher, &email.to.join("\n"))?,
+                        seal(cipher, &email.cc.join("\n"))?,
+                        email.date.timestamp(),
+                        email.read,
+                        email.flagged,
+                        meeting.map(|m| m.start.timestamp()),
+                        meeting.map(|m| m.end.timestamp()),
+                        meeting.map(|m| seal(cipher, &m.location)).transpose()?,
+                        meeting.map(|m| seal(cipher, &m.organizer)).transpose()?,
+                        email.content_type,
+                        email
+                            .headers
+                            .as_ref()
+                            .map(|headers| seal(cipher, &serde_json::to_string(headers)?))
+                            .transpose()?,
+                        serde_json::to_string(&email.thread)?,
+                        email.size as i64,
+                        email.importance as i64,
+                    ],
+                )?;
+
+                tx.execute(
+                    "DELETE FROM attachments WHERE account = ?1 AND email_id = ?2",
+                    params![account, email.id],
+                )?;
+                for attachment in &email.attachments {
+                    tx.execute(
+                        "INSERT INTO attachments (account, email_id, id, name, content_type, size)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        params![
+                            account,
+                            email.id,
+                            attachment.id,
+                            seal(cipher, &attachment.name)?,
+                            attachment.content_type,
+                            attachment.size as i64,
+                        ],
+                    )?;
+                }
+
+                for (format, content) in email.body.loaded() {
+                    Self::insert_body(&tx, cipher, account, &email.id, format, content)?;
+                }
+            }
+        }
+        tx.commit()?;
+        self.prune()?;
+        Ok(())
+    }
+
+    /// Evicts unflagged emails older than the age limit, then the oldest ones
+    /// until the cache fits its size limit, returning how many were evicted.
+    pub fn prune(&self) -> Result<usize> {
+        let mut evicted = 0;
+        if let Some(cutoff) = self.age_cutoff() {
+            evicted += self.conn.execute(
+                "DELETE FROM emails WHERE flagged = 0 AND date < ?1",
+                [cutoff.timestamp()],
+            )?;
+        }
+
+        if let Some(max_size) = self.config.max_size_mb.map(|mb| mb * 1024 * 1024) {
+            while self.size()? > max_size {
+                let batch = self.conn.execute(
+                    "DELETE FROM emails WHERE rowid IN
+                         (SELECT rowid FROM emails WHERE flagged = 0 ORDER BY date LIMIT ?1)",
+                    [EVICTION_BATCH],
+                )?;
+                if batch == 0 {
+                    break;
+                }
+                evicted += batch;
+            }
+        }
+        Ok(evicted)
+    }
+
+    /// Bytes in use by cached data, leaving out pages freed by evictions.
+    pub fn size(&self) -> Result<u64> {
+        let pages: i64 = self.conn.query_row(
+            "SELECT (SELECT page_count FROM pragma_page_count())
+                  - (SELECT freelist_count FROM pragma_freelist_count())",
+            [],
+            |row| row.get(0),
+        )?;
+        let page_size: i64 = self
+            .conn
+            .query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        Ok((pages * page_size) as u64)
+    }
+
+    /// Gives the space freed by evictions back to the file system.
+    pub fn vacuum(&self) -> Result<()> {
+        self.conn.execute_batch("VACUUM")?;
+        Ok(())
+    }
+
+    fn age_cutoff(&self) -> Option<DateTime<Utc>> {
+        self.config
+            .max_age_days
+            .map(|days| Utc::now() - ChronoDuration::days(days as i64))
+    }
+
+    /// Caches a body loaded after the email itself, ignoring emails that aren't
+    /// cached such as server search results.
+    pub fn store_body(
+        &self,
+        account: &str,
+        id: &str,
+        format: BodyFormat,
+        content: &str,
+    ) -> Result<()> {
+        Self::insert_body(
+            &self.conn,
+            self.cipher.as_ref(),
+            account,
+            id,
+            format,
+            content,
+        )
+    }
+
+    /// Keeps the headers fetched for an email, which later syncs don't overwrite.
+    pub fn store_headers(&self, account: &str, id: &str, headers: &[Header]) -> Result<()> {
+        self.conn.execute(
+            "UPDATE emails SET headers = ?3 WHERE account = ?1 AND id = ?2",
+            params![
+                account,
+                id,
+                seal(self.cipher.as_ref(), &serde_json::to_string(headers)?)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn insert_body(
+        conn: &Connection,
+        cipher: Option<&Cipher>,
+        account: &str,
+        id: &str,
+        format: BodyFormat,
+        content: &str,
+    ) -> Result<()> {
+        let sealed = seal(cipher, content)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO bodies (account, email_id, format, content)
+             SELECT ?1, ?2, ?3, ?4
+             WHERE EXISTS (SELECT 1 FROM emails WHERE account = ?1 AND id = ?2)",
+            params![account, id, format.as_str(), sealed],
+        )?;
+        Ok(())
+    }
+}
+
+/// Encrypts a value to be cached, when the cache is encrypted.
+fn seal(cipher: Option<&Cipher>, value: &str) -> Result<String> {
+    match cipher {
+        Some(cipher) => cipher.encrypt(value),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Decrypts a cached value sealed with `seal`.
+fn unseal(cipher: Option<&Cipher>, value: String) -> Result<String> {
+    match cipher {
+        Some(cipher) => cipher.decrypt(&value),
+        None => Ok(value),
+    }
+}
+
+fn timestamp(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap_or_default()
+}
+
+/// Reads back the importance stored as its position in `Importance`.
+fn importance(level: i64) -> Importance {
+    match level {
+        0 => Importance::Low,
+        2 => Importance::High,
+        _ => Importance::Normal,
+    }
+}
+
+fn split_addresses(joined: &str) -> Vec<String> {
+    joined
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(id: &str, body: &str) -> Email {
+        Email {
+            subject: format!("Subject {}", id),
+            sender: "boss@company.com".to_string(),
+            to: vec!["me@company.com".to_string(), "team@company.com".to_string()],
+            date: timestamp(1_717_000_000),
+            body: BodyHandle::from_text(body),
+            attachments: vec![Attachment {
+                id: format!("{}-1", id),
+                name: "report.pdf".to_string(),
+                content_type: "application/pdf".to_string(),
+                size: 1024,
+            }],
+            ..Email::test(id)
+        }
+    }
+
+    #[test]
+    fn test_store_account_reconciles_and_keeps_bodies() {
+        let mut cache = Cache::init(
+            Connection::open_in_memory().unwrap(),
+            CacheConfig::default(),
+        )
+        .unwrap();
+        cache
+            .store_account("work", &[email("1", "first"), email("2", "second")])
+            .unwrap();
+        cache
+            .store_body("work", "1", BodyFormat::Html, "<p>first</p>")
+            .unwrap();
+
+        // The server no longer has email 2 and sent email 1 without its body
+        let mut refreshed = email("1", "");
+        refreshed.body = BodyHandle::default();
+        cache.store_account("work", &[refreshed]).unwrap();
+
+        let emails = cache.load().unwrap();
+        assert_eq!(emails.len(), 1);
+        assert_eq!(emails[0].to.len(), 2);
+        assert_eq!(emails[0].attachments[0].name, "report.pdf");
+        assert_eq!(emails[0].body.text(), "first");
+        assert_eq!(emails[0].body.get(BodyFormat::Html), Some("<p>first</p>"));
+    }
+
+    #[test]
+    fn test_encrypted_cache_seals_envelopes() {
+        let mut cache = Cache::init(
+            Connection::open_in_memory().unwrap(),
+            CacheConfig::default(),
+        )
+        .unwrap();
+        cache.cipher =
+            Some(Cipher::from_passphrase("correct horse", &Cipher::generate_salt()).unwrap());
+        let mut sent = email("1", "first");
+        sent.headers = Some(vec![Header {
+            name: "X-Mailer".to_string(),
+            value: "Outlook".to_string(),
+        }]);
+        cache.store_account("work", &[sent]).unwrap();
+
+        let stored: (String, String, String, String) = cache
+            .conn
+            .query_row(
+                "SELECT subject, sender, recipients, headers FROM emails",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert!(!stored.0.contains("Subject"));
+        assert!(!stored.1.contains("boss@"));
+        assert!(!stored.2.contains("me@"));
+        assert!(!stored.3.contains("Outlook"));
+
+        let emails = cache.load().unwrap();
+        assert_eq!(emails[0].subject, "Subject 1");
+        assert_eq!(emails[0].to, vec!["me@company.com", "team@company.com"]);
+        assert_eq!(emails[0].headers.as_ref().unwrap()[0].value, "Outlook");
+        assert_eq!(emails[0].attachments[0].name, "report.pdf");
+    }
+
+    #[test]
+    fn test_prune_evicts_old_unflagged_mail() {
+        let limits = CacheConfig {
+            max_age_days: Some(30),
+            ..CacheConfig::default()
+        };
+        let mut cache = Cache::init(Connection::open_in_memory().unwrap(), limits).unwrap();
+
+        let mut recent = email("1", "recent");
+        recent.date = Utc::now();
+        let old = email("2", "old");
+        let mut flagged = email("3", "old but flagged");
+        flagged.flagged = true;
+        cache
+            .store_account("work", &[recent, old, flagged])
+            .unwrap();
+
+        let ids: Vec<String> = cache.load().unwrap().into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec!["1", "3"]);
+    }
+}
@@ -1,42 +1,709 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::format::StrftimeItems;
+use chrono::{DateTime, Local, Utc};
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{table, value, ArrayOfTables, DocumentMut, Item, Table};
+use tui::style::Color;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
-    pub exchange: ExchangeConfig,
+    /// Single-account setup, kept for configs written before `[[accounts]]` existed
+    pub exchange: Option<ExchangeConfig>,
+    #[serde(default)]
+    pub accounts: Vec<AccountConfig>,
+    #[serde(default)]
+    pub activity: ActivityConfig,
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub views: Vec<ViewConfig>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Commands bodies of a MIME type are piped through to render them, by type
+    #[serde(default)]
+    pub renderers: HashMap<String, String>,
+    #[serde(default)]
+    pub downloads: DownloadConfig,
+    #[serde(default)]
+    pub prefetch: PrefetchConfig,
+    #[serde(default)]
+    pub compose: ComposeConfig,
+    /// Named lists of addresses, searched for with `from:@name` and `to:@name`
+    #[serde(default)]
+    pub groups: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub list: ListConfig,
+    #[serde(default)]
+    pub images: ImageConfig,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    /// Searches saved with `:save-search`, listed with `'`
+    #[serde(default)]
+    pub searches: Vec<SavedSearch>,
+    /// The file settings changed in the app are written back to
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// Settings for the email list.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ListConfig {
+    /// Order of the inbox and folders picked in the sidebar, changed with `o`
+    #[serde(default)]
+    pub sort: ViewSort,
+    /// Whether `d` asks before deleting an email
+    #[serde(default = "default_true")]
+    pub confirm_delete: bool,
+    /// Percent of the width the list takes next to the content pane, changed
+    /// with `<` and `>`
+    #[serde(default = "default_list_width")]
+    pub width: u16,
+    /// Whether dates read "5m ago", "Tue" or "Mar 12" instead of in full,
+    /// toggled with `E`
+    #[serde(default)]
+    pub relative_dates: bool,
+    /// How dates of emails are written in the list and the content pane
+    #[serde(default)]
+    pub date_format: DateFormat,
+    /// What the list rows show, as a template like `{date:16} | {subject}`,
+    /// instead of the four-line cards
+    pub row_format: Option<RowFormat>,
+}
+
+/// A strftime format for the dates of emails, like `%a %d %b %H:%M`, checked
+/// when the config is read.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub struct DateFormat(pub String);
+
+impl TryFrom<String> for DateFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(format: String) -> Result<Self> {
+        if StrftimeItems::new(&format).any(|item| item == chrono::format::Item::Error) {
+            bail!(
+                "Invalid date format {:?}, use strftime specifiers like %Y-%m-%d",
+                format
+            );
+        }
+        Ok(DateFormat(format))
+    }
+}
+
+impl Default for DateFormat {
+    fn default() -> Self {
+        DateFormat("%Y-%m-%d %H:%M".to_string())
+    }
+}
+
+impl DateFormat {
+    /// `date` in the local time zone, in this format.
+    pub fn format(&self, date: DateTime<Utc>) -> String {
+        date.with_timezone(&Local).format(&self.0).to_string()
+    }
+}
+
+/// A field of an email a list row can show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RowField {
+    Date,
+    Sender,
+    Subject,
+    Size,
+    Account,
+}
+
+/// A piece of a list row: text as written, or a field padded or cut to a width.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowPart {
+    Text(String),
+    Field(RowField, Option<usize>),
+}
+
+/// A template for the rows of the list, like `{date:16} | {sender:24} | {subject}`,
+/// each of its lines a line of the row. A width after `:` pads or cuts the field
+/// so rows line up in columns.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub struct RowFormat {
+    pub lines: Vec<Vec<RowPart>>,
+}
+
+impl TryFrom<String> for RowFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(format: String) -> Result<Self> {
+        let mut lines = Vec::new();
+        for line in format.split('\n') {
+            let mut parts = Vec::new();
+            let mut rest = line;
+            while let Some(open) = rest.find('{') {
+                if open > 0 {
+                    parts.push(RowPart::Text(rest[..open].to_string()));
+                }
+                let Some(close) = rest[open..].find('}') else {
+                    bail!("Unclosed {{ in row format {:?}", format);
+                };
+                let spec = &rest[open + 1..open + close];
+                let (name, width) = match spec.split_once(':') {
+                    Some((name, width)) => {
+                        let width = width
+                            .parse()
+                            .with_context(|| format!("Invalid width in {{{}}}", spec))?;
+                        (name, Some(width))
+                    }
+                    None => (spec, None),
+                };
+                let field = match name {
+                    "date" => RowField::Date,
+                    "sender" => RowField::Sender,
+                    "subject" => RowField::Subject,
+                    "size" => RowField::Size,
+                    "account" => RowField::Account,
+                    _ => bail!(
+                        "Unknown field {{{}}} in row format, use date, sender, subject, size or account",
+                        name
+                    ),
+                };
+                parts.push(RowPart::Field(field, width));
+                rest = &rest[open + close + 1..];
+            }
+            if !rest.is_empty() {
+                parts.push(RowPart::Text(rest.to_string()));
+            }
+            lines.push(parts);
+        }
+        Ok(RowFormat { lines })
+    }
+}
+
+/// Opt-in tracking of which emails were read or replied to, for timesheets.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ActivityConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Seconds a backend request may take before it counts as hung and is cancelled,
+/// with optional overrides per operation.
+#[derive(Debug, Deserialize, Clone)]
+pub struct TimeoutConfig {
+    #[serde(default = "default_timeout_secs")]
+    pub default: u64,
+    pub sync: Option<u64>,
+    pub search: Option<u64>,
+    pub body: Option<u64>,
+    pub attachment: Option<u64>,
+    pub send: Option<u64>,
+    pub update: Option<u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            default: default_timeout_secs(),
+            sync: None,
+            search: None,
+            body: None,
+            attachment: None,
+            send: None,
+            update: None,
+        }
+    }
+}
+
+/// Settings of the offline cache. Past its limits the oldest unflagged emails
+/// are evicted.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct CacheConfig {
+    pub max_size_mb: Option<u64>,
+    pub max_age_days: Option<u64>,
+    #[serde(default)]
+    pub encryption: CacheEncryption,
+}
+
+/// Where the key encrypting the cached mail comes from, if it is encrypted. Bodies,
+/// subjects, addresses, headers and attachment names are sealed; dates, flags, sizes
+/// and ids stay readable so limits can be applied without the key.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheEncryption {
+    #[default]
+    None,
+    /// Derived from the passphrase in `MAIL_TUI_CACHE_PASSPHRASE`
+    Passphrase,
+    /// Generated once and kept in the OS keyring
+    Keyring,
+}
+
+impl CacheEncryption {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheEncryption::None => "none",
+            CacheEncryption::Passphrase => "passphrase",
+            CacheEncryption::Keyring => "keyring",
+        }
+    }
+}
+
+/// Where saved attachments go. Directories are templates with placeholders for
+/// the email they come from, e.g. `~/mail/{account}/{yyyy}/{MM}/{subject_slug}/`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DownloadConfig {
+    #[serde(default = "default_download_directory")]
+    pub directory: String,
+    /// Checked in order, the first whose query matches overrides `directory`
+    #[serde(default)]
+    pub rules: Vec<DownloadRule>,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            directory: default_download_directory(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// A directory template for mail matching a query in the search box syntax.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DownloadRule {
+    pub query: String,
+    pub directory: String,
+}
+
+/// Settings for `mail-tui sync --daemon`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SyncConfig {
+    /// Seconds between syncs
+    #[serde(default = "default_sync_interval_secs")]
+    pub interval: u64,
+    /// Whether new unread mail shows a desktop notification
+    #[serde(default = "default_true")]
+    pub notifications: bool,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_sync_interval_secs(),
+            notifications: true,
+        }
+    }
+}
+
+/// Background downloads of the bodies next to the selected email, so opening
+/// them doesn't wait on the server.
+#[derive(Debug, Deserialize, Clone)]
+pub struct PrefetchConfig {
+    /// Bodies downloaded at the same time, 0 turns prefetching off
+    #[serde(default = "default_prefetch_concurrency")]
+    pub concurrency: usize,
+    /// Emails below the selection to warm, along with the one above it
+    #[serde(default = "default_prefetch_ahead")]
+    pub ahead: usize,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: default_prefetch_concurrency(),
+            ahead: default_prefetch_ahead(),
+        }
+    }
+}
+
+/// Settings for writing and sending mail.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ComposeConfig {
+    /// Recipients past which replying to all asks for confirmation first
+    #[serde(default = "default_reply_all_limit")]
+    pub reply_all_limit: usize,
+    /// Addresses that reach many people, which always ask for confirmation
+    #[serde(default)]
+    pub distribution_lists: Vec<String>,
+    /// Command the body is edited in with Ctrl-E, instead of `$VISUAL` or `$EDITOR`
+    #[serde(default)]
+    pub editor: Option<String>,
+    /// Whether a new message opens its body in the editor right away
+    #[serde(default)]
+    pub use_editor: bool,
+}
+
+impl Default for ComposeConfig {
+    fn default() -> Self {
+        Self {
+            reply_all_limit: default_reply_all_limit(),
+            distribution_lists: Vec::new(),
+            editor: None,
+            use_editor: false,
+        }
+    }
+}
+
+/// Images drawn in the content pane with the terminal's graphics protocol.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ImageConfig {
+    #[serde(default)]
+    pub protocol: ImageProtocol,
+    /// Rows an image takes at most
+    #[serde(default = "default_image_rows")]
+    pub max_rows: u16,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            protocol: ImageProtocol::default(),
+            max_rows: default_image_rows(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageProtocol {
+    /// Kitty graphics or sixel, going by what the environment says about the terminal
+    #[default]
+    Auto,
+    Kitty,
+    Sixel,
+    /// A placeholder naming each image
+    Off,
+}
+
+/// Colors of the interface: a preset, with the colors set here in place of its own.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ThemeConfig {
+    #[serde(default)]
+    pub preset: ThemePreset,
+    /// Borders of the panels without focus
+    pub border: Option<ThemeColor>,
+    /// Border of the focused panel and the current tab
+    pub focus: Option<ThemeColor>,
+    /// Background of the selected row
+    pub selection: Option<ThemeColor>,
+    /// Unread markers and counts
+    pub unread: Option<ThemeColor>,
+    /// Attachment and conversation markers, and status messages
+    pub accent: Option<ThemeColor>,
+    /// Labels like `Subject:` in the content pane and forms
+    pub label: Option<ThemeColor>,
+    /// `From:` and `Date:` in the list
+    pub secondary: Option<ThemeColor>,
+    /// Date headings in the list, prompts and work in progress
+    pub warning: Option<ThemeColor>,
+    pub error: Option<ThemeColor>,
+    /// Placeholders and other text of little importance
+    pub muted: Option<ThemeColor>,
+    /// Meeting requests
+    pub meeting: Option<ThemeColor>,
+    /// Text of popups
+    pub text: Option<ThemeColor>,
+    /// Behind popups
+    pub background: Option<ThemeColor>,
+    /// Behind the status bar
+    pub status_bar: Option<ThemeColor>,
+    /// Behind what a search matched
+    pub highlight: Option<ThemeColor>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemePreset {
+    /// The terminal's own colors, for dark backgrounds
+    #[default]
+    Default,
+    /// For light backgrounds
+    Light,
+    Gruvbox,
+}
+
+/// A color by name, like `yellow` or `darkgray`, as `#rrggbb`, or as a number
+/// of the terminal's 256-color palette.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub struct ThemeColor(pub Color);
+
+impl TryFrom<String> for ThemeColor {
+    type Error = anyhow::Error;
+
+    fn try_from(name: String) -> Result<Self> {
+        let lower = name.trim().to_lowercase().replace(['-', '_', ' '], "");
+        let color = match lower.as_str() {
+            "reset" | "default" => Color::Reset,
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "gray" | "grey" => Color::Gray,
+            "darkgray" | "darkgrey" => Color::DarkGray,
+            "lightred" => Color::LightRed,
+            "lightgreen" => Color::LightGreen,
+            "lightyellow" => Color::LightYellow,
+            "lightblue" => Color::LightBlue,
+            "lightmagenta" => Color::LightMagenta,
+            "lightcyan" => Color::LightCyan,
+            "white" => Color::White,
+            _ => {
+                if let Some(hex) = lower.strip_prefix('#').filter(|hex| hex.len() == 6) {
+                    let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16);
+                    match (channel(0), channel(2), channel(4)) {
+                        (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                        _ => bail!("Invalid color {}", name),
+                    }
+                } else if let Ok(index) = lower.parse::<u8>() {
+                    Color::Indexed(index)
+                } else {
+                    bail!("Unknown color {}, use a name, #rrggbb or 0-255", name)
+                }
+            }
+        };
+        Ok(ThemeColor(color))
+    }
+}
+
+impl Default for ListConfig {
+    fn default() -> Self {
+        Self {
+            sort: ViewSort::default(),
+            confirm_delete: true,
+            width: default_list_width(),
+            relative_dates: false,
+            date_format: DateFormat::default(),
+            row_format: None,
+        }
+    }
+}
+
+/// A query in the search box syntax, kept under a name to run again.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+}
+
+/// A named view combining folders of every account, narrowed by a query in the
+/// search box syntax and sorted one way.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ViewConfig {
+    pub name: String,
+    /// Folder names, matched without regard to case
+    #[serde(default = "default_view_folders")]
+    pub folders: Vec<String>,
+    #[serde(default)]
+    pub query: String,
+    #[serde(default)]
+    pub sort: ViewSort,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewSort {
+    #[default]
+    Newest,
+    Oldest,
+    Sender,
+    Subject,
+    /// Largest first
+    Size,
+    /// High importance first, newest first within each level
+    Importance,
+}
+
+impl ViewSort {
+    /// In the order `o` cycles through them.
+    pub const ALL: [ViewSort; 6] = [
+        ViewSort::Newest,
+        ViewSort::Oldest,
+        ViewSort::Sender,
+        ViewSort::Subject,
+        ViewSort::Size,
+        ViewSort::Importance,
+    ];
+
+    pub fn next(self) -> Self {
+        let position = Self::ALL.iter().position(|&sort| sort == self).unwrap_or(0);
+        Self::ALL[(position + 1) % Self::ALL.len()]
+    }
+
+    /// How the sort is written in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            ViewSort::Newest => "newest",
+            ViewSort::Oldest => "oldest",
+            ViewSort::Sender => "sender",
+            ViewSort::Subject => "subject",
+            ViewSort::Size => "size",
+            ViewSort::Importance => "importance",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ViewSort::Newest => "newest first",
+            ViewSort::Oldest => "oldest first",
+            ViewSort::Sender => "sender",
+            ViewSort::Subject => "subject",
+            ViewSort::Size => "largest first",
+            ViewSort::Importance => "importance",
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ExchangeConfig {
     pub email: String,
     pub password: String,
+    #[serde(default = "default_server")]
     pub server: String,
+    /// Addresses blind-copied on every message sent from this account
+    #[serde(default)]
+    pub auto_bcc: Vec<String>,
+    /// Whether sent messages keep a copy at all
+    #[serde(default = "default_true")]
+    pub save_sent: bool,
+    /// Shared mailbox whose Sent Items should get the copy instead of this account's
+    #[serde(default)]
+    pub sent_mailbox: Option<String>,
+    /// Messages fetched per request when syncing, smaller pages for throttled tenants
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+    /// Text added below new messages, replies and forwards
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// File holding the signature, read each time a message is started
+    #[serde(default)]
+    pub signature_file: Option<PathBuf>,
+}
+
+impl ExchangeConfig {
+    /// The signature, read from `signature_file` when that is set instead of
+    /// `signature`. `None` when neither is set.
+    pub fn signature(&self) -> Result<Option<String>> {
+        let Some(path) = &self.signature_file else {
+            return Ok(self.signature.clone());
+        };
+        let path = match path.strip_prefix("~") {
+            Ok(relative) => dirs::home_dir()
+                .context("No home directory to find the signature in")?
+                .join(relative),
+            Err(_) => path.clone(),
+        };
+        let signature = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Some(signature.trim_end().to_string()))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AccountConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub exchange: ExchangeConfig,
+}
+
+fn default_server() -> String {
+    "outlook.office365.com".to_string()
+}
+
+fn default_page_size() -> usize {
+    100
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_view_folders() -> Vec<String> {
+    vec!["Inbox".to_string()]
+}
+
+fn default_sync_interval_secs() -> u64 {
+    300
+}
+
+fn default_reply_all_limit() -> usize {
+    10
+}
+
+fn default_prefetch_concurrency() -> usize {
+    4
+}
+
+fn default_prefetch_ahead() -> usize {
+    5
+}
+
+fn default_list_width() -> u16 {
+    30
+}
+
+fn default_image_rows() -> u16 {
+    20
+}
+
+fn default_download_directory() -> String {
+    "~/mail/{account}/{yyyy}/{MM}/{subject_slug}/".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Config {
+    /// All configured accounts, with a plain `[exchange]` section named after its address.
+    pub fn accounts(&self) -> Result<Vec<AccountConfig>> {
+        let mut accounts = Vec::new();
+        if let Some(exchange) = &self.exchange {
+            accounts.push(AccountConfig {
+                name: exchange.email.clone(),
+                exchange: exchange.clone(),
+            });
+        }
+        accounts.extend(self.accounts.iter().cloned());
+
+        if accounts.is_empty() {
+            bail!("No accounts configured, add an [exchange] or [[accounts]] section");
+        }
+        Ok(accounts)
+    }
 }
 
 pub fn load_config(config_path: Option<PathBuf>) -> Result<Config> {
     // Create a new config builder
     let mut builder = config::Config::builder();
-    
-    // Set defaults
-    builder = builder.set_default("exchange.server", "outlook.office365.com")?;
+    // The file loaded last wins, so changes are written there
+    let mut path = None;
     
     // Try to load from specified path
-    if let Some(path) = config_path {
-        if path.exists() {
-            builder = builder.add_source(config::File::from(path));
+    if let Some(config_path) = config_path {
+        if config_path.exists() {
+            builder = builder.add_source(config::File::from(config_path.clone()));
         }
+        path = Some(config_path);
     } else {
         // Try current directory
         if std::path::Path::new("config.toml").exists() {
             builder = builder.add_source(config::File::with_name("config"));
+            path = Some(PathBuf::from("config.toml"));
         }
         
         // Try home directory
         if let Some(home) = dirs::home_dir() {
             let config_path = home.join(".config").join("mail-tui").join("config.toml");
             if config_path.exists() {
-                builder = builder.add_source(config::File::from(config_path));
+                builder = builder.add_source(config::File::from(config_path.clone()));
+                path = Some(config_path);
             }
         }
     }
@@ -46,5 +713,187 @@ pub fn load_config(config_path: Option<PathBuf>) -> Result<Config> {
     
     // Build the config and convert to our Config struct
     let config = builder.build()?;
-    config.try_deserialize().context("Failed to parse configuration")
+    let mut config: Config = config
+        .try_deserialize()
+        .context("Failed to parse configuration")?;
+    config.path = path;
+    Ok(config)
+}
+
+/// Writes `sort` to the config file at `path`, for the view named `view` or
+/// else in `[list]`, leaving the rest of the file as it was written.
+pub fn save_sort(path: &Path, view: Option<&str>, sort: ViewSort) -> Result<()> {
+    edit_file(path, |document| {
+        let section = match view {
+            Some(name) => document
+                .get_mut("views")
+                .and_then(Item::as_array_of_tables_mut)
+                .and_then(|views| {
+                    views
+                        .iter_mut()
+                        .find(|view| view.get("name").and_then(Item::as_str) == Some(name))
+                })
+                .with_context(|| format!("No view named {} in {}", name, path.display()))?,
+            None => list_section(document, path)?,
+        };
+        section["sort"] = value(sort.name());
+        Ok(())
+    })
+}
+
+/// Writes the width of the list to `[list]` in the config file at `path`.
+pub fn save_list_width(path: &Path, width: u16) -> Result<()> {
+    edit_file(path, |document| {
+        list_section(document, path)?["width"] = value(width as i64);
+        Ok(())
+    })
+}
+
+/// Writes whether the list shows relative dates to `[list]` in the config file at `path`.
+pub fn save_relative_dates(path: &Path, relative_dates: bool) -> Result<()> {
+    edit_file(path, |document| {
+        list_section(document, path)?["relative_dates"] = value(relative_dates);
+        Ok(())
+    })
+}
+
+/// Writes a saved search to `[[searches]]` in the config file at `path`,
+/// replacing the one of the same name.
+pub fn save_search(path: &Path, search: &SavedSearch) -> Result<()> {
+    edit_file(path, |document| {
+        let searches = document
+            .entry("searches")
+            .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+            .with_context(|| format!("searches in {} isn't [[searches]]", path.display()))?;
+        let existing = searches
+            .iter_mut()
+            .find(|saved| saved.get("name").and_then(Item::as_str) == Some(&search.name));
+        match existing {
+            Some(saved) => saved["query"] = value(&search.query),
+            None => {
+                let mut saved = Table::new();
+                saved["name"] = value(&search.name);
+                saved["query"] = value(&search.query);
+                searches.push(saved);
+            }
+        }
+        Ok(())
+    })
+}
+
+fn list_section<'a>(document: &'a mut DocumentMut, path: &Path) -> Result<&'a mut Table> {
+    document
+        .entry("list")
+        .or_insert(table())
+        .as_table_mut()
+        .with_context(|| format!("[list] in {} isn't a table", path.display()))
+}
+
+/// Changes the config file at `path` with `edit`, keeping its comments and
+/// layout, and creates it if there is none yet.
+fn edit_file(path: &Path, edit: impl FnOnce(&mut DocumentMut) -> Result<()>) -> Result<()> {
+    let contents = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    } else {
+        String::new()
+    };
+    let mut document: DocumentMut = contents
+        .parse()
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    edit(&mut document)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, document.to_string())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accounts_combines_exchange_and_account_sections() {
+        let source = r#"
+            [exchange]
+            email = "me@company.com"
+            password = "secret"
+
+            [[accounts]]
+            name = "lab"
+            email = "me@lab.local"
+            password = "secret"
+            server = "exchange.lab.local"
+        "#;
+        let config: Config = config::Config::builder()
+            .add_source(config::File::from_str(source, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        let accounts = config.accounts().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].name, "me@company.com");
+        assert_eq!(accounts[0].exchange.server, "outlook.office365.com");
+        assert_eq!(accounts[1].name, "lab");
+        assert_eq!(accounts[1].exchange.server, "exchange.lab.local");
+    }
+
+    #[test]
+    fn test_save_sort_keeps_the_rest_of_the_file() {
+        let path = std::env::temp_dir().join(format!("mail-tui-sort-{}.toml", std::process::id()));
+        let source = "# Work mail\n[[views]]\nname = \"Review\" # quarterly\nsort = \"oldest\"\n";
+        fs::write(&path, source).unwrap();
+
+        save_sort(&path, Some("Review"), ViewSort::Size).unwrap();
+        save_sort(&path, None, ViewSort::Importance).unwrap();
+        save_list_width(&path, 45).unwrap();
+        let search = |query: &str| SavedSearch {
+            name: "reports".to_string(),
+            query: query.to_string(),
+        };
+        save_search(&path, &search("report")).unwrap();
+        save_search(&path, &search("subject:report")).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Work mail\n[[views]]\nname = \"Review\" # quarterly\nsort = \"size\"\n\n\
+             [list]\nsort = \"importance\"\nwidth = 45\n\n\
+             [[searches]]\nname = \"reports\"\nquery = \"subject:report\"\n"
+        );
+        assert!(save_sort(&path, Some("Missing"), ViewSort::Oldest).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_date_format_is_checked() {
+        assert!(DateFormat::try_from("%a %d %b %H:%M".to_string()).is_ok());
+        assert!(DateFormat::try_from("%Y-%m-%Q".to_string()).is_err());
+        assert!(DateFormat::try_from("%".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_row_format_is_parsed() {
+        let format = RowFormat::try_from("{date:16} | {subject}\n  {sender}".to_string()).unwrap();
+        assert_eq!(
+            format.lines,
+            vec![
+                vec![
+                    RowPart::Field(RowField::Date, Some(16)),
+                    RowPart::Text(" | ".to_string()),
+                    RowPart::Field(RowField::Subject, None),
+                ],
+                vec![
+                    RowPart::Text("  ".to_string()),
+                    RowPart::Field(RowField::Sender, None),
+                ],
+            ]
+        );
+        assert!(RowFormat::try_from("{date} | {from}".to_string()).is_err());
+        assert!(RowFormat::try_from("{date:wide}".to_string()).is_err());
+        assert!(RowFormat::try_from("{date".to_string()).is_err());
+    }
 }
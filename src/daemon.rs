@@ -0,0 +1,157 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use std::collections::HashSet;
+use std::process::Command;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::email::{self, Account, Email, EmailClient, Progress, UntrustedCertificate};
+use crate::signals;
+use crate::state::LocalState;
+use crate::ui::loading::{self, Operation, Timeouts};
+
+/// Syncs every account into the offline cache, once or, as a daemon, every
+/// `[sync] interval` seconds until interrupted.
+pub async fn run(config: &Config, daemon: bool) -> Result<()> {
+    let Some(mut cache) = Cache::open(&config.cache)? else {
+        bail!("No cache directory to sync into");
+    };
+    let state = LocalState::load()?;
+    let accounts = email::create_clients(config, &state).await?;
+    let timeouts = Timeouts::new(&config.timeouts);
+
+    if !daemon {
+        let failed = sync_once(&accounts, &mut cache, &timeouts).await.failed;
+        if failed > 0 {
+            bail!("{} of {} accounts failed to sync", failed, accounts.len());
+        }
+        return Ok(());
+    }
+
+    let mut notifications = config.sync.notifications;
+    let mut interval = tokio::time::interval(Duration::from_secs(config.sync.interval.max(1)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Listening across iterations, a signal during a sync stops before the next
+    let terminated = signals::terminated();
+    tokio::pin!(terminated);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = &mut terminated => return Ok(()),
+        }
+
+        let new_mail = sync_once(&accounts, &mut cache, &timeouts).await.new_mail;
+        if notifications && !new_mail.is_empty() {
+            // Reloaded every time, as the TUI may have muted or blocked since
+            let state = LocalState::load().unwrap_or_default();
+            let wanted: Vec<&Email> = new_mail
+                .iter()
+                .filter(|e| !state.is_blocked(&e.sender) && !state.is_muted(&e.thread_subject()))
+                .collect();
+            if let Err(e) = notify(&wanted) {
+                log(&format!("Turning off notifications: {:#}", e));
+                notifications = false;
+            }
+        }
+    }
+}
+
+struct SyncOutcome {
+    /// Unread emails that weren't cached before, for accounts that had been synced
+    new_mail: Vec<Email>,
+    failed: usize,
+}
+
+async fn sync_once(
+    accounts: &[Account<impl EmailClient>],
+    cache: &mut Cache,
+    timeouts: &Timeouts,
+) -> SyncOutcome {
+    let mut outcome = SyncOutcome {
+        new_mail: Vec::new(),
+        failed: 0,
+    };
+    let cached: HashSet<(String, String)> = match cache.load() {
+        Ok(emails) => emails.into_iter().map(|e| (e.account, e.id)).collect(),
+        Err(e) => {
+            log(&format!("Failed to read the cache: {:#}", e));
+            HashSet::new()
+        }
+    };
+
+    let limit = timeouts.get(Operation::ListSync);
+    for account in accounts {
+        let progress = Progress::default();
+        let request = account.client.fetch_current_quarter_emails(&progress);
+        let result = loading::with_timeout(limit, request).await;
+        let emails = match result {
+            Ok(emails) => emails,
+            Err(e) => {
+                outcome.failed += 1;
+                if let Some(certificate) = e.downcast_ref::<UntrustedCertificate>() {
+                    log(&format!(
+                        "{}: open mail-tui to review the certificate of {}",
+                        account.name, certificate.host
+                    ));
+                } else {
+                    log(&format!("{}: sync failed: {:#}", account.name, e));
+                }
+                continue;
+            }
+        };
+
+        if let Err(e) = cache.store_account(&account.name, &emails) {
+            outcome.failed += 1;
+            log(&format!(
+                "{}: failed to update the cache: {:#}",
+                account.name, e
+            ));
+            continue;
+        }
+
+        // A first sync finds everything new, which isn't worth a notification
+        let synced_before = cached.iter().any(|(name, _)| name == &account.name);
+        if synced_before {
+            outcome.new_mail.extend(emails.into_iter().filter(|email| {
+                !email.read && !cached.contains(&(email.account.clone(), email.id.clone()))
+            }));
+        }
+    }
+    outcome
+}
+
+/// Shows a desktop notification through `notify-send`, one per email or a
+/// summary when several arrived at once.
+fn notify(emails: &[&Email]) -> Result<()> {
+    let (summary, body) = match emails {
+        [] => return Ok(()),
+        [email] => (
+            format!("New mail from {}", email.sender),
+            email.subject.clone(),
+        ),
+        _ => (
+            format!("{} new emails", emails.len()),
+            emails
+                .iter()
+                .map(|email| format!("{}: {}", email.sender, email.subject))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+    };
+
+    let status = Command::new("notify-send")
+        .args(["--app-name", "mail-tui", &summary, &body])
+        .status()
+        .context("Failed to run notify-send")?;
+    if !status.success() {
+        bail!("notify-send exited with {}", status);
+    }
+    Ok(())
+}
+
+fn log(message: &str) {
+    tracing::info!("{}", message);
+    eprintln!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+}
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::DownloadConfig;
+use crate::email::Email;
+use crate::query::{Groups, Query};
+
+/// Longest subject slug, so deep directory trees stay within path limits.
+const MAX_SLUG_CHARS: usize = 60;
+
+/// The directory attachments of `email` are saved to: the first rule whose query
+/// matches it, or the default template.
+///
+/// Templates may use `{account}`, `{sender}`, `{yyyy}`, `{MM}`, `{dd}` and
+/// `{subject_slug}`. A leading `~` is the home directory, and relative paths are
+/// relative to the directory mail-tui was started in.
+pub fn directory_for(config: &DownloadConfig, groups: &Groups, email: &Email) -> Result<PathBuf> {
+    let mut template = config.directory.as_str();
+    for rule in &config.rules {
+        let query = Query::parse(&rule.query, groups)
+            .with_context(|| format!("Invalid query in download rule '{}'", rule.query))?;
+        if query.matches(email) {
+            template = &rule.directory;
+            break;
+        }
+    }
+    expand(template, email)
+}
+
+fn expand(template: &str, email: &Email) -> Result<PathBuf> {
+    let date = email.date.with_timezone(&Local);
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed placeholder in '{}'", template))?;
+        let value = match &rest[start + 1..start + end] {
+            "account" => path_segment(&email.account),
+            "sender" => path_segment(&email.sender),
+            "yyyy" => date.format("%Y").to_string(),
+            "MM" => date.format("%m").to_string(),
+            "dd" => date.format("%d").to_string(),
+            // Without reply prefixes, so a whole thread saves to one directory
+            "subject_slug" => slug(&email.thread_subject()),
+            other => bail!("Unknown placeholder {{{}}} in '{}'", other, template),
+        };
+        expanded.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+
+    match expanded.strip_prefix('~') {
+        Some(relative) => {
+            let home = dirs::home_dir().context("No home directory to save into")?;
+            Ok(home.join(relative.trim_start_matches('/')))
+        }
+        None => Ok(PathBuf::from(expanded)),
+    }
+}
+
+/// The subject lowercased with runs of anything but letters and digits turned
+/// into single dashes, e.g. `q2-budget-review` for "Q2 Budget Review!".
+fn slug(subject: &str) -> String {
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(MAX_SLUG_CHARS).collect();
+    match slug.trim_end_matches('-') {
+        "" => "no-subject".to_string(),
+        slug => slug.to_string(),
+    }
+}
+
+/// A value usable as a single directory or file name.
+fn path_segment(value: &str) -> String {
+    let segment: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '\0' => '_',
+            c => c,
+        })
+        .collect();
+    match segment.trim_start_matches('.') {
+        "" => "_".to_string(),
+        segment => segment.to_string(),
+    }
+}
+
+/// Writes `content` to `name` in `directory`, creating the directory first. An
+/// existing file is never overwritten: the name gets a number instead, like
+/// `report (2).pdf`. Returns the path written.
+pub fn save(directory: &Path, name: &str, content: &[u8]) -> Result<PathBuf> {
+    fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create {}", directory.display()))?;
+
+    let name = path_segment(name);
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name.as_str(), String::new()),
+    };
+    for copy in 1.. {
+        let path = if copy == 1 {
+            directory.join(&name)
+        } else {
+            directory.join(format!("{} ({}){}", stem, copy, extension))
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(content)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to create {}", path.display()))
+            }
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DownloadRule;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_directory_templates_and_collisions() {
+        let email = Email {
+            subject: "RE: Q2 Budget Review!".to_string(),
+            sender: "boss@company.com".to_string(),
+            date: Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap(),
+            read: true,
+            ..Email::test("1")
+        };
+        let mut config = DownloadConfig {
+            directory: "mail/{account}/{yyyy}/{MM}/{subject_slug}/".to_string(),
+            rules: Vec::new(),
+        };
+        assert_eq!(
+            directory_for(&config, &Groups::new(), &email).unwrap(),
+            PathBuf::from("mail/work/2024/05/q2-budget-review/")
+        );
+
+        config.rules.push(DownloadRule {
+            query: "from:boss".to_string(),
+            directory: "boss/{sender}".to_string(),
+        });
+        assert_eq!(
+            directory_for(&config, &Groups::new(), &email).unwrap(),
+            PathBuf::from("boss/boss@company.com")
+        );
+        config.rules[0].directory = "{unknown}".to_string();
+        assert!(directory_for(&config, &Groups::new(), &email).is_err());
+
+        let directory = std::env::temp_dir().join(format!("mail-tui-test-{}", std::process::id()));
+        let first = save(&directory, "report.pdf", b"one").unwrap();
+        let second = save(&directory, "report.pdf", b"two").unwrap();
+        assert_eq!(first.file_name().unwrap(), "report.pdf");
+        assert_eq!(second.file_name().unwrap(), "report (2).pdf");
+        assert_eq!(fs::read(&first).unwrap(), b"one");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::email::OutgoingEmail;
+use crate::state;
+use crate::ui::compose::ComposeKind;
+
+/// The message being written, saved on every change so it survives the app
+/// crashing or being killed before it is sent.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LocalDraft {
+    /// Name of the account the message is sent from
+    pub account: String,
+    pub kind: ComposeKind,
+    pub message: OutgoingEmail,
+}
+
+fn path() -> Option<PathBuf> {
+    state::state_dir().map(|dir| dir.join("draft.json"))
+}
+
+impl LocalDraft {
+    /// The message left behind by a session that ended while writing it, if any.
+    pub fn load() -> Result<Option<Self>> {
+        let Some(path) = path().filter(|path| path.exists()) else {
+            return Ok(None);
+        };
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let draft = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(draft))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Forgets the saved message once it was sent, saved to Drafts or discarded.
+    pub fn clear() -> Result<()> {
+        match path().filter(|path| path.exists()) {
+            Some(path) => fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display())),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+/// The representations a message body can be fetched in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BodyFormat {
+    Text,
+    /// HTML with scripts, styles and remote content stripped
+    Html,
+    /// The full message source
+    Mime,
+}
+
+impl BodyFormat {
+    /// Name used to store the format, e.g. in the cache.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BodyFormat::Text => "text",
+            BodyFormat::Html => "html",
+            BodyFormat::Mime => "mime",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(BodyFormat::Text),
+            "html" => Some(BodyFormat::Html),
+            "mime" => Some(BodyFormat::Mime),
+            _ => None,
+        }
+    }
+}
+
+/// A message body whose formats are loaded on demand and kept once loaded, so
+/// the list can be fetched without every body and a format is only fetched once.
+#[derive(Clone, Default)]
+pub struct BodyHandle {
+    loaded: HashMap<BodyFormat, String>,
+}
+
+impl BodyHandle {
+    /// A handle with the plain text already loaded, for backends that return it
+    /// along with the headers.
+    pub fn from_text(text: impl Into<String>) -> Self {
+        let mut handle = Self::default();
+        handle.insert(BodyFormat::Text, text.into());
+        handle
+    }
+
+    pub fn get(&self, format: BodyFormat) -> Option<&str> {
+        self.loaded.get(&format).map(String::as_str)
+    }
+
+    pub fn is_loaded(&self, format: BodyFormat) -> bool {
+        self.loaded.contains_key(&format)
+    }
+
+    pub fn insert(&mut self, format: BodyFormat, content: String) {
+        self.loaded.insert(format, content);
+    }
+
+    /// Every format loaded so far.
+    pub fn loaded(&self) -> impl Iterator<Item = (BodyFormat, &str)> {
+        self.loaded
+            .iter()
+            .map(|(format, content)| (*format, content.as_str()))
+    }
+
+    /// Adds the formats loaded in `other` that aren't loaded here.
+    pub fn merge(&mut self, other: &BodyHandle) {
+        for (format, content) in other.loaded() {
+            self.loaded
+                .entry(format)
+                .or_insert_with(|| content.to_string());
+        }
+    }
+
+    /// The plain text, empty until it is loaded.
+    pub fn text(&self) -> &str {
+        self.get(BodyFormat::Text).unwrap_or_default()
+    }
+}
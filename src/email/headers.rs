@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+use super::Email;
+
+/// An Internet message header as the server returned it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+impl Header {
+    pub fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// The first value of a header, matching its name without regard to case.
+pub fn get<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case(name))
+        .map(|header| header.value.as_str())
+}
+
+/// The addresses and URLs in `List-Unsubscribe`, in the order the sender prefers
+/// them, e.g. `mailto:leave@list.example.com` and `https://example.com/unsubscribe`.
+pub fn unsubscribe_targets(headers: &[Header]) -> Vec<String> {
+    let Some(value) = get(headers, "List-Unsubscribe") else {
+        return Vec::new();
+    };
+    value
+        .split(',')
+        .filter_map(|part| {
+            let part = part.trim();
+            let target = part.strip_prefix('<')?.strip_suffix('>')?.trim();
+            (!target.is_empty()).then(|| target.to_string())
+        })
+        .collect()
+}
+
+/// Signs that an email isn't from who it claims to be: failed sender
+/// authentication and replies or bounces going to another domain than the sender's.
+pub fn phishing_warnings(email: &Email, headers: &[Header]) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if let Some(results) = get(headers, "Authentication-Results") {
+        let results = results.to_lowercase();
+        for check in ["spf", "dkim", "dmarc"] {
+            if results.contains(&format!("{}=fail", check))
+                || results.contains(&format!("{}=softfail", check))
+            {
+                warnings.push(format!(
+                    "{} check failed for the sender's domain",
+                    check.to_uppercase()
+                ));
+            }
+        }
+    }
+
+    let sender_domain = domain(&email.sender);
+    for (name, label) in [
+        ("Reply-To", "Replies go to"),
+        ("Return-Path", "Bounces go to"),
+    ] {
+        let Some(value) = get(headers, name) else {
+            continue;
+        };
+        let address = value.trim().trim_start_matches('<').trim_end_matches('>');
+        if !address.is_empty() && domain(address) != sender_domain {
+            warnings.push(format!("{} {}, not the sender's domain", label, address));
+        }
+    }
+    warnings
+}
+
+fn domain(address: &str) -> String {
+    address
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .unwrap_or(address)
+        .trim_end_matches('>')
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsubscribe_and_phishing_checks() {
+        let email = Email {
+            subject: "Verify your account".to_string(),
+            sender: "it-support@company.com".to_string(),
+            ..Email::test("1")
+        };
+        let headers = vec![
+            Header::new(
+                "authentication-results",
+                "mx.company.com; spf=fail smtp.mailfrom=company.com; dkim=pass",
+            ),
+            Header::new("Reply-To", "<helpdesk@c0mpany.net>"),
+            Header::new("Return-Path", "<bounces@company.com>"),
+            Header::new(
+                "List-Unsubscribe",
+                "<mailto:leave@company.com?subject=unsubscribe>, <https://company.com/u/1>",
+            ),
+        ];
+
+        assert_eq!(
+            phishing_warnings(&email, &headers),
+            vec![
+                "SPF check failed for the sender's domain",
+                "Replies go to helpdesk@c0mpany.net, not the sender's domain",
+            ]
+        );
+        assert_eq!(
+            unsubscribe_targets(&headers),
+            vec![
+                "mailto:leave@company.com?subject=unsubscribe",
+                "https://company.com/u/1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use super::{
+    BodyFormat, DateRange, DirectoryEntry, Email, EmailClient, Folder, Header, JunkReport,
+    MeetingResponse, OofSettings, OutgoingEmail, Progress, RecoverableItem, Task,
+};
+
+/// What the requests of one kind cost during the session.
+#[derive(Debug, Clone, Default)]
+pub struct RequestStats {
+    pub requests: u64,
+    pub errors: u64,
+    /// Payload bytes sent and received, leaving out protocol overhead
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub total_time: Duration,
+}
+
+impl RequestStats {
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.requests as f64
+        }
+    }
+
+    pub fn average_time(&self) -> Duration {
+        match u32::try_from(self.requests) {
+            Ok(requests) if requests > 0 => self.total_time / requests,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Stats by request kind, e.g. `fetch_body`.
+pub type Metrics = BTreeMap<&'static str, RequestStats>;
+
+/// Approximate size of a value on the wire.
+trait WireSize {
+    fn wire_size(&self) -> usize;
+}
+
+impl WireSize for () {
+    fn wire_size(&self) -> usize {
+        0
+    }
+}
+
+impl WireSize for str {
+    fn wire_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl WireSize for String {
+    fn wire_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl WireSize for Vec<u8> {
+    fn wire_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T: WireSize> WireSize for [T] {
+    fn wire_size(&self) -> usize {
+        self.iter().map(WireSize::wire_size).sum()
+    }
+}
+
+impl<T: WireSize> WireSize for Vec<T> {
+    fn wire_size(&self) -> usize {
+        self.as_slice().wire_size()
+    }
+}
+
+impl WireSize for Email {
+    fn wire_size(&self) -> usize {
+        self.id.len()
+            + self.subject.len()
+            + self.sender.len()
+            + self.to.wire_size()
+            + self.cc.wire_size()
+            + self
+                .body
+                .loaded()
+                .map(|(_, body)| body.len())
+                .sum::<usize>()
+            + self
+                .attachments
+                .iter()
+                .map(|a| a.id.len() + a.name.len() + a.content_type.len())
+                .sum::<usize>()
+    }
+}
+
+impl WireSize for Folder {
+    fn wire_size(&self) -> usize {
+        self.id.len() + self.name.len()
+    }
+}
+
+impl WireSize for Header {
+    fn wire_size(&self) -> usize {
+        self.name.len() + self.value.len()
+    }
+}
+
+impl WireSize for DirectoryEntry {
+    fn wire_size(&self) -> usize {
+        self.name.len() + self.email.len() + self.job_title.len() + self.department.len()
+    }
+}
+
+impl WireSize for Task {
+    fn wire_size(&self) -> usize {
+        self.subject.len()
+    }
+}
+
+impl WireSize for RecoverableItem {
+    fn wire_size(&self) -> usize {
+        self.email.wire_size()
+    }
+}
+
+impl WireSize for OofSettings {
+    fn wire_size(&self) -> usize {
+        self.internal_reply.len() + self.external_reply.len()
+    }
+}
+
+impl WireSize for OutgoingEmail {
+    fn wire_size(&self) -> usize {
+        self.to.wire_size()
+            + self.cc.wire_size()
+            + self.bcc.wire_size()
+            + self.subject.len()
+            + self.body.len()
+            + self
+                .attachments
+                .iter()
+                .map(|attachment| attachment.id.len())
+                .sum::<usize>()
+            + self
+                .files
+                .iter()
+                .filter_map(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len() as usize)
+                .sum::<usize>()
+            + self.in_reply_to.as_ref().map_or(0, String::len)
+            + self.references.wire_size()
+            + self.draft_id.as_ref().map_or(0, String::len)
+    }
+}
+
+/// A client that counts the requests made through it, their errors, payload
+/// sizes and durations, for the metrics panel.
+pub struct Metered<T: EmailClient> {
+    inner: T,
+    metrics: Mutex<Metrics>,
+}
+
+impl<T: EmailClient> Metered<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            metrics: Mutex::new(Metrics::new()),
+        }
+    }
+
+    async fn track<R: WireSize>(
+        &self,
+        kind: &'static str,
+        sent: usize,
+        request: impl Future<Output = Result<R>>,
+    ) -> Result<R> {
+        let started = Instant::now();
+        let result = request.await;
+        let elapsed = started.elapsed();
+        let mut metrics = self.metrics.lock().unwrap();
+        let stats = metrics.entry(kind).or_default();
+        stats.requests += 1;
+        stats.bytes_sent += sent as u64;
+        stats.total_time += elapsed;
+        match &result {
+            Ok(response) => {
+                let received = response.wire_size();
+                stats.bytes_received += received as u64;
+                debug!(kind, sent, received, ?elapsed, "Request done");
+            }
+            Err(e) => {
+                stats.errors += 1;
+                warn!(kind, ?elapsed, "Request failed: {:#}", e);
+            }
+        }
+        result
+    }
+}
+
+impl<T: EmailClient> EmailClient for Metered<T> {
+    fn trust_certificate(&self, fingerprint: &str) {
+        self.inner.trust_certificate(fingerprint)
+    }
+
+    fn set_date_range(&self, range: DateRange) {
+        self.inner.set_date_range(range)
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    async fn fetch_current_quarter_emails(&self, progress: &Progress) -> Result<Vec<Email>> {
+        let request = self.inner.fetch_current_quarter_emails(progress);
+        self.track("fetch_emails", 0, request).await
+    }
+
+    async fn fetch_folder_emails(&self, folder_id: &str) -> Result<Vec<Email>> {
+        let sent = folder_id.len();
+        self.track(
+            "fetch_emails",
+            sent,
+            self.inner.fetch_folder_emails(folder_id),
+        )
+        .await
+    }
+
+    async fn search_server(&self, query: &str) -> Result<Vec<Email>> {
+        self.track("search", query.len(), self.inner.search_server(query))
+            .await
+    }
+
+    async fn fetch_body(&self, id: &str, format: BodyFormat) -> Result<String> {
+        self.track("fetch_body", id.len(), self.inner.fetch_body(id, format))
+            .await
+    }
+
+    async fn fetch_headers(&self, id: &str) -> Result<Vec<Header>> {
+        self.track("fetch_headers", id.len(), self.inner.fetch_headers(id))
+            .await
+    }
+
+    async fn fetch_attachment(&self, email_id: &str, attachment_id: &str) -> Result<Vec<u8>> {
+        let sent = email_id.len() + attachment_id.len();
+        let request = self.inner.fetch_attachment(email_id, attachment_id);
+        self.track("fetch_attachment", sent, request).await
+    }
+
+    async fn search_directory(&self, query: &str) -> Result<Vec<DirectoryEntry>> {
+        let request = self.inner.search_directory(query);
+        self.track("search_directory", query.len(), request).await
+    }
+
+    async fn fetch_folders(&self) -> Result<Vec<Folder>> {
+        self.track("fetch_folders", 0, self.inner.fetch_folders())
+            .await
+    }
+
+    async fn create_folder(&self, name: &str) -> Result<Folder> {
+        self.track("update_folders", name.len(), self.inner.create_folder(name))
+            .await
+    }
+
+    async fn rename_folder(&self, id: &str, name: &str) -> Result<()> {
+        let sent = id.len() + name.len();
+        self.track("update_folders", sent, self.inner.rename_folder(id, name))
+            .await
+    }
+
+    async fn delete_folder(&self, id: &str) -> Result<()> {
+        self.track("update_folders", id.len(), self.inner.delete_folder(id))
+            .await
+    }
+
+    async fn move_emails(&self, ids: &[String], folder_id: &str) -> Result<()> {
+        let sent = ids.wire_size() + folder_id.len();
+        self.track(
+            "update_emails",
+            sent,
+            self.inner.move_emails(ids, folder_id),
+        )
+        .await
+    }
+
+    async fn delete_emails(&self, ids: &[String]) -> Result<()> {
+        self.track(
+            "update_emails",
+            ids.wire_size(),
+            self.inner.delete_emails(ids),
+        )
+        .await
+    }
+
+    async fn set_read(&self, ids: &[String], read: bool) -> Result<()> {
+        self.track(
+            "update_emails",
+            ids.wire_size(),
+            self.inner.set_read(ids, read),
+        )
+        .await
+    }
+
+    async fn report_junk(&self, id: &str, report: JunkReport) -> Result<()> {
+        self.track(
+            "update_emails",
+            id.len(),
+            self.inner.report_junk(id, report),
+        )
+        .await
+    }
+
+    async fn respond_to_meeting(&self, id: &str, response: MeetingResponse) -> Result<()> {
+        let request = self.inner.respond_to_meeting(id, response);
+        self.track("respond_to_meeting", id.len(), request).await
+    }
+
+    async fn send_email(&self, message: &OutgoingEmail) -> Result<()> {
+        let sent = message.wire_size();
+        self.track("send", sent, self.inner.send_email(message))
+            .await
+    }
+
+    async fn fetch_drafts(&self) -> Result<Vec<Email>> {
+        self.track("fetch_drafts", 0, self.inner.fetch_drafts())
+            .await
+    }
+
+    async fn save_draft(&self, message: &OutgoingEmail) -> Result<String> {
+        let sent = message.wire_size();
+        self.track("save_draft", sent, self.inner.save_draft(message))
+            .await
+    }
+
+    async fn get_oof_settings(&self) -> Result<OofSettings> {
+        self.track("automatic_replies", 0, self.inner.get_oof_settings())
+            .await
+    }
+
+    async fn set_oof_settings(&self, settings: &OofSettings) -> Result<()> {
+        let sent = settings.wire_size();
+        self.track(
+            "automatic_replies",
+            sent,
+            self.inner.set_oof_settings(settings),
+        )
+        .await
+    }
+
+    async fn fetch_tasks(&self) -> Result<Vec<Task>> {
+        self.track("fetch_tasks", 0, self.inner.fetch_tasks()).await
+    }
+
+    async fn fetch_recoverable_items(&self) -> Result<Vec<RecoverableItem>> {
+        let request = self.inner.fetch_recoverable_items();
+        self.track("recoverable_items", 0, request).await
+    }
+
+    async fn restore_item(&self, id: &str) -> Result<()> {
+        self.track("recoverable_items", id.len(), self.inner.restore_item(id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_stats_rates() {
+        let stats = RequestStats {
+            requests: 4,
+            errors: 1,
+            bytes_sent: 0,
+            bytes_received: 0,
+            total_time: Duration::from_millis(200),
+        };
+        assert_eq!(stats.error_rate(), 0.25);
+        assert_eq!(stats.average_time(), Duration::from_millis(50));
+        assert_eq!(RequestStats::default().average_time(), Duration::ZERO);
+    }
+}
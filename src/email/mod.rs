@@ -1,22 +1,487 @@
+mod body;
 mod exchange;
+pub mod headers;
+mod metered;
+pub mod threading;
 
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::config::Config;
+use crate::state::LocalState;
 
+pub use body::{BodyFormat, BodyHandle};
+pub use headers::Header;
+pub use metered::{Metered, Metrics};
+pub use threading::ThreadIds;
+
+#[derive(Clone)]
 pub struct Email {
     pub id: String,
+    /// Name of the account the email was fetched from
+    pub account: String,
     pub subject: String,
     pub sender: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
     pub date: DateTime<Utc>,
+    pub body: BodyHandle,
+    pub attachments: Vec<Attachment>,
+    pub read: bool,
+    /// Flagged for follow-up, which keeps it in the cache past its limits
+    pub flagged: bool,
+    /// MIME type of the body, which picks the renderer it is shown with
+    pub content_type: String,
+    /// Internet headers, which list calls leave out, once fetched with `fetch_headers`
+    pub headers: Option<Vec<Header>>,
+    /// Size on the server in bytes, attachments included
+    pub size: u64,
+    pub importance: Importance,
+    pub thread: ThreadIds,
+    /// Set when the email is a meeting request
+    pub meeting: Option<Meeting>,
+}
+
+impl Email {
+    /// Every address in the headers: the sender first, then To and Cc recipients.
+    pub fn addresses(&self) -> Vec<&str> {
+        let mut addresses = vec![self.sender.as_str()];
+        for address in self.to.iter().chain(self.cc.iter()) {
+            if !addresses.iter().any(|a| a.eq_ignore_ascii_case(address)) {
+                addresses.push(address);
+            }
+        }
+        addresses
+    }
+
+    /// The Message-ID without angle brackets, from the list fetch or else the
+    /// headers once they are fetched.
+    pub fn message_id(&self) -> Option<String> {
+        let id = match &self.thread.message_id {
+            Some(id) => id.as_str(),
+            None => headers::get(self.headers.as_deref()?, "Message-ID")?,
+        };
+        let id = id.trim().trim_start_matches('<').trim_end_matches('>');
+        (!id.is_empty()).then(|| id.to_string())
+    }
+
+    /// The subject without reply and forward prefixes, lowercased, which groups
+    /// the messages of a conversation.
+    pub fn thread_subject(&self) -> String {
+        let mut subject = self.subject.trim();
+        loop {
+            let lower = subject.to_lowercase();
+            let Some(prefix) = ["re:", "fw:", "fwd:"]
+                .iter()
+                .find(|prefix| lower.starts_with(*prefix))
+            else {
+                break;
+            };
+            subject = subject[prefix.len()..].trim_start();
+        }
+        subject.to_lowercase()
+    }
+}
+
+#[cfg(test)]
+impl Email {
+    /// An unread email with `id` for tests, which set the fields they check
+    /// with `Email { subject: …, ..Email::test("1") }`.
+    pub fn test(id: &str) -> Self {
+        use chrono::TimeZone;
+
+        Self {
+            id: id.to_string(),
+            account: "work".to_string(),
+            subject: String::new(),
+            sender: "someone@company.com".to_string(),
+            to: Vec::new(),
+            cc: Vec::new(),
+            date: Utc.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap(),
+            body: BodyHandle::default(),
+            attachments: Vec::new(),
+            read: false,
+            flagged: false,
+            content_type: "text/plain".to_string(),
+            headers: None,
+            size: 2_048,
+            importance: Importance::Normal,
+            thread: ThreadIds::default(),
+            meeting: None,
+        }
+    }
+}
+
+/// How important the sender marked an email.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Importance {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Metadata of a file attached to an email, whose content is fetched separately.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub name: String,
+    pub content_type: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        self.content_type.starts_with("image/")
+    }
+}
+
+/// Details of the meeting a meeting request invites to.
+#[derive(Clone)]
+pub struct Meeting {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub location: String,
+    pub organizer: String,
+}
+
+/// The answer sent to a meeting organizer.
+#[derive(Clone, Copy)]
+pub enum MeetingResponse {
+    Accept,
+    Tentative,
+    Decline,
+}
+
+/// Progress of a task, as tracked by Exchange.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    NotStarted,
+    InProgress,
+    WaitingOnOthers,
+    Deferred,
+    Completed,
+}
+
+/// An item of the mailbox's Tasks folder.
+#[derive(Clone)]
+pub struct Task {
+    pub subject: String,
+    pub due: Option<DateTime<Utc>>,
+    pub status: TaskStatus,
+}
+
+/// A person found in the organization's directory (Global Address List).
+#[derive(Clone)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub email: String,
+    pub job_title: String,
+    pub department: String,
+}
+
+/// A message to be sent.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OutgoingEmail {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: String,
     pub body: String,
+    /// Attachments of a forwarded email, copied from it on the server
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Local files uploaded with the message, read when it is sent
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    /// Message-ID of the email this replies to, without angle brackets
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    /// Message-IDs of the conversation so far, oldest first, for the References header
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// The draft this was written in, which sending it takes out of Drafts
+    #[serde(default)]
+    pub draft_id: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Folder {
+    pub id: String,
+    pub name: String,
+    /// Unread messages in the folder when it was fetched
+    pub unread: usize,
+}
+
+/// A hard-deleted message still held in the Recoverable Items folder.
+#[derive(Clone)]
+pub struct RecoverableItem {
+    pub email: Email,
+    pub deleted_at: DateTime<Utc>,
+    /// When the server purges the item for good
+    pub purge_at: DateTime<Utc>,
+}
+
+/// Whether automatic replies are off, on, or on during a date range.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OofState {
+    Disabled,
+    Enabled,
+    Scheduled,
+}
+
+/// Automatic reply (Out-of-Office) settings of a mailbox.
+#[derive(Clone)]
+pub struct OofSettings {
+    pub state: OofState,
+    /// Start and end of the range, only used when `state` is `Scheduled`
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub internal_reply: String,
+    pub external_reply: String,
+}
+
+/// What a message is reported as when it is moved to Junk Email.
+#[derive(Clone, Copy)]
+pub enum JunkReport {
+    Junk,
+    Phishing,
+}
+
+/// Distinguished id of the Inbox, accepted wherever a folder id is.
+pub const INBOX_FOLDER_ID: &str = "inbox";
+
+/// Distinguished id of the Drafts folder.
+pub const DRAFTS_FOLDER_ID: &str = "drafts";
+
+/// Error of a connection to a server presenting a certificate that isn't signed
+/// by a trusted authority nor pinned by the user.
+#[derive(Debug)]
+pub struct UntrustedCertificate {
+    pub host: String,
+    /// SHA-256 fingerprint as colon-separated hex
+    pub fingerprint: String,
+}
+
+impl fmt::Display for UntrustedCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "untrusted certificate for {}", self.host)
+    }
+}
+
+impl std::error::Error for UntrustedCertificate {}
+
+/// The days list fetches include, `start` to `end` both included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
 }
 
-pub trait EmailClient {
-    async fn fetch_current_quarter_emails(&self) -> Result<Vec<Email>>;
+impl DateRange {
+    /// The calendar quarter `day` falls in.
+    pub fn quarter(day: NaiveDate) -> Self {
+        let start = NaiveDate::from_ymd_opt(day.year(), day.month0() / 3 * 3 + 1, 1).unwrap();
+        let end = start + Months::new(3) - Days::new(1);
+        Self { start, end }
+    }
+
+    /// The quarter before or after the one the range starts in.
+    pub fn step_quarter(&self, forward: bool) -> Self {
+        let start = Self::quarter(self.start).start;
+        Self::quarter(if forward {
+            start + Months::new(3)
+        } else {
+            start - Months::new(3)
+        })
+    }
+
+    /// The first and last second of the range.
+    pub fn bounds(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        (
+            self.start.and_time(NaiveTime::MIN).and_utc(),
+            self.end.and_hms_opt(23, 59, 59).unwrap().and_utc(),
+        )
+    }
+}
+
+impl fmt::Display for DateRange {
+    /// `Q2 2024` for a whole quarter, otherwise both days.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Self::quarter(self.start) {
+            write!(f, "Q{} {}", self.start.month0() / 3 + 1, self.start.year())
+        } else {
+            write!(f, "{} to {}", self.start, self.end)
+        }
+    }
+}
+
+/// How far a paged list fetch has come.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FetchProgress {
+    pub pages: usize,
+    /// Known once the server has answered the first page
+    pub total_pages: Option<usize>,
+    pub messages: usize,
+    pub total_messages: Option<usize>,
+}
+
+/// Where a paged fetch reports its progress, or nowhere by default.
+#[derive(Clone, Default)]
+pub struct Progress(Option<UnboundedSender<FetchProgress>>);
+
+impl Progress {
+    pub fn new(tx: UnboundedSender<FetchProgress>) -> Self {
+        Self(Some(tx))
+    }
+
+    pub fn report(&self, progress: FetchProgress) {
+        if let Some(tx) = &self.0 {
+            // Nobody listening any more is fine, the fetch goes on
+            let _ = tx.send(progress);
+        }
+    }
 }
 
-pub async fn create_client(config: &Config) -> Result<impl EmailClient> {
-    exchange::ExchangeClient::new(&config.exchange).await
+/// Name of the folder the archive action moves messages to.
+pub const ARCHIVE_FOLDER: &str = "Archive";
+
+/// A mail backend. Clients are `'static` so syncs can run on background tasks.
+pub trait EmailClient: 'static {
+    /// Accepts the server certificate with this fingerprint from now on.
+    fn trust_certificate(&self, fingerprint: &str);
+    /// Fetches the mail of `range` from now on instead of the current quarter.
+    fn set_date_range(&self, range: DateRange);
+
+    /// Requests made by this client so far in the session, by kind.
+    fn metrics(&self) -> Metrics {
+        Metrics::new()
+    }
+
+    /// Fetches the Inbox of the current quarter, or of the date range set, a page
+    /// at a time, reporting each page on `progress`.
+    async fn fetch_current_quarter_emails(&self, progress: &Progress) -> Result<Vec<Email>>;
+    /// Emails of the fetched date range in any folder, for views combining folders.
+    async fn fetch_folder_emails(&self, folder_id: &str) -> Result<Vec<Email>>;
+    /// Searches the whole mailbox on the server, not just the fetched date range.
+    async fn search_server(&self, query: &str) -> Result<Vec<Email>>;
+    /// Fetches the body of an email in the given format.
+    async fn fetch_body(&self, id: &str, format: BodyFormat) -> Result<String>;
+    /// Fetches the Internet headers of an email, which list calls don't include.
+    async fn fetch_headers(&self, id: &str) -> Result<Vec<Header>>;
+    /// Downloads the content of one of an email's attachments.
+    async fn fetch_attachment(&self, email_id: &str, attachment_id: &str) -> Result<Vec<u8>>;
+    /// Looks up people in the directory by part of their name or address.
+    async fn search_directory(&self, query: &str) -> Result<Vec<DirectoryEntry>>;
+    async fn fetch_folders(&self) -> Result<Vec<Folder>>;
+    async fn create_folder(&self, name: &str) -> Result<Folder>;
+    async fn rename_folder(&self, id: &str, name: &str) -> Result<()>;
+    /// Deletes a folder, moving it and its messages to Deleted Items.
+    async fn delete_folder(&self, id: &str) -> Result<()>;
+    /// Moves several emails in one call, failing without moving any if one doesn't exist.
+    async fn move_emails(&self, ids: &[String], folder_id: &str) -> Result<()>;
+    /// Moves emails to Deleted Items.
+    async fn delete_emails(&self, ids: &[String]) -> Result<()>;
+    async fn set_read(&self, ids: &[String], read: bool) -> Result<()>;
+
+    /// Moves an email to Junk Email and reports it to the server as junk or phishing.
+    async fn report_junk(&self, id: &str, report: JunkReport) -> Result<()>;
+
+    /// Answers a meeting request, which also removes it from the Inbox.
+    async fn respond_to_meeting(&self, id: &str, response: MeetingResponse) -> Result<()>;
+
+    /// Sends a message, applying the account's auto-BCC and sent copy settings.
+    async fn send_email(&self, message: &OutgoingEmail) -> Result<()>;
+    /// Every message in Drafts, newest first, whatever its date.
+    async fn fetch_drafts(&self) -> Result<Vec<Email>>;
+    /// Saves a message to Drafts without sending it, in place of its `draft_id`
+    /// if it has one, returning the id of the saved draft.
+    async fn save_draft(&self, message: &OutgoingEmail) -> Result<String>;
+
+    async fn get_oof_settings(&self) -> Result<OofSettings>;
+    async fn set_oof_settings(&self, settings: &OofSettings) -> Result<()>;
+
+    async fn fetch_tasks(&self) -> Result<Vec<Task>>;
+
+    /// Lists the Recoverable Items (dumpster) folder, soonest to be purged first.
+    async fn fetch_recoverable_items(&self) -> Result<Vec<RecoverableItem>>;
+    /// Restores a hard-deleted message back into the Inbox.
+    async fn restore_item(&self, id: &str) -> Result<()>;
+
+    /// Moves emails to the Archive folder, creating the folder if it doesn't exist yet.
+    async fn archive_emails(&self, ids: &[String]) -> Result<()> {
+        let folders = self.fetch_folders().await?;
+        let archive = match folders
+            .into_iter()
+            .find(|f| f.name.eq_ignore_ascii_case(ARCHIVE_FOLDER))
+        {
+            Some(folder) => folder,
+            None => self.create_folder(ARCHIVE_FOLDER).await?,
+        };
+        self.move_emails(ids, &archive.id).await
+    }
+}
+
+/// A configured mailbox and the client used to talk to it.
+pub struct Account<T: EmailClient> {
+    pub name: String,
+    pub client: T,
+}
+
+/// Creates a client per configured account, trusting the certificates pinned in `state`.
+pub async fn create_clients(
+    config: &Config,
+    state: &LocalState,
+) -> Result<Vec<Account<impl EmailClient>>> {
+    let mut accounts = Vec::new();
+    for account in config.accounts()? {
+        let pinned = state.pinned_fingerprint(&account.exchange.server);
+        accounts.push(Account {
+            client: Metered::new(
+                exchange::ExchangeClient::new(&account.name, &account.exchange, pinned).await?,
+            ),
+            name: account.name,
+        });
+    }
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_subject_strips_reply_and_forward_prefixes() {
+        let email = Email {
+            subject: "RE: Fwd: re:Budget Review".to_string(),
+            ..Email::test("1")
+        };
+        assert_eq!(email.thread_subject(), "budget review");
+    }
+
+    #[test]
+    fn test_date_range_steps_by_quarter() {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let q1 = DateRange::quarter(day(2024, 2, 15));
+        assert_eq!(q1.step_quarter(false).to_string(), "Q4 2023");
+        assert_eq!(q1.step_quarter(true).to_string(), "Q2 2024");
+
+        // A custom range steps from the quarter it starts in
+        let custom = DateRange {
+            start: day(2024, 5, 20),
+            end: day(2024, 8, 2),
+        };
+        assert_eq!(
+            custom.step_quarter(true),
+            DateRange {
+                start: day(2024, 7, 1),
+                end: day(2024, 9, 30),
+            }
+        );
+    }
 }
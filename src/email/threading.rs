@@ -0,0 +1,401 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::Email;
+
+/// What ties an email to its conversation.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThreadIds {
+    /// Conversation id assigned by the server, for backends that have them
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    /// The Message-ID header, without angle brackets
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// Message-IDs from References and In-Reply-To, oldest ancestor first
+    #[serde(default)]
+    pub references: Vec<String>,
+}
+
+/// A message in a conversation tree, or a gap for a message that replies refer
+/// to but that isn't among the threaded emails.
+#[derive(Debug, PartialEq)]
+pub struct Node {
+    /// Index of the email in the slice given to `threads`
+    pub email: Option<usize>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// Indices of the emails in this subtree, parents before their replies.
+    pub fn emails(&self) -> Vec<usize> {
+        let mut emails: Vec<usize> = self.email.into_iter().collect();
+        for child in &self.children {
+            emails.extend(child.emails());
+        }
+        emails
+    }
+
+    /// Adds the emails of this subtree to `out` with their depth, counted from
+    /// `depth`, parents before their replies. Gaps don't count as a level.
+    fn flatten(&self, depth: usize, out: &mut Vec<(usize, usize)>) {
+        let child_depth = match self.email {
+            Some(email) => {
+                out.push((email, depth));
+                depth + 1
+            }
+            None => depth,
+        };
+        for child in &self.children {
+            child.flatten(child_depth, out);
+        }
+    }
+}
+
+struct Container {
+    email: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// Threads emails by their Message-ID and References with Jamie Zawinski's
+/// algorithm (https://www.jwz.org/doc/threading.html), for backends without
+/// server-side conversation ids. Messages without references are grouped by
+/// subject. Conversations and replies come oldest first.
+pub fn threads(emails: &[&Email]) -> Vec<Node> {
+    let mut containers: Vec<Container> = Vec::new();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+
+    for (idx, email) in emails.iter().enumerate() {
+        let message_id = email.thread.message_id.as_deref().map(normalize_id);
+        let own = match message_id {
+            Some(id)
+                if !id.is_empty()
+                    && by_id
+                        .get(&id)
+                        .is_none_or(|&container| containers[container].email.is_none()) =>
+            {
+                container_for(&mut containers, &mut by_id, id)
+            }
+            // Without a Message-ID, or with one seen before, the email stands alone
+            _ => new_container(&mut containers),
+        };
+        containers[own].email = Some(idx);
+
+        // Link the reference chain parent to child, keeping links made earlier
+        let chain: Vec<usize> = email
+            .thread
+            .references
+            .iter()
+            .map(|id| normalize_id(id))
+            .filter(|id| !id.is_empty())
+            .map(|id| container_for(&mut containers, &mut by_id, id))
+            .collect();
+        for pair in chain.windows(2) {
+            let (parent, child) = (pair[0], pair[1]);
+            if containers[child].parent.is_none() && !is_ancestor(&containers, child, parent) {
+                link(&mut containers, parent, child);
+            }
+        }
+
+        // The email itself replies to the last reference, whatever was linked before
+        unlink(&mut containers, own);
+        if let Some(&parent) = chain.last() {
+            if !is_ancestor(&containers, own, parent) {
+                link(&mut containers, parent, own);
+            }
+        }
+    }
+
+    let mut roots: Vec<Node> = (0..containers.len())
+        .filter(|&c| containers[c].parent.is_none())
+        .flat_map(|c| prune(&containers, c, true))
+        .collect();
+    roots = group_by_subject(emails, roots);
+    sort_by_date(emails, &mut roots);
+    roots
+}
+
+/// The emails of each conversation, as indices into `emails` with their depth
+/// in the conversation. Emails with a server conversation id are grouped by
+/// it, oldest first with the others one level below; the rest by `threads`.
+pub fn conversations(emails: &[&Email]) -> Vec<Vec<(usize, usize)>> {
+    let mut by_server: Vec<(&str, Vec<usize>)> = Vec::new();
+    let mut unthreaded = Vec::new();
+    for (idx, email) in emails.iter().enumerate() {
+        match email.thread.conversation_id.as_deref() {
+            Some(id) => match by_server.iter_mut().find(|(other, _)| *other == id) {
+                Some((_, members)) => members.push(idx),
+                None => by_server.push((id, vec![idx])),
+            },
+            None => unthreaded.push(idx),
+        }
+    }
+
+    let mut conversations: Vec<Vec<(usize, usize)>> = by_server
+        .into_iter()
+        .map(|(_, mut members)| {
+            members.sort_by_key(|&idx| emails[idx].date);
+            members
+                .into_iter()
+                .enumerate()
+                .map(|(n, idx)| (idx, n.min(1)))
+                .collect()
+        })
+        .collect();
+
+    let rest: Vec<&Email> = unthreaded.iter().map(|&idx| emails[idx]).collect();
+    for root in threads(&rest) {
+        let mut members = Vec::new();
+        root.flatten(0, &mut members);
+        conversations.push(
+            members
+                .into_iter()
+                .map(|(idx, depth)| (unthreaded[idx], depth))
+                .collect(),
+        );
+    }
+    conversations
+}
+
+/// A key per email that is the same for all emails of a conversation.
+pub fn thread_keys(emails: &[&Email]) -> Vec<String> {
+    let mut keys = vec![String::new(); emails.len()];
+    for (n, conversation) in conversations(emails).iter().enumerate() {
+        for &(idx, _) in conversation {
+            keys[idx] = n.to_string();
+        }
+    }
+    keys
+}
+
+fn normalize_id(id: &str) -> String {
+    id.trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string()
+}
+
+fn new_container(containers: &mut Vec<Container>) -> usize {
+    containers.push(Container {
+        email: None,
+        parent: None,
+        children: Vec::new(),
+    });
+    containers.len() - 1
+}
+
+fn container_for(
+    containers: &mut Vec<Container>,
+    by_id: &mut HashMap<String, usize>,
+    id: String,
+) -> usize {
+    if let Some(&container) = by_id.get(&id) {
+        return container;
+    }
+    let container = new_container(containers);
+    by_id.insert(id, container);
+    container
+}
+
+/// Whether `ancestor` is `container` or one of its parents.
+fn is_ancestor(containers: &[Container], ancestor: usize, container: usize) -> bool {
+    let mut current = Some(container);
+    while let Some(c) = current {
+        if c == ancestor {
+            return true;
+        }
+        current = containers[c].parent;
+    }
+    false
+}
+
+fn link(containers: &mut [Container], parent: usize, child: usize) {
+    containers[child].parent = Some(parent);
+    containers[parent].children.push(child);
+}
+
+fn unlink(containers: &mut [Container], child: usize) {
+    if let Some(parent) = containers[child].parent.take() {
+        containers[parent].children.retain(|&c| c != child);
+    }
+}
+
+/// The nodes standing for a container: gaps without replies are dropped, and
+/// gaps with replies hand them to their parent, except at the top where a gap
+/// holds several replies together as one conversation.
+fn prune(containers: &[Container], container: usize, root: bool) -> Vec<Node> {
+    let children: Vec<Node> = containers[container]
+        .children
+        .iter()
+        .flat_map(|&child| prune(containers, child, false))
+        .collect();
+    match containers[container].email {
+        Some(email) => vec![Node {
+            email: Some(email),
+            children,
+        }],
+        None if !root || children.len() <= 1 => children,
+        None => vec![Node {
+            email: None,
+            children,
+        }],
+    }
+}
+
+/// The subject a conversation is known by, from its first message.
+fn subject_of<'a>(emails: &[&'a Email], node: &Node) -> Option<&'a Email> {
+    match node.email {
+        Some(idx) => Some(emails[idx]),
+        None => node
+            .children
+            .iter()
+            .find_map(|child| subject_of(emails, child)),
+    }
+}
+
+fn is_reply(email: &Email) -> bool {
+    email.subject.trim().to_lowercase() != email.thread_subject()
+}
+
+/// Joins conversations that share a subject, as replies from clients that drop
+/// the References header would otherwise start threads of their own.
+fn group_by_subject(emails: &[&Email], roots: Vec<Node>) -> Vec<Node> {
+    let mut roots: Vec<Option<Node>> = roots.into_iter().map(Some).collect();
+
+    // Pick the conversation each subject gathers into: a gap, else one not a reply
+    let mut by_subject: HashMap<String, usize> = HashMap::new();
+    for (i, root) in roots.iter().enumerate() {
+        let root = root.as_ref().unwrap();
+        let Some(email) = subject_of(emails, root) else {
+            continue;
+        };
+        let subject = email.thread_subject();
+        if subject.is_empty() {
+            continue;
+        }
+        let better = match by_subject.get(&subject) {
+            None => true,
+            Some(&other) => {
+                let other = roots[other].as_ref().unwrap();
+                (root.email.is_none() && other.email.is_some())
+                    || (other.email.is_some_and(|idx| is_reply(emails[idx]))
+                        && root.email.is_some_and(|idx| !is_reply(emails[idx])))
+            }
+        };
+        if better {
+            by_subject.insert(subject, i);
+        }
+    }
+
+    for i in 0..roots.len() {
+        let Some(subject) = roots[i]
+            .as_ref()
+            .and_then(|root| subject_of(emails, root))
+            .map(|email| email.thread_subject())
+        else {
+            continue;
+        };
+        let Some(&target) = by_subject.get(&subject) else {
+            continue;
+        };
+        if target == i {
+            continue;
+        }
+        let node = roots[i].take().unwrap();
+        let into = roots[target].as_mut().unwrap();
+        match (into.email, node.email) {
+            (None, None) => into.children.extend(node.children),
+            (None, Some(_)) => into.children.push(node),
+            (Some(idx), Some(reply)) if !is_reply(emails[idx]) && is_reply(emails[reply]) => {
+                into.children.push(node)
+            }
+            _ => {
+                let existing = std::mem::replace(
+                    into,
+                    Node {
+                        email: None,
+                        children: Vec::new(),
+                    },
+                );
+                into.children = vec![existing, node];
+            }
+        }
+    }
+    roots.into_iter().flatten().collect()
+}
+
+fn sort_by_date(emails: &[&Email], nodes: &mut [Node]) {
+    for node in nodes.iter_mut() {
+        sort_by_date(emails, &mut node.children);
+    }
+    nodes.sort_by_key(|node| node.emails().into_iter().map(|idx| emails[idx].date).min());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn email(n: i64, subject: &str, message_id: Option<&str>, references: &[&str]) -> Email {
+        let email = Email::test(&n.to_string());
+        Email {
+            subject: subject.to_string(),
+            date: email.date + Duration::hours(n),
+            read: true,
+            thread: ThreadIds {
+                conversation_id: None,
+                message_id: message_id.map(str::to_string),
+                references: references.iter().map(|id| id.to_string()).collect(),
+            },
+            ..email
+        }
+    }
+
+    #[test]
+    fn test_threads_by_references_then_subject() {
+        let emails = [
+            email(3, "Re: Budget", Some("<c@x>"), &["<a@x>", "<b@x>"]),
+            email(1, "Budget", Some("<a@x>"), &[]),
+            email(2, "Re: Budget", Some("<b@x>"), &["<a@x>"]),
+            // A reply whose client dropped References
+            email(4, "RE: budget", None, &[]),
+            // Two replies to a message that wasn't fetched
+            email(5, "Re: Offsite", Some("<e@x>"), &["<lost@x>"]),
+            email(6, "Re: Offsite", Some("<f@x>"), &["<lost@x>"]),
+            email(7, "Lunch", Some("<g@x>"), &[]),
+        ];
+        let refs: Vec<&Email> = emails.iter().collect();
+        let leaf = |email| Node {
+            email: Some(email),
+            children: Vec::new(),
+        };
+
+        assert_eq!(
+            threads(&refs),
+            vec![
+                Node {
+                    email: Some(1),
+                    children: vec![
+                        Node {
+                            email: Some(2),
+                            children: vec![leaf(0)],
+                        },
+                        leaf(3),
+                    ],
+                },
+                Node {
+                    email: None,
+                    children: vec![leaf(4), leaf(5)],
+                },
+                leaf(6),
+            ]
+        );
+
+        let keys = thread_keys(&refs);
+        assert!(keys[..4].iter().all(|key| *key == keys[0]));
+        assert_eq!(keys[4], keys[5]);
+        assert_ne!(keys[0], keys[4]);
+        assert_ne!(keys[6], keys[0]);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::email::Email;
+
+/// Score of each character of the pattern found in the text.
+const MATCH: i64 = 16;
+/// Extra for a character starting a word, like `q` in `Q2 quarterly report`.
+const WORD_START: i64 = 8;
+/// Extra for an upper case letter after a lower case one, like `U` in `PullUp`.
+const CAMEL_CASE: i64 = 6;
+/// Extra for a character right after the previous one found.
+const CONSECUTIVE: i64 = 5;
+/// Cost of skipping text between two characters found, and of every further
+/// character skipped.
+const GAP_START: i64 = 3;
+const GAP_EXTENSION: i64 = 1;
+
+/// How well `pattern` matches `text` the way fzf does, with its characters in
+/// order but not necessarily next to each other, ignoring case. Characters
+/// found together or at the start of words score higher. `None` when some
+/// character isn't there.
+pub fn score(pattern: &str, text: &str) -> Option<i64> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let text = characters(text);
+    if text.len() < pattern.len() {
+        return None;
+    }
+
+    // best[j]: the best score with the pattern so far ending on text[j]
+    let mut best: Vec<Option<i64>> = text
+        .iter()
+        .map(|&(c, bonus)| (c == pattern[0]).then_some(MATCH + bonus))
+        .collect();
+    for &p in &pattern[1..] {
+        let mut next = vec![None; text.len()];
+        // The best score ending before j - 1 with the gap up to j paid for
+        let mut gapped: Option<i64> = None;
+        for j in 1..text.len() {
+            if j >= 2 {
+                let skipped = best[j - 2].map(|score| score - GAP_START);
+                gapped = gapped.map(|score| score - GAP_EXTENSION).max(skipped);
+            }
+            let (c, bonus) = text[j];
+            if c != p {
+                continue;
+            }
+            let adjacent = best[j - 1].map(|score| score + CONSECUTIVE);
+            next[j] = adjacent.max(gapped).map(|score| score + MATCH + bonus);
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+/// How well every word of `pattern` matches the subject or sender of `email`,
+/// added up, or `None` when a word matches neither.
+pub fn score_email(pattern: &str, email: &Email) -> Option<i64> {
+    pattern
+        .split_whitespace()
+        .map(|word| score(word, &email.subject).max(score(word, &email.sender)))
+        .sum()
+}
+
+/// The lower case characters of `text` with the bonus for finding each.
+fn characters(text: &str) -> Vec<(char, i64)> {
+    let mut characters = Vec::with_capacity(text.len());
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        let bonus = match previous {
+            None => WORD_START,
+            Some(p) if !p.is_alphanumeric() && c.is_alphanumeric() => WORD_START,
+            Some(p) if p.is_lowercase() && c.is_uppercase() => CAMEL_CASE,
+            Some(_) => 0,
+        };
+        characters.extend(c.to_lowercase().map(|lower| (lower, bonus)));
+        previous = Some(c);
+    }
+    characters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scores_favour_word_starts_and_runs() {
+        assert!(score("qrep", "Q2 quarterly report").is_some());
+        assert_eq!(score("report q", "Q2 quarterly report"), None);
+        assert_eq!(score("xyz", "Q2 quarterly report"), None);
+
+        // Starts of words beat letters in the middle of them
+        assert!(score("qr", "Quarterly Report") > score("qr", "aquarium"));
+        // Letters together beat letters spread out
+        assert!(score("rep", "the report") > score("rep", "the ruler step"));
+        assert!(score("pu", "PullUp") > score("pu", "spun"));
+    }
+}
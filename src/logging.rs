@@ -0,0 +1,254 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+use crate::state;
+
+/// Size past which the log moves to `log.1`, `log.1` to `log.2` and so on.
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated logs kept besides the current one.
+const KEEP_LOGS: usize = 3;
+/// Events of other crates are only logged from this many `-v` flags on.
+const DEPENDENCY_VERBOSITY: u8 = 3;
+
+/// The most verbose level logged for the number of `-v` flags: warnings and
+/// errors by default, then info, debug and trace.
+pub fn level(verbosity: u8) -> Level {
+    match verbosity {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Sends `tracing` events to `~/.local/state/mail-tui/log`, as the TUI has stdout.
+pub fn init(verbosity: u8) -> Result<()> {
+    let Some(dir) = state::state_dir() else {
+        return Ok(());
+    };
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let subscriber = FileSubscriber {
+        level: level(verbosity),
+        dependencies: verbosity >= DEPENDENCY_VERBOSITY,
+        log: Mutex::new(LogFile::open(dir.join("log"))?),
+        spans: Mutex::new(HashMap::new()),
+        next_id: AtomicU64::new(1),
+    };
+    tracing::subscriber::set_global_default(subscriber).context("Failed to set up logging")
+}
+
+/// Appends to the log, rotating it once it grows past `MAX_LOG_BYTES`.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size })
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > MAX_LOG_BYTES {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        for n in (1..KEEP_LOGS).rev() {
+            let from = rotated(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(&self.path, 1))?;
+        *self = Self::open(self.path.clone())?;
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+struct SpanData {
+    /// The span's name and fields, e.g. `sync{account=work}`
+    label: String,
+    references: usize,
+}
+
+thread_local! {
+    /// Spans entered on this thread, innermost last
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Writes one line per event, prefixed with the spans it happened in.
+struct FileSubscriber {
+    level: Level,
+    dependencies: bool,
+    log: Mutex<LogFile>,
+    spans: Mutex<HashMap<u64, SpanData>>,
+    next_id: AtomicU64,
+}
+
+impl Subscriber for FileSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.level
+            && (self.dependencies || metadata.target().starts_with(env!("CARGO_CRATE_NAME")))
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(LevelFilter::from_level(self.level))
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let label = match fields.0.trim_start() {
+            "" => span.metadata().name().to_string(),
+            fields => format!("{}{{{}}}", span.metadata().name(), fields),
+        };
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.spans.lock().unwrap().insert(
+            id,
+            SpanData {
+                label,
+                references: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let spans = {
+            let spans = self.spans.lock().unwrap();
+            ENTERED.with(|entered| {
+                entered
+                    .borrow()
+                    .iter()
+                    .filter_map(|id| spans.get(id))
+                    .map(|span| format!("{}: ", span.label))
+                    .collect::<String>()
+            })
+        };
+        let metadata = event.metadata();
+        let line = format!(
+            "{} {:>5} {}: {}{}\n",
+            Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+            metadata.level(),
+            metadata.target(),
+            spans,
+            fields.0.trim_start()
+        );
+        // There is nowhere to report a failure to log
+        let _ = self.log.lock().unwrap().write_line(&line);
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(pos) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(pos);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(data) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            data.references += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap();
+        let Some(data) = spans.get_mut(&span.into_u64()) else {
+            return false;
+        };
+        data.references -= 1;
+        if data.references > 0 {
+            return false;
+        }
+        spans.remove(&span.into_u64());
+        true
+    }
+}
+
+/// Formats the message followed by the other fields as `name=value`.
+#[derive(Default)]
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {}", value);
+        } else {
+            let _ = write!(self.0, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_rotates_past_the_size_limit() {
+        let dir = std::env::temp_dir().join(format!("mail-tui-log-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log");
+        let line = "x".repeat(MAX_LOG_BYTES as usize / 2 + 1);
+
+        let mut log = LogFile::open(path.clone()).unwrap();
+        for _ in 0..(KEEP_LOGS + 2) {
+            log.write_line(&line).unwrap();
+        }
+
+        assert_eq!(fs::metadata(&path).unwrap().len(), line.len() as u64);
+        assert!(rotated(&path, KEEP_LOGS).exists());
+        assert!(!rotated(&path, KEEP_LOGS + 1).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
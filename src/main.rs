@@ -1,9 +1,23 @@
+mod activity;
+mod cache;
 mod config;
+mod daemon;
+mod downloads;
+mod draft;
 mod email;
+mod fuzzy;
+mod logging;
+mod outbox;
+mod query;
+mod signals;
+mod state;
 mod ui;
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use email::{Email, EmailClient};
+use std::fs::File;
+use std::io::{self, Write};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -12,21 +26,209 @@ struct Cli {
     /// Path to config file
     #[arg(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
+
+    /// Log more to ~/.local/state/mail-tui/log: -v info, -vv debug, -vvv everything
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Export the recorded activity log as CSV, one row per day
+    ExportActivity {
+        /// Write to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Search every account on the server and in the offline cache, without the TUI
+    Search {
+        /// Same syntax as the search box, e.g. "from:boss after:2024-04-01 budget"
+        query: String,
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Fetch every account into the offline cache without the TUI
+    Sync {
+        /// Keep syncing every `[sync] interval` seconds and notify about new mail
+        #[arg(long)]
+        daemon: bool,
+    },
+    /// Manage the offline cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Evict mail past the `[cache]` limits and shrink the cache file
+    Prune,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    if let Err(e) = logging::init(cli.verbose) {
+        eprintln!("Continuing without a log: {:#}", e);
+    }
 
     // Load configuration
-    let config = config::load_config(cli.config)?;
+    let config = match cli.command {
+        Some(Command::ExportActivity { output }) => return export_activity(output),
+        Some(Command::Search { query, json }) => {
+            let config = config::load_config(cli.config)?;
+            return search(&config, &query, json).await;
+        }
+        Some(Command::Sync { daemon }) => {
+            let config = config::load_config(cli.config)?;
+            return daemon::run(&config, daemon).await;
+        }
+        Some(Command::Cache {
+            command: CacheCommand::Prune,
+        }) => {
+            let config = config::load_config(cli.config)?;
+            return prune_cache(&config);
+        }
+        None => config::load_config(cli.config)?,
+    };
+
+    // Load state kept from previous sessions
+    let local_state = state::LocalState::load()?;
+
+    // Initialize a client per configured account
+    let accounts = email::create_clients(&config, &local_state).await?;
+
+    // Initialize and run the UI application, which opens the cache once it is on screen
+    let mut app = ui::app::App::new(accounts, &config, local_state);
+    // Background syncs run as local tasks, as the clients' futures aren't Send
+    tokio::task::LocalSet::new().run_until(app.run()).await?;
+
+    Ok(())
+}
+
+fn export_activity(output: Option<PathBuf>) -> Result<()> {
+    let entries = activity::read_entries()?;
+    match output {
+        Some(path) => {
+            let mut file = File::create(&path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            activity::export_csv(&entries, &mut file)?;
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            activity::export_csv(&entries, &mut stdout)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn prune_cache(config: &config::Config) -> Result<()> {
+    let Some(cache) = cache::Cache::open(&config.cache)? else {
+        bail!("No cache directory found");
+    };
+    let evicted = cache.prune()?;
+    cache.vacuum()?;
+    println!(
+        "Evicted {} emails, the cache now takes {:.1} MB",
+        evicted,
+        cache.size()? as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+/// Prints the emails matching `query` from every account's server, falling back
+/// to the offline cache for accounts that can't be reached.
+async fn search(config: &config::Config, query: &str, json: bool) -> Result<()> {
+    let parsed = query::Query::parse(query, &config.groups)?;
+    if parsed.is_empty() {
+        bail!("Give a query to search for");
+    }
 
-    // Initialize email client
-    let email_client = email::create_client(&config).await?;
+    let local_state = state::LocalState::load()?;
+    let accounts = email::create_clients(config, &local_state).await?;
+    let cached = match cache::Cache::open(&config.cache) {
+        Ok(Some(cache)) => cache.load()?,
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            eprintln!("Searching without the offline cache: {:#}", e);
+            Vec::new()
+        }
+    };
 
-    // Initialize and run the UI application
-    let mut app = ui::app::App::new(email_client);
-    app.run().await?;
+    let limit =
+        ui::loading::Timeouts::new(&config.timeouts).get(ui::loading::Operation::ServerSearch);
+    let server_text = parsed.server_text();
+    let mut results: Vec<Email> = Vec::new();
+    for account in &accounts {
+        let request = account.client.search_server(&server_text);
+        let found = match ui::loading::with_timeout(limit, request).await {
+            Ok(emails) => emails,
+            Err(e) => {
+                eprintln!(
+                    "{}: server search failed, using cached mail: {:#}",
+                    account.name, e
+                );
+                Vec::new()
+            }
+        };
+        let found = found
+            .into_iter()
+            .filter(|email| parsed.matches_envelope(email));
+        let from_cache = cached
+            .iter()
+            .filter(|email| email.account == account.name && parsed.matches(email))
+            .cloned();
+        for email in found.chain(from_cache) {
+            let known = results
+                .iter()
+                .any(|e| e.account == email.account && e.id == email.id);
+            if !known {
+                results.push(email);
+            }
+        }
+    }
+    results.sort_by_key(|email| std::cmp::Reverse(email.date));
 
+    let mut stdout = io::stdout().lock();
+    if json {
+        let emails: Vec<serde_json::Value> = results
+            .iter()
+            .map(|email| {
+                serde_json::json!({
+                    "account": email.account,
+                    "id": email.id,
+                    "date": email.date.to_rfc3339(),
+                    "from": email.sender,
+                    "to": email.to,
+                    "cc": email.cc,
+                    "subject": email.subject,
+                    "read": email.read,
+                })
+            })
+            .collect();
+        serde_json::to_writer_pretty(&mut stdout, &emails)?;
+        writeln!(stdout)?;
+    } else {
+        for email in &results {
+            writeln!(
+                stdout,
+                "{}  {}  {}  {}",
+                email
+                    .date
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M"),
+                email.account,
+                email.sender,
+                email.subject
+            )?;
+        }
+    }
+    stdout.flush()?;
     Ok(())
 }
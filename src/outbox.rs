@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::email::OutgoingEmail;
+use crate::state;
+
+/// Where a message in the outbox is on its way out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SendState {
+    Queued,
+    Sending,
+    Failed { error: String },
+}
+
+impl SendState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SendState::Queued => "queued",
+            SendState::Sending => "sending",
+            SendState::Failed { .. } => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: u64,
+    /// Name of the account the message is sent from
+    pub account: String,
+    pub message: OutgoingEmail,
+    pub state: SendState,
+    pub queued_at: DateTime<Utc>,
+}
+
+/// Every outgoing message until the server accepted it, kept on disk so a
+/// failed send is never lost.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Outbox {
+    pub entries: Vec<OutboxEntry>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Outbox {
+    pub fn load() -> Result<Self> {
+        let Some(path) = state::state_dir().map(|dir| dir.join("outbox.json")) else {
+            return Ok(Self::default());
+        };
+
+        let mut outbox = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            Self::default()
+        };
+        outbox.path = Some(path);
+        outbox.fail_interrupted();
+        Ok(outbox)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Marks sends cut short by the app exiting as failed, as there is no telling
+    /// whether the server got them.
+    fn fail_interrupted(&mut self) {
+        for entry in &mut self.entries {
+            if entry.state == SendState::Sending {
+                entry.state = SendState::Failed {
+                    error: "Interrupted while sending, check Sent Items before retrying"
+                        .to_string(),
+                };
+            }
+        }
+    }
+
+    /// Adds a message to send, returning its id.
+    pub fn queue(&mut self, account: &str, message: OutgoingEmail) -> u64 {
+        let id = self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        self.entries.push(OutboxEntry {
+            id,
+            account: account.to_string(),
+            message,
+            state: SendState::Queued,
+            queued_at: Utc::now(),
+        });
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&OutboxEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut OutboxEntry> {
+        self.entries.iter_mut().find(|entry| entry.id == id)
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.entries.retain(|entry| entry.id != id);
+    }
+
+    /// Messages waiting to be sent or retried, oldest first.
+    pub fn unsent(&self) -> Vec<u64> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.state != SendState::Sending)
+            .map(|entry| entry.id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interrupted_sends_fail_on_load() {
+        let mut outbox = Outbox::default();
+        let first = outbox.queue("work", OutgoingEmail::default());
+        let second = outbox.queue("work", OutgoingEmail::default());
+        assert_eq!((first, second), (1, 2));
+
+        outbox.get_mut(first).unwrap().state = SendState::Sending;
+        let json = serde_json::to_string(&outbox).unwrap();
+        let mut loaded: Outbox = serde_json::from_str(&json).unwrap();
+        loaded.fail_interrupted();
+
+        assert_eq!(loaded.get(first).unwrap().state.label(), "failed");
+        assert_eq!(loaded.get(second).unwrap().state, SendState::Queued);
+        assert_eq!(loaded.unsent(), vec![first, second]);
+    }
+}
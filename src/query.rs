@@ -0,0 +1,475 @@
+use anyhow::{anyhow, Result};
+use chrono::{Local, NaiveDate};
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::email::{Attachment, Email};
+
+/// Named lists of addresses from the `[groups]` config, e.g. direct reports.
+pub type Groups = HashMap<String, Vec<String>>;
+
+/// A parsed search query, shared by the search box and `mail-tui search`.
+///
+/// Words match the subject, sender or body and must all be present. Quoted
+/// phrases match as a whole, and `from:`, `to:`, `subject:`, `after:` and
+/// `before:` narrow the search to one field, e.g. `from:boss after:2024-04-01`.
+/// `has:attachment`, `filename:` and `filetype:` look at attachment names and
+/// types. `is:unread`, `is:read` and `is:flagged` look at the email's state.
+/// `re:` takes a regular expression matched against the subject, sender or
+/// body, ignoring case, e.g. `re:inv-\d{4}`. `from:@team` and `to:@team`
+/// match any address of a group from the config. A leading `-` excludes mail
+/// matching that word or field instead.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pub text: Vec<String>,
+    /// Regular expressions from `re:`, matched like `text`
+    pub patterns: Vec<Pattern>,
+    pub from: Vec<String>,
+    /// Matches To and Cc recipients
+    pub to: Vec<String>,
+    pub subject: Vec<String>,
+    /// Groups of which the sender must be one, from `from:@group`
+    pub from_groups: Vec<Vec<String>>,
+    /// Groups of which a recipient must be one, from `to:@group`
+    pub to_groups: Vec<Vec<String>>,
+    /// Only mail received on or after this local day
+    pub after: Option<NaiveDate>,
+    /// Only mail received before this local day
+    pub before: Option<NaiveDate>,
+    /// Only mail with at least one attachment, from `has:attachment`
+    pub has_attachment: bool,
+    /// Parts of attachment names, from `filename:`
+    pub filename: Vec<String>,
+    /// Attachment extensions or MIME subtypes, from `filetype:`, e.g. `pdf`
+    pub filetype: Vec<String>,
+    /// Only unread mail, or only read mail, from `is:unread` and `is:read`
+    pub read: Option<bool>,
+    /// Only flagged mail, from `is:flagged`
+    pub flagged: bool,
+    /// Single-term queries that must not match, from `-term` or `-field:value`
+    pub exclude: Vec<Query>,
+}
+
+impl Query {
+    pub fn parse(input: &str, groups: &Groups) -> Result<Self> {
+        let mut query = Query::default();
+        for token in tokenize(input) {
+            match token.strip_prefix('-').filter(|t| !t.is_empty()) {
+                Some(excluded) => {
+                    let mut term = Query::default();
+                    term.add_term(excluded, groups)?;
+                    query.exclude.push(term);
+                }
+                None => query.add_term(&token, groups)?,
+            }
+        }
+        Ok(query)
+    }
+
+    /// Adds a single word, phrase or `field:value`.
+    fn add_term(&mut self, token: &str, groups: &Groups) -> Result<()> {
+        let Some((field, value)) = token.split_once(':').filter(|(_, v)| !v.is_empty()) else {
+            self.text.push(token.to_lowercase());
+            return Ok(());
+        };
+        if field.eq_ignore_ascii_case("re") {
+            let regex = RegexBuilder::new(value)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| {
+                    // Syntax errors draw the pattern with a caret over several lines
+                    let message = e.to_string();
+                    let reason = message.lines().last().unwrap_or_default();
+                    let reason = reason.trim_start_matches("error: ");
+                    anyhow!("'{}' is not a valid pattern: {}", value, reason)
+                })?;
+            self.patterns.push(Pattern(regex));
+            return Ok(());
+        }
+        let value = value.to_lowercase();
+        match field.to_lowercase().as_str() {
+            "from" if value.starts_with('@') => self.from_groups.push(group(groups, &value)?),
+            "to" if value.starts_with('@') => self.to_groups.push(group(groups, &value)?),
+            "from" => self.from.push(value),
+            "to" => self.to.push(value),
+            "subject" => self.subject.push(value),
+            "after" => self.after = Some(parse_date(&value)?),
+            "before" => self.before = Some(parse_date(&value)?),
+            "has" if matches!(value.as_str(), "attachment" | "attachments") => {
+                self.has_attachment = true
+            }
+            "has" => {
+                return Err(anyhow!(
+                    "'has:{}' is not supported, try has:attachment",
+                    value
+                ))
+            }
+            "is" => match value.as_str() {
+                "unread" => self.read = Some(false),
+                "read" => self.read = Some(true),
+                "flagged" => self.flagged = true,
+                _ => {
+                    return Err(anyhow!(
+                        "'is:{}' is not supported, try is:unread, is:read or is:flagged",
+                        value
+                    ))
+                }
+            },
+            "filename" => self.filename.push(value),
+            "filetype" => self
+                .filetype
+                .push(value.trim_start_matches('.').to_string()),
+            _ => self.text.push(token.to_lowercase()),
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Query::default()
+    }
+
+    /// The words to send to a server search, which narrows the results before
+    /// `matches_envelope` applies the rest of the query.
+    pub fn server_text(&self) -> String {
+        self.text.join(" ")
+    }
+
+    /// Like `matches`, for server search results, which come without their
+    /// bodies. The server already matched the words, in the body too, so only
+    /// the envelope fields are checked. Patterns and excluded words could be
+    /// anywhere in the body, so they don't drop a result either.
+    pub fn matches_envelope(&self, email: &Email) -> bool {
+        let envelope = Query {
+            text: Vec::new(),
+            patterns: Vec::new(),
+            exclude: self
+                .exclude
+                .iter()
+                .filter(|excluded| !excluded.reads_body())
+                .cloned()
+                .collect(),
+            ..self.clone()
+        };
+        envelope.matches(email)
+    }
+
+    /// Whether the query looks into the body, through words or patterns.
+    fn reads_body(&self) -> bool {
+        !self.text.is_empty() || !self.patterns.is_empty()
+    }
+
+    pub fn matches(&self, email: &Email) -> bool {
+        let subject = email.subject.to_lowercase();
+        let sender = email.sender.to_lowercase();
+        let recipient_list: Vec<String> = email
+            .to
+            .iter()
+            .chain(email.cc.iter())
+            .map(|address| address.to_lowercase())
+            .collect();
+        let recipients = recipient_list.join(" ");
+        let body = email.body.text().to_lowercase();
+
+        let date = email.date.with_timezone(&Local).date_naive();
+        self.text
+            .iter()
+            .all(|term| subject.contains(term) || sender.contains(term) || body.contains(term))
+            && self.patterns.iter().all(|Pattern(regex)| {
+                regex.is_match(&subject) || regex.is_match(&sender) || regex.is_match(&body)
+            })
+            && self.from.iter().all(|term| sender.contains(term))
+            && self.to.iter().all(|term| recipients.contains(term))
+            && self.subject.iter().all(|term| subject.contains(term))
+            && self.from_groups.iter().all(|group| group.contains(&sender))
+            && self.to_groups.iter().all(|group| {
+                recipient_list
+                    .iter()
+                    .any(|recipient| group.contains(recipient))
+            })
+            && self.after.is_none_or(|after| date >= after)
+            && self.before.is_none_or(|before| date < before)
+            && (!self.has_attachment || !email.attachments.is_empty())
+            && self.read.is_none_or(|read| email.read == read)
+            && (!self.flagged || email.flagged)
+            && self.filename.iter().all(|term| {
+                email
+                    .attachments
+                    .iter()
+                    .any(|a| a.name.to_lowercase().contains(term))
+            })
+            && self
+                .filetype
+                .iter()
+                .all(|kind| email.attachments.iter().any(|a| file_type_matches(a, kind)))
+            && !self.exclude.iter().any(|excluded| excluded.matches(email))
+    }
+}
+
+/// Where in an email a search term can match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Subject,
+    Sender,
+    Body,
+}
+
+/// Finds what a query matched in each field, to highlight it.
+#[derive(Clone, Debug, Default)]
+pub struct Highlighter {
+    subject: Option<Regex>,
+    sender: Option<Regex>,
+    body: Option<Regex>,
+}
+
+impl Highlighter {
+    /// Matches the words, phrases and patterns of `query` in every field, and
+    /// `from:` and `subject:` in their own. Excluded terms are never highlighted.
+    pub fn new(query: &Query) -> Self {
+        let terms: Vec<String> = query
+            .text
+            .iter()
+            .map(|term| regex::escape(term))
+            .chain(
+                query
+                    .patterns
+                    .iter()
+                    .map(|Pattern(regex)| regex.to_string()),
+            )
+            .collect();
+        let with = |field: &[String]| {
+            let escaped = field.iter().map(|term| regex::escape(term));
+            alternation(terms.iter().cloned().chain(escaped))
+        };
+        Self {
+            subject: with(&query.subject),
+            sender: with(&query.from),
+            body: alternation(terms.iter().cloned()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subject.is_none() && self.sender.is_none() && self.body.is_none()
+    }
+
+    /// The byte ranges of `text` matched in `field`, in order and not overlapping.
+    pub fn ranges(&self, field: Field, text: &str) -> Vec<Range<usize>> {
+        let regex = match field {
+            Field::Subject => &self.subject,
+            Field::Sender => &self.sender,
+            Field::Body => &self.body,
+        };
+        regex.as_ref().map_or_else(Vec::new, |regex| {
+            regex
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect()
+        })
+    }
+}
+
+/// A case-insensitive regex matching any of `alternatives`, if there are any.
+fn alternation(alternatives: impl Iterator<Item = String>) -> Option<Regex> {
+    let source = alternatives
+        .map(|alternative| format!("(?:{})", alternative))
+        .collect::<Vec<_>>()
+        .join("|");
+    if source.is_empty() {
+        return None;
+    }
+    RegexBuilder::new(&source)
+        .case_insensitive(true)
+        .build()
+        .ok()
+}
+
+/// A compiled `re:` pattern, equal to another with the same source.
+#[derive(Clone, Debug)]
+pub struct Pattern(pub Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// Whether an attachment is of a type like `pdf`, by its extension or the
+/// subtype of its MIME type.
+fn file_type_matches(attachment: &Attachment, kind: &str) -> bool {
+    let extension = attachment
+        .name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase());
+    let subtype = attachment
+        .content_type
+        .split_once('/')
+        .map(|(_, subtype)| subtype.to_lowercase());
+    extension.as_deref() == Some(kind) || subtype.as_deref() == Some(kind)
+}
+
+/// Splits on whitespace, keeping double-quoted parts together without the quotes.
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// The lowercased addresses of the group named by `@name`.
+fn group(groups: &Groups, value: &str) -> Result<Vec<String>> {
+    let name = &value[1..];
+    groups
+        .iter()
+        .find(|(group, _)| group.eq_ignore_ascii_case(name))
+        .map(|(_, addresses)| addresses.iter().map(|a| a.to_lowercase()).collect())
+        .ok_or_else(|| anyhow!("There is no group named '{}' in [groups]", name))
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow!("'{}' is not a date like 2024-04-01", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fields_dates_and_phrases() {
+        let query = Query::parse(
+            "from:Boss after:2024-04-01 \"budget review\" subject:\"Q2 plan\" draft",
+            &Groups::new(),
+        )
+        .unwrap();
+
+        assert_eq!(query.from, vec!["boss"]);
+        assert_eq!(query.subject, vec!["q2 plan"]);
+        assert_eq!(query.text, vec!["budget review", "draft"]);
+        assert_eq!(query.after, NaiveDate::from_ymd_opt(2024, 4, 1));
+        assert_eq!(query.server_text(), "budget review draft");
+        assert!(Query::parse("before:yesterday", &Groups::new()).is_err());
+
+        let query = Query::parse("report -from:newsletter -draft", &Groups::new()).unwrap();
+        assert_eq!(query.text, vec!["report"]);
+        assert_eq!(query.exclude[0].from, vec!["newsletter"]);
+        assert_eq!(query.exclude[1].text, vec!["draft"]);
+    }
+
+    #[test]
+    fn test_highlight_terms_in_their_fields() {
+        let query = Query::parse(
+            r#"budget from:Alice subject:"Q2" re:inv-\d+ -draft"#,
+            &Groups::new(),
+        )
+        .unwrap();
+        let highlighter = Highlighter::new(&query);
+
+        assert_eq!(
+            highlighter.ranges(Field::Subject, "Q2 Budget, INV-42 draft"),
+            vec![0..2, 3..9, 11..17]
+        );
+        assert_eq!(
+            highlighter.ranges(Field::Sender, "alice@company.com"),
+            vec![0..5]
+        );
+        assert_eq!(
+            highlighter.ranges(Field::Body, "Alice's q2 budget"),
+            vec![11..17]
+        );
+        assert!(Highlighter::new(&Query::default()).is_empty());
+    }
+
+    #[test]
+    fn test_attachment_and_group_operators() {
+        let mut email = Email {
+            subject: "Quarterly numbers".to_string(),
+            sender: "finance@company.com".to_string(),
+            read: true,
+            ..Email::test("1")
+        };
+        let matches = |email: &Email, query: &str| {
+            Query::parse(query, &Groups::new()).unwrap().matches(email)
+        };
+        assert!(!matches(&email, "has:attachment"));
+        assert!(matches(&email, "-has:attachment"));
+
+        email.attachments.push(Attachment {
+            id: "a1".to_string(),
+            name: "Report Q2.XLSX".to_string(),
+            content_type: "application/octet-stream".to_string(),
+            size: 1024,
+        });
+        email.attachments.push(Attachment {
+            id: "a2".to_string(),
+            name: "scan".to_string(),
+            content_type: "application/pdf".to_string(),
+            size: 2048,
+        });
+        assert!(matches(&email, "has:attachments quarterly"));
+        assert!(matches(
+            &email,
+            "filename:report filetype:xlsx filetype:.pdf"
+        ));
+        assert!(!matches(&email, "filetype:docx"));
+        assert!(matches(&email, "is:read -is:unread -is:flagged"));
+        assert!(Query::parse("is:important", &Groups::new()).is_err());
+        assert!(matches(&email, r"re:^quarterly\s+NUMBERS$"));
+        assert!(matches(&email, r"-re:\d{3}"));
+        assert!(Query::parse("re:(unclosed", &Groups::new()).is_err());
+        assert!(!matches(&email, "filename:invoice"));
+        assert!(Query::parse("has:link", &Groups::new()).is_err());
+
+        let groups = Groups::from([(
+            "finance".to_string(),
+            vec![
+                "Finance@company.com".to_string(),
+                "cfo@company.com".to_string(),
+            ],
+        )]);
+        assert!(Query::parse("from:@Finance", &groups)
+            .unwrap()
+            .matches(&email));
+        assert!(!Query::parse("-from:@finance", &groups)
+            .unwrap()
+            .matches(&email));
+        assert!(!Query::parse("to:@finance", &groups)
+            .unwrap()
+            .matches(&email));
+        assert!(Query::parse("from:@team", &groups).is_err());
+    }
+
+    #[test]
+    fn test_server_hits_keep_terms_only_the_body_can_decide() {
+        // Server search results come without their bodies
+        let hit = Email {
+            subject: "Invoice".to_string(),
+            sender: "billing@vendor.com".to_string(),
+            ..Email::test("1")
+        };
+        let matches_envelope = |query: &str| {
+            Query::parse(query, &Groups::new())
+                .unwrap()
+                .matches_envelope(&hit)
+        };
+        assert!(matches_envelope(r"re:inv-\d{4}"));
+        assert!(matches_envelope("-overdue"));
+        assert!(matches_envelope(r"from:billing -re:^paid"));
+        assert!(!matches_envelope(r"from:billing -from:vendor re:inv-\d{4}"));
+        assert!(!matches_envelope("subject:receipt"));
+    }
+}
@@ -0,0 +1,24 @@
+use std::io;
+
+/// Waits until the process is asked to stop with SIGINT, SIGTERM or SIGHUP,
+/// so it can save its state and restore the terminal before exiting.
+#[cfg(unix)]
+pub async fn terminated() -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::select! {
+        _ = interrupt.recv() => {}
+        _ = terminate.recv() => {}
+        _ = hangup.recv() => {}
+    }
+    Ok(())
+}
+
+/// Waits until the process is asked to stop with Ctrl-C.
+#[cfg(not(unix))]
+pub async fn terminated() -> io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
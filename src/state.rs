@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Past searches kept for Up, Down and Ctrl-r in the search popup.
+const SEARCH_HISTORY_LIMIT: usize = 100;
+
+/// A server certificate the user chose to trust on first use.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificatePin {
+    pub host: String,
+    /// SHA-256 fingerprint as colon-separated hex
+    pub fingerprint: String,
+}
+
+/// Data the app accumulates while running and keeps between sessions.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LocalState {
+    #[serde(default)]
+    pub contacts: Vec<String>,
+    #[serde(default)]
+    pub blocked_senders: Vec<String>,
+    /// Thread subjects whose messages are hidden, see `Email::thread_subject`
+    #[serde(default)]
+    pub muted_threads: Vec<String>,
+    /// Version whose "what's new" screen was last shown
+    #[serde(default)]
+    pub last_seen_version: Option<String>,
+    /// Keys pressed at least once, so tips only suggest bindings not used yet
+    #[serde(default)]
+    pub used_keys: Vec<char>,
+    #[serde(default)]
+    pub tips_disabled: bool,
+    #[serde(default)]
+    pub pinned_certificates: Vec<CertificatePin>,
+    /// Queries run from the search popup, oldest first
+    #[serde(default)]
+    pub search_history: Vec<String>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+/// Directory for state files, `~/.local/state/mail-tui` on Linux.
+pub fn state_dir() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("mail-tui"))
+}
+
+impl LocalState {
+    pub fn load() -> Result<Self> {
+        let Some(path) = state_dir().map(|dir| dir.join("state.json")) else {
+            return Ok(Self::default());
+        };
+
+        let mut state = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            Self::default()
+        };
+        state.path = Some(path);
+        Ok(state)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Adds an address to the contacts, returning false if it was already there.
+    pub fn add_contact(&mut self, address: &str) -> bool {
+        add_unique(&mut self.contacts, address)
+    }
+
+    /// Adds an address to the blocked senders, returning false if it was already there.
+    pub fn block_sender(&mut self, address: &str) -> bool {
+        add_unique(&mut self.blocked_senders, address)
+    }
+
+    /// Mutes a thread, returning false if it was already muted.
+    pub fn mute_thread(&mut self, thread: &str) -> bool {
+        add_unique(&mut self.muted_threads, thread)
+    }
+
+    pub fn unmute_thread(&mut self, thread: &str) {
+        self.muted_threads.retain(|t| t != thread);
+    }
+
+    /// Trusts a certificate for a host, replacing any earlier pin for it.
+    pub fn pin_certificate(&mut self, host: &str, fingerprint: &str) {
+        self.pinned_certificates
+            .retain(|pin| !pin.host.eq_ignore_ascii_case(host));
+        self.pinned_certificates.push(CertificatePin {
+            host: host.to_string(),
+            fingerprint: fingerprint.to_string(),
+        });
+    }
+
+    pub fn pinned_fingerprint(&self, host: &str) -> Option<&str> {
+        self.pinned_certificates
+            .iter()
+            .find(|pin| pin.host.eq_ignore_ascii_case(host))
+            .map(|pin| pin.fingerprint.as_str())
+    }
+
+    pub fn record_key_use(&mut self, key: char) {
+        if !self.used_keys.contains(&key) {
+            self.used_keys.push(key);
+        }
+    }
+
+    /// Adds a query to the end of the search history, moving it there if it
+    /// was run before and forgetting the oldest past the limit.
+    pub fn record_search(&mut self, query: &str) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        self.search_history.retain(|past| past != query);
+        self.search_history.push(query.to_string());
+        let excess = self
+            .search_history
+            .len()
+            .saturating_sub(SEARCH_HISTORY_LIMIT);
+        self.search_history.drain(..excess);
+    }
+
+    pub fn is_blocked(&self, address: &str) -> bool {
+        self.blocked_senders
+            .iter()
+            .any(|blocked| blocked.eq_ignore_ascii_case(address))
+    }
+
+    pub fn is_muted(&self, thread: &str) -> bool {
+        self.muted_threads.iter().any(|t| t == thread)
+    }
+}
+
+fn add_unique(list: &mut Vec<String>, value: &str) -> bool {
+    if list.iter().any(|a| a.eq_ignore_ascii_case(value)) {
+        false
+    } else {
+        list.push(value.to_string());
+        true
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use crossterm::event::{Event, EventStream, KeyEvent, MouseEvent};
+use futures::StreamExt;
+use std::any::Any;
+use std::cell::RefCell;
+use std::future;
+use std::io;
+use std::rc::Rc;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::AbortHandle;
+
+use super::app::{BodyUpdate, HeadersUpdate, SyncUpdate};
+use crate::email::{DirectoryEntry, FetchProgress};
+use crate::signals;
+
+/// Everything the event loop reacts to. Input, timers and background tasks send
+/// actions over one channel, and `App::update` applies them one at a time.
+pub enum Action {
+    Key(KeyEvent),
+    /// A click or turn of the wheel
+    Mouse(MouseEvent),
+    /// Animates spinners and expires status messages
+    Tick,
+    /// An account finished syncing in the background
+    Synced(SyncUpdate),
+    /// An account's sync fetched another page
+    SyncProgress {
+        account: String,
+        progress: FetchProgress,
+    },
+    /// A body requested for the content pane arrived or failed
+    BodyLoaded(BodyUpdate),
+    /// A body prefetched near the selection arrived or failed
+    Prefetched(BodyUpdate),
+    /// Typing in the search box paused
+    SearchSettled,
+    /// The open email was drawn with the matches of `/` to jump to
+    FindReady,
+    /// Headers requested for the selected email arrived or failed
+    HeadersLoaded(HeadersUpdate),
+    /// The server accepted or refused a message from the outbox
+    Sent {
+        id: u64,
+        result: Result<()>,
+    },
+    /// The server marked an email read when it was opened, or failed to
+    MarkedRead {
+        account: String,
+        id: String,
+        result: Result<()>,
+    },
+    /// The directory's matches for the address being typed arrived or failed
+    DirectoryMatches {
+        prefix: String,
+        result: Result<Vec<DirectoryEntry>>,
+    },
+    /// An image attachment of the open email downloaded or failed
+    ImageLoaded {
+        account: String,
+        email_id: String,
+        attachment_id: String,
+        result: Result<Vec<u8>>,
+    },
+    /// A request the app sent in the background answered, or failed, with
+    /// what its task returned
+    Response {
+        id: u64,
+        response: Box<dyn Any>,
+    },
+    /// Reading the terminal failed, which ends the app
+    InputFailed(io::Error),
+    /// The terminal closed its input
+    InputClosed,
+    /// The process was asked to stop by a signal
+    Terminated,
+}
+
+/// The task forwarding key presses and mouse events from the terminal, which
+/// is suspended while another program runs in it.
+pub struct Input {
+    tx: UnboundedSender<Action>,
+    /// Kept out of the task so suspending drops it, and stops its reader thread,
+    /// right away rather than the next time the aborted task is polled
+    events: Rc<RefCell<Option<EventStream>>>,
+    task: Option<AbortHandle>,
+}
+
+impl Input {
+    pub fn new(tx: UnboundedSender<Action>) -> Self {
+        Self {
+            tx,
+            events: Rc::default(),
+            task: None,
+        }
+    }
+
+    /// Forwards input until the terminal closes, the app quits or input is suspended.
+    pub fn spawn(&mut self) {
+        let events = Rc::new(RefCell::new(Some(EventStream::new())));
+        self.events = Rc::clone(&events);
+        let tx = self.tx.clone();
+        let task = tokio::task::spawn_local(async move {
+            loop {
+                // Borrowed only while polled, so `suspend` can take the stream in between
+                let next = future::poll_fn(|cx| match events.borrow_mut().as_mut() {
+                    Some(events) => events.poll_next_unpin(cx),
+                    None => Poll::Pending,
+                });
+                let action = match next.await {
+                    Some(Ok(Event::Key(key))) => Action::Key(key),
+                    Some(Ok(Event::Mouse(mouse))) => Action::Mouse(mouse),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => Action::InputFailed(e),
+                    None => Action::InputClosed,
+                };
+                let last = matches!(action, Action::InputFailed(_) | Action::InputClosed);
+                if tx.send(action).is_err() || last {
+                    return;
+                }
+            }
+        });
+        self.task = Some(task.abort_handle());
+    }
+
+    /// Stops reading the terminal, which would otherwise take the keys typed
+    /// into a program run in it and replay them once it exits.
+    pub fn suspend(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.events.borrow_mut().take();
+    }
+}
+
+/// Sends a tick every `period` until the app quits.
+pub fn spawn_ticks(tx: UnboundedSender<Action>, period: Duration) {
+    tokio::task::spawn_local(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if tx.send(Action::Tick).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Asks the app to quit once a termination signal arrives, so it leaves the
+/// terminal as it found it.
+pub fn spawn_signals(tx: UnboundedSender<Action>) {
+    tokio::task::spawn_local(async move {
+        if signals::terminated().await.is_ok() {
+            let _ = tx.send(Action::Terminated);
+        }
+    });
+}
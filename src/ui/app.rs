@@ -1,82 +1,807 @@
-use anyhow::Result;
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use std::{
-    io,
-    time::{Duration, Instant},
+    any::Any,
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    future::Future,
+    io::{self, Stdout, Write},
+    ops::Deref,
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
 };
-use tui::{backend::CrosstermBackend, widgets::ListState, Terminal};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Semaphore;
+use tokio::task::AbortHandle;
+use tracing::{debug, error, info, info_span, trace, warn, Instrument};
+use tui::{backend::CrosstermBackend, text::Spans, widgets::ListState, Terminal};
 
+use super::action::{self, Action, Input};
+use super::browser;
+use super::clipboard;
+use super::commands::{self, Command};
+use super::compose::{self, Compose, ComposeKind, MessageField};
+use super::date_picker::DatePicker;
+use super::directory::DirectoryLookup;
+use super::discovery::{self, Release};
+use super::drafts::DraftList;
+use super::editor;
+use super::file_prompt;
+use super::find::Find;
+use super::folders::{FolderEdit, FolderManager};
+use super::help::Help;
+use super::hints::{HintTarget, Hints};
+use super::images::{self, Graphics, Image, ImageSlot, ImageState};
+use super::keymap::{self, Context, KeyAction};
+use super::links;
+use super::loading::{self, LoadingState, Operation, Timeouts};
+use super::mouse::{self, PaneAreas, Target};
+use super::move_picker::MovePicker;
+use super::out_of_office::{self, OofField, OofForm};
+use super::outbox::{MessageEdit, OutboxView};
+use super::pager;
+use super::recoverable::RecoverableItems;
+use super::render::{self, BodyRenderer, BodyView, PlainRenderer, Renderers};
+use super::search_history::HistoryPicker;
+use super::selection::Selection;
+use super::sidebar::Sidebar;
+use super::status::{Severity, StatusBus};
+use super::tabs::{self, Tab};
+use super::tasks::TaskList;
+use super::terminal::TerminalGuard;
+use super::theme::Theme;
+use super::threads::ThreadList;
 use super::view;
-use crate::email::{Email, EmailClient};
+use super::views::{self, ActiveView, QuickFilters, ViewSource};
+use super::width;
+use crate::activity::{ActivityKind, ActivityLog};
+use crate::cache::Cache;
+use crate::config::{
+    self, CacheConfig, ComposeConfig, Config, DateFormat, DownloadConfig, ExchangeConfig,
+    PrefetchConfig, RowFormat, SavedSearch, ViewConfig, ViewSort,
+};
+use crate::downloads;
+use crate::draft::LocalDraft;
+use crate::email::{
+    threading, Account, Attachment, BodyFormat, DateRange, DirectoryEntry, Email, EmailClient,
+    FetchProgress, Folder, Header, JunkReport, MeetingResponse, OutgoingEmail, Progress,
+    UntrustedCertificate, INBOX_FOLDER_ID,
+};
+use crate::fuzzy;
+use crate::outbox::{Outbox, SendState};
+use crate::query::{Groups, Highlighter, Query};
+use crate::state::LocalState;
+
+/// Search popup prefix that sends the rest of the query to the server.
+pub const SERVER_SEARCH_PREFIX: char = '!';
+/// Search popup prefix that matches the rest fuzzily, ranking the results.
+pub const FUZZY_SEARCH_PREFIX: char = '~';
+/// Query the attachments quick filter runs.
+const ATTACHMENT_FILTER: &str = "has:attachment";
+
+/// Bodies from this size on are shown a window at a time instead of whole.
+pub const LARGE_BODY_BYTES: usize = 256 * 1024;
+/// Pause in typing after which the list follows the search box.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+/// Columns the subject quoted when asking to delete an email takes at most.
+const DELETE_PROMPT_WIDTH: usize = 41;
+/// Characters of an address typed before the directory is asked to complete it
+const DIRECTORY_COMPLETION_CHARS: usize = 3;
+/// Percent of the width `<` and `>` take from or give to the list.
+const LIST_WIDTH_STEP: u16 = 5;
+/// Narrowest and widest the list gets, in percent of the width.
+const LIST_WIDTH_RANGE: (u16, u16) = (10, 90);
+/// Image attachments from this size on are left as a placeholder.
+const MAX_IMAGE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Account, email id and attachment id of an image attachment.
+type ImageKey = (String, String, String);
+
+/// Startup stages after the config is loaded, in the order they run. Cached mail
+/// comes before signing in so it can be read while the accounts connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Startup {
+    Cache,
+    SignIn,
+    Sync,
+}
+
+impl Startup {
+    pub const ALL: [Startup; 3] = [Startup::Cache, Startup::SignIn, Startup::Sync];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Startup::Cache => "cache",
+            Startup::SignIn => "sign-in",
+            Startup::Sync => "sync",
+        }
+    }
+}
 
 pub enum InputMode {
     Normal,
     Help,
     EmailView,
     Search,
+    AddressMenu,
+    Links,
+    Hints,
+    Confirm,
+    SyncHealth,
+    SyncError,
+    Sidebar,
+    Folders,
+    Recoverable,
+    WhatsNew,
+    OutOfOffice,
+    Directory,
+    Move,
+    Compose,
+    Drafts,
+    Tasks,
+    Outbox,
+    OutboxEdit,
+    Headers,
+    Command,
+    Metrics,
+    Searches,
+    Find,
+    SearchHistory,
+    DateRange,
+}
+
+/// The body lines of the content pane as last drawn, which scrolling stays within.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContentSize {
+    /// Lines the pane has room for
+    pub height: usize,
+    /// Lines of the selected email, once wrapped
+    pub lines: usize,
+}
+
+impl ContentSize {
+    pub fn max_scroll(&self) -> usize {
+        self.lines.saturating_sub(self.height)
+    }
+}
+
+/// Actions offered by the address actions menu, each bound to a single key.
+#[derive(Clone, Copy)]
+pub enum AddressAction {
+    Copy,
+    Compose,
+    SearchFrom,
+    AddContact,
+    Block,
+}
+
+impl AddressAction {
+    pub const ALL: [AddressAction; 5] = [
+        AddressAction::Copy,
+        AddressAction::Compose,
+        AddressAction::SearchFrom,
+        AddressAction::AddContact,
+        AddressAction::Block,
+    ];
+
+    pub fn key(&self) -> char {
+        match self {
+            AddressAction::Copy => 'y',
+            AddressAction::Compose => 'c',
+            AddressAction::SearchFrom => 'f',
+            AddressAction::AddContact => 'a',
+            AddressAction::Block => 'b',
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AddressAction::Copy => "Copy address",
+            AddressAction::Compose => "Compose to",
+            AddressAction::SearchFrom => "Search mail from",
+            AddressAction::AddContact => "Add to contacts",
+            AddressAction::Block => "Block sender",
+        }
+    }
+
+    fn from_key(c: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.key() == c)
+    }
+}
+
+/// Server-side actions that take the selected message out of the current list.
+#[derive(Clone, Copy)]
+pub enum MessageAction {
+    Archive,
+    Delete,
+    ReportJunk(JunkReport),
+    RespondToMeeting(MeetingResponse),
+}
+
+impl MessageAction {
+    fn past_tense(&self) -> &'static str {
+        match self {
+            MessageAction::Archive => "Archived",
+            MessageAction::Delete => "Deleted",
+            MessageAction::ReportJunk(JunkReport::Junk) => "Reported as junk",
+            MessageAction::ReportJunk(JunkReport::Phishing) => "Reported as phishing",
+            MessageAction::RespondToMeeting(MeetingResponse::Accept) => "Accepted",
+            MessageAction::RespondToMeeting(MeetingResponse::Tentative) => "Tentatively accepted",
+            MessageAction::RespondToMeeting(MeetingResponse::Decline) => "Declined",
+        }
+    }
+
+    fn verb(&self) -> &'static str {
+        match self {
+            MessageAction::Archive => "archive",
+            MessageAction::Delete => "delete",
+            MessageAction::ReportJunk(_) => "report",
+            MessageAction::RespondToMeeting(_) => "respond to",
+        }
+    }
+}
+
+/// Actions applied to every email tagged or in the visual range at once.
+#[derive(Clone)]
+pub enum SelectionAction {
+    Archive,
+    Delete,
+    /// To the folder of this name in each email's account
+    Move(String),
+    MarkRead,
+}
+
+impl SelectionAction {
+    /// What was done to `count` emails, as "3 emails moved to Projects".
+    fn describe(&self, count: usize) -> String {
+        match self {
+            SelectionAction::Archive => format!("{} emails archived", count),
+            SelectionAction::Delete => format!("{} emails deleted", count),
+            SelectionAction::Move(folder) => format!("{} emails moved to {}", count, folder),
+            SelectionAction::MarkRead => format!("{} emails marked read", count),
+        }
+    }
+}
+
+/// Actions applied to every loaded message of the selected email's thread.
+#[derive(Clone, Copy)]
+pub enum ThreadAction {
+    Archive,
+    MarkRead,
+    Mute,
+    Delete,
+}
+
+impl ThreadAction {
+    fn past_tense(&self) -> &'static str {
+        match self {
+            ThreadAction::Archive => "Archived",
+            ThreadAction::MarkRead => "Marked read",
+            ThreadAction::Mute => "Muted",
+            ThreadAction::Delete => "Deleted",
+        }
+    }
+
+    fn verb(&self) -> &'static str {
+        match self {
+            ThreadAction::Archive => "archive",
+            ThreadAction::MarkRead => "mark read",
+            ThreadAction::Mute => "mute",
+            ThreadAction::Delete => "delete",
+        }
+    }
+}
+
+/// How to reverse a thread action.
+enum UndoChange {
+    /// Move the emails back to the Inbox and into the list at their old indices
+    Restore(Vec<(usize, Email)>),
+    MarkUnread(Vec<String>),
+    Unmute(String),
+}
+
+/// A thread action waiting on the server, with what to report once it is done.
+struct ThreadDone {
+    action: ThreadAction,
+    account: String,
+    subject: String,
+    description: String,
+    count: usize,
+}
+
+/// A thread action that `u` can reverse in one step.
+struct UndoEntry {
+    account: String,
+    description: String,
+    change: UndoChange,
+}
+
+/// An action waiting for the user to confirm it in a modal dialog.
+pub enum ConfirmAction {
+    ReportJunk,
+    DeleteEmail,
+    /// Delete every email tagged or in the visual range
+    DeleteSelection,
+    /// Mark every unread email the search and filters show as read
+    MarkResultsRead,
+    DeleteFolder,
+    RespondToMeeting,
+    Thread,
+    /// Run a server search again after it timed out
+    RetryServerSearch(String),
+    /// Load the selected email's body again after it timed out
+    RetryBodyFetch(BodyFormat),
+    /// Drop a message from the outbox without sending it
+    DiscardOutboxMessage(u64),
+    /// Close the compose form without sending the message
+    DiscardMessage,
+    /// Delete the draft selected in the Drafts popup
+    DeleteDraft,
+    /// Send a reply to all that goes to many people or a distribution list
+    SendReplyAll {
+        account: String,
+        message: OutgoingEmail,
+    },
+    /// Pin a self-signed server certificate, then sync the account again
+    TrustCertificate {
+        account: String,
+        host: String,
+        fingerprint: String,
+    },
+}
+
+impl ConfirmAction {
+    /// The keys accepted by the dialog and what they do.
+    pub fn options(&self) -> &'static [(char, &'static str)] {
+        match self {
+            ConfirmAction::ReportJunk => &[
+                ('j', "Report as junk"),
+                ('p', "Report as phishing"),
+                ('n', "Cancel"),
+            ],
+            ConfirmAction::DeleteEmail
+            | ConfirmAction::DeleteSelection
+            | ConfirmAction::DeleteFolder
+            | ConfirmAction::DeleteDraft => &[('y', "Delete"), ('n', "Cancel")],
+            ConfirmAction::MarkResultsRead => &[('y', "Mark read"), ('n', "Cancel")],
+            ConfirmAction::DiscardOutboxMessage(_) => &[('y', "Discard"), ('n', "Keep")],
+            ConfirmAction::DiscardMessage => &[('y', "Discard"), ('n', "Keep writing")],
+            ConfirmAction::SendReplyAll { .. } => &[('y', "Send to all"), ('n', "Cancel")],
+            ConfirmAction::RespondToMeeting => &[
+                ('a', "Accept"),
+                ('t', "Tentative"),
+                ('d', "Decline"),
+                ('n', "Cancel"),
+            ],
+            ConfirmAction::RetryServerSearch(_) | ConfirmAction::RetryBodyFetch(_) => {
+                &[('r', "Retry"), ('n', "Cancel")]
+            }
+            ConfirmAction::TrustCertificate { .. } => &[
+                ('t', "Trust this certificate for this host"),
+                ('n', "Don't trust it"),
+            ],
+            ConfirmAction::Thread => &[
+                ('a', "Archive thread"),
+                ('r', "Mark thread read"),
+                ('m', "Mute thread"),
+                ('d', "Delete thread"),
+                ('n', "Cancel"),
+            ],
+        }
+    }
+}
+
+pub struct Confirmation {
+    pub prompt: String,
+    pub action: ConfirmAction,
 }
 
 pub enum FocusPanel {
+    Sidebar,
     EmailList,
     EmailContent,
 }
 
+/// How the list and the content pane share the screen, cycled with `z`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaneLayout {
+    SideBySide,
+    /// The list above the content pane
+    Stacked,
+    /// Only the focused one of the two, full size
+    Single,
+}
+
+impl PaneLayout {
+    pub fn next(self) -> Self {
+        match self {
+            PaneLayout::SideBySide => PaneLayout::Stacked,
+            PaneLayout::Stacked => PaneLayout::Single,
+            PaneLayout::Single => PaneLayout::SideBySide,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PaneLayout::SideBySide => "side by side",
+            PaneLayout::Stacked => "list above message",
+            PaneLayout::Single => "one pane at a time",
+        }
+    }
+}
+
+/// An account whose last sync failed, shown in the sync health banner.
+pub struct SyncFailure {
+    pub account: String,
+    pub error: String,
+}
+
+/// The outcome of fetching one account's mail in the background.
+pub struct SyncUpdate {
+    account: String,
+    result: Result<Vec<Email>>,
+}
+
+/// The outcome of fetching a format of an email's body in the background.
+pub struct BodyUpdate {
+    account: String,
+    id: String,
+    format: BodyFormat,
+    result: Result<String>,
+}
+
+/// The outcome of fetching an email's Internet headers in the background.
+pub struct HeadersUpdate {
+    account: String,
+    id: String,
+    result: Result<Vec<Header>>,
+}
+
+/// A body fetch in flight.
+struct BodyFetch {
+    account: String,
+    id: String,
+    format: BodyFormat,
+    handle: AbortHandle,
+}
+
+/// A reply or forward waiting for the body of the email it quotes.
+#[derive(Clone, Copy)]
+enum Quoting {
+    Reply { reply_all: bool },
+    Forward,
+}
+
+/// What to do with the response to a request in flight.
+type Responder<T> = Box<dyn FnOnce(&mut App<T>, Box<dyn Any>)>;
+
+/// The client of one account, owned by a request on its task.
+struct AccountClient<T: EmailClient> {
+    accounts: Rc<Vec<Account<T>>>,
+    idx: usize,
+}
+
+impl<T: EmailClient> Deref for AccountClient<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.accounts[self.idx].client
+    }
+}
+
+/// Lines a renderer produced for the body of an email.
+struct RenderedBody {
+    account: String,
+    id: String,
+    lines: Vec<Spans<'static>>,
+}
+
 pub struct App<T: EmailClient> {
-    pub email_client: T,
+    /// Shared with the background syncs
+    pub accounts: Rc<Vec<Account<T>>>,
     pub emails: Vec<Email>,
     pub filtered_emails: Vec<usize>, // Indices into emails for search results
     pub selected_index: usize,
     pub input_mode: InputMode,
     pub focus: FocusPanel,
     pub list_state: ListState,
-    pub status_message: Option<(String, Instant)>,
+    pub status: StatusBus,
     pub search_input: String,
+    /// What the search shown in the list matched, highlighted in the list and body
+    pub highlighter: Highlighter,
+    /// Position in the search history shown in the search box with Up and Down
+    search_history_index: Option<usize>,
+    /// What was typed in the search box before browsing the history
+    search_draft: String,
+    pub history_picker: Option<HistoryPicker>,
+    /// What `/` looks for in the open email
+    pub find: Option<Find>,
+    /// Rows of the open email with a match, recorded by the view as it draws them
+    pub find_rows: RefCell<Vec<usize>>,
+    /// Pending update of the list to the search being typed
+    search_debounce: Option<AbortHandle>,
+    /// What was typed after `:`
+    pub command_input: String,
+    /// Folder names `:folder` and `:move` complete, fetched on the first Tab
+    command_folders: Option<Vec<String>>,
+    pub loading: LoadingState,
+    timeouts: Timeouts,
+    pub state: LocalState,
+    pub address_menu_index: usize,
+    /// Links of the open email, listed in the links popup
+    pub links: Vec<String>,
+    pub links_index: usize,
+    /// Labels on the links and attachments of the open email in hint mode
+    pub hints: Option<Hints>,
+    pub confirmation: Option<Confirmation>,
+    pub sync_failures: Vec<SyncFailure>,
+    /// Accounts whose sync is in flight, with the handle that cancels it
+    syncing: Vec<(String, AbortHandle)>,
+    /// The latest progress reported by each sync in flight
+    pub sync_progress: Vec<(String, FetchProgress)>,
+    /// Bodies being fetched for the selected email
+    body_fetches: Vec<BodyFetch>,
+    /// Bodies being warmed near the selection, at most `prefetch.concurrency` at a time
+    prefetches: Vec<BodyFetch>,
+    prefetch: PrefetchConfig,
+    prefetch_permits: Rc<Semaphore>,
+    /// Account and id of the email whose headers are being fetched
+    headers_fetch: Option<(String, String, AbortHandle)>,
+    /// Account and id of the email a reply or forward waits for the body of
+    quoting: Option<(String, String, Quoting)>,
+    /// First line shown in the raw headers popup
+    pub headers_scroll: u16,
+    /// Accounts synced together since the last time no sync was in flight,
+    /// summarized once they are all done
+    sync_batch: Vec<String>,
+    /// Requests in flight by id, answered in `update` as they come back
+    responders: HashMap<u64, Responder<T>>,
+    next_request_id: u64,
+    /// Bumped whenever another list is shown, so a view or server search that
+    /// comes back late is dropped
+    list_generation: u64,
+    /// Feeds `update` from input, timers and background tasks
+    actions_tx: UnboundedSender<Action>,
+    actions_rx: UnboundedReceiver<Action>,
+    /// Suspended while the editor or pager has the terminal
+    input: Input,
+    pub banner_dismissed: bool,
+    pub sync_health_index: usize,
+    /// Why the last refresh failed for every account, shown until answered
+    pub sync_error: Option<String>,
+    pub folder_manager: Option<FolderManager>,
+    pub recoverable: Option<RecoverableItems>,
+    pub whats_new: Vec<Release>,
+    pub oof_form: Option<OofForm>,
+    pub directory: Option<DirectoryLookup>,
+    pub move_picker: Option<MovePicker>,
+    /// The message being written, while the compose form is open
+    pub composing: Option<Compose>,
+    pub drafts: Option<DraftList>,
+    pub tasks: Option<TaskList>,
+    pub outbox: Outbox,
+    pub outbox_view: Option<OutboxView>,
+    pub views: Vec<ViewConfig>,
+    /// Searches kept with `:save-search`, listed in the saved searches popup
+    pub searches: Vec<SavedSearch>,
+    pub searches_index: usize,
+    /// Order of the inbox and folders, views have their own
+    pub list_sort: ViewSort,
+    /// Percent of the width the list takes next to the content pane, or of
+    /// the height above it
+    pub list_width: u16,
+    /// Whether the list shows how long ago mail came instead of full dates
+    pub relative_dates: bool,
+    pub date_format: DateFormat,
+    /// Whether quoted reply text shows in full instead of collapsed, toggled
+    /// with `Q` until another email is opened
+    pub quotes_expanded: bool,
+    /// What the list rows show, or `None` for the four-line cards
+    pub row_format: Option<RowFormat>,
+    pub layout: PaneLayout,
+    /// Whether `d` asks before deleting
+    confirm_delete: bool,
+    /// Where a changed sort is remembered
+    config_path: Option<PathBuf>,
+    /// The view shown instead of the inbox, if any
+    pub active_view: Option<ActiveView>,
+    /// The days every account fetches mail for
+    pub date_range: DateRange,
+    pub date_picker: Option<DatePicker>,
+    /// Every tab, the one shown kept in the fields above instead
+    tabs: Vec<Tab>,
+    pub tab_index: usize,
+    /// The first key of a two-key binding waiting for the second, `g` of
+    /// `gg`, `gt` and the other tab keys, `F` of the quick filters or `y` of
+    /// the copy keys
+    pending_prefix: Option<char>,
+    /// Set while the help popup is open
+    pub help: Option<Help>,
+    /// Emails tagged with Space or in the range of `v`, for bulk actions
+    pub selection: Selection,
+    /// What the list is narrowed to besides the search
+    pub quick_filters: QuickFilters,
+    /// The folder list left of the emails, while shown
+    pub sidebar: Option<Sidebar>,
+    /// The conversations of the listed emails and which of them are expanded
+    pub threads: ThreadList,
+    /// First line shown of each email scrolled in the content pane, by account and id
+    body_scrolls: HashMap<(String, String), usize>,
+    /// Written by the view on every draw
+    pub content_size: Cell<ContentSize>,
+    /// Where the view last drew the panes, for the mouse
+    pub pane_areas: RefCell<PaneAreas>,
+    renderers: Renderers,
+    downloads: DownloadConfig,
+    groups: Groups,
+    compose: ComposeConfig,
+    /// The settings of each account by name, for its address and signature
+    account_configs: HashMap<String, ExchangeConfig>,
+    /// The body of the email opened last, as its renderer shows it
+    rendered: Option<RenderedBody>,
+    /// Part of the selected email shown instead of the one its type picks,
+    /// cycled with `V`
+    pub body_view: Option<BodyView>,
+    pub theme: Theme,
+    /// How images are drawn, if the terminal can
+    pub graphics: Option<Graphics>,
+    /// Rows an image takes at most
+    pub image_rows: u16,
+    /// Image attachments of the open email
+    images: HashMap<ImageKey, ImageState>,
+    next_image_id: u32,
+    /// Written by the view on every draw, drawn over the frame after it
+    pub image_slots: RefCell<Vec<ImageSlot>>,
+    /// Where images are on screen since the last frame
+    drawn_images: Vec<ImageSlot>,
+    /// Images a kitty terminal holds, to place without sending them again
+    sent_images: HashSet<u32>,
+    /// Images the terminal holds that aren't shown anymore
+    forgotten_images: Vec<u32>,
+    undo_stack: Vec<UndoEntry>,
+    /// Opened once the first frame is on screen
+    cache: Option<Cache>,
+    cache_config: CacheConfig,
+    /// The stage startup is at, until the first sync is done
+    pub startup: Option<Startup>,
+    activity: ActivityLog,
+    /// Set after handing the terminal to another program, which leaves it blank
+    needs_clear: bool,
     should_quit: bool,
-    loading: bool,
 }
 
 impl<T: EmailClient> App<T> {
-    pub fn new(email_client: T) -> Self {
+    pub fn new(accounts: Vec<Account<T>>, config: &Config, state: LocalState) -> Self {
         let mut list_state = ListState::default();
         list_state.select(Some(0));
+        let (actions_tx, actions_rx) = mpsc::unbounded_channel();
 
         Self {
-            email_client,
+            accounts: Rc::new(accounts),
             emails: Vec::new(),
             filtered_emails: Vec::new(),
             selected_index: 0,
             input_mode: InputMode::Normal,
             focus: FocusPanel::EmailList,
             list_state,
-            status_message: None,
+            status: StatusBus::default(),
             search_input: String::new(),
+            highlighter: Highlighter::default(),
+            search_history_index: None,
+            search_draft: String::new(),
+            history_picker: None,
+            find: None,
+            find_rows: RefCell::new(Vec::new()),
+            search_debounce: None,
+            command_input: String::new(),
+            command_folders: None,
+            loading: LoadingState::default(),
+            timeouts: Timeouts::new(&config.timeouts),
+            state,
+            address_menu_index: 0,
+            links: Vec::new(),
+            links_index: 0,
+            hints: None,
+            confirmation: None,
+            sync_failures: Vec::new(),
+            syncing: Vec::new(),
+            sync_progress: Vec::new(),
+            body_fetches: Vec::new(),
+            prefetches: Vec::new(),
+            prefetch: config.prefetch.clone(),
+            prefetch_permits: Rc::new(Semaphore::new(config.prefetch.concurrency)),
+            headers_fetch: None,
+            quoting: None,
+            headers_scroll: 0,
+            sync_batch: Vec::new(),
+            responders: HashMap::new(),
+            next_request_id: 0,
+            list_generation: 0,
+            input: Input::new(actions_tx.clone()),
+            actions_tx,
+            actions_rx,
+            banner_dismissed: false,
+            sync_health_index: 0,
+            sync_error: None,
+            folder_manager: None,
+            recoverable: None,
+            whats_new: Vec::new(),
+            oof_form: None,
+            directory: None,
+            move_picker: None,
+            composing: None,
+            drafts: None,
+            tasks: None,
+            outbox: Outbox::default(),
+            outbox_view: None,
+            views: config.views.clone(),
+            searches: config.searches.clone(),
+            searches_index: 0,
+            list_sort: config.list.sort,
+            list_width: config
+                .list
+                .width
+                .clamp(LIST_WIDTH_RANGE.0, LIST_WIDTH_RANGE.1),
+            relative_dates: config.list.relative_dates,
+            date_format: config.list.date_format.clone(),
+            quotes_expanded: false,
+            row_format: config.list.row_format.clone(),
+            layout: PaneLayout::SideBySide,
+            confirm_delete: config.list.confirm_delete,
+            config_path: config.path.clone(),
+            active_view: None,
+            date_range: DateRange::quarter(Local::now().date_naive()),
+            date_picker: None,
+            tabs: vec![Tab::default()],
+            tab_index: 0,
+            pending_prefix: None,
+            help: None,
+            selection: Selection::default(),
+            quick_filters: QuickFilters::default(),
+            sidebar: None,
+            threads: ThreadList::default(),
+            body_scrolls: HashMap::new(),
+            content_size: Cell::new(ContentSize::default()),
+            pane_areas: RefCell::new(PaneAreas::default()),
+            renderers: Renderers::new(&config.renderers),
+            downloads: config.downloads.clone(),
+            groups: config.groups.clone(),
+            compose: config.compose.clone(),
+            account_configs: config
+                .accounts()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|account| (account.name, account.exchange))
+                .collect(),
+            rendered: None,
+            body_view: None,
+            theme: Theme::new(&config.theme),
+            graphics: images::detect(config.images.protocol),
+            image_rows: config.images.max_rows,
+            images: HashMap::new(),
+            next_image_id: 1,
+            image_slots: RefCell::new(Vec::new()),
+            drawn_images: Vec::new(),
+            sent_images: HashSet::new(),
+            forgotten_images: Vec::new(),
+            undo_stack: Vec::new(),
+            cache: None,
+            cache_config: config.cache.clone(),
+            startup: None,
+            activity: ActivityLog::new(config.activity.enabled),
+            needs_clear: false,
             should_quit: false,
-            loading: false,
         }
     }
 
     pub fn search(&mut self, query: String) {
-        self.filtered_emails.clear();
-
-        if query.is_empty() {
-            // If search is empty, include all emails
-            self.filtered_emails = (0..self.emails.len()).collect();
-        } else {
-            let query_lower = query.to_lowercase();
-
-            // Filter emails that match the search query
-            for (idx, email) in self.emails.iter().enumerate() {
-                if email.subject.to_lowercase().contains(&query_lower)
-                    || email.sender.to_lowercase().contains(&query_lower)
-                    || email.body.to_lowercase().contains(&query_lower)
-                {
-                    self.filtered_emails.push(idx);
-                }
-            }
+        // Filter emails that match the search query, an empty query matches everything
+        if let Err(e) = self.filter_list(&query) {
+            self.set_error_message(format!("Invalid search: {}", e));
+            return;
         }
 
         // Reset selection
@@ -97,198 +822,5064 @@ impl<T: EmailClient> App<T> {
         }
     }
 
-    pub async fn refresh_emails(&mut self) -> Result<()> {
-        self.loading = true;
-        match self.email_client.fetch_current_quarter_emails().await {
-            Ok(emails) => {
-                self.emails = emails;
+    /// Filters the list to the search box once typing pauses, restarting the wait
+    /// on every key.
+    fn debounce_search(&mut self) {
+        self.cancel_live_search();
+        let tx = self.actions_tx.clone();
+        let handle = tokio::task::spawn_local(async move {
+            tokio::time::sleep(SEARCH_DEBOUNCE).await;
+            let _ = tx.send(Action::SearchSettled);
+        });
+        self.search_debounce = Some(handle.abort_handle());
+    }
 
-                // Reset filtered emails to show all emails
-                self.filtered_emails = (0..self.emails.len()).collect();
+    fn cancel_live_search(&mut self) {
+        if let Some(handle) = self.search_debounce.take() {
+            handle.abort();
+        }
+    }
 
-                if !self.emails.is_empty() {
-                    self.selected_index = self.selected_index.min(self.emails.len() - 1);
-                    self.list_state.select(Some(self.selected_index));
-                }
-                self.set_status_message("Emails refreshed successfully".to_string());
-                Ok(())
+    /// Shows the local matches of the search being typed behind the search box.
+    /// Server searches wait for Enter, and half-typed operators like
+    /// `after:2024-0` keep the last matches until they parse.
+    fn live_search(&mut self) {
+        self.search_debounce = None;
+        if !matches!(self.input_mode, InputMode::Search)
+            || self.search_input.starts_with(SERVER_SEARCH_PREFIX)
+        {
+            return;
+        }
+        let query = self.search_input.clone();
+        if self.filter_list(&query).is_ok() {
+            self.select_index(0);
+        }
+    }
+
+    /// Narrows the list to the local matches of `query`, or of a fuzzy `~query`
+    /// ranked by how well they match.
+    fn filter_list(&mut self, query: &str) -> Result<()> {
+        let Some(pattern) = query.strip_prefix(FUZZY_SEARCH_PREFIX) else {
+            let parsed = Query::parse(query, &self.groups)?;
+            self.filtered_emails = self.visible_indices(|email| parsed.matches(email));
+            self.highlighter = Highlighter::new(&parsed);
+            return Ok(());
+        };
+        let scores: HashMap<usize, i64> = self
+            .matching_indices(|_| true)
+            .into_iter()
+            .filter_map(|idx| Some((idx, fuzzy::score_email(pattern, &self.emails[idx])?)))
+            .collect();
+        // Equal scores keep the order of the list
+        let mut indices: Vec<usize> = scores.keys().copied().collect();
+        views::sort(self.sort(), &self.emails, &mut indices);
+        indices.sort_by_key(|idx| Reverse(scores[idx]));
+        self.filtered_emails = self.threads.group(&self.emails, indices);
+        self.highlighter = Highlighter::default();
+        Ok(())
+    }
+
+    /// Puts an older search from the history in the search box, or a newer one,
+    /// back to what was typed after the newest.
+    fn browse_search_history(&mut self, older: bool) {
+        let count = self.state.search_history.len();
+        let index = match (self.search_history_index, older) {
+            (None, true) if count > 0 => {
+                self.search_draft = self.search_input.clone();
+                Some(count - 1)
             }
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < count => Some(index + 1),
+            (Some(_), false) => None,
+            (None, _) => return,
+        };
+        self.search_history_index = index;
+        self.search_input = match index {
+            Some(index) => self.state.search_history[index].clone(),
+            None => std::mem::take(&mut self.search_draft),
+        };
+        self.debounce_search();
+    }
+
+    /// Shows only mail with attachments, or everything again if that filter is on.
+    fn toggle_attachment_filter(&mut self) {
+        let query = if self.search_input.trim() == ATTACHMENT_FILTER {
+            String::new()
+        } else {
+            ATTACHMENT_FILTER.to_string()
+        };
+        self.search_input = query.clone();
+        self.search(query);
+    }
+
+    /// Runs a query on every account's server and shows the matches, which can
+    /// include mail from outside the fetched quarter.
+    pub fn search_server(&mut self, query: &str) {
+        if query.is_empty() {
+            return;
+        }
+
+        let parsed = match Query::parse(query, &self.groups) {
+            Ok(parsed) => parsed,
             Err(e) => {
-                self.set_status_message(format!("Failed to fetch emails: {}", e));
-                Err(e)
+                self.set_error_message(format!("Invalid search: {}", e));
+                return;
+            }
+        };
+
+        self.loading.start(Operation::ServerSearch);
+        self.list_generation += 1;
+        let generation = self.list_generation;
+        let limit = self.timeouts.get(Operation::ServerSearch);
+        let accounts = Rc::clone(&self.accounts);
+        let server_text = parsed.server_text();
+        let request = async move {
+            let mut responses = Vec::new();
+            for account in accounts.iter() {
+                let request = account.client.search_server(&server_text);
+                let response = loading::with_timeout(limit, request).await;
+                responses.push((account.name.clone(), response));
             }
+            responses
+        };
+        let query = query.to_string();
+        self.spawn_request(request, move |app, responses| {
+            app.loading.finish(Operation::ServerSearch);
+            // Another list was shown while the servers searched
+            if app.list_generation == generation {
+                app.show_server_results(&query, &parsed, responses);
+            }
+        });
+    }
+
+    /// Shows what each account's server found for `query`.
+    fn show_server_results(
+        &mut self,
+        query: &str,
+        parsed: &Query,
+        responses: Vec<(String, Result<Vec<Email>>)>,
+    ) {
+        let mut results = Vec::new();
+        let mut errors = Vec::new();
+        let mut timed_out = false;
+        for (account, response) in responses {
+            match response {
+                Ok(emails) => {
+                    results.extend(emails.into_iter().filter(|e| parsed.matches_envelope(e)))
+                }
+                Err(e) => {
+                    timed_out |= loading::is_timeout(&e);
+                    errors.push(format!("{}: {}", account, e));
+                }
+            }
+        }
+
+        // Add matches that weren't fetched yet so they can be opened like any other email
+        let mut indices = Vec::new();
+        for email in results {
+            let existing = self
+                .emails
+                .iter()
+                .position(|e| e.account == email.account && e.id == email.id);
+            indices.push(existing.unwrap_or_else(|| {
+                self.emails.push(email);
+                self.emails.len() - 1
+            }));
+        }
+        indices.retain(|&idx| {
+            let email = &self.emails[idx];
+            !self.state.is_blocked(&email.sender) && self.quick_filters.matches(email)
+        });
+        self.filtered_emails = self.threads.group(&self.emails, indices);
+        self.highlighter = Highlighter::new(parsed);
+        self.select_first();
+
+        if errors.is_empty() {
+            self.set_status_message(format!(
+                "Server search found {} emails matching '{}'",
+                self.filtered_emails.len(),
+                query
+            ));
+        } else {
+            self.set_error_message(format!(
+                "Server search found {} emails matching '{}', failed for {}",
+                self.filtered_emails.len(),
+                query,
+                errors.join(", ")
+            ));
+        }
+        if timed_out {
+            self.ask_confirmation(
+                format!("Server search for '{}' timed out. Retry?", query),
+                ConfirmAction::RetryServerSearch(query.to_string()),
+            );
         }
     }
 
-    pub fn set_status_message(&mut self, message: String) {
-        self.status_message = Some((message, Instant::now()));
-        self.loading = false;
+    /// Shows only the messages sent by `address`, as the search `from:address`.
+    pub fn filter_by_sender(&mut self, address: &str) {
+        self.filtered_emails =
+            self.visible_indices(|email| email.sender.eq_ignore_ascii_case(address));
+        self.search_input = format!("from:{}", address);
+        self.highlighter = Highlighter::new(&Query {
+            from: vec![address.to_lowercase()],
+            ..Query::default()
+        });
+        self.select_first();
+        self.set_status_message(format!(
+            "Showing {} emails from {}",
+            self.filtered_emails.len(),
+            address
+        ));
     }
 
-    pub async fn run(&mut self) -> Result<()> {
-        // Setup terminal
-        enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
+    /// Shows only the mail from the sender of the selected email in the list,
+    /// or everything again when that's what it shows.
+    fn toggle_sender_filter(&mut self) {
+        let Some(sender) = self.selected_email().map(|email| email.sender.clone()) else {
+            return;
+        };
+        self.input_mode = InputMode::Normal;
+        self.focus = FocusPanel::EmailList;
+        if self.search_input == format!("from:{}", sender) {
+            self.search_input.clear();
+            self.search(String::new());
+            self.set_status_message("Showing all emails".to_string());
+        } else {
+            self.filter_by_sender(&sender);
+        }
+    }
 
-        // Fetch emails
-        self.set_status_message("Loading emails...".to_string());
-        let _ = self.refresh_emails().await;
+    /// Indices of emails matching `matches`, leaving out mail from blocked senders
+    /// and muted threads, and mail outside the active view in its order, grouped
+    /// by conversation.
+    fn visible_indices(&mut self, matches: impl Fn(&Email) -> bool) -> Vec<usize> {
+        let mut indices = self.matching_indices(matches);
+        views::sort(self.sort(), &self.emails, &mut indices);
+        self.threads.group(&self.emails, indices)
+    }
 
-        // Main loop
-        let tick_rate = Duration::from_millis(250);
-        let mut last_tick = Instant::now();
-        let status_timeout = Duration::from_secs(5);
+    /// Indices of emails matching `matches` that the list can show, unordered.
+    fn matching_indices(&self, matches: impl Fn(&Email) -> bool) -> Vec<usize> {
+        self.emails
+            .iter()
+            .enumerate()
+            .filter(|(_, email)| {
+                !self.state.is_blocked(&email.sender)
+                    && !self.state.is_muted(&email.thread_subject())
+                    && self.quick_filters.matches(email)
+                    && self
+                        .active_view
+                        .as_ref()
+                        .is_none_or(|view| view.query.matches(email))
+                    && matches(email)
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
 
-        loop {
-            terminal.draw(|f| view::draw(f, self))?;
+    /// What the list shows: the inbox, a view or a folder picked in the sidebar.
+    pub fn list_name(&self) -> &str {
+        match self.active_view.as_ref().map(|view| &view.source) {
+            Some(ViewSource::Configured(index)) => self
+                .views
+                .get(*index)
+                .map_or("View", |view| view.name.as_str()),
+            Some(ViewSource::Folder(name)) => name,
+            None => "Inbox",
+        }
+    }
 
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
-
-            if crossterm::event::poll(timeout)? {
-                if let Event::Key(key) = event::read()? {
-                    match self.input_mode {
-                        InputMode::Normal => match key.code {
-                            KeyCode::Char('q') => self.should_quit = true,
-                            KeyCode::Char('?') => self.input_mode = InputMode::Help,
-                            KeyCode::Char('r') => {
-                                // Just set the status message, we can't refresh asynchronously
-                                // inside the event loop without changing the architecture
-                                self.set_status_message("Can't refresh emails during UI running. Restart app to refresh.".to_string());
-                            }
-                            KeyCode::Char('/') => {
-                                self.input_mode = InputMode::Search;
-                                self.search_input.clear();
-                            }
-                            KeyCode::Char('j') | KeyCode::Down => {
-                                if !self.emails.is_empty() {
-                                    self.selected_index =
-                                        (self.selected_index + 1) % self.emails.len();
-                                    self.list_state.select(Some(self.selected_index));
-                                }
-                            }
-                            KeyCode::Char('k') | KeyCode::Up => {
-                                if !self.emails.is_empty() {
-                                    self.selected_index = if self.selected_index > 0 {
-                                        self.selected_index - 1
-                                    } else {
-                                        self.emails.len() - 1
-                                    };
-                                    self.list_state.select(Some(self.selected_index));
-                                }
-                            }
-                            KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => {
-                                if !self.emails.is_empty() {
-                                    self.input_mode = InputMode::EmailView;
-                                    self.focus = FocusPanel::EmailContent;
-                                }
-                            }
-                            KeyCode::Char('h') | KeyCode::Left => {
-                                self.focus = FocusPanel::EmailList;
-                            }
-                            KeyCode::Char('g') => {
-                                if !self.emails.is_empty() {
-                                    self.selected_index = 0;
-                                    self.list_state.select(Some(self.selected_index));
-                                }
-                            }
-                            KeyCode::Char('G') => {
-                                if !self.emails.is_empty() {
-                                    self.selected_index = self.emails.len() - 1;
-                                    self.list_state.select(Some(self.selected_index));
-                                }
-                            }
-                            _ => {}
-                        },
-                        InputMode::EmailView => match key.code {
-                            KeyCode::Esc | KeyCode::Char('h') | KeyCode::Left => {
-                                self.input_mode = InputMode::Normal;
-                                self.focus = FocusPanel::EmailList;
-                            }
-                            KeyCode::Char('j') | KeyCode::Down => {
-                                if !self.emails.is_empty() {
-                                    self.selected_index =
-                                        (self.selected_index + 1) % self.emails.len();
-                                    self.list_state.select(Some(self.selected_index));
-                                }
-                            }
-                            KeyCode::Char('k') | KeyCode::Up => {
-                                if !self.emails.is_empty() {
-                                    self.selected_index = if self.selected_index > 0 {
-                                        self.selected_index - 1
-                                    } else {
-                                        self.emails.len() - 1
-                                    };
-                                    self.list_state.select(Some(self.selected_index));
-                                }
-                            }
-                            KeyCode::Char('q') => self.should_quit = true,
-                            KeyCode::Char('?') => self.input_mode = InputMode::Help,
-                            _ => {}
-                        },
-                        InputMode::Help => {
-                            // Any key returns from help mode
-                            self.input_mode = InputMode::Normal;
-                        }
-                        InputMode::Search => match key.code {
-                            KeyCode::Esc => {
-                                // First change the mode to release the borrow
-                                self.input_mode = InputMode::Normal;
-                                // Clear search input
-                                self.search_input.clear();
-                                // Then show all emails
-                                self.search(String::new());
-                            }
-                            KeyCode::Enter => {
-                                // Clone the search input before using it
-                                let query = self.search_input.clone();
-                                // Set input mode first to release the borrow
-                                self.input_mode = InputMode::Normal;
-                                // Then perform the search
-                                self.search(query);
-                            }
-                            KeyCode::Char(c) => {
-                                self.search_input.push(c);
-                            }
-                            KeyCode::Backspace => {
-                                self.search_input.pop();
-                            }
-                            _ => {}
-                        },
-                    }
+    /// Unread and total emails in the list, collapsed conversations included.
+    pub fn list_counts(&self) -> (usize, usize) {
+        let indices = self.threads.ungrouped(&self.emails, &self.filtered_emails);
+        let unread = indices
+            .iter()
+            .filter(|&&idx| !self.emails[idx].read)
+            .count();
+        (unread, indices.len())
+    }
+
+    /// The order of the list shown.
+    pub fn sort(&self) -> ViewSort {
+        self.active_view
+            .as_ref()
+            .map_or(self.list_sort, |view| view.sort)
+    }
+
+    /// Sorts the list shown the next way, keeping it filtered, and remembers the
+    /// sort in the config for the view shown or else for the inbox and folders.
+    fn cycle_sort(&mut self) {
+        let sort = self.sort().next();
+        let view = match &mut self.active_view {
+            Some(view) => {
+                view.sort = sort;
+                match view.source {
+                    ViewSource::Configured(index) => self.views.get_mut(index),
+                    ViewSource::Folder(_) => None,
+                }
+            }
+            None => None,
+        };
+        let view = match view {
+            Some(view) => {
+                view.sort = sort;
+                Some(view.name.clone())
+            }
+            None => {
+                self.list_sort = sort;
+                None
+            }
+        };
+
+        let mut indices = self.threads.ungrouped(&self.emails, &self.filtered_emails);
+        views::sort(sort, &self.emails, &mut indices);
+        self.filtered_emails = self.threads.group(&self.emails, indices);
+        self.select_first();
+
+        let saved = match &self.config_path {
+            Some(path) => config::save_sort(path, view.as_deref(), sort),
+            None => Ok(()),
+        };
+        match saved {
+            Ok(()) => self.set_status_message(format!("Sorted by {}", sort.label())),
+            Err(e) => self.set_error_message(format!(
+                "Sorted by {}, but failed to remember it: {}",
+                sort.label(),
+                e
+            )),
+        }
+    }
+
+    /// Runs what the keymap binds `key`, typed after `prefix`, to in the list
+    /// or the open email, or waits for the second key of a sequence.
+    fn handle_mail_key(&mut self, context: Context, prefix: Option<char>, key: KeyEvent) {
+        match keymap::lookup(context, prefix, &key) {
+            Some(action) => self.run_key_action(action, key),
+            None if prefix.is_none() && keymap::is_prefix(context, &key) => {
+                if let KeyCode::Char(c) = key.code {
+                    self.pending_prefix = Some(c);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Carries out a binding of the keymap. `key` is the key that was pressed,
+    /// which the bindings of `1-9` read their number from.
+    fn run_key_action(&mut self, action: KeyAction, key: KeyEvent) {
+        let digit = match key.code {
+            KeyCode::Char(c @ '1'..='9') => c as usize - '1' as usize,
+            _ => 0,
+        };
+        let tagged = matches!(self.input_mode, InputMode::Normal) && !self.selection.is_empty();
+        match action {
+            KeyAction::Quit => self.should_quit = true,
+            KeyAction::Help => self.open_help(),
+            KeyAction::NextEmail => self.select_next(),
+            KeyAction::PreviousEmail => self.select_previous(),
+            KeyAction::FirstEmail => self.select_first(),
+            KeyAction::LastEmail => self.select_last(),
+            KeyAction::OpenEmail => {
+                if self.selected_email().is_some() {
+                    self.input_mode = InputMode::EmailView;
+                    self.focus = FocusPanel::EmailContent;
+                    self.open_selected();
+                }
+            }
+            KeyAction::Cancel if tagged => {
+                self.selection.clear();
+                self.set_status_message("Selection cleared".to_string());
+            }
+            KeyAction::Cancel => {
+                self.cancel_syncs();
+                self.cancel_body_fetches();
+            }
+            KeyAction::CloseEmail => {
+                self.cancel_body_fetches();
+                self.input_mode = InputMode::Normal;
+                self.focus = FocusPanel::EmailList;
+            }
+            KeyAction::BackToList | KeyAction::LeaveSidebar => {
+                self.input_mode = InputMode::Normal;
+                self.focus = FocusPanel::EmailList;
+            }
+            KeyAction::FocusSidebar if self.sidebar.is_some() => {
+                self.input_mode = InputMode::Sidebar;
+                self.focus = FocusPanel::Sidebar;
+            }
+            KeyAction::FocusSidebar => self.focus = FocusPanel::EmailList,
+            KeyAction::ToggleSidebar => self.toggle_sidebar(),
+            KeyAction::ToggleThread => self.toggle_thread(),
+            KeyAction::Refresh => self.refresh(),
+            KeyAction::Search => {
+                self.input_mode = InputMode::Search;
+                self.search_input.clear();
+                self.search_history_index = None;
+            }
+            KeyAction::SavedSearches => self.open_searches(),
+            KeyAction::CommandLine => {
+                self.input_mode = InputMode::Command;
+                self.command_input.clear();
+                self.command_folders = None;
+            }
+            KeyAction::Tag => self.toggle_tag(),
+            KeyAction::Visual => self.toggle_visual(),
+            KeyAction::MarkRead if tagged => self.apply_to_selection(SelectionAction::MarkRead),
+            KeyAction::MarkRead => self.confirm_mark_results_read(),
+            KeyAction::Archive if tagged => self.apply_to_selection(SelectionAction::Archive),
+            KeyAction::Archive => self.apply_to_selected(MessageAction::Archive),
+            KeyAction::Delete => self.confirm_delete(),
+            KeyAction::Move => self.open_move_picker(),
+            KeyAction::Compose => self.open_compose(),
+            KeyAction::Reply => self.open_reply(false),
+            KeyAction::ReplyAll => self.open_reply(true),
+            KeyAction::Forward => self.open_forward(),
+            KeyAction::ReportJunk => self.confirm_report_junk(),
+            KeyAction::RespondToMeeting => self.confirm_meeting_response(),
+            KeyAction::ThreadActions => self.open_thread_actions(),
+            KeyAction::Undo => self.undo(),
+            KeyAction::AddressMenu => self.open_address_menu(),
+            KeyAction::Directory => self.open_directory(),
+            KeyAction::Headers => self.open_headers(),
+            KeyAction::SaveAttachments => self.save_attachments(),
+            KeyAction::OpenInBrowser => self.open_in_browser(),
+            KeyAction::CycleBodyView => self.cycle_body_view(),
+            KeyAction::ToggleQuotes => self.toggle_quotes(),
+            KeyAction::Links => self.open_links(),
+            KeyAction::Hints => self.start_hints(),
+            KeyAction::SenderFilter => self.toggle_sender_filter(),
+            KeyAction::AttachmentFilter => self.toggle_attachment_filter(),
+            KeyAction::FilterUnread => self.set_quick_filters(QuickFilters {
+                unread: !self.quick_filters.unread,
+                ..self.quick_filters
+            }),
+            KeyAction::FilterFlagged => self.set_quick_filters(QuickFilters {
+                flagged: !self.quick_filters.flagged,
+                ..self.quick_filters
+            }),
+            KeyAction::FilterAttachments => self.set_quick_filters(QuickFilters {
+                attachments: !self.quick_filters.attachments,
+                ..self.quick_filters
+            }),
+            KeyAction::ClearFilters => self.set_quick_filters(QuickFilters::default()),
+            KeyAction::CopyBody => self.copy_body(),
+            KeyAction::CopySender => self.copy_sender(),
+            KeyAction::CopyMessageId => self.copy_message_id(),
+            KeyAction::NewTab => self.new_tab(),
+            KeyAction::CloseTab => self.close_tab(),
+            KeyAction::NextTab => self.switch_tab((self.tab_index + 1) % self.tabs.len()),
+            KeyAction::PreviousTab => {
+                let count = self.tabs.len();
+                self.switch_tab((self.tab_index + count - 1) % count)
+            }
+            KeyAction::GoToTab => self.switch_tab(digit),
+            KeyAction::OpenView => self.open_view(digit),
+            KeyAction::Inbox => self.close_view(),
+            KeyAction::DateRange => {
+                self.date_picker = Some(DatePicker::new(self.date_range));
+                self.input_mode = InputMode::DateRange;
+            }
+            KeyAction::PreviousQuarter | KeyAction::NextQuarter => {
+                let range = self
+                    .date_range
+                    .step_quarter(action == KeyAction::NextQuarter);
+                self.set_date_range(range);
+            }
+            KeyAction::RelativeDates => self.toggle_relative_dates(),
+            KeyAction::Sort => self.cycle_sort(),
+            KeyAction::NarrowList => self.resize_list(false),
+            KeyAction::WidenList => self.resize_list(true),
+            KeyAction::CycleLayout => self.cycle_layout(),
+            KeyAction::SyncHealth => {
+                if !self.sync_failures.is_empty() {
+                    self.sync_health_index = 0;
+                    self.input_mode = InputMode::SyncHealth;
+                }
+            }
+            KeyAction::DismissBanner => self.banner_dismissed = true,
+            KeyAction::Folders => self.open_folder_manager(),
+            KeyAction::Recoverable => self.open_recoverable_items(),
+            KeyAction::OutOfOffice => self.open_out_of_office(),
+            KeyAction::Tasks => self.open_tasks(),
+            KeyAction::Outbox => self.open_outbox(),
+            KeyAction::Drafts => self.open_drafts(),
+            KeyAction::ScrollDown => self.scroll_body(1),
+            KeyAction::ScrollUp => self.scroll_body(-1),
+            KeyAction::PageDown => self.scroll_body_page(true),
+            KeyAction::PageUp => self.scroll_body_page(false),
+            KeyAction::NextAndOpen => {
+                self.select_next();
+                self.open_selected();
+            }
+            KeyAction::PreviousAndOpen => {
+                self.select_previous();
+                self.open_selected();
+            }
+            KeyAction::Find => {
+                self.find = Some(Find::default());
+                self.input_mode = InputMode::Find;
+            }
+            KeyAction::FindNext => self.find_next(true),
+            KeyAction::FindPrevious => self.find_next(false),
+            KeyAction::Pager => self.open_body_in_pager(),
+            KeyAction::NextFolder => {
+                if let Some(sidebar) = self.sidebar.as_mut() {
+                    sidebar.select_next();
                 }
             }
+            KeyAction::PreviousFolder => {
+                if let Some(sidebar) = self.sidebar.as_mut() {
+                    sidebar.select_previous();
+                }
+            }
+            KeyAction::OpenFolder => {
+                let Some(folder) = self.sidebar.as_ref().and_then(Sidebar::selected_folder) else {
+                    return;
+                };
+                let (name, inbox) = (folder.name.clone(), folder.inbox);
+                self.input_mode = InputMode::Normal;
+                self.focus = FocusPanel::EmailList;
+                self.open_folder(&name, inbox);
+            }
+            KeyAction::ReloadFolders => self.load_sidebar(false),
+            KeyAction::DiscardMessage
+            | KeyAction::Send
+            | KeyAction::ToggleSignature
+            | KeyAction::Attach
+            | KeyAction::RemoveAttachment
+            | KeyAction::EditInEditor
+            | KeyAction::SaveDraft => self.run_compose_action(action),
+        }
+    }
 
-            if last_tick.elapsed() >= tick_rate {
-                last_tick = Instant::now();
+    /// Copies the address of the selected email's sender.
+    fn copy_sender(&mut self) {
+        let Some(sender) = self.selected_email().map(|email| email.sender.clone()) else {
+            return;
+        };
+        match clipboard::copy(&sender) {
+            Ok(()) => self.set_status_message(format!("Copied {} to clipboard", sender)),
+            Err(e) => self.set_error_message(format!("Failed to copy the address: {}", e)),
+        }
+    }
 
-                // Clear status message after timeout
-                if let Some((_, instant)) = self.status_message {
-                    if instant.elapsed() >= status_timeout {
-                        self.status_message = None;
+    /// Copies the Message-ID of the selected email, fetching its headers first
+    /// when the list fetch didn't include it.
+    fn copy_message_id(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        if email.message_id().is_none() && email.headers.is_none() {
+            let (account, id) = (email.account.clone(), email.id.clone());
+            let sent = id.clone();
+            self.request(
+                &account.clone(),
+                Operation::BodyFetch,
+                |client| async move { client.fetch_headers(&sent).await },
+                move |app, result| {
+                    let failed = result.is_err();
+                    app.apply_headers(HeadersUpdate {
+                        account: account.clone(),
+                        id: id.clone(),
+                        result,
+                    });
+                    // Copy only what is still selected, now that it has headers
+                    let selected = app
+                        .selected_email()
+                        .is_some_and(|email| email.account == account && email.id == id);
+                    if !failed && selected {
+                        app.copy_message_id();
                     }
+                },
+            );
+            return;
+        }
+        let Some(message_id) = self.selected_email().and_then(Email::message_id) else {
+            self.set_error_message("This email has no Message-ID".to_string());
+            return;
+        };
+        match clipboard::copy(&message_id) {
+            Ok(()) => self.set_status_message(format!("Copied {} to clipboard", message_id)),
+            Err(e) => self.set_error_message(format!("Failed to copy the Message-ID: {}", e)),
+        }
+    }
+
+    /// Shows only what `filters` let through, running the search again under them.
+    fn set_quick_filters(&mut self, filters: QuickFilters) {
+        self.quick_filters = filters;
+        // The search runs again under the new filters
+        let query = self.search_input.clone();
+        match query.strip_prefix(SERVER_SEARCH_PREFIX) {
+            Some(server_query) => self.search_server(server_query.trim()),
+            None => {
+                if self.filter_list(&query).is_err() {
+                    self.filtered_emails = self.visible_indices(|_| true);
                 }
+                self.select_first();
             }
+        }
+        let labels = filters.labels();
+        if labels.is_empty() {
+            self.set_status_message("Quick filters off".to_string());
+        } else {
+            self.set_status_message(format!("Showing only {}", labels.join(", ")));
+        }
+    }
 
-            if self.should_quit {
-                break;
+    /// The title of every tab, shown above the list once there are two.
+    pub fn tab_titles(&self) -> Vec<String> {
+        self.tabs
+            .iter()
+            .enumerate()
+            .map(|(idx, tab)| {
+                if idx == self.tab_index {
+                    tabs::title(self.list_name(), &self.search_input)
+                } else {
+                    tab.title.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Opens a tab next to this one showing the same list, and switches to it.
+    fn new_tab(&mut self) {
+        let tab = Tab {
+            view: self
+                .active_view
+                .clone()
+                .map(|view| (view, self.emails.clone())),
+            ..self.tab_without_view()
+        };
+        self.tabs[self.tab_index] = tab;
+        self.tab_index += 1;
+        self.tabs.insert(self.tab_index, Tab::default());
+        self.set_status_message(format!("Tab {} of {}", self.tab_index + 1, self.tabs.len()));
+    }
+
+    /// Closes this tab for the one after it, or before it if it was the last.
+    fn close_tab(&mut self) {
+        if self.tabs.len() == 1 {
+            self.set_error_message("The last tab stays open".to_string());
+            return;
+        }
+        self.tabs.remove(self.tab_index);
+        self.tab_index = self.tab_index.min(self.tabs.len() - 1);
+        let tab = std::mem::take(&mut self.tabs[self.tab_index]);
+        let inbox_loaded = self.active_view.is_none();
+        self.restore_tab(tab, inbox_loaded);
+    }
+
+    /// Puts this tab's list in the background and shows the tab at `index`.
+    fn switch_tab(&mut self, index: usize) {
+        if index == self.tab_index || index >= self.tabs.len() {
+            return;
+        }
+        let inbox_loaded = self.active_view.is_none();
+        let leaving = self.tab_without_view();
+        self.tabs[self.tab_index] = Tab {
+            view: self
+                .active_view
+                .take()
+                .map(|view| (view, std::mem::take(&mut self.emails))),
+            ..leaving
+        };
+        self.tab_index = index;
+        let tab = std::mem::take(&mut self.tabs[index]);
+        self.restore_tab(tab, inbox_loaded);
+    }
+
+    /// This tab's search and selection, to keep while another tab is shown.
+    fn tab_without_view(&self) -> Tab {
+        let key = |email: &Email| (email.account.clone(), email.id.clone());
+        Tab {
+            view: None,
+            search: self.search_input.clone(),
+            filters: self.quick_filters,
+            listed: self
+                .filtered_emails
+                .iter()
+                .map(|&idx| key(&self.emails[idx]))
+                .collect(),
+            selected: self.selected_email().map(key),
+            title: tabs::title(self.list_name(), &self.search_input),
+        }
+    }
+
+    /// Shows the list of `tab`. The inbox is loaded again unless it is still in
+    /// memory, and the search runs again to include mail that came in since,
+    /// except a server search, whose results are listed as they were.
+    fn restore_tab(&mut self, tab: Tab, inbox_loaded: bool) {
+        self.list_generation += 1;
+        match tab.view {
+            Some((view, emails)) => {
+                self.emails = emails;
+                self.active_view = Some(view);
+            }
+            None => {
+                self.active_view = None;
+                if !inbox_loaded {
+                    self.emails.clear();
+                    self.load_cache();
+                    self.refresh_emails();
+                }
+            }
+        }
+        self.search_input = tab.search;
+        self.quick_filters = tab.filters;
+        let position = |emails: &[Email], (account, id): &(String, String)| {
+            emails
+                .iter()
+                .position(|email| email.account == *account && email.id == *id)
+        };
+        if let Some(query) = self.search_input.strip_prefix(SERVER_SEARCH_PREFIX) {
+            let query = Query::parse(query, &self.groups).unwrap_or_default();
+            self.filtered_emails = tab
+                .listed
+                .iter()
+                .filter_map(|key| position(&self.emails, key))
+                .collect();
+            self.highlighter = Highlighter::new(&query);
+        } else {
+            let query = self.search_input.clone();
+            if self.filter_list(&query).is_err() {
+                self.filtered_emails = self.visible_indices(|_| true);
             }
         }
+        let selected = tab.selected.and_then(|key| {
+            let idx = position(&self.emails, &key)?;
+            self.filtered_emails
+                .iter()
+                .position(|&listed| listed == idx)
+        });
+        self.select_index(selected.unwrap_or(0));
+        self.focus = FocusPanel::EmailList;
+        self.input_mode = InputMode::Normal;
+        self.set_status_message(format!(
+            "Tab {} of {}: {}",
+            self.tab_index + 1,
+            self.tabs.len(),
+            self.list_name()
+        ));
+    }
 
-        // Restore terminal
-        disable_raw_mode()?;
-        execute!(
-            terminal.backend_mut(),
-            LeaveAlternateScreen,
-            DisableMouseCapture
-        )?;
-        terminal.show_cursor()?;
+    fn cycle_layout(&mut self) {
+        self.layout = self.layout.next();
+        self.set_status_message(format!("Layout: {}", self.layout.label()));
+    }
 
-        Ok(())
+    /// Widens or narrows the list by a step and remembers its width in the config.
+    fn resize_list(&mut self, wider: bool) {
+        let width = if wider {
+            self.list_width + LIST_WIDTH_STEP
+        } else {
+            self.list_width.saturating_sub(LIST_WIDTH_STEP)
+        }
+        .clamp(LIST_WIDTH_RANGE.0, LIST_WIDTH_RANGE.1);
+        if width == self.list_width {
+            return;
+        }
+        self.list_width = width;
+        if let Some(path) = &self.config_path {
+            if let Err(e) = config::save_list_width(path, width) {
+                self.set_error_message(format!("Failed to remember the list width: {}", e));
+            }
+        }
+    }
+
+    /// Switches the list between relative and full dates, remembering the choice in the config.
+    fn toggle_relative_dates(&mut self) {
+        self.relative_dates = !self.relative_dates;
+        self.set_status_message(if self.relative_dates {
+            "Showing how long ago mail came".to_string()
+        } else {
+            "Showing full dates".to_string()
+        });
+        if let Some(path) = &self.config_path {
+            if let Err(e) = config::save_relative_dates(path, self.relative_dates) {
+                self.set_error_message(format!("Failed to remember the date format: {}", e));
+            }
+        }
+    }
+
+    /// Shows the configured view at `index`, fetching its folders from every account.
+    fn open_view(&mut self, index: usize) {
+        let Some(config) = self.views.get(index).cloned() else {
+            return;
+        };
+        let query = match Query::parse(&config.query, &self.groups) {
+            Ok(query) => query,
+            Err(e) => {
+                self.set_error_message(format!("Invalid query in view {}: {}", config.name, e));
+                return;
+            }
+        };
+        let view = ActiveView {
+            source: ViewSource::Configured(index),
+            query,
+            sort: config.sort,
+        };
+        self.show_view(view, &config.name, &config.folders);
+    }
+
+    /// Loads the shown view or folder again, or syncs the inbox of every account.
+    fn refresh(&mut self) {
+        match self.active_view.as_ref().map(|v| v.source.clone()) {
+            Some(ViewSource::Configured(index)) => self.open_view(index),
+            Some(ViewSource::Folder(name)) => self.open_folder(&name, false),
+            None => {
+                self.set_status_message("Refreshing...".to_string());
+                self.refresh_emails();
+            }
+        }
+    }
+
+    /// Shows the folders named `name` of every account, or the inbox.
+    fn open_folder(&mut self, name: &str, inbox: bool) {
+        if inbox {
+            self.close_view();
+            return;
+        }
+        let view = ActiveView {
+            source: ViewSource::Folder(name.to_string()),
+            query: Query::default(),
+            sort: self.list_sort,
+        };
+        self.show_view(view, name, &[name.to_string()]);
+    }
+
+    /// Replaces the list with `folders` of every account, narrowed and sorted by `view`.
+    fn show_view(&mut self, view: ActiveView, name: &str, folders: &[String]) {
+        self.loading.start(Operation::ListSync);
+        self.list_generation += 1;
+        let generation = self.list_generation;
+        let limit = self.timeouts.get(Operation::ListSync);
+        let accounts = Rc::clone(&self.accounts);
+        let folders = folders.to_vec();
+        let request = async move {
+            let mut emails = Vec::new();
+            let mut errors = Vec::new();
+            for account in accounts.iter() {
+                let request = account.client.fetch_folders();
+                let account_folders = match loading::with_timeout(limit, request).await {
+                    Ok(folders) => folders,
+                    Err(e) => {
+                        errors.push(format!("{}: {}", account.name, e));
+                        continue;
+                    }
+                };
+                for wanted in &folders {
+                    let Some(folder) = account_folders
+                        .iter()
+                        .find(|f| f.name.eq_ignore_ascii_case(wanted))
+                    else {
+                        errors.push(format!("{}: no folder named {}", account.name, wanted));
+                        continue;
+                    };
+                    let request = account.client.fetch_folder_emails(&folder.id);
+                    match loading::with_timeout(limit, request).await {
+                        Ok(found) => emails.extend(found),
+                        Err(e) => errors.push(format!("{}: {}", account.name, e)),
+                    }
+                }
+            }
+            (emails, errors)
+        };
+        let name = name.to_string();
+        self.spawn_request(request, move |app, (emails, errors)| {
+            app.loading.finish(Operation::ListSync);
+            // Another list was shown while the folders loaded
+            if app.list_generation == generation {
+                app.apply_view(view, &name, emails, errors);
+            }
+        });
+    }
+
+    /// Replaces the list with the `emails` fetched for `view`.
+    fn apply_view(
+        &mut self,
+        view: ActiveView,
+        name: &str,
+        mut emails: Vec<Email>,
+        errors: Vec<String>,
+    ) {
+        // Keep bodies loaded earlier, which the folder fetch may not include
+        for email in emails.iter_mut() {
+            if let Some(previous) = self
+                .emails
+                .iter()
+                .find(|e| e.account == email.account && e.id == email.id)
+            {
+                email.body.merge(&previous.body);
+            }
+        }
+        self.emails = emails;
+        self.active_view = Some(view);
+        self.show_all_emails();
+        self.select_first();
+
+        match errors.as_slice() {
+            [] => {
+                self.set_status_message(format!("{}: {} emails", name, self.filtered_emails.len()))
+            }
+            [error] => self.set_error_message(format!("{}: {}", name, error)),
+            [error, rest @ ..] => self.set_error_message(format!(
+                "{}: {} (and {} more errors)",
+                name,
+                error,
+                rest.len()
+            )),
+        }
+    }
+
+    /// Leaves the active view for the inbox of every account.
+    fn close_view(&mut self) {
+        self.list_generation += 1;
+        if self.active_view.take().is_none() {
+            return;
+        }
+        self.emails.clear();
+        self.load_cache();
+        self.select_first();
+        self.refresh_emails();
+    }
+
+    /// Syncs every account independently in the background, keeping the mail of
+    /// the accounts that succeed and recording the others for the sync health banner.
+    pub fn refresh_emails(&mut self) {
+        let names: Vec<String> = self.accounts.iter().map(|a| a.name.clone()).collect();
+        for name in names {
+            self.sync_in_background(&name);
+        }
+    }
+
+    /// Retries the sync of a single account, e.g. from the sync health popup.
+    fn retry_account(&mut self, name: &str) {
+        self.sync_in_background(name);
+    }
+
+    /// Starts fetching one account's mail on a local task, unless that is already
+    /// running. The result arrives in `apply_sync` through the event loop.
+    fn sync_in_background(&mut self, name: &str) {
+        let Some(idx) = self.accounts.iter().position(|a| a.name == name) else {
+            return;
+        };
+        if self.syncing.iter().any(|(n, _)| n == name) {
+            return;
+        }
+        if !self.sync_batch.iter().any(|n| n == name) {
+            self.sync_batch.push(name.to_string());
+        }
+        self.loading.start(Operation::ListSync);
+
+        // Pages fetched so far arrive until the sync ends and drops its sender
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        let tx = self.actions_tx.clone();
+        let account_name = name.to_string();
+        tokio::task::spawn_local(async move {
+            while let Some(progress) = progress_rx.recv().await {
+                let update = Action::SyncProgress {
+                    account: account_name.clone(),
+                    progress,
+                };
+                if tx.send(update).is_err() {
+                    return;
+                }
+            }
+        });
+
+        let accounts = Rc::clone(&self.accounts);
+        let limit = self.timeouts.get(Operation::ListSync);
+        let tx = self.actions_tx.clone();
+        info!(account = name, "Sync started");
+        let span = info_span!("sync", account = name);
+        let handle = tokio::task::spawn_local(
+            async move {
+                let account = &accounts[idx];
+                let progress = Progress::new(progress_tx);
+                let request = account.client.fetch_current_quarter_emails(&progress);
+                let result = loading::with_timeout(limit, request).await;
+                // The receiver only goes away when the app quits
+                let _ = tx.send(Action::Synced(SyncUpdate {
+                    account: account.name.clone(),
+                    result,
+                }));
+            }
+            .instrument(span),
+        );
+        self.syncing.push((name.to_string(), handle.abort_handle()));
+    }
+
+    /// Replaces what was previously fetched for an account with the mail of a
+    /// finished sync, in place, and summarizes the batch once it is done.
+    fn apply_sync(&mut self, update: SyncUpdate) {
+        let SyncUpdate {
+            account: name,
+            result,
+        } = update;
+        let before = self.syncing.len();
+        self.syncing.retain(|(n, _)| n != &name);
+        if self.syncing.len() < before {
+            self.loading.finish(Operation::ListSync);
+        }
+        self.sync_progress.retain(|(n, _)| n != &name);
+        self.sync_failures.retain(|failure| failure.account != name);
+
+        match result {
+            Ok(mut emails) => {
+                info!(account = name, emails = emails.len(), "Sync finished");
+                // Keep bodies loaded earlier, which the list fetch may not include
+                for email in emails.iter_mut() {
+                    if let Some(previous) = self
+                        .emails
+                        .iter()
+                        .find(|e| e.account == name && e.id == email.id)
+                    {
+                        email.body.merge(&previous.body);
+                        if email.headers.is_none() {
+                            email.headers = previous.headers.clone();
+                        }
+                    }
+                }
+                let cached = self
+                    .cache
+                    .as_mut()
+                    .map(|cache| cache.store_account(&name, &emails));
+                if let Some(Err(e)) = cached {
+                    self.set_error_message(format!("Failed to update the cache: {}", e));
+                }
+
+                // A view shows other folders, which this sync doesn't cover
+                if self.active_view.is_none() {
+                    let selected = self
+                        .selected_email()
+                        .map(|e| (e.account.clone(), e.id.clone()));
+                    let position = self
+                        .emails
+                        .iter()
+                        .position(|email| email.account == name)
+                        .unwrap_or(self.emails.len());
+                    self.emails.retain(|email| email.account != name);
+                    let position = position.min(self.emails.len());
+                    self.emails.splice(position..position, emails);
+                    self.show_all_emails();
+                    self.keep_selection(selected);
+                }
+            }
+            Err(e) => {
+                warn!(account = name, "Sync failed: {:#}", e);
+                if let Some(certificate) = e.downcast_ref::<UntrustedCertificate>() {
+                    if self.confirmation.is_none() {
+                        self.ask_trust_certificate(&name, certificate);
+                    }
+                }
+                self.sync_failures.push(SyncFailure {
+                    account: name,
+                    error: e.to_string(),
+                });
+            }
+        }
+
+        if self.syncing.is_empty() {
+            self.startup = None;
+            self.summarize_sync_batch();
+        }
+    }
+
+    /// Asks whether to retry or work offline when nothing could be synced, unless
+    /// a popup is open, which gets a status message instead.
+    fn show_sync_error(&mut self, message: String) {
+        if !matches!(self.input_mode, InputMode::Normal | InputMode::EmailView) {
+            self.set_error_message(message);
+            return;
+        }
+        error!("{}", message);
+        self.sync_error = Some(message);
+        self.input_mode = InputMode::SyncError;
+    }
+
+    fn handle_sync_error_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('R') | KeyCode::Char('r') => {
+                self.sync_error = None;
+                self.close_popup();
+                self.refresh_emails();
+            }
+            KeyCode::Char('o') => {
+                self.sync_error = None;
+                self.banner_dismissed = true;
+                self.close_popup();
+                self.set_status_message(format!(
+                    "Working offline with {} cached emails, r to sync again",
+                    self.emails.len()
+                ));
+            }
+            KeyCode::Esc => {
+                self.sync_error = None;
+                self.close_popup();
+            }
+            _ => {}
+        }
+    }
+
+    fn summarize_sync_batch(&mut self) {
+        let batch = std::mem::take(&mut self.sync_batch);
+        let failed: Vec<&SyncFailure> = self
+            .sync_failures
+            .iter()
+            .filter(|failure| batch.contains(&failure.account))
+            .collect();
+
+        match (batch.as_slice(), failed.as_slice()) {
+            ([name], []) => self.set_status_message(format!("Synced {}", name)),
+            ([name], [failure]) => {
+                let message = format!("Failed to sync {}: {}", name, failure.error);
+                self.show_sync_error(message);
+            }
+            (_, []) => self.set_status_message("Emails refreshed successfully".to_string()),
+            (_, [first, ..]) if failed.len() == batch.len() => {
+                let message = format!("Failed to fetch emails: {}", first.error);
+                self.show_sync_error(message);
+            }
+            _ => {
+                let message = format!(
+                    "Synced {} of {} accounts",
+                    batch.len() - failed.len(),
+                    batch.len()
+                );
+                self.set_status_message(message);
+            }
+        }
+
+        if !self.sync_failures.is_empty() {
+            self.banner_dismissed = false;
+        }
+        if matches!(self.input_mode, InputMode::SyncHealth) {
+            if self.sync_failures.is_empty() {
+                self.close_popup();
+            } else {
+                self.sync_health_index = self.sync_health_index.min(self.sync_failures.len() - 1);
+            }
+        }
+    }
+
+    /// Selects the email identified by `selected` again after the list changed.
+    fn keep_selection(&mut self, selected: Option<(String, String)>) {
+        let Some((account, id)) = selected else {
+            return;
+        };
+        if let Some(position) = self
+            .filtered_emails
+            .iter()
+            .position(|&idx| self.emails[idx].account == account && self.emails[idx].id == id)
+        {
+            self.select_index(position);
+        }
+    }
+
+    /// Shows the mail cached by earlier sessions, grouped by account in config order.
+    fn load_cache(&mut self) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        match cache.load() {
+            Ok(mut emails) => {
+                emails.retain(|email| self.accounts.iter().any(|a| a.name == email.account));
+                emails.sort_by_key(|email| {
+                    self.accounts.iter().position(|a| a.name == email.account)
+                });
+                self.emails = emails;
+                self.show_all_emails();
+            }
+            Err(e) => self.set_error_message(format!("Failed to load the cache: {}", e)),
+        }
+    }
+
+    /// Resets the filtered list to show all emails, keeping the selection in range.
+    fn show_all_emails(&mut self) {
+        self.filtered_emails = self.visible_indices(|_| true);
+        self.highlighter = Highlighter::default();
+
+        if !self.filtered_emails.is_empty() {
+            self.selected_index = self.selected_index.min(self.filtered_emails.len() - 1);
+            self.list_state.select(Some(self.selected_index));
+        }
+    }
+
+    /// The client of the account an email was fetched from.
+    fn client_for(&self, account: &str) -> Result<&T> {
+        self.accounts
+            .iter()
+            .find(|a| a.name == account)
+            .map(|a| &a.client)
+            .ok_or_else(|| anyhow!("Unknown account {}", account))
+    }
+
+    /// A handle on the client of `account` that a request can take to its task.
+    fn account_client(&self, account: &str) -> Result<AccountClient<T>> {
+        let idx = self
+            .accounts
+            .iter()
+            .position(|a| a.name == account)
+            .ok_or_else(|| anyhow!("Unknown account {}", account))?;
+        Ok(AccountClient {
+            accounts: Rc::clone(&self.accounts),
+            idx,
+        })
+    }
+
+    /// Runs `request` on a local task and hands what it returns to `respond`
+    /// through the event loop, the way syncs report back, so keys keep working
+    /// while the server answers.
+    fn spawn_request<R: 'static>(
+        &mut self,
+        request: impl Future<Output = R> + 'static,
+        respond: impl FnOnce(&mut Self, R) + 'static,
+    ) {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        self.responders.insert(
+            id,
+            Box::new(move |app, response| {
+                // Only the task of this request answers with its id
+                if let Ok(response) = response.downcast::<R>() {
+                    respond(app, *response);
+                }
+            }),
+        );
+        let tx = self.actions_tx.clone();
+        tokio::task::spawn_local(async move {
+            let response = request.await;
+            // The receiver only goes away when the app quits
+            let _ = tx.send(Action::Response {
+                id,
+                response: Box::new(response),
+            });
+        });
+    }
+
+    /// Sends the request `send` makes with the client of `account` in the
+    /// background, cancelled past the timeout of `operation`, see `spawn_request`.
+    fn request<R: 'static, F: Future<Output = Result<R>> + 'static>(
+        &mut self,
+        account: &str,
+        operation: Operation,
+        send: impl FnOnce(AccountClient<T>) -> F,
+        respond: impl FnOnce(&mut Self, Result<R>) + 'static,
+    ) {
+        let client = match self.account_client(account) {
+            Ok(client) => client,
+            Err(e) => return respond(self, Err(e)),
+        };
+        let limit = self.timeouts.get(operation);
+        self.spawn_request(loading::with_timeout(limit, send(client)), respond);
+    }
+
+    pub fn set_status_message(&mut self, message: String) {
+        self.status.post(Severity::Info, message);
+    }
+
+    pub fn set_error_message(&mut self, message: String) {
+        error!("{}", message);
+        self.status.post(Severity::Error, message);
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        // Setup terminal, which the guard restores however `run` ends
+        let guard = TerminalGuard::enter()?;
+        let backend = CrosstermBackend::new(io::stdout());
+        let mut terminal = Terminal::new(backend)?;
+
+        // Paint the frame before anything slow, then bring up cached mail and the
+        // accounts stage by stage, with the stage shown in the status bar
+        self.startup = Some(Startup::Cache);
+        terminal.draw(|f| view::draw(f, self))?;
+        debug!("Opening the cache");
+        match Cache::open(&self.cache_config) {
+            Ok(cache) => self.cache = cache,
+            Err(e) => {
+                self.set_error_message(format!("Continuing without the offline cache: {:#}", e))
+            }
+        }
+        self.load_cache();
+        debug!(emails = self.emails.len(), "Loaded the cache");
+        self.startup = Some(Startup::SignIn);
+        self.refresh_emails();
+        if self.syncing.is_empty() {
+            self.startup = None;
+        }
+
+        // Messages the last session didn't get out wait in the outbox
+        match Outbox::load() {
+            Ok(outbox) => self.outbox = outbox,
+            Err(e) => self.set_error_message(format!("Failed to load the outbox: {:#}", e)),
+        }
+        let unsent = self.outbox.unsent().len();
+        if unsent > 0 {
+            self.set_status_message(format!(
+                "{} unsent message(s) in the outbox, P to review",
+                unsent
+            ));
+        }
+
+        // A message being written when the last session ended opens again
+        match LocalDraft::load() {
+            Ok(Some(draft)) => {
+                self.composing = Some(Compose::from_message(
+                    draft.account,
+                    draft.kind,
+                    &draft.message,
+                ));
+                self.input_mode = InputMode::Compose;
+                self.set_status_message(
+                    "Recovered the message being written when mail-tui last exited".to_string(),
+                );
+            }
+            Ok(None) => {}
+            Err(e) => self.set_error_message(format!("Failed to recover the draft: {:#}", e)),
+        }
+
+        // Show what changed since the last version the user ran
+        self.whats_new = discovery::releases_since(self.state.last_seen_version.as_deref());
+        if !self.whats_new.is_empty() {
+            self.input_mode = InputMode::WhatsNew;
+        }
+
+        // Main loop, redrawing after every action
+        self.input.spawn();
+        action::spawn_ticks(self.actions_tx.clone(), Duration::from_millis(250));
+        action::spawn_signals(self.actions_tx.clone());
+        let result = self.event_loop(&mut terminal).await;
+
+        // Restore terminal, also when the loop failed
+        drop(guard);
+
+        // Keep key usage for the tips
+        self.state.save()?;
+
+        result
+    }
+
+    async fn event_loop(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<()> {
+        while !self.should_quit {
+            if std::mem::take(&mut self.needs_clear) {
+                terminal.clear()?;
+                self.drawn_images.clear();
+            }
+            terminal.draw(|f| view::draw(f, self))?;
+            self.draw_images(terminal)?;
+
+            // The app holds a sender itself, so the channel never closes
+            let Some(action) = self.actions_rx.recv().await else {
+                break;
+            };
+            self.update(action)?;
+        }
+        Ok(())
+    }
+
+    /// Draws the images the view left room for over the frame just drawn,
+    /// unless they are where they were last time.
+    fn draw_images(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        let Some(graphics) = self.graphics else {
+            return Ok(());
+        };
+        let slots = self.image_slots.borrow().clone();
+        if slots == self.drawn_images && self.forgotten_images.is_empty() {
+            return Ok(());
+        }
+
+        let mut commands = String::new();
+        match graphics {
+            Graphics::Kitty => {
+                commands.push_str(images::KITTY_CLEAR);
+                for id in std::mem::take(&mut self.forgotten_images) {
+                    commands.push_str(&images::kitty_delete(id));
+                    self.sent_images.remove(&id);
+                }
+                for slot in &slots {
+                    let Some(image) = self.ready_image(slot.id) else {
+                        continue;
+                    };
+                    if !self.sent_images.contains(&slot.id) {
+                        commands.push_str(&image.kitty_transmit());
+                    }
+                    commands.push_str(&format!("\x1b[{};{}H", slot.y + 1, slot.x + 1));
+                    commands.push_str(&image.kitty_place(slot.cols, slot.rows));
+                }
+                self.sent_images.extend(slots.iter().map(|slot| slot.id));
+            }
+            Graphics::Sixel => {
+                self.forgotten_images.clear();
+                // Sixels stay on screen until written over, which the frame
+                // only does where it changed
+                if !self.drawn_images.is_empty() {
+                    terminal.clear()?;
+                    terminal.draw(|f| view::draw(f, self))?;
+                }
+                let (cell_width, cell_height) = images::cell_size();
+                for slot in &slots {
+                    let Some(image) = self.ready_image(slot.id) else {
+                        continue;
+                    };
+                    let width = slot.cols as u32 * cell_width as u32;
+                    let height = slot.rows as u32 * cell_height as u32;
+                    match image.sixel(width, height) {
+                        Ok(sixel) => {
+                            commands.push_str(&format!("\x1b[{};{}H", slot.y + 1, slot.x + 1));
+                            commands.push_str(&sixel);
+                        }
+                        Err(e) => debug!("Image {} can't be drawn: {:#}", slot.id, e),
+                    }
+                }
+            }
+        }
+        let out = terminal.backend_mut();
+        out.write_all(commands.as_bytes())?;
+        out.flush()?;
+        self.drawn_images = slots;
+        Ok(())
+    }
+
+    fn ready_image(&self, id: u32) -> Option<&Image> {
+        self.images.values().find_map(|state| match state {
+            ImageState::Ready(image) if image.id == id => Some(image),
+            _ => None,
+        })
+    }
+
+    /// What became of the image `attachment` of `email`, if it is being shown.
+    pub fn image(&self, email: &Email, attachment: &Attachment) -> Option<&ImageState> {
+        self.images.get(&(
+            email.account.clone(),
+            email.id.clone(),
+            attachment.id.clone(),
+        ))
+    }
+
+    /// Applies one action to the state.
+    fn update(&mut self, action: Action) -> Result<()> {
+        // Keys and syncs can change the selection or the list around it
+        let moved = matches!(
+            action,
+            Action::Key(_) | Action::Mouse(_) | Action::Synced(_) | Action::Response { .. }
+        );
+        match action {
+            Action::Key(key) => self.handle_key(key),
+            Action::Mouse(mouse) => self.handle_mouse(mouse),
+            Action::Tick => self.status.expire(),
+            Action::Synced(update) => self.apply_sync(update),
+            Action::SyncProgress { account, progress } => {
+                // A late report from a cancelled sync would linger forever
+                if self.syncing.iter().any(|(name, _)| *name == account) {
+                    // The first report comes once the account has signed in
+                    if self.startup == Some(Startup::SignIn) {
+                        self.startup = Some(Startup::Sync);
+                    }
+                    match self
+                        .sync_progress
+                        .iter_mut()
+                        .find(|(name, _)| *name == account)
+                    {
+                        Some((_, latest)) => *latest = progress,
+                        None => self.sync_progress.push((account, progress)),
+                    }
+                }
+            }
+            Action::BodyLoaded(update) => self.apply_body(update),
+            Action::Prefetched(update) => self.apply_prefetch(update),
+            Action::SearchSettled => self.live_search(),
+            Action::FindReady => self.find_next(true),
+            Action::HeadersLoaded(update) => self.apply_headers(update),
+            Action::Sent { id, result } => self.apply_send(id, result),
+            Action::MarkedRead {
+                account,
+                id,
+                result,
+            } => self.apply_marked_read(&account, &id, result),
+            Action::DirectoryMatches { prefix, result } => {
+                self.apply_directory_matches(&prefix, result)
+            }
+            Action::ImageLoaded {
+                account,
+                email_id,
+                attachment_id,
+                result,
+            } => self.apply_image((account, email_id, attachment_id), result),
+            Action::Response { id, response } => {
+                if let Some(respond) = self.responders.remove(&id) {
+                    respond(self, response);
+                }
+            }
+            Action::InputFailed(e) => return Err(e.into()),
+            Action::InputClosed | Action::Terminated => self.should_quit = true,
+        }
+        if moved {
+            self.prefetch_bodies();
+        }
+        Ok(())
+    }
+
+    /// Selects the email clicked in the list, focuses the pane clicked and
+    /// scrolls the pane under the wheel. Popups leave the mouse alone.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if !matches!(
+            self.input_mode,
+            InputMode::Normal | InputMode::EmailView | InputMode::Sidebar
+        ) {
+            return;
+        }
+        let Some(target) = self.pane_areas.borrow().target(mouse.column, mouse.row) else {
+            return;
+        };
+        let down = match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.pending_prefix = None;
+                self.click(target);
+                return;
+            }
+            MouseEventKind::ScrollDown => true,
+            MouseEventKind::ScrollUp => false,
+            _ => return,
+        };
+        match target {
+            Target::Sidebar => {
+                if let Some(sidebar) = self.sidebar.as_mut() {
+                    if down {
+                        sidebar.select_next();
+                    } else {
+                        sidebar.select_previous();
+                    }
+                }
+            }
+            Target::Email(_) | Target::List => {
+                // The wheel stops at the ends instead of wrapping around
+                let last = self.filtered_emails.len().saturating_sub(1);
+                let position = if down {
+                    (self.selected_index + 1).min(last)
+                } else {
+                    self.selected_index.saturating_sub(1)
+                };
+                if !self.filtered_emails.is_empty() && position != self.selected_index {
+                    self.select_index(position);
+                }
+            }
+            Target::Content => self.scroll_body(if down {
+                mouse::SCROLL_LINES
+            } else {
+                -mouse::SCROLL_LINES
+            }),
+        }
+    }
+
+    fn click(&mut self, target: Target) {
+        match target {
+            Target::Sidebar => {
+                self.input_mode = InputMode::Sidebar;
+                self.focus = FocusPanel::Sidebar;
+            }
+            Target::Email(position) => {
+                self.select_index(position);
+                self.input_mode = InputMode::Normal;
+                self.focus = FocusPanel::EmailList;
+            }
+            Target::List => {
+                self.input_mode = InputMode::Normal;
+                self.focus = FocusPanel::EmailList;
+            }
+            Target::Content => {
+                if !matches!(self.focus, FocusPanel::EmailContent)
+                    && self.selected_email().is_some()
+                {
+                    self.input_mode = InputMode::EmailView;
+                    self.focus = FocusPanel::EmailContent;
+                    self.open_selected();
+                }
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        trace!(?key, "Key");
+        if let (InputMode::Normal | InputMode::EmailView, KeyCode::Char(c)) =
+            (&self.input_mode, key.code)
+        {
+            self.state.record_key_use(c);
+        }
+
+        let prefix = self.pending_prefix.take();
+        match self.input_mode {
+            InputMode::Normal => self.handle_mail_key(Context::List, prefix, key),
+            InputMode::EmailView => self.handle_mail_key(Context::Reading, prefix, key),
+            InputMode::Help => {
+                let Some(help) = self.help.as_mut() else {
+                    self.close_popup();
+                    return;
+                };
+                match key.code {
+                    KeyCode::Char('j') | KeyCode::Down => help.scroll_by(1),
+                    KeyCode::Char('k') | KeyCode::Up => help.scroll_by(-1),
+                    KeyCode::PageDown | KeyCode::Char(' ') => help.scroll_page(true),
+                    KeyCode::PageUp => help.scroll_page(false),
+                    KeyCode::Char('g') | KeyCode::Home => help.scroll = 0,
+                    KeyCode::Char('G') | KeyCode::End => help.scroll_to_end(),
+                    // Any other key returns from help mode
+                    _ => {
+                        self.help = None;
+                        self.close_popup();
+                    }
+                }
+            }
+            InputMode::WhatsNew => {
+                // Any key dismisses the screen until the next upgrade
+                self.whats_new.clear();
+                self.state.last_seen_version = Some(discovery::CURRENT_VERSION.to_string());
+                self.close_popup();
+                if let Err(e) = self.state.save() {
+                    self.set_error_message(format!("Failed to save local state: {}", e));
+                }
+            }
+            InputMode::Search => match key.code {
+                KeyCode::Esc => {
+                    self.cancel_live_search();
+                    // First change the mode to release the borrow
+                    self.input_mode = InputMode::Normal;
+                    // Clear search input
+                    self.search_input.clear();
+                    // Then show all emails
+                    self.search(String::new());
+                }
+                KeyCode::Enter => {
+                    self.cancel_live_search();
+                    // Clone the search input before using it
+                    let query = self.search_input.clone();
+                    self.state.record_search(&query);
+                    self.search_history_index = None;
+                    // Set input mode first to release the borrow
+                    self.input_mode = InputMode::Normal;
+                    // Then perform the search, on the server for `!query`
+                    match query.strip_prefix(SERVER_SEARCH_PREFIX) {
+                        Some(server_query) => self.search_server(server_query.trim()),
+                        None => self.search(query),
+                    }
+                }
+                KeyCode::Up => self.browse_search_history(true),
+                KeyCode::Down => self.browse_search_history(false),
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.history_picker = Some(HistoryPicker::new(&self.state.search_history));
+                    self.input_mode = InputMode::SearchHistory;
+                }
+                KeyCode::Char(c) => {
+                    self.search_history_index = None;
+                    self.search_input.push(c);
+                    self.debounce_search();
+                }
+                KeyCode::Backspace => {
+                    self.search_history_index = None;
+                    self.search_input.pop();
+                    self.debounce_search();
+                }
+                _ => {}
+            },
+            InputMode::SearchHistory => {
+                let Some(picker) = self.history_picker.as_mut() else {
+                    self.input_mode = InputMode::Search;
+                    return;
+                };
+                match key.code {
+                    KeyCode::Esc => {
+                        self.history_picker = None;
+                        self.input_mode = InputMode::Search;
+                    }
+                    KeyCode::Enter => {
+                        if let Some(entry) = picker.selected_entry() {
+                            self.search_input = entry.to_string();
+                            self.search_history_index = None;
+                            self.debounce_search();
+                        }
+                        self.history_picker = None;
+                        self.input_mode = InputMode::Search;
+                    }
+                    KeyCode::Down => picker.select_next(),
+                    KeyCode::Up => picker.select_previous(),
+                    KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        picker.select_next()
+                    }
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        picker.select_previous()
+                    }
+                    KeyCode::Char(c) => picker.push(c),
+                    KeyCode::Backspace => picker.pop(),
+                    _ => {}
+                }
+            }
+            InputMode::Find => match key.code {
+                KeyCode::Esc => {
+                    self.find = None;
+                    self.input_mode = InputMode::EmailView;
+                }
+                KeyCode::Enter => {
+                    self.input_mode = InputMode::EmailView;
+                    if let Some(find) = self.find.as_mut() {
+                        find.confirm();
+                        if find.is_confirmed() {
+                            // The rows of the matches are known once the email is drawn
+                            let _ = self.actions_tx.send(Action::FindReady);
+                        } else {
+                            self.find = None;
+                        }
+                    }
+                }
+                KeyCode::Char(c) => {
+                    if let Some(find) = self.find.as_mut() {
+                        find.input.push(c);
+                    }
+                }
+                KeyCode::Backspace => {
+                    if let Some(find) = self.find.as_mut() {
+                        find.input.pop();
+                    }
+                }
+                _ => {}
+            },
+            InputMode::AddressMenu => {
+                let count = self.selected_email().map_or(0, |e| e.addresses().len());
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.close_popup(),
+                    KeyCode::Char('j') | KeyCode::Down if count > 0 => {
+                        self.address_menu_index = (self.address_menu_index + 1) % count;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up if count > 0 => {
+                        self.address_menu_index = (self.address_menu_index + count - 1) % count;
+                    }
+                    KeyCode::Char(c) => {
+                        if let Some(action) = AddressAction::from_key(c) {
+                            self.run_address_action(action);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            InputMode::Links => {
+                let count = self.links.len();
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.close_popup(),
+                    KeyCode::Char('j') | KeyCode::Down if count > 0 => {
+                        self.links_index = (self.links_index + 1) % count;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up if count > 0 => {
+                        self.links_index = (self.links_index + count - 1) % count;
+                    }
+                    KeyCode::Enter | KeyCode::Char('o') => self.open_link(self.links_index),
+                    KeyCode::Char('y') => self.copy_link(self.links_index),
+                    KeyCode::Char(c @ '1'..='9') => self.open_link(c as usize - '1' as usize),
+                    _ => {}
+                }
+            }
+            InputMode::Searches => {
+                let count = self.searches.len();
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.close_popup(),
+                    KeyCode::Char('j') | KeyCode::Down if count > 0 => {
+                        self.searches_index = (self.searches_index + 1) % count;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up if count > 0 => {
+                        self.searches_index = (self.searches_index + count - 1) % count;
+                    }
+                    KeyCode::Enter => self.run_saved_search(self.searches_index),
+                    KeyCode::Char(c @ '1'..='9') => {
+                        self.run_saved_search(c as usize - '1' as usize)
+                    }
+                    _ => {}
+                }
+            }
+            InputMode::Hints => match key.code {
+                KeyCode::Esc => {
+                    self.hints = None;
+                    self.close_popup();
+                }
+                KeyCode::Backspace => {
+                    if let Some(hints) = self.hints.as_mut() {
+                        hints.typed.pop();
+                    }
+                }
+                KeyCode::Char(c) => self.type_hint(c),
+                _ => {}
+            },
+            InputMode::Confirm => match key.code {
+                KeyCode::Esc => self.cancel_confirmation(),
+                KeyCode::Char(c) => self.confirm(c),
+                _ => {}
+            },
+            InputMode::SyncHealth => {
+                let count = self.sync_failures.len();
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.close_popup(),
+                    KeyCode::Char('j') | KeyCode::Down if count > 0 => {
+                        self.sync_health_index = (self.sync_health_index + 1) % count;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up if count > 0 => {
+                        self.sync_health_index = (self.sync_health_index + count - 1) % count;
+                    }
+                    KeyCode::Enter | KeyCode::Char('r') => {
+                        if let Some(failure) = self.sync_failures.get(self.sync_health_index) {
+                            let name = failure.account.clone();
+                            self.retry_account(&name);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            InputMode::SyncError => self.handle_sync_error_key(key),
+            InputMode::Sidebar => self.handle_sidebar_key(key),
+            InputMode::Folders => self.handle_folder_manager_key(key),
+            InputMode::OutOfOffice => self.handle_out_of_office_key(key),
+            InputMode::Directory => self.handle_directory_key(key),
+            InputMode::Move => self.handle_move_key(key),
+            InputMode::DateRange => self.handle_date_range_key(key),
+            InputMode::Compose => self.handle_compose_key(key),
+            InputMode::Drafts => self.handle_drafts_key(key),
+            InputMode::Recoverable => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => {
+                    self.recoverable = None;
+                    self.close_popup();
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    if let Some(recoverable) = self.recoverable.as_mut() {
+                        recoverable.select_next();
+                    }
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    if let Some(recoverable) = self.recoverable.as_mut() {
+                        recoverable.select_previous();
+                    }
+                }
+                KeyCode::Enter | KeyCode::Char('r') => self.restore_selected_item(),
+                _ => {}
+            },
+            InputMode::Outbox => self.handle_outbox_key(key),
+            InputMode::Headers => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('H') => self.close_popup(),
+                KeyCode::Char('j') | KeyCode::Down => {
+                    self.headers_scroll = self.headers_scroll.saturating_add(1)
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.headers_scroll = self.headers_scroll.saturating_sub(1)
+                }
+                _ => {}
+            },
+            InputMode::OutboxEdit => self.handle_outbox_edit_key(key),
+            InputMode::Command => match key.code {
+                KeyCode::Esc => self.input_mode = InputMode::Normal,
+                KeyCode::Enter => {
+                    self.input_mode = InputMode::Normal;
+                    let command = std::mem::take(&mut self.command_input);
+                    self.run_command(command.trim());
+                }
+                KeyCode::Tab => self.complete_command(),
+                KeyCode::Char(c) => self.command_input.push(c),
+                KeyCode::Backspace => {
+                    self.command_input.pop();
+                }
+                _ => {}
+            },
+            InputMode::Metrics => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => self.close_popup(),
+                _ => {}
+            },
+            InputMode::Tasks => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => {
+                    self.tasks = None;
+                    self.close_popup();
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    if let Some(tasks) = self.tasks.as_mut() {
+                        tasks.select_next();
+                    }
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    if let Some(tasks) = self.tasks.as_mut() {
+                        tasks.select_previous();
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+
+    /// The account popups operate on: the selected email's account, or the first one.
+    fn current_account(&self) -> Option<String> {
+        match self.selected_email() {
+            Some(email) => Some(email.account.clone()),
+            None => self.accounts.first().map(|account| account.name.clone()),
+        }
+    }
+
+    /// Whether no popup is open, so one whose contents just arrived can open.
+    fn no_popup_open(&self) -> bool {
+        matches!(
+            self.input_mode,
+            InputMode::Normal | InputMode::EmailView | InputMode::Sidebar
+        )
+    }
+
+    fn open_tasks(&mut self) {
+        let Some(account) = self.current_account() else {
+            return;
+        };
+        let owner = account.clone();
+        self.request(
+            &account,
+            Operation::ListSync,
+            |client| async move { client.fetch_tasks().await },
+            |app, result| match result {
+                Ok(tasks) if app.no_popup_open() => {
+                    app.tasks = Some(TaskList::new(owner, tasks));
+                    app.input_mode = InputMode::Tasks;
+                }
+                Ok(_) => {}
+                Err(e) => app.set_error_message(format!("Failed to load tasks: {}", e)),
+            },
+        );
+    }
+
+    fn open_out_of_office(&mut self) {
+        let Some(account) = self.current_account() else {
+            return;
+        };
+        let owner = account.clone();
+        self.request(
+            &account,
+            Operation::ListSync,
+            |client| async move { client.get_oof_settings().await },
+            |app, result| match result {
+                Ok(settings) if app.no_popup_open() => {
+                    app.oof_form = Some(OofForm::new(owner, &settings));
+                    app.input_mode = InputMode::OutOfOffice;
+                }
+                Ok(_) => {}
+                Err(e) => app.set_error_message(format!("Failed to load automatic replies: {}", e)),
+            },
+        );
+    }
+
+    fn handle_out_of_office_key(&mut self, key: KeyEvent) {
+        let Some(form) = self.oof_form.as_mut() else {
+            self.close_popup();
+            return;
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.oof_form = None;
+                self.close_popup();
+            }
+            KeyCode::Enter => self.save_out_of_office(),
+            KeyCode::Tab | KeyCode::Down => form.next_field(),
+            KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+            KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right
+                if form.field == OofField::State =>
+            {
+                form.cycle_state()
+            }
+            KeyCode::Char(c) => {
+                if let Some(text) = form.focused_text_mut() {
+                    text.push(c);
+                }
+            }
+            KeyCode::Backspace => {
+                if let Some(text) = form.focused_text_mut() {
+                    text.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Saves the automatic replies, closing the form once the server took them.
+    fn save_out_of_office(&mut self) {
+        let Some(form) = &self.oof_form else {
+            return;
+        };
+        let settings = match form.to_settings() {
+            Ok(settings) => settings,
+            Err(e) => {
+                self.set_error_message(e.to_string());
+                return;
+            }
+        };
+        let account = form.account.clone();
+        let state = settings.state;
+        self.request(
+            &account,
+            Operation::Update,
+            |client| async move { client.set_oof_settings(&settings).await },
+            move |app, result| match result {
+                Ok(()) => {
+                    app.set_status_message(format!(
+                        "Automatic replies: {}",
+                        out_of_office::state_label(state)
+                    ));
+                    if app.oof_form.is_some() {
+                        app.oof_form = None;
+                        app.close_popup();
+                    }
+                }
+                Err(e) => app.set_error_message(format!("Failed to save automatic replies: {}", e)),
+            },
+        );
+    }
+
+    fn open_directory(&mut self) {
+        if let Some(account) = self.current_account() {
+            self.directory = Some(DirectoryLookup::new(account));
+            self.input_mode = InputMode::Directory;
+        }
+    }
+
+    fn handle_directory_key(&mut self, key: KeyEvent) {
+        let Some(directory) = self.directory.as_mut() else {
+            self.close_popup();
+            return;
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.directory = None;
+                self.close_popup();
+            }
+            KeyCode::Enter => self.search_directory(),
+            KeyCode::Down => directory.select_next(),
+            KeyCode::Up => directory.select_previous(),
+            KeyCode::Tab => {
+                if let Some(entry) = directory.selected_entry() {
+                    let address = entry.email.clone();
+                    match clipboard::copy(&address) {
+                        Ok(()) => {
+                            self.set_status_message(format!("Copied {} to clipboard", address))
+                        }
+                        Err(e) => self.set_error_message(format!("Failed to copy address: {}", e)),
+                    }
+                }
+            }
+            KeyCode::Char(c) => directory.query.push(c),
+            KeyCode::Backspace => {
+                directory.query.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn search_directory(&mut self) {
+        let Some(directory) = &self.directory else {
+            return;
+        };
+        let account = directory.account.clone();
+        let query = directory.query.clone();
+        self.request(
+            &account,
+            Operation::ServerSearch,
+            |client| async move { client.search_directory(&query).await },
+            |app, result| match result {
+                Ok(results) => {
+                    if let Some(directory) = app.directory.as_mut() {
+                        directory.set_results(results);
+                    }
+                }
+                Err(e) => app.set_error_message(format!("Directory lookup failed: {}", e)),
+            },
+        );
+    }
+
+    /// Lists the folders of the selected email's account to move it to.
+    fn open_move_picker(&mut self) {
+        let Some(account) = self.selected_email().map(|email| email.account.clone()) else {
+            return;
+        };
+        let owner = account.clone();
+        self.request(
+            &account,
+            Operation::ListSync,
+            |client| async move { client.fetch_folders().await },
+            |app, result| match result {
+                Ok(folders) if app.no_popup_open() => {
+                    app.move_picker = Some(MovePicker::new(owner, folders));
+                    app.input_mode = InputMode::Move;
+                }
+                Ok(_) => {}
+                Err(e) => app.set_error_message(format!("Failed to load folders: {}", e)),
+            },
+        );
+    }
+
+    fn handle_move_key(&mut self, key: KeyEvent) {
+        let Some(picker) = self.move_picker.as_mut() else {
+            self.close_popup();
+            return;
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.move_picker = None;
+                self.close_popup();
+            }
+            KeyCode::Enter => {
+                if let Some(folder) = picker.selected_folder().cloned() {
+                    self.move_picker = None;
+                    self.close_popup();
+                    if self.selection.is_empty() {
+                        self.move_selected(&folder);
+                    } else {
+                        self.apply_to_selection(SelectionAction::Move(folder.name));
+                    }
+                }
+            }
+            KeyCode::Down => picker.select_next(),
+            KeyCode::Up => picker.select_previous(),
+            KeyCode::Char(c) => picker.push(c),
+            KeyCode::Backspace => picker.pop(),
+            _ => {}
+        }
+    }
+
+    fn handle_date_range_key(&mut self, key: KeyEvent) {
+        let Some(picker) = self.date_picker.as_mut() else {
+            self.close_popup();
+            return;
+        };
+
+        if picker.field.is_some() {
+            match key.code {
+                KeyCode::Esc => picker.field = None,
+                KeyCode::Enter => match picker.custom_range() {
+                    Ok(range) => self.set_date_range(range),
+                    Err(e) => self.set_error_message(format!("{:#}", e)),
+                },
+                KeyCode::Tab | KeyCode::BackTab => picker.switch_field(),
+                KeyCode::Char(c) => picker.push(c),
+                KeyCode::Backspace => picker.pop(),
+                _ => {}
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.date_picker = None;
+                self.close_popup();
+            }
+            KeyCode::Enter => {
+                if let Some(range) = picker.choose(Local::now().date_naive()) {
+                    self.set_date_range(range);
+                }
+            }
+            KeyCode::Char('j') | KeyCode::Down => picker.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => picker.select_previous(),
+            _ => {}
+        }
+    }
+
+    /// Fetches the mail of `range` from every account instead of what the
+    /// list shows, dropping syncs of the previous range still in flight.
+    fn set_date_range(&mut self, range: DateRange) {
+        self.date_picker = None;
+        self.close_popup();
+        self.date_range = range;
+        for account in self.accounts.iter() {
+            account.client.set_date_range(range);
+        }
+        self.cancel_syncs();
+        self.refresh();
+    }
+
+    /// Moves the selected email to `folder` on the server, taking it out of the
+    /// list and putting it back if the server refuses.
+    fn move_selected(&mut self, folder: &Folder) {
+        if self.selected_email().is_some() {
+            self.move_email(self.selected_index, folder);
+        }
+    }
+
+    /// Moves the email at `position` in the filtered list to `folder`.
+    fn move_email(&mut self, position: usize, folder: &Folder) {
+        let (real_idx, email) = self.take_email(position);
+
+        let (account, id, folder_id) = (email.account.clone(), email.id.clone(), folder.id.clone());
+        let folder_name = folder.name.clone();
+        self.request(
+            &account,
+            Operation::Update,
+            |client| async move { client.move_emails(&[id], &folder_id).await },
+            move |app, result| match result {
+                Ok(()) => {
+                    app.set_status_message(format!("Moved '{}' to {}", email.subject, folder_name))
+                }
+                Err(e) => {
+                    app.set_error_message(format!(
+                        "Failed to move '{}' to {}: {}",
+                        email.subject, folder_name, e
+                    ));
+                    app.restore_email(position, real_idx, email);
+                }
+            },
+        );
+    }
+
+    fn open_recoverable_items(&mut self) {
+        let Some(account) = self.current_account() else {
+            return;
+        };
+        let owner = account.clone();
+        self.request(
+            &account,
+            Operation::ListSync,
+            |client| async move { client.fetch_recoverable_items().await },
+            |app, result| match result {
+                Ok(items) if app.no_popup_open() => {
+                    app.recoverable = Some(RecoverableItems::new(owner, items));
+                    app.input_mode = InputMode::Recoverable;
+                }
+                Ok(_) => {}
+                Err(e) => app.set_error_message(format!("Failed to load recoverable items: {}", e)),
+            },
+        );
+    }
+
+    /// Restores the selected hard-deleted message and resyncs its account so it shows up.
+    fn restore_selected_item(&mut self) {
+        let Some(recoverable) = &self.recoverable else {
+            return;
+        };
+        let Some(item) = recoverable.selected_item() else {
+            return;
+        };
+        let account = recoverable.account.clone();
+        let id = item.email.id.clone();
+        let subject = item.email.subject.clone();
+
+        let (owner, item_id) = (account.clone(), id.clone());
+        self.request(
+            &account,
+            Operation::Update,
+            |client| async move { client.restore_item(&item_id).await },
+            move |app, result| match result {
+                Ok(()) => {
+                    if let Some(recoverable) = app.recoverable.as_mut() {
+                        recoverable.remove(&id);
+                    }
+                    app.sync_in_background(&owner);
+                    app.set_status_message(format!("Restored '{}' to the Inbox", subject));
+                }
+                Err(e) => app.set_error_message(format!("Failed to restore '{}': {}", subject, e)),
+            },
+        );
+    }
+
+    /// Opens the folder manager for the selected email's account, or the first account.
+    /// Shows the folder sidebar, focused, or hides it.
+    fn toggle_sidebar(&mut self) {
+        if self.sidebar.take().is_some() {
+            if matches!(self.focus, FocusPanel::Sidebar) {
+                self.input_mode = InputMode::Normal;
+                self.focus = FocusPanel::EmailList;
+            }
+            return;
+        }
+        self.load_sidebar(true);
+    }
+
+    /// Fetches the folders of every account with their unread counts into the
+    /// sidebar, selecting the one shown. The sidebar opens with focus when
+    /// `open` is set, and is only refreshed if it is still open otherwise.
+    fn load_sidebar(&mut self, open: bool) {
+        let limit = self.timeouts.get(Operation::ListSync);
+        let accounts = Rc::clone(&self.accounts);
+        let request = async move {
+            let mut folders = Vec::new();
+            let mut errors = Vec::new();
+            for account in accounts.iter() {
+                match loading::with_timeout(limit, account.client.fetch_folders()).await {
+                    Ok(found) => folders.extend(found),
+                    Err(e) => errors.push(format!("{}: {}", account.name, e)),
+                }
+            }
+            (folders, errors)
+        };
+        self.spawn_request(request, move |app, (folders, errors)| {
+            if let Some(error) = errors.first() {
+                app.set_error_message(format!("Failed to load folders of {}", error));
+            }
+            if folders.is_empty() || (open && !app.no_popup_open()) {
+                return;
+            }
+            if !open && app.sidebar.is_none() {
+                return;
+            }
+            app.show_sidebar(folders);
+            if open {
+                app.input_mode = InputMode::Sidebar;
+                app.focus = FocusPanel::Sidebar;
+            }
+        });
+    }
+
+    /// Puts `folders` in the sidebar, selecting the one shown.
+    fn show_sidebar(&mut self, folders: Vec<Folder>) {
+        let mut sidebar = Sidebar::new(folders);
+        match self.active_view.as_ref().map(|view| &view.source) {
+            Some(ViewSource::Folder(name)) => sidebar.select(Some(name)),
+            _ => sidebar.select(None),
+        }
+        self.sidebar = Some(sidebar);
+    }
+
+    fn handle_sidebar_key(&mut self, key: KeyEvent) {
+        if self.sidebar.is_none() {
+            self.input_mode = InputMode::Normal;
+            self.focus = FocusPanel::EmailList;
+            return;
+        }
+        if let Some(action) = keymap::lookup(Context::Sidebar, None, &key) {
+            self.run_key_action(action, key);
+        }
+    }
+
+    fn open_folder_manager(&mut self) {
+        let Some(account) = self.current_account() else {
+            return;
+        };
+
+        let owner = account.clone();
+        self.request(
+            &account,
+            Operation::ListSync,
+            |client| async move { client.fetch_folders().await },
+            |app, result| match result {
+                Ok(folders) if app.no_popup_open() => {
+                    app.folder_manager = Some(FolderManager::new(owner, folders));
+                    app.input_mode = InputMode::Folders;
+                }
+                Ok(_) => {}
+                Err(e) => app.set_error_message(format!("Failed to load folders: {}", e)),
+            },
+        );
+    }
+
+    fn handle_folder_manager_key(&mut self, key: KeyEvent) {
+        let Some(manager) = self.folder_manager.as_mut() else {
+            self.close_popup();
+            return;
+        };
+
+        if manager.edit.is_some() {
+            match key.code {
+                KeyCode::Esc => manager.cancel_edit(),
+                KeyCode::Enter => self.submit_folder_edit(),
+                KeyCode::Char(c) => manager.input.push(c),
+                KeyCode::Backspace => {
+                    manager.input.pop();
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.folder_manager = None;
+                self.close_popup();
+            }
+            KeyCode::Char('j') | KeyCode::Down => manager.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => manager.select_previous(),
+            KeyCode::Char('n') => manager.start_create(),
+            KeyCode::Char('r') => manager.start_rename(),
+            KeyCode::Char('d') => {
+                if let Some(folder) = manager.selected_folder() {
+                    let prompt = format!(
+                        "Delete folder '{}' and move its mail to Deleted Items?",
+                        folder.name
+                    );
+                    self.ask_confirmation(prompt, ConfirmAction::DeleteFolder);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn submit_folder_edit(&mut self) {
+        let Some(manager) = self.folder_manager.as_mut() else {
+            return;
+        };
+        let Some(edit) = manager.edit.take() else {
+            return;
+        };
+        let name = manager.input.trim().to_string();
+        manager.input.clear();
+        if name.is_empty() {
+            return;
+        }
+        let account = manager.account.clone();
+
+        let folder_name = name.clone();
+        self.request(
+            &account,
+            Operation::Update,
+            |client| async move {
+                match &edit {
+                    FolderEdit::Create => client.create_folder(&folder_name).await.map(|_| ()),
+                    FolderEdit::Rename(id) => client.rename_folder(id, &folder_name).await,
+                }
+                .map(|()| edit)
+            },
+            move |app, result| {
+                match result {
+                    Ok(FolderEdit::Create) => {
+                        app.set_status_message(format!("Created folder '{}'", name))
+                    }
+                    Ok(FolderEdit::Rename(_)) => {
+                        app.set_status_message(format!("Renamed folder to '{}'", name))
+                    }
+                    Err(e) => app.set_error_message(format!("Folder change failed: {}", e)),
+                }
+                app.reload_folder_manager();
+            },
+        );
+    }
+
+    fn delete_selected_folder(&mut self) {
+        let Some(manager) = &self.folder_manager else {
+            return;
+        };
+        let Some(folder) = manager.selected_folder().cloned() else {
+            return;
+        };
+        let account = manager.account.clone();
+        let id = folder.id.clone();
+        self.request(
+            &account,
+            Operation::Update,
+            |client| async move { client.delete_folder(&id).await },
+            move |app, result| {
+                match result {
+                    Ok(()) => app.set_status_message(format!("Deleted folder '{}'", folder.name)),
+                    Err(e) => app.set_error_message(format!("Failed to delete folder: {}", e)),
+                }
+                app.reload_folder_manager();
+            },
+        );
+    }
+
+    /// Refetches the folder manager's list after a change.
+    fn reload_folder_manager(&mut self) {
+        let Some(manager) = &self.folder_manager else {
+            return;
+        };
+        let account = manager.account.clone();
+        self.request(
+            &account,
+            Operation::ListSync,
+            |client| async move { client.fetch_folders().await },
+            |app, result| match result {
+                Ok(folders) => {
+                    if let Some(manager) = app.folder_manager.as_mut() {
+                        manager.set_folders(folders);
+                    }
+                }
+                Err(e) => app.set_error_message(format!("Failed to load folders: {}", e)),
+            },
+        );
+    }
+
+    fn confirm_report_junk(&mut self) {
+        if let Some(email) = self.selected_email() {
+            let prompt = format!("Move '{}' to Junk Email?", email.subject);
+            self.ask_confirmation(prompt, ConfirmAction::ReportJunk);
+        }
+    }
+
+    /// Deletes the selected email, or the emails selected for bulk actions,
+    /// asking first unless `[list]` turns that off.
+    fn confirm_delete(&mut self) {
+        if !self.selection.is_empty() {
+            let count = self.selected_for_action().len();
+            if !self.confirm_delete {
+                self.apply_to_selection(SelectionAction::Delete);
+            } else if count > 0 {
+                let prompt = format!("Delete {} selected emails?", count);
+                self.ask_confirmation(prompt, ConfirmAction::DeleteSelection);
+            }
+            return;
+        }
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        if !self.confirm_delete {
+            self.apply_to_selected(MessageAction::Delete);
+            return;
+        }
+        let subject = width::truncate(&email.subject, DELETE_PROMPT_WIDTH);
+        let prompt = format!("Delete '{}'?", subject);
+        self.ask_confirmation(prompt, ConfirmAction::DeleteEmail);
+    }
+
+    fn confirm_meeting_response(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        if email.meeting.is_some() {
+            let prompt = format!("Respond to '{}'?", email.subject);
+            self.ask_confirmation(prompt, ConfirmAction::RespondToMeeting);
+        } else {
+            self.set_status_message("Not a meeting request".to_string());
+        }
+    }
+
+    /// Indices in `emails` of the selected email's conversation within its
+    /// account, with the subject it is muted by.
+    fn selected_thread(&self) -> Option<(String, String, Vec<usize>)> {
+        let email = self.selected_email()?;
+        let account: Vec<usize> = (0..self.emails.len())
+            .filter(|&idx| self.emails[idx].account == email.account)
+            .collect();
+        let emails: Vec<&Email> = account.iter().map(|&idx| &self.emails[idx]).collect();
+        let keys = threading::thread_keys(&emails);
+        let selected = account
+            .iter()
+            .position(|&idx| std::ptr::eq(&self.emails[idx], email))?;
+        let indices = account
+            .iter()
+            .zip(&keys)
+            .filter(|(_, key)| **key == keys[selected])
+            .map(|(&idx, _)| idx)
+            .collect();
+        Some((email.account.clone(), email.thread_subject(), indices))
+    }
+
+    fn open_thread_actions(&mut self) {
+        let Some((_, _, indices)) = self.selected_thread() else {
+            return;
+        };
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let prompt = format!(
+            "Apply to thread '{}' ({} messages)?",
+            email.subject,
+            indices.len()
+        );
+        self.ask_confirmation(prompt, ConfirmAction::Thread);
+    }
+
+    fn ask_trust_certificate(&mut self, account: &str, certificate: &UntrustedCertificate) {
+        let prompt = format!(
+            "{} (account {}) presents a certificate not signed by a trusted authority.\n\
+             SHA-256 {}\n\
+             Only trust it if the fingerprint matches the one of the server.",
+            certificate.host, account, certificate.fingerprint
+        );
+        self.ask_confirmation(
+            prompt,
+            ConfirmAction::TrustCertificate {
+                account: account.to_string(),
+                host: certificate.host.clone(),
+                fingerprint: certificate.fingerprint.clone(),
+            },
+        );
+    }
+
+    /// Pins a certificate for its host in the local state and syncs the account with it.
+    fn trust_certificate(&mut self, account: &str, host: &str, fingerprint: &str) {
+        self.state.pin_certificate(host, fingerprint);
+        if let Err(e) = self.state.save() {
+            self.set_error_message(format!("Failed to save local state: {}", e));
+            return;
+        }
+        if let Ok(client) = self.client_for(account) {
+            client.trust_certificate(fingerprint);
+        }
+        self.retry_account(account);
+    }
+
+    fn ask_confirmation(&mut self, prompt: String, action: ConfirmAction) {
+        self.confirmation = Some(Confirmation { prompt, action });
+        self.input_mode = InputMode::Confirm;
+    }
+
+    fn cancel_confirmation(&mut self) {
+        self.confirmation = None;
+        self.close_popup();
+    }
+
+    /// Handles a key pressed in the confirmation dialog, ignoring keys it doesn't offer.
+    fn confirm(&mut self, key: char) {
+        let Some(confirmation) = &self.confirmation else {
+            return;
+        };
+        if !confirmation.action.options().iter().any(|(k, _)| *k == key) {
+            return;
+        }
+        let Some(confirmation) = self.confirmation.take() else {
+            return;
+        };
+        self.close_popup();
+
+        match (confirmation.action, key) {
+            (ConfirmAction::ReportJunk, 'j') => {
+                self.apply_to_selected(MessageAction::ReportJunk(JunkReport::Junk))
+            }
+            (ConfirmAction::ReportJunk, 'p') => {
+                self.apply_to_selected(MessageAction::ReportJunk(JunkReport::Phishing))
+            }
+            (ConfirmAction::DeleteEmail, 'y') => self.apply_to_selected(MessageAction::Delete),
+            (ConfirmAction::DeleteSelection, 'y') => {
+                self.apply_to_selection(SelectionAction::Delete)
+            }
+            (ConfirmAction::MarkResultsRead, 'y') => {
+                let unread: Vec<usize> = self
+                    .filtered_emails
+                    .iter()
+                    .copied()
+                    .filter(|&idx| !self.emails[idx].read)
+                    .collect();
+                self.apply_to_emails(&unread, SelectionAction::MarkRead)
+            }
+            (ConfirmAction::DeleteFolder, 'y') => self.delete_selected_folder(),
+            (ConfirmAction::DiscardOutboxMessage(id), 'y') => {
+                self.outbox.remove(id);
+                if let Some(view) = self.outbox_view.as_mut() {
+                    view.clamp(&self.outbox);
+                }
+                self.save_outbox();
+            }
+            (ConfirmAction::DiscardMessage, 'y') => self.close_compose(),
+            (ConfirmAction::DeleteDraft, 'y') => self.delete_selected_draft(),
+            (ConfirmAction::SendReplyAll { account, message }, 'y') => {
+                self.send_composed(&account, message)
+            }
+            (ConfirmAction::Thread, 'a') => self.apply_to_thread(ThreadAction::Archive),
+            (ConfirmAction::Thread, 'r') => self.apply_to_thread(ThreadAction::MarkRead),
+            (ConfirmAction::Thread, 'm') => self.apply_to_thread(ThreadAction::Mute),
+            (ConfirmAction::Thread, 'd') => self.apply_to_thread(ThreadAction::Delete),
+            (
+                ConfirmAction::TrustCertificate {
+                    account,
+                    host,
+                    fingerprint,
+                },
+                't',
+            ) => self.trust_certificate(&account, &host, &fingerprint),
+            (ConfirmAction::RetryServerSearch(query), 'r') => self.search_server(&query),
+            (ConfirmAction::RetryBodyFetch(format), 'r') => self.load_selected_body(format),
+            (ConfirmAction::RespondToMeeting, 'a' | 't' | 'd') => {
+                let response = match key {
+                    'a' => MeetingResponse::Accept,
+                    't' => MeetingResponse::Tentative,
+                    _ => MeetingResponse::Decline,
+                };
+                self.apply_to_selected(MessageAction::RespondToMeeting(response))
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs `action` against the selected email, removing it from the list straight away
+    /// and putting it back if the server rejects the change.
+    fn apply_to_selected(&mut self, action: MessageAction) {
+        if self.selected_email().is_none() {
+            return;
+        }
+        let position = self.selected_index;
+        let (real_idx, email) = self.take_email(position);
+
+        let (account, id) = (email.account.clone(), email.id.clone());
+        self.request(
+            &account,
+            Operation::Update,
+            |client| async move {
+                let ids = std::slice::from_ref(&id);
+                match action {
+                    MessageAction::Archive => client.archive_emails(ids).await,
+                    MessageAction::Delete => client.delete_emails(ids).await,
+                    MessageAction::ReportJunk(report) => client.report_junk(&id, report).await,
+                    MessageAction::RespondToMeeting(response) => {
+                        client.respond_to_meeting(&id, response).await
+                    }
+                }
+            },
+            move |app, result| match result {
+                Ok(()) => {
+                    app.set_status_message(format!("{} '{}'", action.past_tense(), email.subject))
+                }
+                Err(e) => {
+                    app.set_error_message(format!(
+                        "Failed to {} '{}': {}",
+                        action.verb(),
+                        email.subject,
+                        e
+                    ));
+                    app.restore_email(position, real_idx, email);
+                }
+            },
+        );
+    }
+
+    /// Shows the quoted reply text of the open email in full, or collapsed again.
+    fn toggle_quotes(&mut self) {
+        self.quotes_expanded = !self.quotes_expanded;
+        self.set_status_message(if self.quotes_expanded {
+            "Showing quoted text (Q to collapse it)".to_string()
+        } else {
+            "Collapsed quoted text".to_string()
+        });
+    }
+
+    /// Opens the help on the bindings of the focused pane.
+    fn open_help(&mut self) {
+        let context = match self.focus {
+            _ if matches!(self.input_mode, InputMode::Compose) => Context::Compose,
+            FocusPanel::Sidebar => Context::Sidebar,
+            FocusPanel::EmailList => Context::List,
+            FocusPanel::EmailContent => Context::Reading,
+        };
+        self.help = Some(Help::new(context));
+        self.input_mode = InputMode::Help;
+    }
+
+    /// Indices in `emails` of the emails tagged or in the visual range, in list order.
+    pub fn selected_for_action(&self) -> Vec<usize> {
+        self.filtered_emails
+            .iter()
+            .enumerate()
+            .filter(|(position, &idx)| {
+                self.selection
+                    .contains(&self.emails[idx], *position, self.selected_index)
+            })
+            .map(|(_, &idx)| idx)
+            .collect()
+    }
+
+    /// Tags or untags the selected email and moves on to the next.
+    fn toggle_tag(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let email = email.clone();
+        self.selection.toggle(&email);
+        if self.selected_index + 1 < self.filtered_emails.len() {
+            self.select_index(self.selected_index + 1);
+        }
+    }
+
+    /// Starts a visual range at the selected email, or ends it, keeping the
+    /// emails it covered tagged.
+    fn toggle_visual(&mut self) {
+        if self.selection.anchor.is_none() {
+            if !self.filtered_emails.is_empty() {
+                self.selection.anchor = Some(self.selected_index);
+            }
+        } else {
+            for idx in self.selected_for_action() {
+                let email = self.emails[idx].clone();
+                self.selection.tag(&email);
+            }
+            self.selection.anchor = None;
+        }
+    }
+
+    /// Runs `action` on every email tagged or in the visual range.
+    fn apply_to_selection(&mut self, action: SelectionAction) {
+        let indices = self.selected_for_action();
+        self.selection.clear();
+        self.apply_to_emails(&indices, action);
+    }
+
+    /// Asks to mark every unread email the search and filters show as read.
+    fn confirm_mark_results_read(&mut self) {
+        let count = self
+            .filtered_emails
+            .iter()
+            .filter(|&&idx| !self.emails[idx].read)
+            .count();
+        if count == 0 {
+            self.set_status_message("No unread emails in the list".to_string());
+            return;
+        }
+        let prompt = format!("Mark the {} unread emails in the list as read?", count);
+        self.ask_confirmation(prompt, ConfirmAction::MarkResultsRead);
+    }
+
+    /// Runs `action` on the emails at `indices` with one server call per
+    /// account, recording an undo entry for each account.
+    fn apply_to_emails(&mut self, indices: &[usize], action: SelectionAction) {
+        let keys: Vec<(String, String)> = indices
+            .iter()
+            .map(|&idx| {
+                (
+                    self.emails[idx].account.clone(),
+                    self.emails[idx].id.clone(),
+                )
+            })
+            .collect();
+        if keys.is_empty() {
+            self.set_status_message("No selected emails in the list".to_string());
+            return;
+        }
+        let mut accounts: Vec<String> = Vec::new();
+        for (account, _) in &keys {
+            if !accounts.contains(account) {
+                accounts.push(account.clone());
+            }
+        }
+
+        let mut errors = Vec::new();
+        let mut requests = Vec::new();
+        for account in accounts {
+            let mut ids: Vec<String> = keys
+                .iter()
+                .filter(|(a, _)| *a == account)
+                .map(|(_, id)| id.clone())
+                .collect();
+            if matches!(action, SelectionAction::MarkRead) {
+                ids.retain(|id| {
+                    self.emails
+                        .iter()
+                        .any(|e| e.account == account && e.id == *id && !e.read)
+                });
+                if ids.is_empty() {
+                    continue;
+                }
+            }
+            match self.account_client(&account) {
+                Ok(client) => requests.push((account, ids, client)),
+                Err(e) => errors.push(format!("{}: {}", account, e)),
+            }
+        }
+
+        let limit = self.timeouts.get(Operation::Update);
+        let folders_limit = self.timeouts.get(Operation::ListSync);
+        let sent = action.clone();
+        let request = async move {
+            let mut results = Vec::new();
+            for (account, ids, client) in requests {
+                let result = match &sent {
+                    SelectionAction::Archive => {
+                        loading::with_timeout(limit, client.archive_emails(&ids)).await
+                    }
+                    SelectionAction::Delete => {
+                        loading::with_timeout(limit, client.delete_emails(&ids)).await
+                    }
+                    SelectionAction::MarkRead => {
+                        loading::with_timeout(limit, client.set_read(&ids, true)).await
+                    }
+                    SelectionAction::Move(name) => {
+                        match loading::with_timeout(folders_limit, client.fetch_folders()).await {
+                            Ok(folders) => match folders
+                                .iter()
+                                .find(|folder| folder.name.eq_ignore_ascii_case(name))
+                            {
+                                Some(folder) => {
+                                    let request = client.move_emails(&ids, &folder.id);
+                                    loading::with_timeout(limit, request).await
+                                }
+                                None => Err(anyhow!("no folder named {}", name)),
+                            },
+                            Err(e) => Err(e),
+                        }
+                    }
+                };
+                results.push((account, ids, result));
+            }
+            results
+        };
+        self.spawn_request(request, move |app, results| {
+            app.finish_emails_action(action, results, errors)
+        });
+    }
+
+    /// Applies locally what the server did for `apply_to_emails`, recording an
+    /// undo entry for each account it worked for.
+    fn finish_emails_action(
+        &mut self,
+        action: SelectionAction,
+        results: Vec<(String, Vec<String>, Result<()>)>,
+        mut errors: Vec<String>,
+    ) {
+        let mut done = 0;
+        for (account, ids, result) in results {
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", account, e));
+                continue;
+            }
+
+            done += ids.len();
+            let description = action.describe(ids.len());
+            let change = if matches!(action, SelectionAction::MarkRead) {
+                self.set_read_locally(&account, &ids, true);
+                UndoChange::MarkUnread(ids)
+            } else {
+                let taken: Vec<usize> = self
+                    .emails
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, e)| e.account == account && ids.contains(&e.id))
+                    .map(|(idx, _)| idx)
+                    .collect();
+                UndoChange::Restore(self.take_emails(&taken))
+            };
+            self.undo_stack.push(UndoEntry {
+                account,
+                description,
+                change,
+            });
+        }
+
+        let summary = action.describe(done);
+        match errors.as_slice() {
+            [] => self.set_status_message(format!("{}, u to undo", summary)),
+            _ => self.set_error_message(format!("{}, failed for {}", summary, errors.join("; "))),
+        }
+    }
+
+    /// Runs `action` on the selected email's whole thread with one server call,
+    /// recording a single undo entry for it.
+    fn apply_to_thread(&mut self, action: ThreadAction) {
+        let Some((account, thread, indices)) = self.selected_thread() else {
+            return;
+        };
+        let subject = self.emails[indices[0]].subject.clone();
+        let description = format!(
+            "{} thread '{}'",
+            action.past_tense().to_lowercase(),
+            subject
+        );
+        let ids: Vec<String> = indices
+            .iter()
+            .map(|&idx| self.emails[idx].id.clone())
+            .collect();
+
+        let done = ThreadDone {
+            action,
+            account: account.clone(),
+            subject: subject.clone(),
+            description,
+            count: indices.len(),
+        };
+        match action {
+            ThreadAction::Archive | ThreadAction::Delete => {
+                let previous = (
+                    self.threads.ungrouped(&self.emails, &self.filtered_emails),
+                    self.selected_index,
+                    self.emails.len(),
+                );
+                let taken = self.take_emails(&indices);
+                self.request(
+                    &account,
+                    Operation::Update,
+                    |client| async move {
+                        if matches!(action, ThreadAction::Archive) {
+                            client.archive_emails(&ids).await
+                        } else {
+                            client.delete_emails(&ids).await
+                        }
+                    },
+                    move |app, result| {
+                        if let Err(e) = result {
+                            app.set_error_message(format!(
+                                "Failed to {} thread '{}': {}",
+                                action.verb(),
+                                subject,
+                                e
+                            ));
+                            for (idx, email) in taken {
+                                let idx = idx.min(app.emails.len());
+                                app.emails.insert(idx, email);
+                            }
+                            // The rows from before only fit a list nothing else changed
+                            if app.emails.len() == previous.2 {
+                                app.filtered_emails = app.threads.group(&app.emails, previous.0);
+                                app.select_index(previous.1);
+                            } else {
+                                app.show_all_emails();
+                            }
+                            return;
+                        }
+                        app.finish_thread_action(done, UndoChange::Restore(taken));
+                    },
+                );
+            }
+            ThreadAction::MarkRead => {
+                let unread: Vec<String> = indices
+                    .iter()
+                    .filter(|&&idx| !self.emails[idx].read)
+                    .map(|&idx| self.emails[idx].id.clone())
+                    .collect();
+                if unread.is_empty() {
+                    self.set_status_message(format!("Thread '{}' is already read", subject));
+                    return;
+                }
+                let sent = unread.clone();
+                self.request(
+                    &account,
+                    Operation::Update,
+                    |client| async move { client.set_read(&sent, true).await },
+                    move |app, result| {
+                        if let Err(e) = result {
+                            app.set_error_message(format!(
+                                "Failed to mark thread '{}' read: {}",
+                                subject, e
+                            ));
+                            return;
+                        }
+                        app.set_read_locally(&done.account, &unread, true);
+                        app.finish_thread_action(done, UndoChange::MarkUnread(unread));
+                    },
+                );
+            }
+            ThreadAction::Mute => {
+                // Muting is kept locally, so it also hides messages that arrive later
+                self.state.mute_thread(&thread);
+                if let Err(e) = self.state.save() {
+                    self.state.unmute_thread(&thread);
+                    self.set_error_message(format!("Failed to mute thread '{}': {}", subject, e));
+                    return;
+                }
+                let position = self.selected_index;
+                self.filtered_emails.retain(|idx| !indices.contains(idx));
+                self.reselect(position);
+                self.finish_thread_action(done, UndoChange::Unmute(thread));
+            }
+        }
+    }
+
+    /// Reports a thread action the server carried out and records how to undo it.
+    fn finish_thread_action(&mut self, done: ThreadDone, change: UndoChange) {
+        self.set_status_message(format!(
+            "{} thread '{}' ({} messages), u to undo",
+            done.action.past_tense(),
+            done.subject,
+            done.count
+        ));
+        self.undo_stack.push(UndoEntry {
+            account: done.account,
+            description: done.description,
+            change,
+        });
+    }
+
+    /// Reverses the last thread action.
+    fn undo(&mut self) {
+        let Some(entry) = self.undo_stack.pop() else {
+            self.set_status_message("Nothing to undo".to_string());
+            return;
+        };
+
+        let account = entry.account.clone();
+        let sent = match &entry.change {
+            UndoChange::Restore(emails) => {
+                Some((emails.iter().map(|(_, e)| e.id.clone()).collect(), true))
+            }
+            UndoChange::MarkUnread(ids) => Some((ids.clone(), false)),
+            UndoChange::Unmute(_) => None,
+        };
+        match sent {
+            Some((ids, restore)) => self.request(
+                &account,
+                Operation::Update,
+                |client| async move {
+                    if restore {
+                        client.move_emails(&ids, INBOX_FOLDER_ID).await
+                    } else {
+                        client.set_read(&ids, false).await
+                    }
+                },
+                move |app, result| app.finish_undo(entry, result),
+            ),
+            None => self.finish_undo(entry, Ok(())),
+        }
+    }
+
+    /// Reverses locally the undo entry the server has, or has not, undone.
+    fn finish_undo(&mut self, entry: UndoEntry, result: Result<()>) {
+        if let Err(e) = result {
+            self.set_error_message(format!("Failed to undo {}: {}", entry.description, e));
+            self.undo_stack.push(entry);
+            return;
+        }
+
+        match entry.change {
+            UndoChange::Restore(emails) => {
+                for (idx, email) in emails {
+                    let idx = idx.min(self.emails.len());
+                    self.emails.insert(idx, email);
+                }
+                self.show_all_emails();
+            }
+            UndoChange::MarkUnread(ids) => self.set_read_locally(&entry.account, &ids, false),
+            UndoChange::Unmute(thread) => {
+                self.state.unmute_thread(&thread);
+                if let Err(e) = self.state.save() {
+                    self.state.mute_thread(&thread);
+                    self.set_error_message(format!("Failed to undo {}: {}", entry.description, e));
+                    self.undo_stack.push(UndoEntry {
+                        change: UndoChange::Unmute(thread),
+                        ..entry
+                    });
+                    return;
+                }
+                self.show_all_emails();
+            }
+        }
+        self.set_status_message(format!("Undid: {}", entry.description));
+    }
+
+    fn set_read_locally(&mut self, account: &str, ids: &[String], read: bool) {
+        for email in self
+            .emails
+            .iter_mut()
+            .filter(|e| e.account == account && ids.contains(&e.id))
+        {
+            email.read = read;
+        }
+    }
+
+    /// Removes the emails at `indices` in `emails` from the local list, returning
+    /// them with their indices in ascending order so they can be put back.
+    fn take_emails(&mut self, indices: &[usize]) -> Vec<(usize, Email)> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        let rows = self.threads.ungrouped(&self.emails, &self.filtered_emails);
+        let mut taken: Vec<(usize, Email)> = indices
+            .iter()
+            .rev()
+            .map(|&idx| (idx, self.emails.remove(idx)))
+            .collect();
+        taken.reverse();
+
+        let position = self.selected_index;
+        let rows: Vec<usize> = rows
+            .into_iter()
+            .filter(|idx| !indices.contains(idx))
+            .map(|idx| idx - indices.iter().filter(|&&removed| removed < idx).count())
+            .collect();
+        self.filtered_emails = self.threads.group(&self.emails, rows);
+        self.reselect(position);
+        taken
+    }
+
+    /// Removes the email at `position` in the filtered list from the local list,
+    /// returning its index in `emails` so it can be restored.
+    fn take_email(&mut self, position: usize) -> (usize, Email) {
+        let real_idx = self.filtered_emails[position];
+        self.take_emails(&[real_idx]).remove(0)
+    }
+
+    /// Selects the email at `position` after emails were removed, leaving the
+    /// email view when the list became empty.
+    fn reselect(&mut self, position: usize) {
+        if self.filtered_emails.is_empty() {
+            self.input_mode = InputMode::Normal;
+            self.focus = FocusPanel::EmailList;
+            self.select_index(0);
+        } else {
+            self.select_index(position.min(self.filtered_emails.len() - 1));
+        }
+    }
+
+    /// Puts back an email removed by `take_email` and selects it again.
+    fn restore_email(&mut self, position: usize, real_idx: usize, email: Email) {
+        // The list may have shrunk while the server answered
+        let real_idx = real_idx.min(self.emails.len());
+        let mut rows = self.threads.ungrouped(&self.emails, &self.filtered_emails);
+        // Back before the email that took its place
+        let at = self
+            .filtered_emails
+            .get(position)
+            .and_then(|next| rows.iter().position(|idx| idx == next))
+            .unwrap_or(rows.len());
+        self.emails.insert(real_idx, email);
+        for idx in rows.iter_mut() {
+            if *idx >= real_idx {
+                *idx += 1;
+            }
+        }
+        rows.insert(at, real_idx);
+        self.filtered_emails = self.threads.group(&self.emails, rows);
+        self.select_index(position.min(self.filtered_emails.len() - 1));
+    }
+
+    fn open_address_menu(&mut self) {
+        if self.selected_email().is_some() {
+            self.address_menu_index = 0;
+            self.input_mode = InputMode::AddressMenu;
+        }
+    }
+
+    /// Lists the links in the body shown for the selected email.
+    fn open_links(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let links = links::extract(&self.displayed_text(email));
+        if links.is_empty() {
+            self.set_status_message("No links in this email".to_string());
+            return;
+        }
+        self.links = links;
+        self.links_index = 0;
+        self.input_mode = InputMode::Links;
+    }
+
+    fn open_link(&mut self, index: usize) {
+        let Some(link) = self.links.get(index).cloned() else {
+            return;
+        };
+        self.close_popup();
+        self.open_url(&link);
+    }
+
+    fn open_url(&mut self, link: &str) {
+        match browser::open(link) {
+            Ok(()) => self.set_status_message(format!("Opened {}", link)),
+            Err(e) => self.set_error_message(format!("Failed to open the link: {:#}", e)),
+        }
+    }
+
+    /// Labels the links and attachments of the selected email, to open one by
+    /// typing its label.
+    fn start_hints(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let links = links::extract(&self.displayed_text(email));
+        if links.is_empty() && email.attachments.is_empty() {
+            self.set_status_message("No links or attachments in this email".to_string());
+            return;
+        }
+        self.hints = Some(Hints::new(links, &email.attachments));
+        self.input_mode = InputMode::Hints;
+    }
+
+    /// Adds `c` to the label typed in hint mode, opening its target once the
+    /// label is complete.
+    fn type_hint(&mut self, c: char) {
+        let Some(hints) = self.hints.as_mut() else {
+            self.close_popup();
+            return;
+        };
+        hints.typed.push(c);
+        let target = hints.chosen().cloned();
+        if target.is_none() && hints.is_possible() {
+            return;
+        }
+        let typed = hints.typed.clone();
+        self.hints = None;
+        self.close_popup();
+        match target {
+            Some(HintTarget::Link(link)) => self.open_url(&link),
+            Some(HintTarget::Attachment(attachment)) => self.open_attachment(&attachment),
+            None => self.set_status_message(format!("No hint {}", typed)),
+        }
+    }
+
+    /// Downloads an attachment of the selected email and opens it with the
+    /// system's default application.
+    fn open_attachment(&mut self, attachment: &Attachment) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let (account, email_id) = (email.account.clone(), email.id.clone());
+        let (sent_email_id, attachment_id) = (email_id.clone(), attachment.id.clone());
+        let attachment = attachment.clone();
+
+        self.loading.start(Operation::AttachmentDownload);
+        self.request(
+            &account,
+            Operation::AttachmentDownload,
+            |client| async move {
+                client
+                    .fetch_attachment(&sent_email_id, &attachment_id)
+                    .await
+            },
+            move |app, result| {
+                app.loading.finish(Operation::AttachmentDownload);
+                let result = result
+                    .and_then(|content| browser::write_attachment(&email_id, &attachment, &content))
+                    .and_then(|path| browser::open(&path.display().to_string()));
+                match result {
+                    Ok(()) => app.set_status_message(format!("Opened {}", attachment.name)),
+                    Err(e) => app
+                        .set_error_message(format!("Failed to open {}: {:#}", attachment.name, e)),
+                }
+            },
+        );
+    }
+
+    fn copy_link(&mut self, index: usize) {
+        let Some(link) = self.links.get(index).cloned() else {
+            return;
+        };
+        self.close_popup();
+        match clipboard::copy(&link) {
+            Ok(()) => self.set_status_message(format!("Copied {} to clipboard", link)),
+            Err(e) => self.set_error_message(format!("Failed to copy the link: {}", e)),
+        }
+    }
+
+    /// Copies the body of the open email, as the content pane shows it.
+    fn copy_body(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        if !email.body.is_loaded(BodyFormat::Text) && self.rendered_lines(email).is_none() {
+            self.set_status_message("The message hasn't loaded yet".to_string());
+            return;
+        }
+        let body = self.displayed_text(email);
+        match clipboard::copy(&body) {
+            Ok(()) => self.set_status_message(format!(
+                "Copied the body ({} lines) to clipboard",
+                body.lines().count()
+            )),
+            Err(e) => self.set_error_message(format!("Failed to copy the body: {}", e)),
+        }
+    }
+
+    /// Returns from a popup to the mode matching the focused panel.
+    fn close_popup(&mut self) {
+        if self.composing.is_some() {
+            self.input_mode = InputMode::Compose;
+            return;
+        }
+        if self.drafts.is_some() {
+            self.input_mode = InputMode::Drafts;
+            return;
+        }
+        if self.folder_manager.is_some() {
+            self.input_mode = InputMode::Folders;
+            return;
+        }
+        if self.outbox_view.is_some() {
+            self.input_mode = InputMode::Outbox;
+            return;
+        }
+        self.input_mode = match self.focus {
+            FocusPanel::Sidebar => InputMode::Sidebar,
+            FocusPanel::EmailContent => InputMode::EmailView,
+            FocusPanel::EmailList => InputMode::Normal,
+        };
+    }
+
+    /// The address highlighted in the address actions menu.
+    pub fn selected_address(&self) -> Option<String> {
+        self.selected_email()
+            .and_then(|email| email.addresses().get(self.address_menu_index).copied())
+            .map(str::to_string)
+    }
+
+    fn run_address_action(&mut self, action: AddressAction) {
+        let Some(address) = self.selected_address() else {
+            return;
+        };
+        self.close_popup();
+
+        match action {
+            AddressAction::Copy => match clipboard::copy(&address) {
+                Ok(()) => self.set_status_message(format!("Copied {} to clipboard", address)),
+                Err(e) => self.set_error_message(format!("Failed to copy address: {}", e)),
+            },
+            AddressAction::Compose => {
+                if let Some(account) = self.current_account() {
+                    let mut compose = Compose::new(account);
+                    compose.form.to = address;
+                    compose.form.field = MessageField::Subject;
+                    self.start_compose(compose);
+                }
+            }
+            AddressAction::SearchFrom => {
+                self.input_mode = InputMode::Normal;
+                self.focus = FocusPanel::EmailList;
+                self.filter_by_sender(&address);
+            }
+            AddressAction::AddContact => {
+                if self.state.add_contact(&address) {
+                    self.save_state(format!("Added {} to contacts", address));
+                } else {
+                    self.set_status_message(format!("{} is already a contact", address));
+                }
+            }
+            AddressAction::Block => {
+                if self.state.block_sender(&address) {
+                    self.input_mode = InputMode::Normal;
+                    self.focus = FocusPanel::EmailList;
+                    self.search(String::new());
+                    self.save_state(format!("Blocked {}, their mail is now hidden", address));
+                } else {
+                    self.set_status_message(format!("{} is already blocked", address));
+                }
+            }
+        }
+    }
+
+    /// Starts loading the selected email's text if needed, along with the format
+    /// its renderer needs, and records it as read. The body is rendered once it is
+    /// loaded.
+    fn open_selected(&mut self) {
+        self.body_view = None;
+        self.find = None;
+        self.quotes_expanded = false;
+        self.load_selected_body(BodyFormat::Text);
+        if let Some(email) = self.selected_email() {
+            let format = render::body_format(email);
+            if format != BodyFormat::Text {
+                self.load_selected_body(format);
+            }
+        }
+        self.load_selected_headers();
+        self.load_selected_images();
+        self.render_selected();
+        self.record_activity(ActivityKind::Read);
+        self.mark_selected_read();
+    }
+
+    /// Marks the selected email read right away and on the server on a local
+    /// task, whose result arrives in `apply_marked_read` through the event loop.
+    fn mark_selected_read(&mut self) {
+        let Some(email) = self.selected_email().filter(|email| !email.read) else {
+            return;
+        };
+        let (account, id) = (email.account.clone(), email.id.clone());
+        let Some(idx) = self.accounts.iter().position(|a| a.name == account) else {
+            return;
+        };
+        self.set_read_locally(&account, std::slice::from_ref(&id), true);
+
+        let accounts = Rc::clone(&self.accounts);
+        let limit = self.timeouts.get(Operation::BodyFetch);
+        let tx = self.actions_tx.clone();
+        tokio::task::spawn_local(async move {
+            let request = accounts[idx]
+                .client
+                .set_read(std::slice::from_ref(&id), true);
+            let result = loading::with_timeout(limit, request).await;
+            // The receiver only goes away when the app quits
+            let _ = tx.send(Action::MarkedRead {
+                account,
+                id,
+                result,
+            });
+        });
+    }
+
+    /// Downloads the image attachments of the selected email on local tasks to
+    /// draw in the content pane, forgetting the ones of other emails. Each
+    /// arrives in `apply_image` through the event loop.
+    fn load_selected_images(&mut self) {
+        if self.graphics.is_none() {
+            return;
+        }
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let (account, email_id) = (email.account.clone(), email.id.clone());
+        let wanted: Vec<(ImageKey, u64)> = email
+            .attachments
+            .iter()
+            .filter(|attachment| attachment.is_image())
+            .map(|attachment| {
+                let key = (account.clone(), email_id.clone(), attachment.id.clone());
+                (key, attachment.size)
+            })
+            .collect();
+        self.images.retain(|key, state| {
+            let keep = wanted.iter().any(|(wanted, _)| wanted == key);
+            if let (false, ImageState::Ready(image)) = (keep, state) {
+                self.forgotten_images.push(image.id);
+            }
+            keep
+        });
+        let Some(idx) = self.accounts.iter().position(|a| a.name == account) else {
+            return;
+        };
+
+        for (key, size) in wanted {
+            if self.images.contains_key(&key) {
+                continue;
+            }
+            if size >= MAX_IMAGE_BYTES {
+                self.images.insert(key, ImageState::Unavailable);
+                continue;
+            }
+            self.images.insert(key.clone(), ImageState::Loading);
+            let accounts = Rc::clone(&self.accounts);
+            let limit = self.timeouts.get(Operation::AttachmentDownload);
+            let tx = self.actions_tx.clone();
+            tokio::task::spawn_local(async move {
+                let (account, email_id, attachment_id) = key;
+                let request = accounts[idx]
+                    .client
+                    .fetch_attachment(&email_id, &attachment_id);
+                let result = loading::with_timeout(limit, request).await;
+                // The receiver only goes away when the app quits
+                let _ = tx.send(Action::ImageLoaded {
+                    account,
+                    email_id,
+                    attachment_id,
+                    result,
+                });
+            });
+        }
+    }
+
+    /// Keeps a downloaded image to draw, unless another email is open by now.
+    /// Images that aren't PNGs or failed to download stay a placeholder.
+    fn apply_image(&mut self, key: ImageKey, result: Result<Vec<u8>>) {
+        if !self.images.contains_key(&key) {
+            return;
+        }
+        let state = match result.and_then(|data| Image::new(self.next_image_id, data)) {
+            Ok(image) => {
+                self.next_image_id += 1;
+                ImageState::Ready(image)
+            }
+            Err(e) => {
+                debug!("Image {} can't be shown: {:#}", key.2, e);
+                ImageState::Unavailable
+            }
+        };
+        self.images.insert(key, state);
+    }
+
+    /// Shows the email unread again if the server didn't take the change.
+    fn apply_marked_read(&mut self, account: &str, id: &str, result: Result<()>) {
+        if let Err(e) = result {
+            self.set_read_locally(account, &[id.to_string()], false);
+            self.set_error_message(format!("Failed to mark the email read: {}", e));
+        }
+    }
+
+    /// Fetches the selected email's headers on a local task unless they were
+    /// fetched before. The result arrives in `apply_headers` through the event loop.
+    fn load_selected_headers(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        if email.headers.is_some() {
+            return;
+        }
+        let (account, id) = (email.account.clone(), email.id.clone());
+        if let Some((fetching_account, fetching_id, handle)) = &self.headers_fetch {
+            if *fetching_account == account && *fetching_id == id {
+                return;
+            }
+            handle.abort();
+        }
+        let Some(idx) = self.accounts.iter().position(|a| a.name == account) else {
+            return;
+        };
+
+        let accounts = Rc::clone(&self.accounts);
+        let limit = self.timeouts.get(Operation::BodyFetch);
+        let tx = self.actions_tx.clone();
+        let (task_account, task_id) = (account.clone(), id.clone());
+        let handle = tokio::task::spawn_local(async move {
+            let request = accounts[idx].client.fetch_headers(&task_id);
+            let result = loading::with_timeout(limit, request).await;
+            // The receiver only goes away when the app quits
+            let _ = tx.send(Action::HeadersLoaded(HeadersUpdate {
+                account: task_account,
+                id: task_id,
+                result,
+            }));
+        });
+        self.headers_fetch = Some((account, id, handle.abort_handle()));
+    }
+
+    fn apply_headers(&mut self, update: HeadersUpdate) {
+        let HeadersUpdate {
+            account,
+            id,
+            result,
+        } = update;
+        self.headers_fetch = None;
+        match result {
+            Ok(headers) => {
+                if let Some(cache) = &self.cache {
+                    if let Err(e) = cache.store_headers(&account, &id, &headers) {
+                        self.set_error_message(format!("Failed to update the cache: {}", e));
+                    }
+                }
+                if let Some(email) = self
+                    .emails
+                    .iter_mut()
+                    .find(|email| email.account == account && email.id == id)
+                {
+                    email.headers = Some(headers);
+                }
+            }
+            Err(e) => self.set_error_message(format!("Failed to load the headers: {}", e)),
+        }
+    }
+
+    /// Runs a command typed after `:`.
+    fn run_command(&mut self, input: &str) {
+        if input.is_empty() {
+            return;
+        }
+        let command = match Command::parse(input) {
+            Ok(command) => command,
+            Err(e) => {
+                self.set_error_message(e.to_string());
+                return;
+            }
+        };
+        match command {
+            Command::Archive => self.apply_to_selected(MessageAction::Archive),
+            Command::Delete => self.confirm_delete(),
+            Command::Folder(name) => {
+                let inbox = name.eq_ignore_ascii_case("inbox");
+                self.open_folder(&name, inbox);
+            }
+            Command::Help => self.open_help(),
+            Command::Metrics => self.input_mode = InputMode::Metrics,
+            Command::Move(name) => self.move_selected_to(&name),
+            Command::Quit => self.should_quit = true,
+            Command::Refresh => self.refresh(),
+            Command::SaveSearch(name) => self.save_search(name),
+            Command::Search(query) => self.run_search(query),
+        }
+    }
+
+    /// Puts `query` in the search box and runs it, on the server for `!query`.
+    fn run_search(&mut self, query: String) {
+        self.search_input = query.clone();
+        match query.strip_prefix(SERVER_SEARCH_PREFIX) {
+            Some(server_query) => self.search_server(server_query.trim()),
+            None => self.search(query),
+        }
+    }
+
+    /// Keeps the current search under `name`, in the config file when there is one.
+    fn save_search(&mut self, name: String) {
+        let query = self.search_input.trim().to_string();
+        if query.is_empty() {
+            self.set_error_message("No search to save, search with / first".to_string());
+            return;
+        }
+        let search = SavedSearch { name, query };
+        if let Some(path) = &self.config_path {
+            if let Err(e) = config::save_search(path, &search) {
+                self.set_error_message(format!("Failed to save the search: {}", e));
+                return;
+            }
+        }
+        self.set_status_message(format!("Saved search {}", search.name));
+        match self.searches.iter_mut().find(|s| s.name == search.name) {
+            Some(saved) => *saved = search,
+            None => self.searches.push(search),
+        }
+    }
+
+    fn open_searches(&mut self) {
+        if self.searches.is_empty() {
+            self.set_status_message("No saved searches, keep one with :save-search".to_string());
+            return;
+        }
+        self.searches_index = self.searches_index.min(self.searches.len() - 1);
+        self.input_mode = InputMode::Searches;
+    }
+
+    fn run_saved_search(&mut self, index: usize) {
+        let Some(search) = self.searches.get(index).cloned() else {
+            return;
+        };
+        self.searches_index = index;
+        self.close_popup();
+        self.run_search(search.query);
+    }
+
+    /// Completes the command line, fetching the folder names of every account
+    /// the first time one is completed.
+    fn complete_command(&mut self) {
+        if commands::takes_folder(&self.command_input) && self.command_folders.is_none() {
+            let limit = self.timeouts.get(Operation::ListSync);
+            let accounts = Rc::clone(&self.accounts);
+            let request = async move {
+                let mut names: Vec<String> = Vec::new();
+                for account in accounts.iter() {
+                    let request = account.client.fetch_folders();
+                    match loading::with_timeout(limit, request).await {
+                        Ok(folders) => {
+                            for folder in folders {
+                                if !names.iter().any(|n| n.eq_ignore_ascii_case(&folder.name)) {
+                                    names.push(folder.name);
+                                }
+                            }
+                        }
+                        Err(e) => debug!("Folders of {} for completion: {:#}", account.name, e),
+                    }
+                }
+                names
+            };
+            let input = self.command_input.clone();
+            self.spawn_request(request, move |app, names| {
+                app.command_folders = Some(names);
+                // Complete what was typed if it is still on the command line
+                if matches!(app.input_mode, InputMode::Command) && app.command_input == input {
+                    app.complete_command();
+                }
+            });
+            return;
+        }
+        let folders = self.command_folders.as_deref().unwrap_or_default();
+        self.command_input = commands::complete(&self.command_input, folders);
+    }
+
+    /// Moves the selected email to its account's folder named `name`, ignoring case.
+    fn move_selected_to(&mut self, name: &str) {
+        if !self.selection.is_empty() {
+            self.apply_to_selection(SelectionAction::Move(name.to_string()));
+            return;
+        }
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let (account, id) = (email.account.clone(), email.id.clone());
+        let name = name.to_string();
+        self.request(
+            &account.clone(),
+            Operation::ListSync,
+            |client| async move { client.fetch_folders().await },
+            move |app, result| match result {
+                Ok(folders) => match folders
+                    .into_iter()
+                    .find(|folder| folder.name.eq_ignore_ascii_case(&name))
+                {
+                    Some(folder) => {
+                        // The selection may have moved on while the folders loaded
+                        if let Some(position) = app.filtered_emails.iter().position(|&idx| {
+                            app.emails[idx].account == account && app.emails[idx].id == id
+                        }) {
+                            app.move_email(position, &folder);
+                        }
+                    }
+                    None => {
+                        app.set_error_message(format!("{} has no folder named {}", account, name))
+                    }
+                },
+                Err(e) => app.set_error_message(format!("Failed to load folders: {}", e)),
+            },
+        );
+    }
+
+    /// Shows the selected email's raw headers, fetching them if needed.
+    fn open_headers(&mut self) {
+        if self.selected_email().is_none() {
+            return;
+        }
+        self.load_selected_headers();
+        self.headers_scroll = 0;
+        self.input_mode = InputMode::Headers;
+    }
+
+    /// Fetches a format of the selected email's body on a local task unless it was
+    /// loaded before, dropping fetches for emails no longer selected. The result
+    /// arrives in `apply_body` through the event loop.
+    fn load_selected_body(&mut self, format: BodyFormat) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let (account, id) = (email.account.clone(), email.id.clone());
+        let loaded = email.body.is_loaded(format);
+
+        let loading = &mut self.loading;
+        self.body_fetches.retain(|fetch| {
+            let current = fetch.account == account && fetch.id == id;
+            if !current {
+                fetch.handle.abort();
+                loading.finish(Operation::BodyFetch);
+            }
+            current
+        });
+        if loaded || self.body_fetches.iter().any(|fetch| fetch.format == format) {
+            return;
+        }
+        // A prefetch already on its way only needs to be waited for
+        if let Some(pos) = self
+            .prefetches
+            .iter()
+            .position(|fetch| fetch.account == account && fetch.id == id && fetch.format == format)
+        {
+            self.loading.start(Operation::BodyFetch);
+            let fetch = self.prefetches.remove(pos);
+            self.body_fetches.push(fetch);
+            return;
+        }
+        let Some(idx) = self.accounts.iter().position(|a| a.name == account) else {
+            return;
+        };
+
+        self.loading.start(Operation::BodyFetch);
+        let accounts = Rc::clone(&self.accounts);
+        let limit = self.timeouts.get(Operation::BodyFetch);
+        let tx = self.actions_tx.clone();
+        let (task_account, task_id) = (account.clone(), id.clone());
+        let span = info_span!("fetch_body", account, id);
+        let handle = tokio::task::spawn_local(
+            async move {
+                let request = accounts[idx].client.fetch_body(&task_id, format);
+                let result = loading::with_timeout(limit, request).await;
+                // The receiver only goes away when the app quits
+                let _ = tx.send(Action::BodyLoaded(BodyUpdate {
+                    account: task_account,
+                    id: task_id,
+                    format,
+                    result,
+                }));
+            }
+            .instrument(span),
+        );
+        self.body_fetches.push(BodyFetch {
+            account,
+            id,
+            format,
+            handle: handle.abort_handle(),
+        });
+    }
+
+    /// Downloads the text bodies of the emails around the selection in the
+    /// background, at most `prefetch.concurrency` at a time, and drops prefetches
+    /// for emails the selection moved away from.
+    fn prefetch_bodies(&mut self) {
+        if self.prefetch.concurrency == 0 || self.filtered_emails.is_empty() {
+            return;
+        }
+        let start = self.selected_index.saturating_sub(1);
+        let end = (self.selected_index + self.prefetch.ahead + 1).min(self.filtered_emails.len());
+        let wanted: Vec<(String, String)> = self.filtered_emails[start.min(end)..end]
+            .iter()
+            .map(|&idx| &self.emails[idx])
+            .filter(|email| !email.body.is_loaded(BodyFormat::Text))
+            .map(|email| (email.account.clone(), email.id.clone()))
+            .collect();
+
+        self.prefetches.retain(|fetch| {
+            let keep = wanted
+                .iter()
+                .any(|(account, id)| *account == fetch.account && *id == fetch.id);
+            if !keep {
+                fetch.handle.abort();
+            }
+            keep
+        });
+
+        for (account, id) in wanted {
+            let in_flight = self
+                .prefetches
+                .iter()
+                .chain(self.body_fetches.iter())
+                .any(|fetch| {
+                    fetch.account == account && fetch.id == id && fetch.format == BodyFormat::Text
+                });
+            if in_flight {
+                continue;
+            }
+            let Some(idx) = self.accounts.iter().position(|a| a.name == account) else {
+                continue;
+            };
+            let accounts = Rc::clone(&self.accounts);
+            let permits = Rc::clone(&self.prefetch_permits);
+            let limit = self.timeouts.get(Operation::BodyFetch);
+            let tx = self.actions_tx.clone();
+            let (task_account, task_id) = (account.clone(), id.clone());
+            let span = info_span!("prefetch", account, id);
+            let handle = tokio::task::spawn_local(
+                async move {
+                    // The semaphore is never closed
+                    let Ok(_permit) = permits.acquire().await else {
+                        return;
+                    };
+                    let request = accounts[idx].client.fetch_body(&task_id, BodyFormat::Text);
+                    let result = loading::with_timeout(limit, request).await;
+                    let _ = tx.send(Action::Prefetched(BodyUpdate {
+                        account: task_account,
+                        id: task_id,
+                        format: BodyFormat::Text,
+                        result,
+                    }));
+                }
+                .instrument(span),
+            );
+            self.prefetches.push(BodyFetch {
+                account,
+                id,
+                format: BodyFormat::Text,
+                handle: handle.abort_handle(),
+            });
+        }
+    }
+
+    /// Keeps a prefetched body. Failures stay quiet, as opening the email fetches
+    /// it again and reports the error then.
+    fn apply_prefetch(&mut self, update: BodyUpdate) {
+        // The email was opened while the prefetch was on its way
+        if self.body_fetches.iter().any(|fetch| {
+            fetch.account == update.account
+                && fetch.id == update.id
+                && fetch.format == update.format
+        }) {
+            self.apply_body(update);
+            return;
+        }
+        self.prefetches
+            .retain(|fetch| !(fetch.account == update.account && fetch.id == update.id));
+        let Ok(content) = update.result else {
+            return;
+        };
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.store_body(&update.account, &update.id, update.format, &content) {
+                self.set_error_message(format!("Failed to update the cache: {}", e));
+            }
+        }
+        if let Some(email) = self
+            .emails
+            .iter_mut()
+            .find(|email| email.account == update.account && email.id == update.id)
+        {
+            email.body.insert(update.format, content);
+        }
+    }
+
+    /// Keeps a fetched body, rendering it if its email is still selected.
+    fn apply_body(&mut self, update: BodyUpdate) {
+        let BodyUpdate {
+            account,
+            id,
+            format,
+            result,
+        } = update;
+        let before = self.body_fetches.len();
+        self.body_fetches.retain(|fetch| {
+            !(fetch.account == account && fetch.id == id && fetch.format == format)
+        });
+        for _ in self.body_fetches.len()..before {
+            self.loading.finish(Operation::BodyFetch);
+        }
+        let selected = self
+            .selected_email()
+            .is_some_and(|email| email.account == account && email.id == id);
+
+        match result {
+            Ok(content) => {
+                let empty_text = format == BodyFormat::Text && content.trim().is_empty();
+                if let Some(cache) = &self.cache {
+                    if let Err(e) = cache.store_body(&account, &id, format, &content) {
+                        self.set_error_message(format!("Failed to update the cache: {}", e));
+                    }
+                }
+                if let Some(email) = self
+                    .emails
+                    .iter_mut()
+                    .find(|email| email.account == account && email.id == id)
+                {
+                    email.body.insert(format, content);
+                }
+                if selected && empty_text {
+                    // Only the HTML part has something to show
+                    self.load_selected_body(BodyFormat::Html);
+                }
+                if selected {
+                    self.render_selected();
+                }
+                if !(selected && empty_text) {
+                    self.finish_quoting(&account, &id);
+                }
+            }
+            Err(e) => {
+                self.quoting = None;
+                self.set_error_message(format!("Failed to load message: {}", e));
+                if selected && loading::is_timeout(&e) {
+                    self.ask_confirmation(
+                        format!("Loading the message {}. Retry?", e),
+                        ConfirmAction::RetryBodyFetch(format),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Drops the body and header fetches in flight, returning whether there were any.
+    fn cancel_body_fetches(&mut self) -> bool {
+        if let Some((_, _, handle)) = self.headers_fetch.take() {
+            handle.abort();
+        }
+        if self.body_fetches.is_empty() {
+            return false;
+        }
+        for fetch in self.body_fetches.drain(..) {
+            fetch.handle.abort();
+            self.loading.finish(Operation::BodyFetch);
+        }
+        self.quoting = None;
+        self.set_status_message("Stopped loading the message".to_string());
+        true
+    }
+
+    /// Drops the syncs in flight, leaving the list as it was before the refresh.
+    /// Returns whether there were any.
+    fn cancel_syncs(&mut self) -> bool {
+        if self.syncing.is_empty() {
+            return false;
+        }
+        for (_, handle) in self.syncing.drain(..) {
+            handle.abort();
+            self.loading.finish(Operation::ListSync);
+        }
+        self.sync_batch.clear();
+        self.sync_progress.clear();
+        self.startup = None;
+        self.set_status_message("Refresh cancelled".to_string());
+        true
+    }
+
+    /// Renders the selected email's body with the renderer for its type,
+    /// leaving it to be shown as plain text if that fails.
+    fn render_selected(&mut self) {
+        self.rendered = None;
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let format = self
+            .body_view
+            .map_or_else(|| render::body_format(email), BodyView::format);
+        let Some(body) = email.body.get(format) else {
+            return;
+        };
+        if body.len() >= LARGE_BODY_BYTES {
+            return;
+        }
+        let renderer: &dyn BodyRenderer = match self.body_view {
+            Some(BodyView::Plain | BodyView::Source) => &PlainRenderer,
+            Some(BodyView::Html) => self.renderers.for_type("text/html"),
+            None if format == render::source_format(&email.content_type) => {
+                self.renderers.for_type(&email.content_type)
+            }
+            None => self.renderers.for_type("text/html"),
+        };
+        let result = renderer.render(body);
+        match result {
+            Ok(lines) => {
+                self.rendered = Some(RenderedBody {
+                    account: email.account.clone(),
+                    id: email.id.clone(),
+                    lines,
+                });
+            }
+            Err(e) => {
+                let content_type = email.content_type.clone();
+                self.set_error_message(format!(
+                    "Failed to render {}, showing plain text: {:#}",
+                    content_type, e
+                ));
+            }
+        }
+    }
+
+    /// Shows the next part of the selected email: its plain text, its HTML laid
+    /// out as text, then its source.
+    fn cycle_body_view(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let view = self
+            .body_view
+            .unwrap_or_else(|| BodyView::of(render::body_format(email)))
+            .next();
+        self.body_view = Some(view);
+        self.load_selected_body(view.format());
+        self.render_selected();
+        self.set_status_message(format!(
+            "Showing the {} (V for the next part)",
+            view.label()
+        ));
+    }
+
+    /// The rendered lines of `email`'s body, if it is the one rendered last.
+    pub fn rendered_lines(&self, email: &Email) -> Option<&[Spans<'static>]> {
+        self.rendered
+            .as_ref()
+            .filter(|rendered| rendered.account == email.account && rendered.id == email.id)
+            .map(|rendered| rendered.lines.as_slice())
+    }
+
+    /// Logs activity on the selected email when the activity log is enabled.
+    fn record_activity(&mut self, kind: ActivityKind) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        if let Err(e) = self.activity.record(kind, email) {
+            self.set_error_message(format!("Failed to record activity: {}", e));
+        }
+    }
+
+    /// Logs a reply to the email with `message_id`, if it is in the list.
+    fn record_reply(&mut self, message_id: &str) {
+        let message_id = message_id
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>');
+        let Some(email) = self
+            .emails
+            .iter()
+            .find(|email| email.message_id().as_deref() == Some(message_id))
+        else {
+            return;
+        };
+        if let Err(e) = self.activity.record(ActivityKind::Replied, email) {
+            self.set_error_message(format!("Failed to record activity: {}", e));
+        }
+    }
+
+    /// Persists the local state, reporting `success` or the failure in the status bar.
+    fn save_state(&mut self, success: String) {
+        match self.state.save() {
+            Ok(()) => self.set_status_message(success),
+            Err(e) => self.set_error_message(format!("Failed to save local state: {}", e)),
+        }
+    }
+
+    /// Shows or hides the replies of the selected conversation, selecting its first email.
+    fn toggle_thread(&mut self) {
+        match self
+            .threads
+            .toggle(&self.emails, &mut self.filtered_emails, self.selected_index)
+        {
+            Some(position) => self.select_index(position),
+            None => self.set_status_message("No other emails in this conversation".to_string()),
+        }
+    }
+
+    fn select_index(&mut self, index: usize) {
+        self.selected_index = index;
+        self.list_state.select(Some(index));
+    }
+
+    /// First line of the selected email shown in the content pane, kept per email
+    /// so going back to one shows where it was left.
+    pub fn body_scroll(&self) -> usize {
+        self.selected_email()
+            .and_then(|email| {
+                self.body_scrolls
+                    .get(&(email.account.clone(), email.id.clone()))
+            })
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Scrolls the selected email by `lines`, up when negative, within its body.
+    fn scroll_body(&mut self, lines: isize) {
+        let Some(key) = self
+            .selected_email()
+            .map(|email| (email.account.clone(), email.id.clone()))
+        else {
+            return;
+        };
+        let max = self.content_size.get().max_scroll();
+        let scroll = self
+            .body_scroll()
+            .min(max)
+            .saturating_add_signed(lines)
+            .min(max);
+        if scroll == 0 {
+            self.body_scrolls.remove(&key);
+        } else {
+            self.body_scrolls.insert(key, scroll);
+        }
+    }
+
+    /// Scrolls the open email to the next match of `/`, or the previous one.
+    fn find_next(&mut self, forward: bool) {
+        let scroll = self.body_scroll().min(self.content_size.get().max_scroll());
+        let rows = self.find_rows.borrow().clone();
+        let Some(find) = self.find.as_mut().filter(|find| find.is_confirmed()) else {
+            self.set_status_message("Press / to find in this email".to_string());
+            return;
+        };
+        let Some(row) = find.step(&rows, scroll, forward) else {
+            let message = format!("Not found in this email: {}", find.input.trim());
+            self.set_error_message(message);
+            return;
+        };
+        let message = format!("Match {} of {}", find.current.unwrap_or(0) + 1, rows.len());
+        self.scroll_body(row as isize - scroll as isize);
+        self.set_status_message(message);
+    }
+
+    /// Scrolls the selected email by a page, keeping a line of overlap.
+    fn scroll_body_page(&mut self, down: bool) {
+        let page = self.content_size.get().height.saturating_sub(1).max(1) as isize;
+        self.scroll_body(if down { page } else { -page });
+    }
+
+    /// Opens the compose form for a new message from the selected email's
+    /// account, or the first one.
+    fn open_compose(&mut self) {
+        if let Some(account) = self.current_account() {
+            self.start_compose(Compose::new(account));
+        }
+    }
+
+    /// Opens the compose popup with a reply to the selected email.
+    fn open_reply(&mut self, reply_all: bool) {
+        self.quote_selected(Quoting::Reply { reply_all });
+    }
+
+    /// Opens the compose popup quoting the selected email, or first fetches the
+    /// text body it quotes, after which `apply_body` opens it.
+    fn quote_selected(&mut self, quoting: Quoting) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        if email.body.loaded().next().is_none() {
+            self.quoting = Some((email.account.clone(), email.id.clone(), quoting));
+            self.load_selected_body(BodyFormat::Text);
+            return;
+        }
+        let compose = self.quoting_compose(email, quoting);
+        self.start_compose(compose);
+    }
+
+    /// Opens the compose popup that waited for the body of this email, unless
+    /// another popup was opened in the meantime.
+    fn finish_quoting(&mut self, account: &str, id: &str) {
+        let Some((_, _, quoting)) = self
+            .quoting
+            .take_if(|(quoted_account, quoted_id, _)| quoted_account == account && quoted_id == id)
+        else {
+            return;
+        };
+        if !self.no_popup_open() {
+            return;
+        }
+        let Some(email) = self
+            .emails
+            .iter()
+            .find(|email| email.account == account && email.id == id)
+        else {
+            return;
+        };
+        let compose = self.quoting_compose(email, quoting);
+        self.start_compose(compose);
+    }
+
+    fn quoting_compose(&self, email: &Email, quoting: Quoting) -> Compose {
+        match quoting {
+            Quoting::Reply { reply_all } => {
+                let own: Vec<String> = self
+                    .account_configs
+                    .values()
+                    .map(|config| config.email.clone())
+                    .collect();
+                Compose::reply(email, &self.quotable_text(email), reply_all, &own)
+            }
+            Quoting::Forward => Compose::forward(email, &self.quotable_text(email)),
+        }
+    }
+
+    /// Opens the compose popup forwarding the selected email with its attachments.
+    fn open_forward(&mut self) {
+        self.quote_selected(Quoting::Forward);
+    }
+
+    /// Shows the compose popup for `compose`, signed unless it is a saved draft,
+    /// with the body open in the editor first if `compose.use_editor` is set.
+    fn start_compose(&mut self, mut compose: Compose) {
+        if compose.kind != ComposeKind::Draft {
+            match self
+                .account_configs
+                .get(&compose.account)
+                .map(ExchangeConfig::signature)
+            {
+                Some(Ok(Some(signature))) => compose.sign(signature),
+                Some(Err(e)) => {
+                    self.set_error_message(format!("Failed to add the signature: {:#}", e))
+                }
+                _ => {}
+            }
+        }
+        self.composing = Some(compose);
+        self.input_mode = InputMode::Compose;
+        if self.compose.use_editor {
+            self.edit_body_in_editor();
+        }
+        self.save_local_draft();
+    }
+
+    /// Hands the terminal to the editor with the body being written, taking
+    /// back the text it saves.
+    fn edit_body_in_editor(&mut self) {
+        let Some(compose) = self.composing.as_mut() else {
+            return;
+        };
+        let command = self.compose.editor.as_deref();
+        let result = editor::edit(&mut self.input, command, &compose.form.body);
+        self.needs_clear = true;
+        match result {
+            Ok(body) => {
+                compose.form.body = body;
+                compose.form.field = MessageField::Body;
+            }
+            Err(e) => self.set_error_message(format!("Failed to open the editor: {}", e)),
+        }
+    }
+
+    /// The body of `email` as the content pane shows it: the rendered lines, or
+    /// the plain text before they are.
+    fn displayed_text(&self, email: &Email) -> String {
+        match self.rendered_lines(email) {
+            Some(lines) => lines
+                .iter()
+                .map(|line| {
+                    line.0
+                        .iter()
+                        .map(|span| span.content.as_ref())
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => email.body.text().to_string(),
+        }
+    }
+
+    /// The body of `email` to quote in a reply or forward: the plain text, or
+    /// the rendered lines when only another format was loaded.
+    fn quotable_text(&self, email: &Email) -> String {
+        if email.body.is_loaded(BodyFormat::Text) {
+            email.body.text().to_string()
+        } else {
+            self.displayed_text(email)
+        }
+    }
+
+    fn handle_compose_key(&mut self, key: KeyEvent) {
+        let Some(compose) = self.composing.as_mut() else {
+            self.close_popup();
+            return;
+        };
+        if let Some(path) = compose.attach_prompt.as_mut() {
+            match key.code {
+                KeyCode::Esc => compose.attach_prompt = None,
+                KeyCode::Tab => *path = file_prompt::complete(path),
+                KeyCode::Backspace => {
+                    path.pop();
+                }
+                KeyCode::Char(c) => path.push(c),
+                KeyCode::Enter => {
+                    let file = file_prompt::expand(path);
+                    if file.is_file() {
+                        compose.files.push(file);
+                        compose.attach_prompt = None;
+                        self.save_local_draft();
+                    } else {
+                        self.set_error_message(format!("Not a file: {}", file.display()));
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+        if !compose.completions.is_empty() {
+            let count = compose.completions.len();
+            match key.code {
+                KeyCode::Down => {
+                    compose.completion_index = (compose.completion_index + 1) % count;
+                    return;
+                }
+                KeyCode::Up => {
+                    compose.completion_index = (compose.completion_index + count - 1) % count;
+                    return;
+                }
+                KeyCode::Tab => {
+                    let address = compose.completions[compose.completion_index].clone();
+                    compose.form.complete(&address);
+                    compose.completions.clear();
+                    self.save_local_draft();
+                    return;
+                }
+                KeyCode::Esc => {
+                    compose.completions.clear();
+                    return;
+                }
+                _ => {}
+            }
+        }
+        match keymap::lookup(Context::Compose, None, &key) {
+            Some(action) => self.run_key_action(action, key),
+            None => {
+                compose.form.handle_key(key);
+                self.save_local_draft();
+                self.complete_address();
+            }
+        }
+    }
+
+    /// Carries out a binding of the compose window.
+    fn run_compose_action(&mut self, action: KeyAction) {
+        let Some(compose) = self.composing.as_mut() else {
+            return;
+        };
+        match action {
+            KeyAction::DiscardMessage => {
+                let message = compose.form.to_message();
+                if message.to.is_empty()
+                    && message.cc.is_empty()
+                    && message.subject.is_empty()
+                    && message.body.is_empty()
+                    && compose.files.is_empty()
+                {
+                    self.close_compose();
+                } else {
+                    self.ask_confirmation(
+                        "Discard this message?".to_string(),
+                        ConfirmAction::DiscardMessage,
+                    );
+                }
+            }
+            KeyAction::Send => {
+                let message = compose.to_message();
+                if message.to.is_empty() && message.cc.is_empty() {
+                    self.set_error_message("Add a recipient before sending".to_string());
+                    return;
+                }
+                let account = compose.account.clone();
+                if compose.kind == ComposeKind::ReplyAll {
+                    self.send_reply_all(&account, message);
+                } else {
+                    self.send_composed(&account, message);
+                }
+            }
+            KeyAction::ToggleSignature => {
+                if compose.signature.is_some() {
+                    compose.with_signature = !compose.with_signature;
+                    self.save_local_draft();
+                } else {
+                    let message = format!("No signature set up for {}", compose.account);
+                    self.set_status_message(message);
+                }
+            }
+            KeyAction::Attach => {
+                compose.attach_prompt = Some(String::new());
+            }
+            KeyAction::RemoveAttachment => {
+                // Local files were added last, so they go first
+                let removed = match compose.files.pop() {
+                    Some(path) => Some(path.display().to_string()),
+                    None => compose.attachments.pop().map(|attachment| attachment.name),
+                };
+                match removed {
+                    Some(name) => {
+                        self.set_status_message(format!("Removed {}", name));
+                        self.save_local_draft();
+                    }
+                    None => self.set_status_message("No attachments to remove".to_string()),
+                }
+            }
+            KeyAction::EditInEditor => {
+                self.edit_body_in_editor();
+                self.save_local_draft();
+            }
+            KeyAction::SaveDraft => {
+                self.save_draft();
+            }
+            _ => {}
+        }
+    }
+
+    /// Saves the message being written to the server's Drafts, closing the
+    /// compose popup and opening it again if the server refuses.
+    fn save_draft(&mut self) {
+        let Some(compose) = self.composing.take() else {
+            return;
+        };
+        let (account, message) = (compose.account.clone(), compose.to_message());
+        self.close_compose();
+        self.request(
+            &account,
+            Operation::Update,
+            |client| async move { client.save_draft(&message).await.map(|_| message) },
+            move |app, result| match result {
+                Ok(message) => {
+                    app.set_status_message(format!("Saved \"{}\" to Drafts", message.subject))
+                }
+                Err(e) => {
+                    app.set_error_message(format!("Failed to save the draft: {}", e));
+                    if app.composing.is_none() && app.no_popup_open() {
+                        app.composing = Some(compose);
+                        app.input_mode = InputMode::Compose;
+                    } else {
+                        // Kept on disk to come back the next time the app starts
+                        let draft = LocalDraft {
+                            account: compose.account.clone(),
+                            kind: compose.kind,
+                            message: compose.to_message(),
+                        };
+                        if let Err(e) = draft.save() {
+                            warn!("Failed to save the message being written: {:#}", e);
+                        }
+                        return;
+                    }
+                    app.save_local_draft();
+                }
+            },
+        );
+    }
+
+    /// Offers the known addresses matching the one being typed in To or Cc,
+    /// and looks it up in the directory on a local task. The directory's
+    /// matches arrive in `apply_directory_matches` through the event loop.
+    fn complete_address(&mut self) {
+        let Some(compose) = &self.composing else {
+            return;
+        };
+        let Some(prefix) = compose.form.address_prefix().map(str::to_string) else {
+            if let Some(compose) = self.composing.as_mut() {
+                compose.completions.clear();
+            }
+            return;
+        };
+        let account = compose.account.clone();
+        let known = self
+            .state
+            .contacts
+            .iter()
+            .chain(self.emails.iter().map(|email| &email.sender))
+            .map(String::as_str);
+        let completions = compose::completions(&prefix, known);
+        if let Some(compose) = self.composing.as_mut() {
+            compose.completions = completions;
+            compose.completion_index = 0;
+        }
+
+        if prefix.chars().count() < DIRECTORY_COMPLETION_CHARS {
+            return;
+        }
+        let Some(idx) = self.accounts.iter().position(|a| a.name == account) else {
+            return;
+        };
+        let accounts = Rc::clone(&self.accounts);
+        let limit = self.timeouts.get(Operation::ServerSearch);
+        let tx = self.actions_tx.clone();
+        tokio::task::spawn_local(async move {
+            let request = accounts[idx].client.search_directory(&prefix);
+            let result = loading::with_timeout(limit, request).await;
+            // The receiver only goes away when the app quits
+            let _ = tx.send(Action::DirectoryMatches { prefix, result });
+        });
+    }
+
+    /// Adds the directory's matches to the addresses offered, unless another
+    /// address is being typed by now. A failed lookup leaves the known ones.
+    fn apply_directory_matches(&mut self, prefix: &str, result: Result<Vec<DirectoryEntry>>) {
+        let Some(compose) = self.composing.as_mut() else {
+            return;
+        };
+        if compose.form.address_prefix() != Some(prefix) {
+            return;
+        }
+        let entries = match result {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("Directory lookup for completion failed: {:#}", e);
+                return;
+            }
+        };
+        let known = compose
+            .completions
+            .iter()
+            .map(String::as_str)
+            .chain(entries.iter().map(|entry| entry.email.as_str()));
+        let completions = compose::completions(prefix, known);
+        compose.completions = completions;
+    }
+
+    /// Keeps what is being written on disk, to bring it back if the app exits
+    /// before it is sent.
+    fn save_local_draft(&mut self) {
+        let Some(compose) = &self.composing else {
+            return;
+        };
+        let draft = LocalDraft {
+            account: compose.account.clone(),
+            kind: compose.kind,
+            message: compose.to_message(),
+        };
+        if let Err(e) = draft.save() {
+            warn!("Failed to save the message being written: {:#}", e);
+        }
+    }
+
+    /// Closes the compose popup, forgetting the message written in it.
+    fn close_compose(&mut self) {
+        self.composing = None;
+        if let Err(e) = LocalDraft::clear() {
+            warn!("Failed to remove the message being written: {:#}", e);
+        }
+        self.close_popup();
+    }
+
+    fn open_drafts(&mut self) {
+        let Some(account) = self.current_account() else {
+            return;
+        };
+        let owner = account.clone();
+        self.request(
+            &account,
+            Operation::ListSync,
+            |client| async move { client.fetch_drafts().await },
+            |app, result| match result {
+                Ok(drafts) if app.no_popup_open() => {
+                    app.drafts = Some(DraftList::new(owner, drafts));
+                    app.input_mode = InputMode::Drafts;
+                }
+                Ok(_) => {}
+                Err(e) => app.set_error_message(format!("Failed to load drafts: {}", e)),
+            },
+        );
+    }
+
+    fn handle_drafts_key(&mut self, key: KeyEvent) {
+        let Some(drafts) = self.drafts.as_mut() else {
+            self.close_popup();
+            return;
+        };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.drafts = None;
+                self.close_popup();
+            }
+            KeyCode::Char('j') | KeyCode::Down => drafts.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => drafts.select_previous(),
+            KeyCode::Enter => self.resume_selected_draft(),
+            KeyCode::Char('d') => {
+                if let Some(draft) = drafts.selected_draft() {
+                    let prompt = format!("Delete draft '{}'?", draft.subject);
+                    self.ask_confirmation(prompt, ConfirmAction::DeleteDraft);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Opens the selected draft in the compose popup, loading its body first.
+    fn resume_selected_draft(&mut self) {
+        let Some(draft) = self
+            .drafts
+            .as_ref()
+            .and_then(|drafts| drafts.selected_draft())
+            .cloned()
+        else {
+            return;
+        };
+        if draft.body.is_loaded(BodyFormat::Text) {
+            let text = draft.body.text().to_string();
+            return self.resume_draft(&draft, Ok(text));
+        }
+        let (account, id) = (draft.account.clone(), draft.id.clone());
+        self.request(
+            &account,
+            Operation::BodyFetch,
+            |client| async move { client.fetch_body(&id, BodyFormat::Text).await },
+            move |app, text| {
+                // Unless the drafts were closed while the body loaded
+                if matches!(app.input_mode, InputMode::Drafts) {
+                    app.resume_draft(&draft, text);
+                }
+            },
+        );
+    }
+
+    /// Opens `draft` with the body `text` in the compose popup.
+    fn resume_draft(&mut self, draft: &Email, text: Result<String>) {
+        match text {
+            Ok(text) => {
+                self.drafts = None;
+                self.start_compose(Compose::from_draft(draft, &text));
+            }
+            Err(e) => self.set_error_message(format!("Failed to load the draft: {}", e)),
+        }
+    }
+
+    fn delete_selected_draft(&mut self) {
+        let Some(drafts) = self.drafts.as_mut() else {
+            return;
+        };
+        let Some(draft) = drafts.remove_selected() else {
+            return;
+        };
+        let (account, id) = (draft.account.clone(), draft.id.clone());
+        self.request(
+            &account,
+            Operation::Update,
+            |client| async move { client.delete_emails(&[id]).await },
+            move |app, result| match result {
+                Ok(()) => app.set_status_message(format!("Deleted draft '{}'", draft.subject)),
+                Err(e) => {
+                    if let Some(drafts) = app.drafts.as_mut() {
+                        drafts
+                            .drafts
+                            .insert(drafts.selected.min(drafts.drafts.len()), draft);
+                    }
+                    app.set_error_message(format!("Failed to delete the draft: {}", e));
+                }
+            },
+        );
+    }
+
+    /// Queues a message in the outbox and starts sending it, so it is kept until
+    /// the server accepts it.
+    pub fn send(&mut self, account: &str, message: OutgoingEmail) {
+        let id = self.outbox.queue(account, message);
+        self.save_outbox();
+        self.send_from_outbox(id);
+    }
+
+    /// Closes the compose popup and sends what was written in it.
+    fn send_composed(&mut self, account: &str, message: OutgoingEmail) {
+        self.close_compose();
+        self.send(account, message);
+    }
+
+    /// Sends a reply to all, first asking to confirm when it goes to more people
+    /// than `compose.reply_all_limit` or to a distribution list.
+    pub fn send_reply_all(&mut self, account: &str, message: OutgoingEmail) {
+        match compose::reply_all_warning(&message, &self.compose) {
+            Some(prompt) => self.ask_confirmation(
+                prompt,
+                ConfirmAction::SendReplyAll {
+                    account: account.to_string(),
+                    message,
+                },
+            ),
+            None => self.send_composed(account, message),
+        }
+    }
+
+    /// Starts sending a queued or failed message on a local task. The result
+    /// arrives in `apply_send` through the event loop.
+    fn send_from_outbox(&mut self, id: u64) {
+        let Some(entry) = self.outbox.get_mut(id) else {
+            return;
+        };
+        if entry.state == SendState::Sending {
+            return;
+        }
+        let Some(idx) = self.accounts.iter().position(|a| a.name == entry.account) else {
+            entry.state = SendState::Failed {
+                error: format!("Unknown account {}", entry.account),
+            };
+            self.save_outbox();
+            return;
+        };
+        entry.state = SendState::Sending;
+        let message = entry.message.clone();
+        self.save_outbox();
+        self.loading.start(Operation::Send);
+
+        let accounts = Rc::clone(&self.accounts);
+        let limit = self.timeouts.get(Operation::Send);
+        let tx = self.actions_tx.clone();
+        info!(id, "Sending from the outbox");
+        let span = info_span!("send", id);
+        tokio::task::spawn_local(
+            async move {
+                let result =
+                    loading::with_timeout(limit, accounts[idx].client.send_email(&message)).await;
+                // The receiver only goes away when the app quits
+                let _ = tx.send(Action::Sent { id, result });
+            }
+            .instrument(span),
+        );
+    }
+
+    /// Drops a sent message from the outbox, or keeps it with the error.
+    fn apply_send(&mut self, id: u64, result: Result<()>) {
+        let Some(entry) = self.outbox.get_mut(id) else {
+            return;
+        };
+        let subject = entry.message.subject.clone();
+        let in_reply_to = entry.message.in_reply_to.clone();
+        match result {
+            Ok(()) => {
+                self.outbox.remove(id);
+                self.set_status_message(format!("Sent \"{}\"", subject));
+                if let Some(message_id) = in_reply_to {
+                    self.record_reply(&message_id);
+                }
+            }
+            Err(e) => {
+                entry.state = SendState::Failed {
+                    error: format!("{:#}", e),
+                };
+                self.set_error_message(format!(
+                    "Failed to send \"{}\", it is kept in the outbox (P): {}",
+                    subject, e
+                ));
+            }
+        }
+        if !self
+            .outbox
+            .entries
+            .iter()
+            .any(|e| e.state == SendState::Sending)
+        {
+            self.loading.finish(Operation::Send);
+        }
+        if let Some(view) = self.outbox_view.as_mut() {
+            view.clamp(&self.outbox);
+        }
+        self.save_outbox();
+    }
+
+    fn save_outbox(&mut self) {
+        if let Err(e) = self.outbox.save() {
+            self.set_error_message(format!("Failed to save the outbox: {:#}", e));
+        }
+    }
+
+    fn open_outbox(&mut self) {
+        self.outbox_view = Some(OutboxView::default());
+        self.input_mode = InputMode::Outbox;
+    }
+
+    fn handle_outbox_key(&mut self, key: KeyEvent) {
+        let Some(view) = self.outbox_view.as_mut() else {
+            self.close_popup();
+            return;
+        };
+        let selected = view.selected_id(&self.outbox);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.outbox_view = None;
+                self.close_popup();
+            }
+            KeyCode::Char('j') | KeyCode::Down => view.select_next(&self.outbox),
+            KeyCode::Char('k') | KeyCode::Up => view.select_previous(&self.outbox),
+            KeyCode::Enter | KeyCode::Char('r') => {
+                if let Some(id) = selected {
+                    self.send_from_outbox(id);
+                }
+            }
+            KeyCode::Char('f') => {
+                let unsent = self.outbox.unsent();
+                if unsent.is_empty() {
+                    self.set_status_message("Nothing to send".to_string());
+                }
+                for id in unsent {
+                    self.send_from_outbox(id);
+                }
+            }
+            KeyCode::Char('e') => {
+                let Some(entry) = selected.and_then(|id| self.outbox.get(id)) else {
+                    return;
+                };
+                if entry.state == SendState::Sending {
+                    self.set_status_message("The message is being sent".to_string());
+                    return;
+                }
+                view.edit = Some(MessageEdit::new(entry.id, &entry.message));
+                self.input_mode = InputMode::OutboxEdit;
+            }
+            KeyCode::Char('d') => {
+                let Some(entry) = selected.and_then(|id| self.outbox.get(id)) else {
+                    return;
+                };
+                if entry.state == SendState::Sending {
+                    self.set_status_message("The message is being sent".to_string());
+                    return;
+                }
+                let prompt = format!("Discard \"{}\" without sending it?", entry.message.subject);
+                let action = ConfirmAction::DiscardOutboxMessage(entry.id);
+                self.ask_confirmation(prompt, action);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_outbox_edit_key(&mut self, key: KeyEvent) {
+        let Some(edit) = self
+            .outbox_view
+            .as_mut()
+            .and_then(|view| view.edit.as_mut())
+        else {
+            self.close_popup();
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => {
+                if let Some(view) = self.outbox_view.as_mut() {
+                    view.edit = None;
+                }
+                self.close_popup();
+            }
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let (id, message) = (edit.id, edit.to_message());
+                if let Some(view) = self.outbox_view.as_mut() {
+                    view.edit = None;
+                }
+                self.close_popup();
+                let Some(entry) = self.outbox.get_mut(id) else {
+                    return;
+                };
+                entry.message = message;
+                entry.state = SendState::Queued;
+                self.send_from_outbox(id);
+            }
+            _ => edit.form.handle_key(key),
+        }
+    }
+
+    /// Saves every attachment of the selected email into its download directory.
+    fn save_attachments(&mut self) {
+        let Some(email) = self.selected_email().cloned() else {
+            return;
+        };
+        if email.attachments.is_empty() {
+            self.set_status_message("No attachments to save".to_string());
+            return;
+        }
+        let directory = match downloads::directory_for(&self.downloads, &self.groups, &email) {
+            Ok(directory) => directory,
+            Err(e) => {
+                self.set_error_message(format!("Failed to save attachments: {:#}", e));
+                return;
+            }
+        };
+        let Ok(client) = self.account_client(&email.account) else {
+            return;
+        };
+
+        self.loading.start(Operation::AttachmentDownload);
+        let limit = self.timeouts.get(Operation::AttachmentDownload);
+        let count = email.attachments.len();
+        let request = async move {
+            let mut saved = 0;
+            let mut errors = Vec::new();
+            for attachment in &email.attachments {
+                let result = loading::with_timeout(
+                    limit,
+                    client.fetch_attachment(&email.id, &attachment.id),
+                )
+                .await
+                .and_then(|content| downloads::save(&directory, &attachment.name, &content));
+                match result {
+                    Ok(_) => saved += 1,
+                    Err(e) => errors.push(format!("{}: {:#}", attachment.name, e)),
+                }
+            }
+            (directory, saved, errors)
+        };
+        self.spawn_request(request, move |app, (directory, saved, errors)| {
+            app.loading.finish(Operation::AttachmentDownload);
+            if errors.is_empty() {
+                app.set_status_message(format!(
+                    "Saved {} attachment(s) to {}",
+                    saved,
+                    directory.display()
+                ));
+            } else {
+                app.set_error_message(format!(
+                    "Saved {} of {} attachment(s), failed {}",
+                    saved,
+                    count,
+                    errors.join("; ")
+                ));
+            }
+        });
+    }
+
+    /// Opens the selected email's HTML in the browser, with the inline images
+    /// it shows downloaded next to it.
+    fn open_in_browser(&mut self) {
+        let Some(email) = self.selected_email().cloned() else {
+            return;
+        };
+        let Ok(client) = self.account_client(&email.account) else {
+            return;
+        };
+        let body_limit = self.timeouts.get(Operation::BodyFetch);
+        let attachment_limit = self.timeouts.get(Operation::AttachmentDownload);
+
+        self.loading.start(Operation::BodyFetch);
+        let subject = email.subject.clone();
+        let request = async move {
+            let html = match email.body.get(BodyFormat::Html) {
+                Some(html) => html.to_string(),
+                None => {
+                    let request = client.fetch_body(&email.id, BodyFormat::Html);
+                    loading::with_timeout(body_limit, request).await?
+                }
+            };
+            let mut images = Vec::new();
+            for image in browser::inline_images(&html, &email.attachments) {
+                let request = client.fetch_attachment(&email.id, &image.id);
+                images.push((
+                    image,
+                    loading::with_timeout(attachment_limit, request).await?,
+                ));
+            }
+            let page = browser::write_page(&email.id, &html, &images)?;
+            browser::open(&page.display().to_string())
+        };
+        self.spawn_request(request, move |app, result: Result<()>| {
+            app.loading.finish(Operation::BodyFetch);
+            match result {
+                Ok(()) => app.set_status_message(format!("Opened \"{}\" in the browser", subject)),
+                Err(e) => app.set_error_message(format!("Failed to open the browser: {:#}", e)),
+            }
+        });
+    }
+
+    fn open_body_in_pager(&mut self) {
+        let Some(email) = self.selected_email() else {
+            return;
+        };
+        let text = email.body.text().to_string();
+        let result = pager::show(&mut self.input, &text);
+        self.needs_clear = true;
+        if let Err(e) = result {
+            self.set_error_message(format!("Failed to open the pager: {}", e));
+        }
+    }
+
+    fn select_next(&mut self) {
+        let len = self.filtered_emails.len();
+        if len > 0 {
+            self.select_index((self.selected_index + 1) % len);
+        }
+    }
+
+    fn select_previous(&mut self) {
+        let len = self.filtered_emails.len();
+        if len > 0 {
+            self.select_index(if self.selected_index > 0 {
+                self.selected_index - 1
+            } else {
+                len - 1
+            });
+        }
+    }
+
+    fn select_first(&mut self) {
+        if !self.filtered_emails.is_empty() {
+            self.select_index(0);
+        }
+    }
+
+    fn select_last(&mut self) {
+        if !self.filtered_emails.is_empty() {
+            self.select_index(self.filtered_emails.len() - 1);
+        }
     }
 
     pub fn selected_email(&self) -> Option<&Email> {
@@ -302,7 +5893,7 @@ impl<T: EmailClient> App<T> {
         }
     }
 
-    pub fn is_loading(&self) -> bool {
-        self.loading
+    pub fn is_loading(&self, operation: Operation) -> bool {
+        self.loading.is_loading(operation)
     }
 }
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::email::Attachment;
+
+/// Opens `target`, a URL or file, in `$BROWSER`, or else the system's default
+/// handler, without waiting for it to close.
+pub fn open(target: &str) -> Result<()> {
+    let browser = std::env::var("BROWSER")
+        .ok()
+        .filter(|browser| !browser.trim().is_empty());
+    let mut command = match &browser {
+        Some(browser) => {
+            let mut parts = browser.split_whitespace();
+            let mut command = Command::new(parts.next().unwrap_or_default());
+            command.args(parts);
+            command
+        }
+        None if cfg!(target_os = "macos") => Command::new("open"),
+        None if cfg!(windows) => {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        }
+        None => Command::new("xdg-open"),
+    };
+    command
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to open {}", target))?;
+    Ok(())
+}
+
+/// Writes `html` to a page of its own, with `images`, the inline images it
+/// shows and their content, next to it, and returns the page's path.
+pub fn write_page(
+    email_id: &str,
+    html: &str,
+    images: &[(&Attachment, Vec<u8>)],
+) -> Result<PathBuf> {
+    let dir = page_dir(email_id)?;
+    let mut saved = Vec::with_capacity(images.len());
+    for (image, content) in images {
+        let extension = image
+            .name
+            .rsplit_once('.')
+            .map_or("", |(_, extension)| extension);
+        let path = dir.join(format!("{}.{}", file_name(&image.id), file_name(extension)));
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        saved.push((*image, path));
+    }
+    let page = dir.join("message.html");
+    fs::write(&page, resolve_inline_images(html, &saved))
+        .with_context(|| format!("Failed to write {}", page.display()))?;
+    Ok(page)
+}
+
+/// Writes an attachment to open next to the email's page and returns its path.
+pub fn write_attachment(
+    email_id: &str,
+    attachment: &Attachment,
+    content: &[u8],
+) -> Result<PathBuf> {
+    let name = match attachment.name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}.{}", file_name(stem), file_name(extension)),
+        None => file_name(&attachment.name),
+    };
+    let path = page_dir(email_id)?.join(name);
+    fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+fn page_dir(email_id: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir()
+        .join("mail-tui")
+        .join(file_name(email_id));
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// `text` with anything but ASCII letters, digits and dashes replaced, to name a file.
+fn file_name(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The attachments `html` shows inline through `cid:` references, which name
+/// them by id, by file name, or by file name followed by `@` and a domain.
+pub fn inline_images<'a>(html: &str, attachments: &'a [Attachment]) -> Vec<&'a Attachment> {
+    attachments
+        .iter()
+        .filter(|attachment| content_ids(html).any(|cid| refers_to(cid, attachment)))
+        .collect()
+}
+
+/// `html` with its `cid:` references pointing at the saved copies in `images`.
+fn resolve_inline_images(html: &str, images: &[(&Attachment, PathBuf)]) -> String {
+    let mut resolved = html.to_string();
+    for cid in content_ids(html) {
+        if let Some((_, path)) = images.iter().find(|(image, _)| refers_to(cid, image)) {
+            resolved = resolved.replace(&format!("cid:{}", cid), &file_url(path));
+        }
+    }
+    resolved
+}
+
+fn content_ids(html: &str) -> impl Iterator<Item = &str> {
+    html.split("cid:").skip(1).filter_map(|rest| {
+        let end = rest.find(['"', '\'', ')', ' ', '>'])?;
+        Some(&rest[..end])
+    })
+}
+
+fn refers_to(cid: &str, attachment: &Attachment) -> bool {
+    let name = cid.split('@').next().unwrap_or_default();
+    cid == attachment.id || cid == attachment.name || name == attachment.name
+}
+
+fn file_url(path: &Path) -> String {
+    format!("file://{}", path.display()).replace(' ', "%20")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_images_point_at_saved_copies() {
+        let attachment = |id: &str, name: &str| Attachment {
+            id: id.to_string(),
+            name: name.to_string(),
+            content_type: "image/png".to_string(),
+            size: 1_024,
+        };
+        let attachments = [
+            attachment("1-1", "logo.png"),
+            attachment("1-2", "chart.png"),
+            attachment("1-3", "report.pdf"),
+        ];
+        let html = r#"<img src="cid:logo.png@01DA2B"><img src='cid:1-2'>"#;
+
+        let images = inline_images(html, &attachments);
+        assert_eq!(images.len(), 2);
+
+        let saved: Vec<(&Attachment, PathBuf)> = images
+            .into_iter()
+            .map(|image| (image, PathBuf::from("/tmp/page").join(&image.name)))
+            .collect();
+        assert_eq!(
+            resolve_inline_images(html, &saved),
+            r#"<img src="file:///tmp/page/logo.png"><img src='file:///tmp/page/chart.png'>"#
+        );
+    }
+}
@@ -0,0 +1,12 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::io::{self, Write};
+
+/// Copies text to the system clipboard using the OSC 52 terminal escape sequence,
+/// which also works over SSH as long as the terminal supports it.
+pub fn copy(text: &str) -> Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use anyhow::{bail, Result};
+
+/// Names of the commands typed after `:`, completed and matched by prefix.
+const NAMES: [&str; 10] = [
+    "archive",
+    "delete",
+    "folder",
+    "help",
+    "metrics",
+    "move",
+    "quit",
+    "refresh",
+    "save-search",
+    "search",
+];
+
+/// A command typed after `:`.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Archive,
+    Delete,
+    /// Show the folder named so, or the inbox
+    Folder(String),
+    Help,
+    Metrics,
+    /// Move the selected email to the folder named so
+    Move(String),
+    Quit,
+    Refresh,
+    /// Keep the current search under the name given, to run again from `'`
+    SaveSearch(String),
+    /// Narrow the list like `/`, on the server for a `!query`
+    Search(String),
+}
+
+impl Command {
+    /// Parses `input`, whose command can be shortened as long as only one
+    /// starts that way, like `:fo Sent` or `:q`.
+    pub fn parse(input: &str) -> Result<Self> {
+        let (name, argument) = match input.trim().split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (input.trim(), ""),
+        };
+        let name = full_name(name)?;
+        let needs_argument = |what: &str| -> Result<String> {
+            if argument.is_empty() {
+                bail!(":{} needs {}", name, what);
+            }
+            Ok(argument.to_string())
+        };
+        Ok(match name {
+            "archive" => Command::Archive,
+            "delete" => Command::Delete,
+            "folder" => Command::Folder(needs_argument("a folder name")?),
+            "help" => Command::Help,
+            "metrics" => Command::Metrics,
+            "move" => Command::Move(needs_argument("a folder name")?),
+            "quit" => Command::Quit,
+            "refresh" => Command::Refresh,
+            "save-search" => Command::SaveSearch(needs_argument("a name")?),
+            _ => Command::Search(needs_argument("a query")?),
+        })
+    }
+}
+
+fn full_name(name: &str) -> Result<&'static str> {
+    if let Some(full) = NAMES.into_iter().find(|full| *full == name) {
+        return Ok(full);
+    }
+    let matches: Vec<&str> = NAMES
+        .into_iter()
+        .filter(|full| full.starts_with(name))
+        .collect();
+    match matches.as_slice() {
+        [] => bail!("Unknown command: {}", name),
+        [full] => Ok(full),
+        _ => bail!("Ambiguous command {}: {}", name, matches.join(", ")),
+    }
+}
+
+/// Whether the argument of the command being typed in `input` is a folder name.
+pub fn takes_folder(input: &str) -> bool {
+    match input.trim_start().split_once(char::is_whitespace) {
+        Some((name, _)) => matches!(full_name(name), Ok("folder" | "move")),
+        None => false,
+    }
+}
+
+/// Completes the command name being typed in `input`, or the folder name after
+/// `:folder` and `:move` from `folders`, as far as the candidates agree.
+pub fn complete(input: &str, folders: &[String]) -> String {
+    let Some((name, argument)) = input.trim_start().split_once(char::is_whitespace) else {
+        let candidates: Vec<&str> = NAMES
+            .into_iter()
+            .filter(|full| full.starts_with(input.trim_start()))
+            .collect();
+        return match candidates.as_slice() {
+            [single] => format!("{} ", single),
+            _ => common_prefix(&candidates).unwrap_or(input).to_string(),
+        };
+    };
+    if !takes_folder(input) {
+        return input.to_string();
+    }
+    let typed = argument.trim_start().to_lowercase();
+    let candidates: Vec<&str> = folders
+        .iter()
+        .map(String::as_str)
+        .filter(|folder| folder.to_lowercase().starts_with(&typed))
+        .collect();
+    match candidates.as_slice() {
+        [] => input.to_string(),
+        [single] => format!("{} {}", name, single),
+        _ => match common_prefix(&candidates) {
+            Some(common) if common.len() > typed.len() => format!("{} {}", name, common),
+            _ => input.to_string(),
+        },
+    }
+}
+
+/// The longest start all of `words` share, if there are any.
+fn common_prefix<'a>(words: &[&'a str]) -> Option<&'a str> {
+    let (first, rest) = words.split_first()?;
+    let mut common = first.len();
+    for other in rest {
+        common = first
+            .char_indices()
+            .zip(other.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((idx, a), _)| idx + a.len_utf8())
+            .min(common);
+    }
+    Some(&first[..common])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_complete_commands() {
+        assert_eq!(Command::parse("q").unwrap(), Command::Quit);
+        assert_eq!(
+            Command::parse("fo  Sent Items").unwrap(),
+            Command::Folder("Sent Items".to_string())
+        );
+        assert_eq!(
+            Command::parse("search from:alice").unwrap(),
+            Command::Search("from:alice".to_string())
+        );
+        assert_eq!(
+            Command::parse("sa quarterly-reports").unwrap(),
+            Command::SaveSearch("quarterly-reports".to_string())
+        );
+        assert!(Command::parse("s report").is_err());
+        assert!(Command::parse("m Archive").is_err());
+        assert!(Command::parse("move").is_err());
+
+        let folders = vec![
+            "Archive".to_string(),
+            "Sent Items".to_string(),
+            "Sent Receipts".to_string(),
+        ];
+        assert_eq!(complete("ar", &folders), "archive ");
+        assert_eq!(complete("m", &folders), "m");
+        assert_eq!(complete("move ar", &folders), "move Archive");
+        assert_eq!(complete("folder s", &folders), "folder Sent ");
+        assert_eq!(complete("search s", &folders), "search s");
+    }
+}
@@ -0,0 +1,564 @@
+use std::path::PathBuf;
+
+use chrono::Local;
+use crossterm::event::{KeyCode, KeyEvent};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ComposeConfig;
+use crate::email::{Attachment, Email, OutgoingEmail};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MessageField {
+    To,
+    Cc,
+    Subject,
+    Body,
+}
+
+impl MessageField {
+    pub const ALL: [MessageField; 4] = [
+        MessageField::To,
+        MessageField::Cc,
+        MessageField::Subject,
+        MessageField::Body,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MessageField::To => "To",
+            MessageField::Cc => "Cc",
+            MessageField::Subject => "Subject",
+            MessageField::Body => "Body",
+        }
+    }
+}
+
+/// The fields of a message being written, with the addresses comma-separated.
+pub struct MessageForm {
+    pub to: String,
+    pub cc: String,
+    pub subject: String,
+    pub body: String,
+    pub field: MessageField,
+}
+
+impl Default for MessageForm {
+    fn default() -> Self {
+        Self {
+            to: String::new(),
+            cc: String::new(),
+            subject: String::new(),
+            body: String::new(),
+            field: MessageField::To,
+        }
+    }
+}
+
+impl MessageForm {
+    pub fn from_message(message: &OutgoingEmail) -> Self {
+        Self {
+            to: message.to.join(", "),
+            cc: message.cc.join(", "),
+            subject: message.subject.clone(),
+            body: message.body.clone(),
+            field: MessageField::To,
+        }
+    }
+
+    pub fn to_message(&self) -> OutgoingEmail {
+        OutgoingEmail {
+            to: parse_addresses(&self.to),
+            cc: parse_addresses(&self.cc),
+            bcc: Vec::new(),
+            subject: self.subject.clone(),
+            body: self.body.clone(),
+            ..OutgoingEmail::default()
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        let idx = MessageField::ALL
+            .iter()
+            .position(|f| *f == self.field)
+            .unwrap_or(0);
+        self.field = MessageField::ALL[(idx + 1) % MessageField::ALL.len()];
+    }
+
+    pub fn previous_field(&mut self) {
+        let idx = MessageField::ALL
+            .iter()
+            .position(|f| *f == self.field)
+            .unwrap_or(0);
+        self.field =
+            MessageField::ALL[(idx + MessageField::ALL.len() - 1) % MessageField::ALL.len()];
+    }
+
+    pub fn text(&self, field: MessageField) -> &str {
+        match field {
+            MessageField::To => &self.to,
+            MessageField::Cc => &self.cc,
+            MessageField::Subject => &self.subject,
+            MessageField::Body => &self.body,
+        }
+    }
+
+    pub fn focused_text_mut(&mut self) -> &mut String {
+        match self.field {
+            MessageField::To => &mut self.to,
+            MessageField::Cc => &mut self.cc,
+            MessageField::Subject => &mut self.subject,
+            MessageField::Body => &mut self.body,
+        }
+    }
+
+    /// The part of the address being typed in the To or Cc field, after the
+    /// last comma. `None` in other fields or right after a comma.
+    pub fn address_prefix(&self) -> Option<&str> {
+        let text = match self.field {
+            MessageField::To => &self.to,
+            MessageField::Cc => &self.cc,
+            _ => return None,
+        };
+        let prefix = text.rsplit(',').next().unwrap_or_default().trim_start();
+        (!prefix.is_empty()).then_some(prefix)
+    }
+
+    /// Replaces the address being typed with `address`, ready for the next one.
+    pub fn complete(&mut self, address: &str) {
+        let Some(prefix) = self.address_prefix() else {
+            return;
+        };
+        let keep = self.text(self.field).len() - prefix.len();
+        let text = self.focused_text_mut();
+        text.truncate(keep);
+        text.push_str(address);
+        text.push_str(", ");
+    }
+
+    /// Types into the focused field, Tab and Enter moving to the next one
+    /// except in the body, where Enter starts a new line.
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter if self.field == MessageField::Body => self.body.push('\n'),
+            KeyCode::Tab | KeyCode::Enter => self.next_field(),
+            KeyCode::BackTab => self.previous_field(),
+            KeyCode::Char(c) => self.focused_text_mut().push(c),
+            KeyCode::Backspace => {
+                self.focused_text_mut().pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComposeKind {
+    New,
+    Reply,
+    /// Confirmed before going to many people
+    ReplyAll,
+    Forward,
+    /// Resumed from the Drafts folder
+    Draft,
+}
+
+impl ComposeKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            ComposeKind::New => "New Message",
+            ComposeKind::Reply => "Reply",
+            ComposeKind::ReplyAll => "Reply to All",
+            ComposeKind::Forward => "Forward",
+            ComposeKind::Draft => "Draft",
+        }
+    }
+}
+
+/// A new message being written, sent from `account`.
+pub struct Compose {
+    pub account: String,
+    pub form: MessageForm,
+    pub kind: ComposeKind,
+    /// Attachments of a forwarded email, sent along
+    pub attachments: Vec<Attachment>,
+    /// Local files uploaded with the message
+    pub files: Vec<PathBuf>,
+    /// Path being typed in the attach prompt while it is open
+    pub attach_prompt: Option<String>,
+    in_reply_to: Option<String>,
+    references: Vec<String>,
+    /// The draft in the Drafts folder this continues, if any
+    draft_id: Option<String>,
+    /// The account's signature, added below the body when sent
+    pub signature: Option<String>,
+    /// Whether the signature goes out, toggled while writing
+    pub with_signature: bool,
+    /// Addresses offered for the one being typed in To or Cc
+    pub completions: Vec<String>,
+    /// Position in `completions`
+    pub completion_index: usize,
+}
+
+impl Compose {
+    pub fn new(account: String) -> Self {
+        Self {
+            account,
+            form: MessageForm::default(),
+            kind: ComposeKind::New,
+            attachments: Vec::new(),
+            files: Vec::new(),
+            attach_prompt: None,
+            in_reply_to: None,
+            references: Vec::new(),
+            draft_id: None,
+            signature: None,
+            with_signature: false,
+            completions: Vec::new(),
+            completion_index: 0,
+        }
+    }
+
+    /// Goes on writing `message`, as it was saved locally or to Drafts.
+    pub fn from_message(account: String, kind: ComposeKind, message: &OutgoingEmail) -> Self {
+        Self {
+            account,
+            form: MessageForm::from_message(message),
+            kind,
+            attachments: message.attachments.clone(),
+            files: message.files.clone(),
+            attach_prompt: None,
+            in_reply_to: message.in_reply_to.clone(),
+            references: message.references.clone(),
+            draft_id: message.draft_id.clone(),
+            // A saved message has its signature in the body already
+            signature: None,
+            with_signature: false,
+            completions: Vec::new(),
+            completion_index: 0,
+        }
+    }
+
+    /// Goes on writing a draft from the Drafts folder, whose body is `text`.
+    pub fn from_draft(draft: &Email, text: &str) -> Self {
+        let message = OutgoingEmail {
+            to: draft.to.clone(),
+            cc: draft.cc.clone(),
+            subject: draft.subject.clone(),
+            body: text.to_string(),
+            attachments: draft.attachments.clone(),
+            // A reply's references end with the email it replies to
+            in_reply_to: draft.thread.references.last().cloned(),
+            references: draft.thread.references.clone(),
+            draft_id: Some(draft.id.clone()),
+            ..OutgoingEmail::default()
+        };
+        Self::from_message(draft.account.clone(), ComposeKind::Draft, &message)
+    }
+
+    /// A reply to `email` quoting `text`, its body, sent to the sender or, with
+    /// `reply_all`, to everyone on it but the addresses in `own`.
+    pub fn reply(email: &Email, text: &str, reply_all: bool, own: &[String]) -> Self {
+        let is_own = |address: &String| own.iter().any(|a| a.eq_ignore_ascii_case(address));
+        let mut to = vec![email.sender.clone()];
+        let mut cc = Vec::new();
+        if reply_all {
+            for address in &email.to {
+                push_address(&mut to, address);
+            }
+            to.retain(|address| !is_own(address));
+            if to.is_empty() {
+                // A reply to one of our own messages goes back to its recipients
+                to.push(email.sender.clone());
+            }
+            for address in email.cc.iter().filter(|address| !is_own(address)) {
+                if !to.iter().any(|a| a.eq_ignore_ascii_case(address)) {
+                    push_address(&mut cc, address);
+                }
+            }
+        }
+
+        let mut body = format!(
+            "\n\nOn {}, {} wrote:\n",
+            email.date.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            email.sender
+        );
+        body.push_str(&quote(text));
+
+        let mut references = email.thread.references.clone();
+        references.extend(email.thread.message_id.clone());
+        Self {
+            account: email.account.clone(),
+            form: MessageForm {
+                to: to.join(", "),
+                cc: cc.join(", "),
+                subject: prefixed_subject("Re:", &["re:"], &email.subject),
+                body,
+                field: MessageField::Body,
+            },
+            kind: if reply_all {
+                ComposeKind::ReplyAll
+            } else {
+                ComposeKind::Reply
+            },
+            attachments: Vec::new(),
+            files: Vec::new(),
+            attach_prompt: None,
+            in_reply_to: email.thread.message_id.clone(),
+            references,
+            draft_id: None,
+            signature: None,
+            with_signature: false,
+            completions: Vec::new(),
+            completion_index: 0,
+        }
+    }
+
+    /// `email` forwarded with its headers, `text` and its attachments, waiting
+    /// for the recipients.
+    pub fn forward(email: &Email, text: &str) -> Self {
+        let mut body = format!(
+            "\n\n---------- Forwarded message ----------\n\
+             From: {}\nDate: {}\nSubject: {}\nTo: {}\n",
+            email.sender,
+            email.date.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            email.subject,
+            email.to.join(", ")
+        );
+        if !email.cc.is_empty() {
+            body.push_str(&format!("Cc: {}\n", email.cc.join(", ")));
+        }
+        body.push('\n');
+        body.push_str(&quote(text));
+
+        Self {
+            account: email.account.clone(),
+            form: MessageForm {
+                subject: prefixed_subject("Fw:", &["fw:", "fwd:"], &email.subject),
+                body,
+                ..MessageForm::default()
+            },
+            kind: ComposeKind::Forward,
+            attachments: email.attachments.clone(),
+            files: Vec::new(),
+            attach_prompt: None,
+            in_reply_to: None,
+            references: Vec::new(),
+            draft_id: None,
+            signature: None,
+            with_signature: false,
+            completions: Vec::new(),
+            completion_index: 0,
+        }
+    }
+
+    /// Adds `signature` below what is written, which the sender can leave out.
+    pub fn sign(&mut self, signature: String) {
+        self.signature = Some(signature);
+        self.with_signature = true;
+    }
+
+    pub fn to_message(&self) -> OutgoingEmail {
+        let mut message = OutgoingEmail {
+            attachments: self.attachments.clone(),
+            files: self.files.clone(),
+            in_reply_to: self.in_reply_to.clone(),
+            references: self.references.clone(),
+            draft_id: self.draft_id.clone(),
+            ..self.form.to_message()
+        };
+        if let Some(signature) = self.signature.as_ref().filter(|_| self.with_signature) {
+            let body = message.body.trim_end();
+            message.body = if body.is_empty() {
+                format!("\n\n-- \n{}\n", signature)
+            } else {
+                format!("{}\n\n-- \n{}\n", body, signature)
+            };
+        }
+        message
+    }
+}
+
+/// `text` with every line marked as quoted.
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len());
+    for line in text.lines() {
+        quoted.push_str(if line.is_empty() { ">" } else { "> " });
+        quoted.push_str(line);
+        quoted.push('\n');
+    }
+    quoted
+}
+
+/// Adds `address` unless it is already in `addresses`, ignoring case.
+fn push_address(addresses: &mut Vec<String>, address: &str) {
+    if !addresses.iter().any(|a| a.eq_ignore_ascii_case(address)) {
+        addresses.push(address.to_string());
+    }
+}
+
+/// The subject with `prefix` in front, unless it already starts with one of
+/// `existing`, lowercase.
+fn prefixed_subject(prefix: &str, existing: &[&str], subject: &str) -> String {
+    let lower = subject.trim_start().to_lowercase();
+    if existing.iter().any(|existing| lower.starts_with(existing)) {
+        subject.to_string()
+    } else {
+        format!("{} {}", prefix, subject)
+    }
+}
+
+/// Most addresses offered while typing one.
+const MAX_COMPLETIONS: usize = 6;
+
+/// The addresses among `known` containing `prefix`, ignoring case, those
+/// starting with it first, without repeats.
+pub fn completions<'a>(prefix: &str, known: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let prefix = prefix.to_lowercase();
+    let mut matches: Vec<(bool, &str)> = Vec::new();
+    for address in known {
+        let lower = address.to_lowercase();
+        if !lower.contains(&prefix) || matches.iter().any(|(_, a)| a.eq_ignore_ascii_case(address))
+        {
+            continue;
+        }
+        matches.push((!lower.starts_with(&prefix), address));
+    }
+    // Stable, so equally good matches keep the order they were known in
+    matches.sort_by_key(|(later, _)| *later);
+    matches
+        .into_iter()
+        .take(MAX_COMPLETIONS)
+        .map(|(_, address)| address.to_string())
+        .collect()
+}
+
+/// The addresses in a comma-separated list, without blanks.
+pub fn parse_addresses(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Why a reply to all should be confirmed before it goes out: more recipients
+/// than `reply_all_limit`, or a distribution list among them. `None` when it can
+/// be sent right away.
+pub fn reply_all_warning(message: &OutgoingEmail, config: &ComposeConfig) -> Option<String> {
+    let recipients: Vec<&String> = message
+        .to
+        .iter()
+        .chain(message.cc.iter())
+        .chain(message.bcc.iter())
+        .collect();
+    let lists: Vec<&str> = recipients
+        .iter()
+        .filter(|address| {
+            config
+                .distribution_lists
+                .iter()
+                .any(|list| list.eq_ignore_ascii_case(address))
+        })
+        .map(|address| address.as_str())
+        .collect();
+
+    if lists.is_empty() && recipients.len() <= config.reply_all_limit {
+        return None;
+    }
+    let mut warning = format!("Reply to all {} recipients", recipients.len());
+    if !lists.is_empty() {
+        warning.push_str(&format!(", including {}", lists.join(", ")));
+    }
+    warning.push('?');
+    Some(warning)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::ThreadIds;
+
+    #[test]
+    fn test_reply_all_addresses_everyone_else() {
+        let email = Email {
+            subject: "Budget".to_string(),
+            sender: "boss@company.com".to_string(),
+            to: vec![
+                "Me@company.com".to_string(),
+                "alice@company.com".to_string(),
+            ],
+            cc: vec!["bob@company.com".to_string(), "me@company.com".to_string()],
+            read: true,
+            thread: ThreadIds {
+                conversation_id: None,
+                message_id: Some("2@company.com".to_string()),
+                references: vec!["1@company.com".to_string()],
+            },
+            ..Email::test("1")
+        };
+        let own = vec!["me@company.com".to_string()];
+
+        let reply = Compose::reply(&email, "Numbers attached.\n\nBoss", true, &own);
+        let message = reply.to_message();
+        assert_eq!(message.to, vec!["boss@company.com", "alice@company.com"]);
+        assert_eq!(message.cc, vec!["bob@company.com"]);
+        assert_eq!(message.subject, "Re: Budget");
+        assert!(message
+            .body
+            .ends_with("boss@company.com wrote:\n> Numbers attached.\n>\n> Boss\n"));
+        assert_eq!(message.in_reply_to.as_deref(), Some("2@company.com"));
+        assert_eq!(message.references, vec!["1@company.com", "2@company.com"]);
+
+        let reply = Compose::reply(&email, "", false, &own);
+        assert_eq!(reply.form.to, "boss@company.com");
+        assert_eq!(reply.form.subject, "Re: Budget");
+    }
+
+    #[test]
+    fn test_completions_prefer_addresses_starting_with_the_prefix() {
+        let known = [
+            "mallory@company.com",
+            "Alice@company.com",
+            "alice@company.com",
+            "bob@alicorp.com",
+        ];
+        assert_eq!(
+            completions("ali", known),
+            vec!["Alice@company.com", "bob@alicorp.com"]
+        );
+
+        let mut form = MessageForm {
+            to: "bob@alicorp.com, ali".to_string(),
+            ..MessageForm::default()
+        };
+        assert_eq!(form.address_prefix(), Some("ali"));
+        form.complete("alice@company.com");
+        assert_eq!(form.to, "bob@alicorp.com, alice@company.com, ");
+        assert_eq!(form.address_prefix(), None);
+    }
+
+    #[test]
+    fn test_reply_all_warning() {
+        let config = ComposeConfig {
+            reply_all_limit: 3,
+            distribution_lists: vec!["All-Staff@company.com".to_string()],
+            ..ComposeConfig::default()
+        };
+        let mut message = OutgoingEmail {
+            to: vec!["boss@company.com".to_string()],
+            cc: vec![
+                "alice@company.com".to_string(),
+                "bob@company.com".to_string(),
+            ],
+            ..OutgoingEmail::default()
+        };
+        assert_eq!(reply_all_warning(&message, &config), None);
+
+        message.cc.push("all-staff@company.com".to_string());
+        assert_eq!(
+            reply_all_warning(&message, &config).as_deref(),
+            Some("Reply to all 4 recipients, including all-staff@company.com?")
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Days, Months, NaiveDate};
+
+use crate::email::DateRange;
+
+/// Format of the days typed for a custom range.
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+/// Ranges offered by the date range popup.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    ThisWeek,
+    ThisMonth,
+    ThisQuarter,
+    Custom,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::ThisWeek,
+        Preset::ThisMonth,
+        Preset::ThisQuarter,
+        Preset::Custom,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Preset::ThisWeek => "This week",
+            Preset::ThisMonth => "This month",
+            Preset::ThisQuarter => "This quarter",
+            Preset::Custom => "Custom...",
+        }
+    }
+
+    /// The days of the preset around `today`, weeks starting on Monday, or
+    /// `None` for a custom range.
+    pub fn range(self, today: NaiveDate) -> Option<DateRange> {
+        match self {
+            Preset::ThisWeek => {
+                let start = today - Days::new(today.weekday().num_days_from_monday().into());
+                Some(DateRange {
+                    start,
+                    end: start + Days::new(6),
+                })
+            }
+            Preset::ThisMonth => {
+                let start = today.with_day(1).unwrap();
+                Some(DateRange {
+                    start,
+                    end: start + Months::new(1) - Days::new(1),
+                })
+            }
+            Preset::ThisQuarter => Some(DateRange::quarter(today)),
+            Preset::Custom => None,
+        }
+    }
+}
+
+/// The day of a custom range being typed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeField {
+    Start,
+    End,
+}
+
+/// State of the popup picking the days the list is fetched for.
+pub struct DatePicker {
+    /// Position in `Preset::ALL`
+    pub selected: usize,
+    pub start: String,
+    pub end: String,
+    /// Set while typing a custom range
+    pub field: Option<RangeField>,
+}
+
+impl DatePicker {
+    /// Opens on the presets, with the days of `current` ready to edit as a
+    /// custom range.
+    pub fn new(current: DateRange) -> Self {
+        Self {
+            selected: 0,
+            start: current.start.format(DAY_FORMAT).to_string(),
+            end: current.end.format(DAY_FORMAT).to_string(),
+            field: None,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % Preset::ALL.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + Preset::ALL.len() - 1) % Preset::ALL.len();
+    }
+
+    /// The range of the selected preset, or `None` after starting to type a
+    /// custom one.
+    pub fn choose(&mut self, today: NaiveDate) -> Option<DateRange> {
+        let range = Preset::ALL[self.selected].range(today);
+        if range.is_none() {
+            self.field = Some(RangeField::Start);
+        }
+        range
+    }
+
+    pub fn switch_field(&mut self) {
+        self.field = match self.field {
+            Some(RangeField::Start) => Some(RangeField::End),
+            Some(RangeField::End) => Some(RangeField::Start),
+            None => None,
+        };
+    }
+
+    fn input(&mut self) -> Option<&mut String> {
+        match self.field? {
+            RangeField::Start => Some(&mut self.start),
+            RangeField::End => Some(&mut self.end),
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        if let Some(input) = self.input() {
+            input.push(c);
+        }
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(input) = self.input() {
+            input.pop();
+        }
+    }
+
+    /// The custom range typed, if both days parse and are in order.
+    pub fn custom_range(&self) -> Result<DateRange> {
+        let parse = |input: &str| {
+            NaiveDate::parse_from_str(input.trim(), DAY_FORMAT)
+                .with_context(|| format!("{:?} is not a YYYY-MM-DD date", input.trim()))
+        };
+        let range = DateRange {
+            start: parse(&self.start)?,
+            end: parse(&self.end)?,
+        };
+        if range.start > range.end {
+            bail!("The range ends before {}", range.start);
+        }
+        Ok(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_and_custom_ranges() {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let range = |start, end| Some(DateRange { start, end });
+        // A Thursday in a leap year
+        let today = day(2024, 2, 15);
+
+        assert_eq!(
+            Preset::ThisWeek.range(today),
+            range(day(2024, 2, 12), day(2024, 2, 18))
+        );
+        assert_eq!(
+            Preset::ThisMonth.range(today),
+            range(day(2024, 2, 1), day(2024, 2, 29))
+        );
+        assert_eq!(
+            Preset::ThisQuarter.range(today),
+            range(day(2024, 1, 1), day(2024, 3, 31))
+        );
+        assert_eq!(DateRange::quarter(day(2024, 11, 3)).to_string(), "Q4 2024");
+
+        let mut picker = DatePicker::new(DateRange::quarter(today));
+        picker.select_previous();
+        assert_eq!(picker.choose(today), None);
+        assert_eq!(picker.field, Some(RangeField::Start));
+        picker.pop();
+        picker.push('5');
+        picker.switch_field();
+        picker.end = "2024-01-04".to_string();
+        assert!(picker.custom_range().is_err());
+        picker.end = "2024-01-31".to_string();
+        let custom = picker.custom_range().unwrap();
+        assert_eq!(custom.start, day(2024, 1, 5));
+        assert_eq!(custom.to_string(), "2024-01-05 to 2024-01-31");
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+
+/// Sections the email list is split into when sorted by date.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateGroup {
+    Today,
+    Yesterday,
+    /// The six days before yesterday
+    LastWeek,
+    Older,
+}
+
+impl DateGroup {
+    /// The section of mail received at `date`, by the local calendar day `today`.
+    pub fn of(date: DateTime<Utc>, today: NaiveDate) -> Self {
+        match (today - date.with_timezone(&Local).date_naive()).num_days() {
+            // Clocks that are ahead put mail in the future
+            ..=0 => DateGroup::Today,
+            1 => DateGroup::Yesterday,
+            2..=7 => DateGroup::LastWeek,
+            _ => DateGroup::Older,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DateGroup::Today => "Today",
+            DateGroup::Yesterday => "Yesterday",
+            DateGroup::LastWeek => "Last week",
+            DateGroup::Older => "Older",
+        }
+    }
+}
+
+/// How long ago `date` was, the way mail clients list it: minutes or hours
+/// within a day, the weekday within a week, then the day and, from other
+/// years, the year.
+pub fn relative(date: DateTime<Utc>, now: DateTime<Local>) -> String {
+    let local = date.with_timezone(&Local);
+    let age = now.signed_duration_since(local);
+    if age < Duration::minutes(1) {
+        // Clocks that are ahead put mail in the future
+        "now".to_string()
+    } else if age < Duration::hours(1) {
+        format!("{}m ago", age.num_minutes())
+    } else if age < Duration::days(1) {
+        format!("{}h ago", age.num_hours())
+    } else if (now.date_naive() - local.date_naive()).num_days() < 7 {
+        local.format("%a").to_string()
+    } else if local.year() == now.year() {
+        local.format("%b %-d").to_string()
+    } else {
+        local.format("%b %-d %Y").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_date_groups_by_local_day() {
+        let today = NaiveDate::from_ymd_opt(2024, 4, 10).unwrap();
+        let at = |day: u32, hour: u32| {
+            Local
+                .with_ymd_and_hms(2024, 4, day, hour, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        assert_eq!(DateGroup::of(at(10, 0), today), DateGroup::Today);
+        assert_eq!(DateGroup::of(at(11, 9), today), DateGroup::Today);
+        assert_eq!(DateGroup::of(at(9, 23), today), DateGroup::Yesterday);
+        assert_eq!(DateGroup::of(at(8, 0), today), DateGroup::LastWeek);
+        assert_eq!(DateGroup::of(at(3, 12), today), DateGroup::LastWeek);
+        assert_eq!(DateGroup::of(at(2, 12), today), DateGroup::Older);
+    }
+
+    #[test]
+    fn test_relative_dates_by_age() {
+        // Wednesday
+        let now = Local.with_ymd_and_hms(2024, 4, 10, 15, 0, 0).unwrap();
+        let ago = |duration: Duration| (now - duration).with_timezone(&Utc);
+
+        assert_eq!(relative(ago(Duration::seconds(20)), now), "now");
+        assert_eq!(relative(ago(-Duration::minutes(3)), now), "now");
+        assert_eq!(relative(ago(Duration::minutes(5)), now), "5m ago");
+        assert_eq!(relative(ago(Duration::hours(3)), now), "3h ago");
+        assert_eq!(relative(ago(Duration::days(1)), now), "Tue");
+        assert_eq!(relative(ago(Duration::days(6)), now), "Thu");
+        assert_eq!(relative(ago(Duration::days(29)), now), "Mar 12");
+        assert_eq!(relative(ago(Duration::days(120)), now), "Dec 12 2023");
+    }
+}
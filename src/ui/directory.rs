@@ -0,0 +1,45 @@
+use crate::email::DirectoryEntry;
+
+/// State of the directory lookup popup for one account.
+pub struct DirectoryLookup {
+    pub account: String,
+    pub query: String,
+    pub results: Vec<DirectoryEntry>,
+    pub selected: usize,
+    /// Whether a lookup ran, to tell "no matches" apart from "not searched yet"
+    pub searched: bool,
+}
+
+impl DirectoryLookup {
+    pub fn new(account: String) -> Self {
+        Self {
+            account,
+            query: String::new(),
+            results: Vec::new(),
+            selected: 0,
+            searched: false,
+        }
+    }
+
+    pub fn selected_entry(&self) -> Option<&DirectoryEntry> {
+        self.results.get(self.selected)
+    }
+
+    pub fn set_results(&mut self, results: Vec<DirectoryEntry>) {
+        self.results = results;
+        self.selected = 0;
+        self.searched = true;
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.results.is_empty() {
+            self.selected = (self.selected + 1) % self.results.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.results.is_empty() {
+            self.selected = (self.selected + self.results.len() - 1) % self.results.len();
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! The one-time "what's new" screen and the keybinding tips shown in the idle status bar.
+
+const CHANGELOG: &str = include_str!("../../CHANGELOG.md");
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Seconds each tip stays in the status bar before the next one is shown.
+const TIP_ROTATION_SECS: u64 = 10;
+
+/// Keybindings worth suggesting, with what they do.
+pub const TIPS: &[(char, &str)] = &[
+    ('/', "search emails"),
+    ('a', "archive the selected email"),
+    ('s', "act on the sender or recipients"),
+    ('J', "report junk or phishing"),
+    ('M', "manage folders"),
+    ('U', "recover hard-deleted emails"),
+    ('g', "jump to the first email"),
+    ('G', "jump to the last email"),
+    ('?', "see every keybinding"),
+];
+
+/// One version's section of the embedded changelog.
+pub struct Release {
+    pub version: String,
+    pub notes: Vec<String>,
+}
+
+/// Changelog sections newer than `last_seen`, newest first. Everything is new
+/// when no version has been seen yet.
+pub fn releases_since(last_seen: Option<&str>) -> Vec<Release> {
+    newer_releases(parse_changelog(CHANGELOG), last_seen)
+}
+
+fn newer_releases(releases: Vec<Release>, last_seen: Option<&str>) -> Vec<Release> {
+    match last_seen {
+        Some(seen) => releases
+            .into_iter()
+            .filter(|release| parse_version(&release.version) > parse_version(seen))
+            .collect(),
+        None => releases,
+    }
+}
+
+/// The tip to show at `seconds`, rotating through the keybindings not used yet.
+pub fn current_tip(used_keys: &[char], seconds: u64) -> Option<(char, &'static str)> {
+    let unused: Vec<(char, &'static str)> = TIPS
+        .iter()
+        .filter(|(key, _)| !used_keys.contains(key))
+        .copied()
+        .collect();
+    if unused.is_empty() {
+        return None;
+    }
+    Some(unused[(seconds / TIP_ROTATION_SECS) as usize % unused.len()])
+}
+
+fn parse_changelog(text: &str) -> Vec<Release> {
+    let mut releases: Vec<Release> = Vec::new();
+    for line in text.lines() {
+        if let Some(version) = line.strip_prefix("## ") {
+            releases.push(Release {
+                version: version.trim().to_string(),
+                notes: Vec::new(),
+            });
+        } else if let (Some(release), Some(note)) = (releases.last_mut(), line.strip_prefix("- ")) {
+            release.notes.push(note.trim().to_string());
+        }
+    }
+    releases
+}
+
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map(|part| part.trim().parse().unwrap_or(0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_releases_since_filters_seen_versions() {
+        let changelog = "# Changelog\n\n## 0.10.0\n\n- New\n\n## 0.9.1\n\n- Old\n";
+        assert_eq!(newer_releases(parse_changelog(changelog), None).len(), 2);
+
+        let newer = newer_releases(parse_changelog(changelog), Some("0.9.1"));
+        assert_eq!(newer.len(), 1);
+        assert_eq!(newer[0].version, "0.10.0");
+        assert_eq!(newer[0].notes, vec!["New".to_string()]);
+
+        assert!(newer_releases(parse_changelog(changelog), Some("0.10.0")).is_empty());
+    }
+
+    #[test]
+    fn test_current_tip_skips_used_keys() {
+        let used: Vec<char> = TIPS
+            .iter()
+            .map(|(key, _)| *key)
+            .filter(|k| *k != 'a')
+            .collect();
+        assert_eq!(current_tip(&used, 0).map(|(key, _)| key), Some('a'));
+        assert_eq!(current_tip(&used, 12345).map(|(key, _)| key), Some('a'));
+
+        let all: Vec<char> = TIPS.iter().map(|(key, _)| *key).collect();
+        assert!(current_tip(&all, 0).is_none());
+    }
+}
@@ -0,0 +1,44 @@
+use crate::email::Email;
+
+/// State of the popup listing the Drafts folder of one account.
+pub struct DraftList {
+    pub account: String,
+    pub drafts: Vec<Email>,
+    pub selected: usize,
+}
+
+impl DraftList {
+    pub fn new(account: String, drafts: Vec<Email>) -> Self {
+        Self {
+            account,
+            drafts,
+            selected: 0,
+        }
+    }
+
+    pub fn selected_draft(&self) -> Option<&Email> {
+        self.drafts.get(self.selected)
+    }
+
+    /// Takes the selected draft out of the list, keeping the selection in range.
+    pub fn remove_selected(&mut self) -> Option<Email> {
+        if self.selected >= self.drafts.len() {
+            return None;
+        }
+        let draft = self.drafts.remove(self.selected);
+        self.selected = self.selected.min(self.drafts.len().saturating_sub(1));
+        Some(draft)
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.drafts.is_empty() {
+            self.selected = (self.selected + 1) % self.drafts.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.drafts.is_empty() {
+            self.selected = (self.selected + self.drafts.len() - 1) % self.drafts.len();
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::process::Command;
+
+use super::action::Input;
+use super::terminal;
+
+/// Opens `text` in `command`, or else `$VISUAL`, `$EDITOR` or `vi`, handing it
+/// the terminal until it exits, and returns the text as it was saved.
+pub fn edit(input: &mut Input, command: Option<&str>, text: &str) -> Result<String> {
+    let editor = command
+        .map(str::to_string)
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    let path = std::env::temp_dir().join(format!("mail-tui-{}.txt", std::process::id()));
+    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    let result = terminal::hand_over(input, || run(&editor, &path));
+    let edited = result.and_then(|()| {
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+    });
+    let _ = fs::remove_file(&path);
+    edited
+}
+
+fn run(editor: &str, path: &std::path::Path) -> Result<()> {
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to start {}", editor))?;
+    if !status.success() {
+        bail!("{} exited with {}", editor, status);
+    }
+    Ok(())
+}
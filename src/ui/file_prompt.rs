@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// The path typed in `input`, with a leading `~` standing for the home directory.
+pub fn expand(input: &str) -> PathBuf {
+    match input.strip_prefix('~') {
+        Some(relative) => match dirs::home_dir() {
+            Some(home) => home.join(relative.trim_start_matches('/')),
+            None => PathBuf::from(input),
+        },
+        None => PathBuf::from(input),
+    }
+}
+
+/// Completes the last part of the path typed in `input` as far as the entries of
+/// its directory agree on, adding a `/` after a directory. Hidden entries only
+/// match once a `.` is typed.
+pub fn complete(input: &str) -> String {
+    let (directory, name) = match input.rfind('/') {
+        Some(slash) => input.split_at(slash + 1),
+        None => ("", input),
+    };
+    let path = if directory.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand(directory)
+    };
+    let Ok(entries) = fs::read_dir(path) else {
+        return input.to_string();
+    };
+    let mut matches: Vec<(String, bool)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let entry_name = entry.file_name().into_string().ok()?;
+            let hidden = entry_name.starts_with('.') && !name.starts_with('.');
+            (entry_name.starts_with(name) && !hidden).then(|| (entry_name, entry.path().is_dir()))
+        })
+        .collect();
+    matches.sort();
+
+    match matches.as_slice() {
+        [] => input.to_string(),
+        [(entry_name, is_dir)] => {
+            let slash = if *is_dir { "/" } else { "" };
+            format!("{}{}{}", directory, entry_name, slash)
+        }
+        [(first, _), rest @ ..] => {
+            let mut common = first.len();
+            for (other, _) in rest {
+                common = first
+                    .char_indices()
+                    .zip(other.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .last()
+                    .map_or(0, |((idx, a), _)| idx + a.len_utf8())
+                    .min(common);
+            }
+            format!("{}{}", directory, &first[..common])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_as_far_as_entries_agree() {
+        let dir = std::env::temp_dir().join(format!("mail-tui-complete-{}", std::process::id()));
+        fs::create_dir_all(dir.join("reports")).unwrap();
+        fs::write(dir.join("report-q1.pdf"), "").unwrap();
+        fs::write(dir.join("report-q2.pdf"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        let base = format!("{}/", dir.display());
+
+        assert_eq!(complete(&format!("{}rep", base)), format!("{}report", base));
+        assert_eq!(
+            complete(&format!("{}reports", base)),
+            format!("{}reports/", base)
+        );
+        assert_eq!(
+            complete(&format!("{}report-q2", base)),
+            format!("{}report-q2.pdf", base)
+        );
+        assert_eq!(complete(&format!("{}h", base)), format!("{}h", base));
+        assert_eq!(complete(&format!("{}.h", base)), format!("{}.hidden", base));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// Lines shown above a match the content pane jumps to.
+const CONTEXT_LINES: usize = 2;
+
+/// A word or phrase found in the open email's body with `/`, stepped through
+/// with `n` and `N`.
+#[derive(Default)]
+pub struct Find {
+    pub input: String,
+    /// The pattern typed, once confirmed with Enter
+    regex: Option<Regex>,
+    /// The match jumped to last, counted over the lines with a match
+    pub current: Option<usize>,
+}
+
+impl Find {
+    /// Confirms the typed text, matched literally and ignoring case.
+    pub fn confirm(&mut self) {
+        let text = self.input.trim();
+        self.regex = (!text.is_empty())
+            .then(|| {
+                RegexBuilder::new(&regex::escape(text))
+                    .case_insensitive(true)
+                    .build()
+                    .ok()
+            })
+            .flatten();
+        self.current = None;
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.regex.is_some()
+    }
+
+    /// The byte ranges of `line` matching the confirmed pattern.
+    pub fn ranges(&self, line: &str) -> Vec<Range<usize>> {
+        self.regex.as_ref().map_or_else(Vec::new, |regex| {
+            regex.find_iter(line).map(|found| found.range()).collect()
+        })
+    }
+
+    /// Steps to the next match, or the previous one, out of the rows of the
+    /// lines with a match. The first step goes to the first match from
+    /// `scroll` on. Returns the row to scroll to.
+    pub fn step(&mut self, rows: &[usize], scroll: usize, forward: bool) -> Option<usize> {
+        if rows.is_empty() {
+            return None;
+        }
+        let count = rows.len();
+        let next = match self.current {
+            None => rows.iter().position(|&row| row >= scroll).unwrap_or(0),
+            Some(current) if forward => (current + 1) % count,
+            Some(current) => (current.min(count - 1) + count - 1) % count,
+        };
+        self.current = Some(next);
+        Some(rows[next].saturating_sub(CONTEXT_LINES))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_wrap_around_the_matches() {
+        let mut find = Find {
+            input: " Budget ".to_string(),
+            ..Find::default()
+        };
+        find.confirm();
+        assert_eq!(find.ranges("The budget and BUDGET"), vec![4..10, 15..21]);
+
+        let rows = [3, 10, 40];
+        assert_eq!(find.step(&rows, 5, true), Some(8));
+        assert_eq!(find.step(&rows, 8, true), Some(38));
+        assert_eq!(find.step(&rows, 38, true), Some(1));
+        assert_eq!(find.step(&rows, 1, false), Some(38));
+        assert_eq!(find.step(&[], 0, true), None);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::email::Folder;
+
+/// What the folder manager's name prompt is being used for.
+pub enum FolderEdit {
+    Create,
+    /// Renaming the folder with this id
+    Rename(String),
+}
+
+/// State of the folder management popup for one account.
+pub struct FolderManager {
+    pub account: String,
+    pub folders: Vec<Folder>,
+    pub selected: usize,
+    pub edit: Option<FolderEdit>,
+    pub input: String,
+}
+
+impl FolderManager {
+    pub fn new(account: String, folders: Vec<Folder>) -> Self {
+        Self {
+            account,
+            folders,
+            selected: 0,
+            edit: None,
+            input: String::new(),
+        }
+    }
+
+    pub fn selected_folder(&self) -> Option<&Folder> {
+        self.folders.get(self.selected)
+    }
+
+    /// Replaces the folder list after a change, keeping the selection in range.
+    pub fn set_folders(&mut self, folders: Vec<Folder>) {
+        self.folders = folders;
+        self.selected = self.selected.min(self.folders.len().saturating_sub(1));
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.folders.is_empty() {
+            self.selected = (self.selected + 1) % self.folders.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.folders.is_empty() {
+            self.selected = (self.selected + self.folders.len() - 1) % self.folders.len();
+        }
+    }
+
+    pub fn start_create(&mut self) {
+        self.edit = Some(FolderEdit::Create);
+        self.input.clear();
+    }
+
+    pub fn start_rename(&mut self) {
+        if let Some(folder) = self.folders.get(self.selected) {
+            self.edit = Some(FolderEdit::Rename(folder.id.clone()));
+            self.input = folder.name.clone();
+        }
+    }
+
+    pub fn cancel_edit(&mut self) {
+        self.edit = None;
+        self.input.clear();
+    }
+}
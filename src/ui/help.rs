@@ -0,0 +1,78 @@
+use std::cell::Cell;
+
+use super::keymap::{self, Context};
+
+/// What the mouse does, listed with the keys of the panes it works in.
+const MOUSE: &str = "Click an email or pane to select it, scroll with the wheel";
+
+/// The keys of `context` and what they do, in keymap order, as the help popup lists them.
+pub fn rows(context: Context) -> impl Iterator<Item = (String, &'static str)> {
+    let mouse = (context != Context::Compose).then(|| ("Mouse".to_string(), MOUSE));
+    keymap::bindings_for(context)
+        .map(|binding| (binding.label(), binding.description))
+        .chain(mouse)
+}
+
+/// State of the help popup, scrolled with j/k when the bindings don't fit.
+pub struct Help {
+    pub context: Context,
+    pub scroll: usize,
+    /// How far the bindings can scroll and how many lines show at once,
+    /// recorded by the view on every draw
+    pub max_scroll: Cell<usize>,
+    pub height: Cell<usize>,
+}
+
+impl Help {
+    pub fn new(context: Context) -> Self {
+        Self {
+            context,
+            scroll: 0,
+            max_scroll: Cell::new(0),
+            height: Cell::new(0),
+        }
+    }
+
+    pub fn scroll_by(&mut self, lines: isize) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(lines)
+            .min(self.max_scroll.get());
+    }
+
+    pub fn scroll_page(&mut self, down: bool) {
+        let page = self.height.get().max(1) as isize;
+        self.scroll_by(if down { page } else { -page });
+    }
+
+    pub fn scroll_to_end(&mut self) {
+        self.scroll = self.max_scroll.get();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_lists_the_bindings_of_its_context() {
+        let keys = |context| -> Vec<String> { rows(context).map(|(keys, _)| keys).collect() };
+        let sidebar = keys(Context::Sidebar);
+        assert!(sidebar.contains(&"b".to_string()) && sidebar.contains(&"q".to_string()));
+        assert!(!sidebar.contains(&"a".to_string()));
+        assert!(keys(Context::Reading).contains(&"V".to_string()));
+        assert!(!keys(Context::List).contains(&"V".to_string()));
+        assert!(keys(Context::List).contains(&"yi".to_string()));
+        let compose = keys(Context::Compose);
+        assert!(compose.contains(&"Ctrl-D".to_string()));
+        assert!(!compose.contains(&"Mouse".to_string()));
+
+        let mut help = Help::new(Context::List);
+        help.max_scroll.set(5);
+        help.scroll_by(-1);
+        assert_eq!(help.scroll, 0);
+        help.scroll_by(3);
+        help.scroll_by(3);
+        assert_eq!(help.scroll, 5);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::email::Attachment;
+
+/// Letters labels are made of, home row first.
+const LETTERS: &[u8] = b"asdfghjkl";
+
+/// Something a hint label opens.
+#[derive(Clone)]
+pub enum HintTarget {
+    Link(String),
+    Attachment(Attachment),
+}
+
+/// State of hint mode: a label for each link and attachment of the open email,
+/// and the letters typed so far.
+pub struct Hints {
+    pub targets: Vec<HintTarget>,
+    labels: Vec<String>,
+    pub typed: String,
+}
+
+impl Hints {
+    pub fn new(links: Vec<String>, attachments: &[Attachment]) -> Self {
+        let targets: Vec<HintTarget> = links
+            .into_iter()
+            .map(HintTarget::Link)
+            .chain(attachments.iter().cloned().map(HintTarget::Attachment))
+            .collect();
+        let labels = labels(targets.len());
+        Self {
+            targets,
+            labels,
+            typed: String::new(),
+        }
+    }
+
+    /// The label of the link to `address`, if it has one.
+    pub fn link_label(&self, address: &str) -> Option<&str> {
+        self.targets
+            .iter()
+            .position(|target| matches!(target, HintTarget::Link(link) if link == address))
+            .map(|idx| self.labels[idx].as_str())
+    }
+
+    /// The label of the attachment `id`, if it has one.
+    pub fn attachment_label(&self, id: &str) -> Option<&str> {
+        self.targets
+            .iter()
+            .position(
+                |target| matches!(target, HintTarget::Attachment(attachment) if attachment.id == id),
+            )
+            .map(|idx| self.labels[idx].as_str())
+    }
+
+    /// The target whose label is typed in full.
+    pub fn chosen(&self) -> Option<&HintTarget> {
+        let idx = self.labels.iter().position(|label| *label == self.typed)?;
+        self.targets.get(idx)
+    }
+
+    /// Whether some label starts with what is typed.
+    pub fn is_possible(&self) -> bool {
+        self.labels
+            .iter()
+            .any(|label| label.starts_with(&self.typed))
+    }
+}
+
+/// `count` labels of the same length, as short as they can be.
+fn labels(count: usize) -> Vec<String> {
+    let base = LETTERS.len();
+    let mut length = 1;
+    while base.pow(length) < count {
+        length += 1;
+    }
+    (0..count)
+        .map(|mut n| {
+            let mut label = vec![0; length as usize];
+            for slot in label.iter_mut().rev() {
+                *slot = LETTERS[n % base];
+                n /= base;
+            }
+            String::from_utf8(label).unwrap_or_default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_grow_with_targets() {
+        assert_eq!(labels(3), vec!["a", "s", "d"]);
+        let many = labels(12);
+        assert_eq!(many[0], "aa");
+        assert_eq!(many[9], "sa");
+        assert_eq!(many[11], "sd");
+
+        let mut hints = Hints::new(
+            (0..12)
+                .map(|n| format!("https://example.com/{}", n))
+                .collect(),
+            &[],
+        );
+        hints.typed.push('s');
+        assert!(hints.is_possible() && hints.chosen().is_none());
+        hints.typed.push('s');
+        assert!(matches!(hints.chosen(), Some(HintTarget::Link(link)) if link.ends_with("/10")));
+    }
+}
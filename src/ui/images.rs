@@ -0,0 +1,234 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::fmt::Write;
+
+use super::png;
+use crate::config::ImageProtocol;
+
+/// Bytes of image data sent per kitty graphics command, as the protocol asks.
+const KITTY_CHUNK: usize = 4096;
+
+/// Removes every image placement, keeping the images for placing again.
+pub const KITTY_CLEAR: &str = "\x1b_Ga=d,d=a,q=2\x1b\\";
+
+/// The command freeing an image sent to a kitty terminal.
+pub fn kitty_delete(id: u32) -> String {
+    format!("\x1b_Ga=d,d=I,i={},q=2\x1b\\", id)
+}
+
+/// How images are drawn in the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Graphics {
+    Kitty,
+    Sixel,
+}
+
+/// The graphics protocol to draw images with, from the config or, on `auto`,
+/// from what the environment says about the terminal.
+pub fn detect(setting: ImageProtocol) -> Option<Graphics> {
+    match setting {
+        ImageProtocol::Off => None,
+        ImageProtocol::Kitty => Some(Graphics::Kitty),
+        ImageProtocol::Sixel => Some(Graphics::Sixel),
+        ImageProtocol::Auto => {
+            let var = |name: &str| std::env::var(name).unwrap_or_default();
+            let term = var("TERM");
+            let program = var("TERM_PROGRAM");
+            if std::env::var_os("KITTY_WINDOW_ID").is_some()
+                || term == "xterm-kitty"
+                || matches!(program.as_str(), "WezTerm" | "ghostty")
+            {
+                Some(Graphics::Kitty)
+            } else if term.contains("sixel")
+                || term.starts_with("foot")
+                || term == "mlterm"
+                || program == "iTerm.app"
+            {
+                Some(Graphics::Sixel)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// An image attachment of the open email, as far as it got.
+pub enum ImageState {
+    Loading,
+    Ready(Image),
+    /// Not a PNG, too large or failed to download, shown as a placeholder
+    Unavailable,
+}
+
+/// Where the view left room for an image, in cells of the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageSlot {
+    pub id: u32,
+    pub x: u16,
+    pub y: u16,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// The size of a terminal cell in pixels, assuming 8 by 16 when the terminal
+/// doesn't tell.
+pub fn cell_size() -> (u16, u16) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => {
+            (size.width / size.columns, size.height / size.rows)
+        }
+        _ => (8, 16),
+    }
+}
+
+/// A PNG ready to draw, with the id the terminal knows it by.
+pub struct Image {
+    pub id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(id: u32, data: Vec<u8>) -> Result<Self> {
+        let (width, height) = png::dimensions(&data)?;
+        Ok(Self {
+            id,
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// The columns and rows the image takes at its own size, shrunk to fit in
+    /// `max_cols` by `max_rows`, for cells of `cell` pixels.
+    pub fn fit(&self, max_cols: u16, max_rows: u16, cell: (u16, u16)) -> (u16, u16) {
+        let (cell_width, cell_height) = (cell.0.max(1) as f64, cell.1.max(1) as f64);
+        let cols = self.width as f64 / cell_width;
+        let rows = self.height as f64 / cell_height;
+        let scale = (max_cols as f64 / cols)
+            .min(max_rows as f64 / rows)
+            .min(1.0);
+        (
+            ((cols * scale).round() as u16).clamp(1, max_cols.max(1)),
+            ((rows * scale).round() as u16).clamp(1, max_rows.max(1)),
+        )
+    }
+
+    /// The commands sending the image to a kitty terminal to place later.
+    pub fn kitty_transmit(&self) -> String {
+        let encoded = STANDARD.encode(&self.data);
+        let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+        let mut out = String::new();
+        for (idx, chunk) in chunks.iter().enumerate() {
+            let more = u8::from(idx + 1 < chunks.len());
+            let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+            if idx == 0 {
+                let _ = write!(
+                    out,
+                    "\x1b_Ga=t,f=100,i={},q=2,m={};{}\x1b\\",
+                    self.id, more, chunk
+                );
+            } else {
+                let _ = write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk);
+            }
+        }
+        out
+    }
+
+    /// The command placing the sent image at the cursor over `cols` by `rows` cells.
+    pub fn kitty_place(&self, cols: u16, rows: u16) -> String {
+        format!(
+            "\x1b_Ga=p,i={},c={},r={},C=1,q=2\x1b\\",
+            self.id, cols, rows
+        )
+    }
+
+    /// The image as sixels, scaled to `width` by `height` pixels with colors
+    /// from a 6×6×6 cube. Transparent pixels are left out.
+    pub fn sixel(&self, width: u32, height: u32) -> Result<String> {
+        let decoded = png::decode(&self.data)?;
+        let (width, height) = (width.max(1), height.max(1));
+        let color_at = |x: u32, y: u32| -> Option<usize> {
+            let [r, g, b, a] =
+                decoded.pixel(x * decoded.width / width, y * decoded.height / height);
+            let level = |v: u8| (v as usize * 5 + 127) / 255;
+            (a >= 128).then(|| level(r) * 36 + level(g) * 6 + level(b))
+        };
+
+        let mut out = format!("\x1bPq\"1;1;{};{}", width, height);
+        for color in 0..216 {
+            let percent = |level: usize| level * 100 / 5;
+            let _ = write!(
+                out,
+                "#{};2;{};{};{}",
+                color,
+                percent(color / 36),
+                percent(color / 6 % 6),
+                percent(color % 6)
+            );
+        }
+        for band in (0..height).step_by(6) {
+            let rows = band..(band + 6).min(height);
+            // The bits of each column in each color used in the band
+            let mut colors: Vec<(usize, Vec<u8>)> = Vec::new();
+            for x in 0..width {
+                for y in rows.clone() {
+                    let Some(color) = color_at(x, y) else {
+                        continue;
+                    };
+                    let bits = match colors.iter_mut().find(|(c, _)| *c == color) {
+                        Some((_, bits)) => bits,
+                        None => {
+                            colors.push((color, vec![0; width as usize]));
+                            &mut colors.last_mut().unwrap().1
+                        }
+                    };
+                    bits[x as usize] |= 1 << (y - band);
+                }
+            }
+            for (color, bits) in &colors {
+                let _ = write!(out, "#{}", color);
+                push_runs(&mut out, bits);
+                out.push('$');
+            }
+            out.push('-');
+        }
+        out.push_str("\x1b\\");
+        Ok(out)
+    }
+}
+
+/// Adds sixel columns to `out`, repeating ones that occur in a row with `!`.
+fn push_runs(out: &mut String, bits: &[u8]) {
+    let mut idx = 0;
+    while idx < bits.len() {
+        let run = bits[idx..].iter().take_while(|&&b| b == bits[idx]).count();
+        let c = (63 + bits[idx]) as char;
+        if run > 3 {
+            let _ = write!(out, "!{}{}", run, c);
+        } else {
+            out.extend(std::iter::repeat_n(c, run));
+        }
+        idx += run;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_keeps_the_aspect_ratio() {
+        let image = Image {
+            id: 1,
+            width: 800,
+            height: 400,
+            data: Vec::new(),
+        };
+        // 100 by 25 cells at their own size, shrunk to the width of the pane
+        assert_eq!(image.fit(50, 20, (8, 16)), (50, 13));
+        // Small images aren't blown up
+        assert_eq!(image.fit(200, 40, (8, 16)), (100, 25));
+    }
+}
@@ -0,0 +1,849 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Where a key was pressed, which decides what it does and which bindings the
+/// help popup lists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Context {
+    List,
+    Reading,
+    Sidebar,
+    Compose,
+}
+
+impl Context {
+    pub fn label(self) -> &'static str {
+        match self {
+            Context::List => "Email list",
+            Context::Reading => "Reading an email",
+            Context::Sidebar => "Folder sidebar",
+            Context::Compose => "Writing a message",
+        }
+    }
+}
+
+/// One key of a binding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Plain(KeyCode),
+    Ctrl(char),
+    /// 1 to 9, which the action reads back from the key pressed
+    Digits,
+}
+
+impl Key {
+    fn matches(self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match self {
+            Key::Plain(code) => !ctrl && key.code == code,
+            Key::Ctrl(c) => ctrl && key.code == KeyCode::Char(c),
+            Key::Digits => !ctrl && matches!(key.code, KeyCode::Char('1'..='9')),
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Key::Plain(KeyCode::Char(' ')) => "Space".to_string(),
+            Key::Plain(KeyCode::Char(c)) => c.to_string(),
+            Key::Plain(KeyCode::Up) => "↑".to_string(),
+            Key::Plain(KeyCode::Down) => "↓".to_string(),
+            Key::Plain(KeyCode::Left) => "←".to_string(),
+            Key::Plain(KeyCode::Right) => "→".to_string(),
+            Key::Plain(KeyCode::PageUp) => "PgUp".to_string(),
+            Key::Plain(KeyCode::PageDown) => "PgDn".to_string(),
+            Key::Plain(KeyCode::Delete) => "Del".to_string(),
+            Key::Plain(KeyCode::F(n)) => format!("F{}", n),
+            Key::Plain(code) => format!("{:?}", code),
+            Key::Ctrl(c) => format!("Ctrl-{}", c.to_ascii_uppercase()),
+            Key::Digits => "1-9".to_string(),
+        }
+    }
+}
+
+/// What a binding does, carried out by `App::run_key_action`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyAction {
+    Quit,
+    Help,
+    NextEmail,
+    PreviousEmail,
+    FirstEmail,
+    LastEmail,
+    OpenEmail,
+    /// Esc in the list: clears the selection, or stops syncs and body fetches
+    Cancel,
+    CloseEmail,
+    BackToList,
+    FocusSidebar,
+    ToggleSidebar,
+    ToggleThread,
+    Refresh,
+    Search,
+    SavedSearches,
+    CommandLine,
+    Tag,
+    Visual,
+    MarkRead,
+    Archive,
+    Delete,
+    Move,
+    Compose,
+    Reply,
+    ReplyAll,
+    Forward,
+    ReportJunk,
+    RespondToMeeting,
+    ThreadActions,
+    Undo,
+    AddressMenu,
+    Directory,
+    Headers,
+    SaveAttachments,
+    OpenInBrowser,
+    CycleBodyView,
+    ToggleQuotes,
+    Links,
+    Hints,
+    SenderFilter,
+    AttachmentFilter,
+    FilterUnread,
+    FilterFlagged,
+    FilterAttachments,
+    ClearFilters,
+    CopyBody,
+    CopySender,
+    CopyMessageId,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    GoToTab,
+    OpenView,
+    Inbox,
+    DateRange,
+    PreviousQuarter,
+    NextQuarter,
+    RelativeDates,
+    Sort,
+    NarrowList,
+    WidenList,
+    CycleLayout,
+    SyncHealth,
+    DismissBanner,
+    Folders,
+    Recoverable,
+    OutOfOffice,
+    Tasks,
+    Outbox,
+    Drafts,
+    ScrollDown,
+    ScrollUp,
+    PageDown,
+    PageUp,
+    NextAndOpen,
+    PreviousAndOpen,
+    Find,
+    FindNext,
+    FindPrevious,
+    Pager,
+    NextFolder,
+    PreviousFolder,
+    OpenFolder,
+    ReloadFolders,
+    LeaveSidebar,
+    DiscardMessage,
+    Send,
+    ToggleSignature,
+    Attach,
+    RemoveAttachment,
+    EditInEditor,
+    SaveDraft,
+}
+
+/// Keys, typed after `prefix` when there is one, and what they do where.
+pub struct Binding {
+    pub prefix: Option<char>,
+    pub keys: &'static [Key],
+    pub action: KeyAction,
+    pub description: &'static str,
+    pub contexts: &'static [Context],
+}
+
+impl Binding {
+    /// The keys as the help popup shows them, e.g. `j ↓` or `gt`.
+    pub fn label(&self) -> String {
+        let prefix = self.prefix.map(String::from).unwrap_or_default();
+        self.keys
+            .iter()
+            .map(|key| format!("{}{}", prefix, key.label()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+const LIST: &[Context] = &[Context::List];
+const READING: &[Context] = &[Context::Reading];
+const SIDEBAR: &[Context] = &[Context::Sidebar];
+const COMPOSE: &[Context] = &[Context::Compose];
+const MAIL: &[Context] = &[Context::List, Context::Reading];
+const BROWSING: &[Context] = &[Context::List, Context::Reading, Context::Sidebar];
+
+const fn ch(c: char) -> Key {
+    Key::Plain(KeyCode::Char(c))
+}
+
+const fn bind(
+    keys: &'static [Key],
+    action: KeyAction,
+    description: &'static str,
+    contexts: &'static [Context],
+) -> Binding {
+    Binding {
+        prefix: None,
+        keys,
+        action,
+        description,
+        contexts,
+    }
+}
+
+const fn after(
+    prefix: char,
+    keys: &'static [Key],
+    action: KeyAction,
+    description: &'static str,
+    contexts: &'static [Context],
+) -> Binding {
+    Binding {
+        prefix: Some(prefix),
+        keys,
+        action,
+        description,
+        contexts,
+    }
+}
+
+const UP: Key = Key::Plain(KeyCode::Up);
+const DOWN: Key = Key::Plain(KeyCode::Down);
+const LEFT: Key = Key::Plain(KeyCode::Left);
+const RIGHT: Key = Key::Plain(KeyCode::Right);
+const ENTER: Key = Key::Plain(KeyCode::Enter);
+const ESC: Key = Key::Plain(KeyCode::Esc);
+
+/// The keymap, which decides what keys do and is listed by the help popup in
+/// this order.
+pub const BINDINGS: &[Binding] = &[
+    bind(&[ch('j'), DOWN], KeyAction::NextEmail, "Next email", LIST),
+    bind(
+        &[ch('k'), UP],
+        KeyAction::PreviousEmail,
+        "Previous email",
+        LIST,
+    ),
+    bind(
+        &[ch('j')],
+        KeyAction::ScrollDown,
+        "Scroll the message down",
+        READING,
+    ),
+    bind(
+        &[ch('k')],
+        KeyAction::ScrollUp,
+        "Scroll the message up",
+        READING,
+    ),
+    bind(
+        &[DOWN],
+        KeyAction::NextAndOpen,
+        "Open the next email",
+        READING,
+    ),
+    bind(
+        &[UP],
+        KeyAction::PreviousAndOpen,
+        "Open the previous email",
+        READING,
+    ),
+    bind(
+        &[ch('j'), DOWN],
+        KeyAction::NextFolder,
+        "Next folder",
+        SIDEBAR,
+    ),
+    bind(
+        &[ch('k'), UP],
+        KeyAction::PreviousFolder,
+        "Previous folder",
+        SIDEBAR,
+    ),
+    bind(
+        &[ch('l'), RIGHT, ENTER],
+        KeyAction::OpenEmail,
+        "View selected email details",
+        LIST,
+    ),
+    bind(
+        &[ch('l'), RIGHT, ENTER],
+        KeyAction::OpenFolder,
+        "Open the selected folder",
+        SIDEBAR,
+    ),
+    bind(
+        &[ESC],
+        KeyAction::CloseEmail,
+        "Return to email list, stopping the body fetch",
+        READING,
+    ),
+    bind(
+        &[ch('h'), LEFT],
+        KeyAction::BackToList,
+        "Return to email list",
+        READING,
+    ),
+    bind(
+        &[ch('h'), LEFT],
+        KeyAction::FocusSidebar,
+        "Focus the folder sidebar, when shown",
+        LIST,
+    ),
+    bind(
+        &[ESC],
+        KeyAction::LeaveSidebar,
+        "Return to email list",
+        SIDEBAR,
+    ),
+    bind(
+        &[ESC],
+        KeyAction::Cancel,
+        "Clear the selection, or stop syncing and loading bodies",
+        LIST,
+    ),
+    bind(
+        &[Key::Plain(KeyCode::PageDown), ch(' ')],
+        KeyAction::PageDown,
+        "Scroll the message down a page",
+        READING,
+    ),
+    bind(
+        &[Key::Plain(KeyCode::PageUp)],
+        KeyAction::PageUp,
+        "Scroll the message up a page",
+        READING,
+    ),
+    bind(
+        &[ch('|')],
+        KeyAction::Pager,
+        "Open the message in $PAGER",
+        READING,
+    ),
+    bind(&[ch('/')], KeyAction::Find, "Find in the email", READING),
+    bind(
+        &[ch('n')],
+        KeyAction::FindNext,
+        "Jump to the next match",
+        READING,
+    ),
+    bind(
+        &[ch('N')],
+        KeyAction::FindPrevious,
+        "Jump to the previous match",
+        READING,
+    ),
+    after(
+        'g',
+        &[ch('g')],
+        KeyAction::FirstEmail,
+        "Go to the first email",
+        LIST,
+    ),
+    bind(
+        &[ch('G')],
+        KeyAction::LastEmail,
+        "Go to the last email",
+        LIST,
+    ),
+    after(
+        'g',
+        &[ch('n')],
+        KeyAction::NewTab,
+        "Open a tab with the same list",
+        LIST,
+    ),
+    after('g', &[ch('x')], KeyAction::CloseTab, "Close the tab", LIST),
+    after('g', &[ch('t')], KeyAction::NextTab, "Next tab", LIST),
+    after(
+        'g',
+        &[ch('T')],
+        KeyAction::PreviousTab,
+        "Previous tab",
+        LIST,
+    ),
+    after(
+        'g',
+        &[Key::Digits],
+        KeyAction::GoToTab,
+        "Go to a numbered tab",
+        LIST,
+    ),
+    bind(&[ch('r')], KeyAction::Refresh, "Refresh emails", LIST),
+    bind(
+        &[ch('r')],
+        KeyAction::ReloadFolders,
+        "Reload the folders",
+        SIDEBAR,
+    ),
+    bind(
+        &[ch('/')],
+        KeyAction::Search,
+        "Search emails (!query searches the server)",
+        LIST,
+    ),
+    bind(
+        &[ch('\'')],
+        KeyAction::SavedSearches,
+        "Saved searches (:save-search <name> keeps the current one)",
+        LIST,
+    ),
+    bind(
+        &[ch(' ')],
+        KeyAction::Tag,
+        "Tag the email for a, d, m and I (mark read)",
+        LIST,
+    ),
+    bind(
+        &[ch('v')],
+        KeyAction::Visual,
+        "Start or end a range of emails for a, d, m and I",
+        LIST,
+    ),
+    bind(
+        &[ch('I')],
+        KeyAction::MarkRead,
+        "Mark everything in the list (or selected) read",
+        LIST,
+    ),
+    bind(
+        &[ch('a')],
+        KeyAction::Archive,
+        "Archive selected email (or the tagged ones)",
+        MAIL,
+    ),
+    bind(
+        &[ch('d'), Key::Plain(KeyCode::Delete)],
+        KeyAction::Delete,
+        "Delete selected email, after asking",
+        MAIL,
+    ),
+    bind(
+        &[ch('m')],
+        KeyAction::Move,
+        "Move selected email to a folder",
+        MAIL,
+    ),
+    bind(
+        &[ch('c')],
+        KeyAction::Compose,
+        "Compose a new message",
+        MAIL,
+    ),
+    bind(
+        &[ch('R')],
+        KeyAction::Reply,
+        "Reply, quoting the email",
+        MAIL,
+    ),
+    bind(
+        &[ch('A')],
+        KeyAction::ReplyAll,
+        "Reply to all, quoting the email",
+        MAIL,
+    ),
+    bind(
+        &[ch('F')],
+        KeyAction::Forward,
+        "Forward the email with its attachments",
+        READING,
+    ),
+    after(
+        'F',
+        &[ch('F')],
+        KeyAction::Forward,
+        "Forward the email with its attachments",
+        LIST,
+    ),
+    after(
+        'F',
+        &[ch('u')],
+        KeyAction::FilterUnread,
+        "Only unread emails",
+        LIST,
+    ),
+    after(
+        'F',
+        &[ch('f')],
+        KeyAction::FilterFlagged,
+        "Only flagged emails",
+        LIST,
+    ),
+    after(
+        'F',
+        &[ch('a')],
+        KeyAction::FilterAttachments,
+        "Only emails with attachments",
+        LIST,
+    ),
+    after(
+        'F',
+        &[ch('c')],
+        KeyAction::ClearFilters,
+        "Clear the quick filters",
+        LIST,
+    ),
+    bind(
+        &[ch('D')],
+        KeyAction::Drafts,
+        "Drafts, Enter resumes one",
+        LIST,
+    ),
+    bind(
+        &[ch('J')],
+        KeyAction::ReportJunk,
+        "Report as junk or phishing",
+        MAIL,
+    ),
+    bind(
+        &[ch('i')],
+        KeyAction::RespondToMeeting,
+        "Accept, tentatively accept or decline a meeting request",
+        MAIL,
+    ),
+    bind(
+        &[ch('T')],
+        KeyAction::ThreadActions,
+        "Archive, mark read, mute or delete the whole thread",
+        MAIL,
+    ),
+    bind(
+        &[ch('u')],
+        KeyAction::Undo,
+        "Undo the last action on many emails or a thread",
+        MAIL,
+    ),
+    after('y', &[ch('y')], KeyAction::CopyBody, "Copy the body", MAIL),
+    after(
+        'y',
+        &[ch('s')],
+        KeyAction::CopySender,
+        "Copy the sender's address",
+        MAIL,
+    ),
+    after(
+        'y',
+        &[ch('i')],
+        KeyAction::CopyMessageId,
+        "Copy the Message-ID",
+        MAIL,
+    ),
+    bind(
+        &[ch('s')],
+        KeyAction::AddressMenu,
+        "Address actions for the sender and recipients",
+        MAIL,
+    ),
+    bind(
+        &[ch('@')],
+        KeyAction::Directory,
+        "Look up a colleague's address in the directory",
+        MAIL,
+    ),
+    bind(
+        &[ch('H')],
+        KeyAction::Headers,
+        "Show the raw headers of the selected email",
+        MAIL,
+    ),
+    bind(
+        &[ch('S')],
+        KeyAction::SaveAttachments,
+        "Save the attachments to the download directory",
+        MAIL,
+    ),
+    bind(
+        &[ch('W')],
+        KeyAction::OpenInBrowser,
+        "Open the email's HTML in the browser",
+        MAIL,
+    ),
+    bind(
+        &[ch('V')],
+        KeyAction::CycleBodyView,
+        "Switch the open email between plain text, HTML and source",
+        READING,
+    ),
+    bind(
+        &[ch('Q')],
+        KeyAction::ToggleQuotes,
+        "Show quoted reply text in full, or collapse it again",
+        MAIL,
+    ),
+    bind(
+        &[ch('L')],
+        KeyAction::Links,
+        "List the links in the email to open or copy one",
+        READING,
+    ),
+    bind(
+        &[ch(';')],
+        KeyAction::Hints,
+        "Label the links and attachments to open one by typing its label",
+        MAIL,
+    ),
+    bind(
+        &[ch('*')],
+        KeyAction::SenderFilter,
+        "Show only mail from this sender (again to show all)",
+        MAIL,
+    ),
+    bind(
+        &[ch('&')],
+        KeyAction::AttachmentFilter,
+        "Show only mail with attachments (again to show all)",
+        LIST,
+    ),
+    bind(
+        &[ch('w')],
+        KeyAction::DateRange,
+        "Pick the dates to fetch mail for",
+        LIST,
+    ),
+    bind(
+        &[ch('[')],
+        KeyAction::PreviousQuarter,
+        "Fetch the previous quarter",
+        LIST,
+    ),
+    bind(
+        &[ch(']')],
+        KeyAction::NextQuarter,
+        "Fetch the next quarter",
+        LIST,
+    ),
+    bind(
+        &[ch('E')],
+        KeyAction::RelativeDates,
+        "Show dates as how long ago (\"5m ago\", \"Tue\") or in full",
+        LIST,
+    ),
+    bind(
+        &[ch('o')],
+        KeyAction::Sort,
+        "Sort by date, sender, subject, size or importance",
+        LIST,
+    ),
+    bind(
+        &[Key::Plain(KeyCode::Tab)],
+        KeyAction::ToggleThread,
+        "Expand or collapse the selected conversation",
+        LIST,
+    ),
+    bind(
+        &[Key::Digits],
+        KeyAction::OpenView,
+        "Switch to a configured view",
+        LIST,
+    ),
+    bind(&[ch('0')], KeyAction::Inbox, "Back to the inbox", LIST),
+    bind(
+        &[ch('b')],
+        KeyAction::ToggleSidebar,
+        "Show or hide the folder sidebar",
+        &[Context::List, Context::Sidebar],
+    ),
+    bind(
+        &[ch('<')],
+        KeyAction::NarrowList,
+        "Narrow the email list",
+        MAIL,
+    ),
+    bind(
+        &[ch('>')],
+        KeyAction::WidenList,
+        "Widen the email list",
+        MAIL,
+    ),
+    bind(
+        &[ch('z')],
+        KeyAction::CycleLayout,
+        "Panes side by side, list above message, or one at a time",
+        MAIL,
+    ),
+    bind(
+        &[ch(':')],
+        KeyAction::CommandLine,
+        "Run a command (:search, :folder, :move, :archive, :q...), Tab completes",
+        LIST,
+    ),
+    bind(
+        &[ch('!')],
+        KeyAction::SyncHealth,
+        "Retry accounts that failed to sync",
+        LIST,
+    ),
+    bind(
+        &[ch('x')],
+        KeyAction::DismissBanner,
+        "Dismiss the sync failure banner",
+        LIST,
+    ),
+    bind(&[ch('M')], KeyAction::Folders, "Manage folders", LIST),
+    bind(
+        &[ch('U')],
+        KeyAction::Recoverable,
+        "Recover hard-deleted emails",
+        LIST,
+    ),
+    bind(
+        &[ch('O')],
+        KeyAction::OutOfOffice,
+        "View and set automatic replies (Out-of-Office)",
+        LIST,
+    ),
+    bind(
+        &[ch('t')],
+        KeyAction::Tasks,
+        "Show tasks from the Tasks folder",
+        LIST,
+    ),
+    bind(
+        &[ch('P')],
+        KeyAction::Outbox,
+        "Outbox: retry, edit or discard unsent mail",
+        LIST,
+    ),
+    bind(
+        &[Key::Ctrl('s')],
+        KeyAction::Send,
+        "Send the message",
+        COMPOSE,
+    ),
+    bind(
+        &[Key::Ctrl('d')],
+        KeyAction::SaveDraft,
+        "Save the message to Drafts",
+        COMPOSE,
+    ),
+    bind(
+        &[Key::Ctrl('e')],
+        KeyAction::EditInEditor,
+        "Edit the body in $EDITOR",
+        COMPOSE,
+    ),
+    bind(
+        &[Key::Ctrl('a')],
+        KeyAction::Attach,
+        "Attach a file",
+        COMPOSE,
+    ),
+    bind(
+        &[Key::Ctrl('x')],
+        KeyAction::RemoveAttachment,
+        "Remove the last attachment",
+        COMPOSE,
+    ),
+    bind(
+        &[Key::Ctrl('t')],
+        KeyAction::ToggleSignature,
+        "Add or leave out the signature",
+        COMPOSE,
+    ),
+    bind(
+        &[ESC],
+        KeyAction::DiscardMessage,
+        "Close the message, asking before discarding it",
+        COMPOSE,
+    ),
+    bind(&[ch('?')], KeyAction::Help, "Show this help", BROWSING),
+    bind(
+        &[Key::Plain(KeyCode::F(1))],
+        KeyAction::Help,
+        "Show this help",
+        COMPOSE,
+    ),
+    bind(&[ch('q')], KeyAction::Quit, "Quit application", BROWSING),
+];
+
+/// The bindings that work in `context`.
+pub fn bindings_for(context: Context) -> impl Iterator<Item = &'static Binding> {
+    BINDINGS
+        .iter()
+        .filter(move |binding| binding.contexts.contains(&context))
+}
+
+/// What `key`, typed after `prefix`, does in `context`.
+pub fn lookup(context: Context, prefix: Option<char>, key: &KeyEvent) -> Option<KeyAction> {
+    bindings_for(context)
+        .find(|binding| binding.prefix == prefix && binding.keys.iter().any(|k| k.matches(key)))
+        .map(|binding| binding.action)
+}
+
+/// Whether `key` starts a sequence of two keys in `context`, such as `g` of `gg`.
+pub fn is_prefix(context: Context, key: &KeyEvent) -> bool {
+    !key.modifiers.contains(KeyModifiers::CONTROL)
+        && bindings_for(context)
+            .any(|binding| binding.prefix.is_some_and(|p| key.code == KeyCode::Char(p)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_keys_do_what_the_keymap_says_where_they_are_pressed() {
+        let j = press(KeyCode::Char('j'));
+        assert_eq!(lookup(Context::List, None, &j), Some(KeyAction::NextEmail));
+        assert_eq!(
+            lookup(Context::Reading, None, &j),
+            Some(KeyAction::ScrollDown)
+        );
+        assert_eq!(
+            lookup(Context::Sidebar, None, &j),
+            Some(KeyAction::NextFolder)
+        );
+
+        let t = press(KeyCode::Char('t'));
+        assert_eq!(lookup(Context::List, None, &t), Some(KeyAction::Tasks));
+        assert_eq!(
+            lookup(Context::List, Some('g'), &t),
+            Some(KeyAction::NextTab)
+        );
+        assert_eq!(
+            lookup(Context::List, Some('g'), &press(KeyCode::Char('4'))),
+            Some(KeyAction::GoToTab)
+        );
+
+        // F forwards straight away while reading, and starts the quick filters in the list
+        let f = press(KeyCode::Char('F'));
+        assert_eq!(lookup(Context::Reading, None, &f), Some(KeyAction::Forward));
+        assert_eq!(lookup(Context::List, None, &f), None);
+        assert!(is_prefix(Context::List, &f));
+        assert!(!is_prefix(Context::List, &t));
+
+        let save = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(lookup(Context::Compose, None, &save), Some(KeyAction::Send));
+        assert_eq!(lookup(Context::List, None, &save), None);
+    }
+
+    #[test]
+    fn test_labels_spell_out_prefixes_and_modifiers() {
+        let labels: Vec<String> = bindings_for(Context::List).map(Binding::label).collect();
+        assert!(labels.contains(&"j ↓".to_string()));
+        assert!(labels.contains(&"g1-9".to_string()));
+        assert!(labels.contains(&"Fu".to_string()));
+        let compose: Vec<String> = bindings_for(Context::Compose).map(Binding::label).collect();
+        assert!(compose.contains(&"Ctrl-S".to_string()));
+        assert!(compose.contains(&"Esc".to_string()));
+    }
+}
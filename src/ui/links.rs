@@ -0,0 +1,102 @@
+use std::ops::Range;
+
+/// Starts of the links found in text, `www.` ones opening over HTTPS.
+const SCHEMES: [&str; 4] = ["https://", "http://", "mailto:", "www."];
+
+/// The web and mail links in `text`, each once, in the order they appear.
+pub fn extract(text: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for (_, link) in find(text) {
+        if !links.contains(&link) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// Where each link is in `text`, with the address it opens.
+pub fn find(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut links = Vec::new();
+    let mut position = 0;
+    while position < text.len() {
+        let rest = &text[position..];
+        let at_word_start = text[..position]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric() && c != '/' && c != '.');
+        let scheme = SCHEMES
+            .iter()
+            .find(|scheme| at_word_start && starts_with_ignore_case(rest, scheme));
+        let Some(scheme) = scheme else {
+            position += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\'' | '`'))
+            .unwrap_or(rest.len());
+        let link = trim_punctuation(&rest[..end]);
+        let start = position;
+        position += end.max(1);
+        if link.len() <= scheme.len() {
+            continue;
+        }
+        let address = if *scheme == "www." {
+            format!("https://{}", link)
+        } else {
+            link.to_string()
+        };
+        links.push((start..start + link.len(), address));
+    }
+    links
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// `link` without the punctuation of the sentence around it, keeping closing
+/// brackets that belong to it, as in `https://en.wikipedia.org/wiki/Rust_(language)`.
+fn trim_punctuation(mut link: &str) -> &str {
+    loop {
+        let Some(last) = link.chars().next_back() else {
+            return link;
+        };
+        let open = match last {
+            ')' => '(',
+            ']' => '[',
+            '}' => '{',
+            '.' | ',' | ';' | ':' | '!' | '?' => {
+                link = &link[..link.len() - 1];
+                continue;
+            }
+            _ => return link,
+        };
+        if link.matches(open).count() >= link.matches(last).count() {
+            return link;
+        }
+        link = &link[..link.len() - 1];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_links_once_without_punctuation() {
+        let text = "See https://example.com/notes. Or (www.example.org/faq), \
+                    mail <mailto:team@example.com>, https://en.wikipedia.org/wiki/Rust_(language)\n\
+                    [1] https://example.com/notes";
+        assert_eq!(
+            extract(text),
+            vec![
+                "https://example.com/notes",
+                "https://www.example.org/faq",
+                "mailto:team@example.com",
+                "https://en.wikipedia.org/wiki/Rust_(language)",
+            ]
+        );
+    }
+}
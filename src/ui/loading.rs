@@ -0,0 +1,178 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::config::TimeoutConfig;
+
+/// A long-running backend operation that can be in flight independently of the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    ListSync,
+    ServerSearch,
+    BodyFetch,
+    AttachmentDownload,
+    Send,
+    /// Archiving, moving, deleting or marking emails, and other changes to the mailbox
+    Update,
+}
+
+impl Operation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Operation::ListSync => "Syncing emails",
+            Operation::ServerSearch => "Searching server",
+            Operation::BodyFetch => "Loading message",
+            Operation::AttachmentDownload => "Downloading attachment",
+            Operation::Send => "Sending",
+            Operation::Update => "Saving changes",
+        }
+    }
+}
+
+/// Tracks which operations are currently running, how many requests of each
+/// and since when.
+#[derive(Debug, Default)]
+pub struct LoadingState {
+    active: HashMap<Operation, (Instant, usize)>,
+}
+
+impl LoadingState {
+    /// Counts one more request of `operation`. Every call is matched by one
+    /// call to `finish`.
+    pub fn start(&mut self, operation: Operation) {
+        self.active
+            .entry(operation)
+            .or_insert_with(|| (Instant::now(), 0))
+            .1 += 1;
+    }
+
+    /// Counts one request of `operation` as done, and the operation as no
+    /// longer running once it was the last.
+    pub fn finish(&mut self, operation: Operation) {
+        let Some((_, count)) = self.active.get_mut(&operation) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.active.remove(&operation);
+        }
+    }
+
+    pub fn is_loading(&self, operation: Operation) -> bool {
+        self.active.contains_key(&operation)
+    }
+
+    /// Running operations, oldest first.
+    pub fn active(&self) -> Vec<Operation> {
+        let mut operations: Vec<(Operation, Instant)> = self
+            .active
+            .iter()
+            .map(|(op, (since, _))| (*op, *since))
+            .collect();
+        operations.sort_by_key(|(_, since)| *since);
+        operations.into_iter().map(|(op, _)| op).collect()
+    }
+}
+
+/// How long each operation may run before the watchdog cancels it.
+#[derive(Debug)]
+pub struct Timeouts {
+    default: Duration,
+    overrides: HashMap<Operation, Duration>,
+}
+
+impl Timeouts {
+    pub fn new(config: &TimeoutConfig) -> Self {
+        let overrides = [
+            (Operation::ListSync, config.sync),
+            (Operation::ServerSearch, config.search),
+            (Operation::BodyFetch, config.body),
+            (Operation::AttachmentDownload, config.attachment),
+            (Operation::Send, config.send),
+            (Operation::Update, config.update),
+        ]
+        .into_iter()
+        .filter_map(|(op, secs)| secs.map(|secs| (op, Duration::from_secs(secs))))
+        .collect();
+        Self {
+            default: Duration::from_secs(config.default),
+            overrides,
+        }
+    }
+
+    pub fn get(&self, operation: Operation) -> Duration {
+        self.overrides
+            .get(&operation)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// The error of a request the watchdog gave up on.
+#[derive(Debug)]
+pub struct TimedOut(pub Duration);
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Prints "30s", or "250ms" for limits under a second
+        write!(f, "request timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Awaits a backend request, cancelling it by dropping it once `limit` has passed.
+pub async fn with_timeout<R>(
+    limit: Duration,
+    request: impl Future<Output = Result<R>>,
+) -> Result<R> {
+    match tokio::time::timeout(limit, request).await {
+        Ok(result) => result,
+        Err(_) => Err(TimedOut(limit).into()),
+    }
+}
+
+pub fn is_timeout(error: &anyhow::Error) -> bool {
+    error.is::<TimedOut>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_timeout_cancels_hung_request() {
+        let hung = std::future::pending::<Result<()>>();
+        let error = with_timeout(Duration::from_millis(10), hung)
+            .await
+            .unwrap_err();
+
+        assert!(is_timeout(&error));
+        assert_eq!(
+            TimedOut(Duration::from_secs(30)).to_string(),
+            "request timed out after 30s"
+        );
+        assert_eq!(error.to_string(), "request timed out after 10ms");
+    }
+
+    #[test]
+    fn test_operation_runs_until_its_last_request_finishes() {
+        let mut loading = LoadingState::default();
+        loading.start(Operation::AttachmentDownload);
+        loading.start(Operation::AttachmentDownload);
+        loading.start(Operation::Send);
+
+        loading.finish(Operation::AttachmentDownload);
+        assert!(loading.is_loading(Operation::AttachmentDownload));
+        assert_eq!(
+            loading.active(),
+            vec![Operation::AttachmentDownload, Operation::Send]
+        );
+
+        loading.finish(Operation::AttachmentDownload);
+        assert!(!loading.is_loading(Operation::AttachmentDownload));
+        assert_eq!(loading.active(), vec![Operation::Send]);
+    }
+}
@@ -1,2 +1,43 @@
+pub mod action;
 pub mod app;
+pub mod browser;
+pub mod clipboard;
+pub mod commands;
+pub mod compose;
+pub mod date_picker;
+pub mod dates;
+pub mod directory;
+pub mod discovery;
+pub mod drafts;
+pub mod editor;
+pub mod file_prompt;
+pub mod find;
+pub mod folders;
+pub mod help;
+pub mod hints;
+pub mod images;
+pub mod keymap;
+pub mod links;
+pub mod loading;
+pub mod mouse;
+pub mod move_picker;
+pub mod out_of_office;
+pub mod outbox;
+pub mod pager;
+pub mod png;
+pub mod quotes;
+pub mod recoverable;
+pub mod render;
+pub mod scrollbar;
+pub mod search_history;
+pub mod selection;
+pub mod sidebar;
+pub mod status;
+pub mod tabs;
+pub mod tasks;
+pub mod terminal;
+pub mod theme;
+pub mod threads;
 pub mod view;
+pub mod views;
+pub mod width;
@@ -0,0 +1,130 @@
+use tui::layout::Rect;
+
+/// Lines the content pane scrolls per notch of the mouse wheel.
+pub const SCROLL_LINES: isize = 3;
+
+/// What was drawn where the mouse was clicked or scrolled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Sidebar,
+    /// An email of the list, by position
+    Email(usize),
+    /// The list, around its emails
+    List,
+    Content,
+}
+
+/// Where the panes and the emails of the list were last drawn, recorded by
+/// the view on every draw.
+#[derive(Clone, Debug, Default)]
+pub struct PaneAreas {
+    pub sidebar: Option<Rect>,
+    pub list: Option<Rect>,
+    pub content: Option<Rect>,
+    /// The rows of each email shown, with its position in the list
+    pub emails: Vec<(Rect, usize)>,
+}
+
+impl PaneAreas {
+    /// What is drawn at `column` and `row` of the terminal.
+    pub fn target(&self, column: u16, row: u16) -> Option<Target> {
+        let contains = |area: &Rect| {
+            (area.x..area.x + area.width).contains(&column)
+                && (area.y..area.y + area.height).contains(&row)
+        };
+        if let Some((_, position)) = self.emails.iter().find(|(area, _)| contains(area)) {
+            return Some(Target::Email(*position));
+        }
+        if self.sidebar.as_ref().is_some_and(contains) {
+            Some(Target::Sidebar)
+        } else if self.list.as_ref().is_some_and(contains) {
+            Some(Target::List)
+        } else if self.content.as_ref().is_some_and(contains) {
+            Some(Target::Content)
+        } else {
+            None
+        }
+    }
+
+    /// Records the list drawn in `area` with items of these heights, those of
+    /// emails with their position, scrolled to show the item `selected`.
+    pub fn record_list(
+        &mut self,
+        area: Rect,
+        items: &[(usize, Option<usize>)],
+        selected: Option<usize>,
+    ) {
+        self.list = Some(area);
+        self.emails.clear();
+        // Inside the borders
+        let top = area.y + 1;
+        let bottom = top + area.height.saturating_sub(2);
+        let heights: Vec<usize> = items.iter().map(|(height, _)| *height).collect();
+        let start = first_visible(&heights, selected, (bottom - top).into());
+        let mut y = top;
+        for (height, position) in items.iter().skip(start) {
+            if y >= bottom {
+                break;
+            }
+            let height = (*height as u16).min(bottom - y);
+            if let Some(position) = position {
+                let row = Rect::new(area.x + 1, y, area.width.saturating_sub(2), height);
+                self.emails.push((row, *position));
+            }
+            y += height;
+        }
+    }
+}
+
+/// The first item a `List` draws from the top when it scrolls just enough to
+/// show `selected`, the way tui lays it out.
+fn first_visible(heights: &[usize], selected: Option<usize>, max_height: usize) -> usize {
+    let Some(selected) = selected.filter(|_| !heights.is_empty()) else {
+        return 0;
+    };
+    let selected = selected.min(heights.len() - 1);
+    let mut start = 0;
+    let mut height = 0;
+    for &item in &heights[..=selected] {
+        height += item;
+        while height > max_height && start < selected {
+            height -= heights[start];
+            start += 1;
+        }
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clicks_find_the_emails_scrolled_into_view() {
+        let mut areas = PaneAreas {
+            content: Some(Rect::new(30, 0, 50, 12)),
+            ..PaneAreas::default()
+        };
+        // A section header, two emails, another header and a third email
+        let items = [
+            (1, None),
+            (4, Some(0)),
+            (4, Some(1)),
+            (1, None),
+            (4, Some(2)),
+        ];
+
+        areas.record_list(Rect::new(0, 0, 30, 12), &items, Some(1));
+        assert_eq!(areas.target(5, 2), Some(Target::Email(0)));
+        assert_eq!(areas.target(5, 8), Some(Target::Email(1)));
+        assert_eq!(areas.target(5, 10), Some(Target::List));
+        assert_eq!(areas.target(40, 3), Some(Target::Content));
+        assert_eq!(areas.target(90, 3), None);
+
+        // Selecting the third email scrolls the first two headers and emails away
+        areas.record_list(Rect::new(0, 0, 30, 10), &items, Some(4));
+        assert_eq!(areas.target(5, 1), Some(Target::List));
+        assert_eq!(areas.target(5, 2), Some(Target::Email(2)));
+        assert_eq!(areas.emails.len(), 1);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::email::Folder;
+
+/// State of the popup picking a folder to move the selected email to.
+pub struct MovePicker {
+    pub account: String,
+    pub folders: Vec<Folder>,
+    pub query: String,
+    /// Position in `matches`
+    pub selected: usize,
+}
+
+impl MovePicker {
+    pub fn new(account: String, folders: Vec<Folder>) -> Self {
+        Self {
+            account,
+            folders,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// The folders whose name contains the query's characters in order, best
+    /// matches first.
+    pub fn matches(&self) -> Vec<&Folder> {
+        let mut matches: Vec<(usize, &Folder)> = self
+            .folders
+            .iter()
+            .filter_map(|folder| {
+                fuzzy_score(&self.query, &folder.name).map(|score| (score, folder))
+            })
+            .collect();
+        matches.sort_by_key(|(score, _)| *score);
+        matches.into_iter().map(|(_, folder)| folder).collect()
+    }
+
+    pub fn selected_folder(&self) -> Option<&Folder> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    pub fn select_next(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + count - 1) % count;
+        }
+    }
+}
+
+/// How well `candidate` matches the characters of `query` in order, ignoring
+/// case, lower is better: the characters skipped between the first and last
+/// match, plus where the match starts. `None` if they don't all appear.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut position = 0;
+    let mut first = None;
+    let mut skipped = 0;
+    for c in query.to_lowercase().chars() {
+        let offset = candidate[position..].iter().position(|&other| other == c)?;
+        if first.is_none() {
+            first = Some(position + offset);
+        } else {
+            skipped += offset;
+        }
+        position += offset + 1;
+    }
+    Some(skipped + first.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(name: &str) -> Folder {
+        Folder {
+            id: name.to_lowercase(),
+            name: name.to_string(),
+            unread: 0,
+        }
+    }
+
+    #[test]
+    fn test_matches_rank_contiguous_matches_first() {
+        let mut picker = MovePicker::new(
+            "work".to_string(),
+            vec![
+                folder("Inbox"),
+                folder("Archive/Receipts"),
+                folder("Projects"),
+                folder("Reports"),
+            ],
+        );
+        for c in "rep".chars() {
+            picker.push(c);
+        }
+
+        let names: Vec<&str> = picker
+            .matches()
+            .iter()
+            .map(|folder| folder.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Reports", "Archive/Receipts"]);
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+
+use crate::email::{OofSettings, OofState};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OofField {
+    State,
+    Start,
+    End,
+    InternalReply,
+    ExternalReply,
+}
+
+impl OofField {
+    pub const ALL: [OofField; 5] = [
+        OofField::State,
+        OofField::Start,
+        OofField::End,
+        OofField::InternalReply,
+        OofField::ExternalReply,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OofField::State => "Status",
+            OofField::Start => "Start (YYYY-MM-DD)",
+            OofField::End => "End (YYYY-MM-DD)",
+            OofField::InternalReply => "Internal reply",
+            OofField::ExternalReply => "External reply",
+        }
+    }
+}
+
+pub fn state_label(state: OofState) -> &'static str {
+    match state {
+        OofState::Disabled => "Off",
+        OofState::Enabled => "On",
+        OofState::Scheduled => "Scheduled",
+    }
+}
+
+/// Editable copy of an account's Out-of-Office settings.
+pub struct OofForm {
+    pub account: String,
+    pub state: OofState,
+    pub start: String,
+    pub end: String,
+    pub internal_reply: String,
+    pub external_reply: String,
+    pub field: OofField,
+}
+
+impl OofForm {
+    pub fn new(account: String, settings: &OofSettings) -> Self {
+        let format_date = |date: Option<DateTime<Utc>>| {
+            date.map(|d| d.with_timezone(&Local).format(DATE_FORMAT).to_string())
+                .unwrap_or_default()
+        };
+        Self {
+            account,
+            state: settings.state,
+            start: format_date(settings.start),
+            end: format_date(settings.end),
+            internal_reply: settings.internal_reply.clone(),
+            external_reply: settings.external_reply.clone(),
+            field: OofField::State,
+        }
+    }
+
+    /// Builds the settings to save, reading the range as whole local days.
+    pub fn to_settings(&self) -> Result<OofSettings> {
+        let (start, end) = if self.state == OofState::Scheduled {
+            let start = parse_date(&self.start, NaiveTime::MIN)?;
+            let end = parse_date(&self.end, NaiveTime::from_hms_opt(23, 59, 59).unwrap())?;
+            (Some(start), Some(end))
+        } else {
+            (None, None)
+        };
+        Ok(OofSettings {
+            state: self.state,
+            start,
+            end,
+            internal_reply: self.internal_reply.clone(),
+            external_reply: self.external_reply.clone(),
+        })
+    }
+
+    pub fn next_field(&mut self) {
+        let idx = OofField::ALL
+            .iter()
+            .position(|f| *f == self.field)
+            .unwrap_or(0);
+        self.field = OofField::ALL[(idx + 1) % OofField::ALL.len()];
+    }
+
+    pub fn previous_field(&mut self) {
+        let idx = OofField::ALL
+            .iter()
+            .position(|f| *f == self.field)
+            .unwrap_or(0);
+        self.field = OofField::ALL[(idx + OofField::ALL.len() - 1) % OofField::ALL.len()];
+    }
+
+    pub fn cycle_state(&mut self) {
+        self.state = match self.state {
+            OofState::Disabled => OofState::Enabled,
+            OofState::Enabled => OofState::Scheduled,
+            OofState::Scheduled => OofState::Disabled,
+        };
+    }
+
+    /// The text of a field, `None` for the status which is cycled rather than typed.
+    pub fn text(&self, field: OofField) -> Option<&str> {
+        match field {
+            OofField::State => None,
+            OofField::Start => Some(&self.start),
+            OofField::End => Some(&self.end),
+            OofField::InternalReply => Some(&self.internal_reply),
+            OofField::ExternalReply => Some(&self.external_reply),
+        }
+    }
+
+    pub fn focused_text_mut(&mut self) -> Option<&mut String> {
+        match self.field {
+            OofField::State => None,
+            OofField::Start => Some(&mut self.start),
+            OofField::End => Some(&mut self.end),
+            OofField::InternalReply => Some(&mut self.internal_reply),
+            OofField::ExternalReply => Some(&mut self.external_reply),
+        }
+    }
+}
+
+fn parse_date(text: &str, time: NaiveTime) -> Result<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(text.trim(), DATE_FORMAT)
+        .map_err(|_| anyhow!("'{}' is not a date like 2024-07-01", text.trim()))?;
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|local| local.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("'{}' doesn't exist in the local timezone", text.trim()))
+}
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use super::compose::MessageForm;
+use crate::email::{Attachment, OutgoingEmail};
+use crate::outbox::Outbox;
+
+/// Editable copy of a queued or failed message, saved back before retrying.
+pub struct MessageEdit {
+    pub id: u64,
+    pub form: MessageForm,
+    /// The message as queued, for what the form has no field for
+    original: OutgoingEmail,
+}
+
+impl MessageEdit {
+    pub fn new(id: u64, message: &OutgoingEmail) -> Self {
+        Self {
+            id,
+            form: MessageForm::from_message(message),
+            original: message.clone(),
+        }
+    }
+
+    pub fn attachments(&self) -> &[Attachment] {
+        &self.original.attachments
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.original.files
+    }
+
+    pub fn to_message(&self) -> OutgoingEmail {
+        let edited = self.form.to_message();
+        OutgoingEmail {
+            to: edited.to,
+            cc: edited.cc,
+            subject: edited.subject,
+            body: edited.body,
+            ..self.original.clone()
+        }
+    }
+}
+
+/// State of the Outbox popup.
+#[derive(Default)]
+pub struct OutboxView {
+    pub selected: usize,
+    pub edit: Option<MessageEdit>,
+}
+
+impl OutboxView {
+    /// Id of the selected message.
+    pub fn selected_id(&self, outbox: &Outbox) -> Option<u64> {
+        outbox.entries.get(self.selected).map(|entry| entry.id)
+    }
+
+    pub fn select_next(&mut self, outbox: &Outbox) {
+        if !outbox.entries.is_empty() {
+            self.selected = (self.selected + 1) % outbox.entries.len();
+        }
+    }
+
+    pub fn select_previous(&mut self, outbox: &Outbox) {
+        if !outbox.entries.is_empty() {
+            self.selected = (self.selected + outbox.entries.len() - 1) % outbox.entries.len();
+        }
+    }
+
+    /// Keeps the selection in range after messages left the outbox.
+    pub fn clamp(&mut self, outbox: &Outbox) {
+        self.selected = self.selected.min(outbox.entries.len().saturating_sub(1));
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::action::Input;
+use super::terminal;
+
+/// Shows `text` in `$PAGER`, `less` by default, handing it the terminal until it exits.
+pub fn show(input: &mut Input, text: &str) -> Result<()> {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+
+    terminal::hand_over(input, || run(&pager, text))
+}
+
+fn run(pager: &str, text: &str) -> Result<()> {
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start {}", pager))?;
+
+    // Quitting before the end closes the pipe, which isn't an error
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{} exited with {}", pager, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use anyhow::{bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// A decoded image, four bytes per pixel, row by row.
+pub struct Rgba {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Rgba {
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let at = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[at],
+            self.pixels[at + 1],
+            self.pixels[at + 2],
+            self.pixels[at + 3],
+        ]
+    }
+}
+
+/// The width and height of a PNG, read from its header without decoding it.
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    if !data.starts_with(SIGNATURE) || data.len() < 24 || &data[12..16] != b"IHDR" {
+        bail!("Not a PNG image");
+    }
+    Ok((be_u32(&data[16..20]), be_u32(&data[20..24])))
+}
+
+/// Decodes an 8-bit, non-interlaced PNG of any color type.
+pub fn decode(data: &[u8]) -> Result<Rgba> {
+    let (width, height) = dimensions(data)?;
+    let mut rest = &data[SIGNATURE.len()..];
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+    while rest.len() >= 12 {
+        let length = be_u32(&rest[..4]) as usize;
+        let kind = &rest[4..8];
+        let Some(chunk) = rest.get(8..8 + length) else {
+            bail!("Truncated PNG chunk");
+        };
+        match kind {
+            b"IHDR" if chunk.len() >= 13 => header = Some((chunk[8], chunk[9], chunk[12])),
+            b"PLTE" => palette = chunk,
+            b"tRNS" => transparency = chunk,
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Data, then the checksum, which is left unchecked
+        rest = &rest[(8 + length + 4).min(rest.len())..];
+    }
+    let Some((depth, color, interlace)) = header else {
+        bail!("PNG without a header");
+    };
+    if depth != 8 || interlace != 0 {
+        bail!("Only 8-bit, non-interlaced PNG images are supported");
+    }
+    let channels = match color {
+        0 | 3 => 1,
+        4 => 2,
+        2 => 3,
+        6 => 4,
+        _ => bail!("Unknown PNG color type {}", color),
+    };
+
+    let mut raw = Vec::new();
+    ZlibDecoder::new(compressed.as_slice())
+        .read_to_end(&mut raw)
+        .context("Failed to inflate the PNG data")?;
+    let stride = width as usize * channels;
+    if raw.len() < (stride + 1) * height as usize {
+        bail!("PNG data is shorter than the image");
+    }
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    let mut previous = vec![0u8; stride];
+    for row in raw.chunks_exact(stride + 1).take(height as usize) {
+        let line = unfilter(row[0], &row[1..], &previous, channels)?;
+        for sample in line.chunks_exact(channels) {
+            let rgba = match color {
+                0 => [sample[0], sample[0], sample[0], 255],
+                4 => [sample[0], sample[0], sample[0], sample[1]],
+                2 => [sample[0], sample[1], sample[2], 255],
+                6 => [sample[0], sample[1], sample[2], sample[3]],
+                _ => {
+                    let index = sample[0] as usize;
+                    let Some(rgb) = palette.get(index * 3..index * 3 + 3) else {
+                        bail!("PNG palette index out of range");
+                    };
+                    let alpha = transparency.get(index).copied().unwrap_or(255);
+                    [rgb[0], rgb[1], rgb[2], alpha]
+                }
+            };
+            pixels.extend_from_slice(&rgba);
+        }
+        previous = line;
+    }
+    Ok(Rgba {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Undoes the filter a scanline was written with, given the one above it.
+fn unfilter(filter: u8, line: &[u8], previous: &[u8], channels: usize) -> Result<Vec<u8>> {
+    let mut out = line.to_vec();
+    for i in 0..out.len() {
+        let left = if i >= channels { out[i - channels] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= channels {
+            previous[i - channels]
+        } else {
+            0
+        };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => bail!("Unknown PNG filter {}", filter),
+        };
+        out[i] = out[i].wrapping_add(predicted);
+    }
+    Ok(out)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let to_left = (estimate - left as i16).abs();
+    let to_up = (estimate - up as i16).abs();
+    let to_up_left = (estimate - up_left as i16).abs();
+    if to_left <= to_up && to_left <= to_up_left {
+        left
+    } else if to_up <= to_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        // The checksum isn't checked
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    #[test]
+    fn test_decode_filtered_rgb() {
+        // 2x2 RGB: red, green / blue, white, the second row filtered with Up
+        let raw = [
+            0, 255, 0, 0, 0, 255, 0, //
+            2, 1, 0, 255, 255, 0, 255,
+        ];
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw).unwrap();
+        let mut header = Vec::new();
+        header.extend_from_slice(&2u32.to_be_bytes());
+        header.extend_from_slice(&2u32.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = SIGNATURE.to_vec();
+        png.extend(chunk(b"IHDR", &header));
+        png.extend(chunk(b"IDAT", &encoder.finish().unwrap()));
+        png.extend(chunk(b"IEND", &[]));
+
+        assert_eq!(dimensions(&png).unwrap(), (2, 2));
+        let image = decode(&png).unwrap();
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(1, 0), [0, 255, 0, 255]);
+        assert_eq!(image.pixel(0, 1), [0, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 1), [255, 255, 255, 255]);
+    }
+}
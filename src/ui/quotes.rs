@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use tui::style::Style;
+use tui::text::{Span, Spans};
+
+/// Quoted blocks shorter than this stay shown, reading as fast as their marker.
+const MIN_COLLAPSED_LINES: usize = 3;
+
+/// Whether `line` quotes earlier mail, with `>` in plain text or the `│` that
+/// rendered HTML and Markdown quotes start with.
+fn is_quoted(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('>') || line.starts_with('│')
+}
+
+/// The lines quoting earlier mail: runs of quoted lines, each with the
+/// "On … wrote:" introducing it, which mail clients may wrap over two lines.
+pub fn quoted_blocks(lines: &[String]) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+    let mut idx = 0;
+    while idx < lines.len() {
+        if !is_quoted(&lines[idx]) {
+            idx += 1;
+            continue;
+        }
+        let mut start = idx;
+        // Blank lines between quoted paragraphs belong to the quote
+        while idx < lines.len()
+            && (is_quoted(&lines[idx])
+                || lines[idx].trim().is_empty() && lines.get(idx + 1).is_some_and(|l| is_quoted(l)))
+        {
+            idx += 1;
+        }
+
+        let mut before = start;
+        while before > 0 && lines[before - 1].trim().is_empty() {
+            before -= 1;
+        }
+        if before > 0 && lines[before - 1].trim_end().ends_with("wrote:") {
+            before -= 1;
+            if !lines[before].trim_start().starts_with("On ")
+                && before > 0
+                && lines[before - 1].trim_start().starts_with("On ")
+            {
+                before -= 1;
+            }
+            start = before;
+        }
+        if idx - start >= MIN_COLLAPSED_LINES {
+            blocks.push(start..idx);
+        }
+    }
+    blocks
+}
+
+/// `lines` with each quoted block replaced by a `[+ 42 quoted lines]` marker.
+pub fn collapse(lines: Vec<Spans>, style: Style) -> Vec<Spans> {
+    let texts: Vec<String> = lines
+        .iter()
+        .map(|line| line.0.iter().map(|span| span.content.as_ref()).collect())
+        .collect();
+    let mut blocks = quoted_blocks(&texts).into_iter().peekable();
+    if blocks.peek().is_none() {
+        return lines;
+    }
+
+    let mut collapsed = Vec::with_capacity(lines.len());
+    for (idx, line) in lines.into_iter().enumerate() {
+        let Some(block) = blocks.peek().filter(|block| block.contains(&idx)) else {
+            collapsed.push(line);
+            continue;
+        };
+        if idx == block.start {
+            collapsed.push(Spans::from(Span::styled(
+                format!("[+ {} quoted lines]", block.len()),
+                style,
+            )));
+        }
+        if idx + 1 == block.end {
+            blocks.next();
+        }
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quotes_collapse_with_their_attribution() {
+        let body = "Sounds good, see you then.\n\
+                    \n\
+                    On Mon, 3 Mar 2025 at 10:02, Bob Smith <bob@company.com>\n\
+                    wrote:\n\
+                    > Can we meet at 3?\n\
+                    >\n\
+                    > Bob\n\
+                    \n\
+                    > ok\n\
+                    Thanks";
+        let lines: Vec<Spans> = body.lines().map(Spans::from).collect();
+        let texts: Vec<String> = body.lines().map(String::from).collect();
+        assert_eq!(quoted_blocks(&texts), vec![2..9]);
+
+        let collapsed: Vec<String> = collapse(lines, Style::default())
+            .iter()
+            .map(|line| line.0.iter().map(|span| span.content.as_ref()).collect())
+            .collect();
+        assert_eq!(
+            collapsed,
+            vec![
+                "Sounds good, see you then.",
+                "",
+                "[+ 7 quoted lines]",
+                "Thanks"
+            ]
+        );
+
+        // A short quote stays as it is
+        let texts: Vec<String> = vec!["> yes".into(), "Agreed".into()];
+        assert!(quoted_blocks(&texts).is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+use crate::email::RecoverableItem;
+
+/// State of the Recoverable Items popup for one account.
+pub struct RecoverableItems {
+    pub account: String,
+    pub items: Vec<RecoverableItem>,
+    pub selected: usize,
+}
+
+impl RecoverableItems {
+    pub fn new(account: String, items: Vec<RecoverableItem>) -> Self {
+        Self {
+            account,
+            items,
+            selected: 0,
+        }
+    }
+
+    pub fn selected_item(&self) -> Option<&RecoverableItem> {
+        self.items.get(self.selected)
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        }
+    }
+
+    /// Drops a restored item from the list, keeping the selection in range.
+    pub fn remove(&mut self, id: &str) {
+        self.items.retain(|item| item.email.id != id);
+        self.selected = self.selected.min(self.items.len().saturating_sub(1));
+    }
+}
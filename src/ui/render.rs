@@ -0,0 +1,691 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+
+use crate::email::{BodyFormat, Email};
+
+/// Turns a message body into the lines shown in the content pane.
+pub trait BodyRenderer {
+    fn render(&self, body: &str) -> Result<Vec<Spans<'static>>>;
+}
+
+/// The renderers by MIME type: external commands from `[renderers]` first, then
+/// the built-in ones, with plain text for everything else.
+pub struct Renderers {
+    external: HashMap<String, ExternalRenderer>,
+}
+
+impl Renderers {
+    pub fn new(commands: &HashMap<String, String>) -> Self {
+        let external = commands
+            .iter()
+            .map(|(content_type, command)| {
+                (
+                    content_type.to_lowercase(),
+                    ExternalRenderer {
+                        command: command.clone(),
+                    },
+                )
+            })
+            .collect();
+        Self { external }
+    }
+
+    pub fn for_type(&self, content_type: &str) -> &dyn BodyRenderer {
+        let content_type = essence(content_type);
+        if let Some(renderer) = self.external.get(&content_type) {
+            return renderer;
+        }
+        match content_type.as_str() {
+            "text/html" => &HtmlRenderer,
+            "text/markdown" | "text/x-markdown" => &MarkdownRenderer,
+            "text/x-diff" | "text/x-patch" | "text/x-diff-patch" => &DiffRenderer,
+            "text/calendar" => &CalendarRenderer,
+            _ => &PlainRenderer,
+        }
+    }
+}
+
+/// The body format a renderer for `content_type` works on.
+pub fn source_format(content_type: &str) -> BodyFormat {
+    if essence(content_type) == "text/html" {
+        BodyFormat::Html
+    } else {
+        BodyFormat::Text
+    }
+}
+
+/// A part of an email the content pane shows instead of the one its type picks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyView {
+    /// The plain text part as it is
+    Plain,
+    /// The HTML part laid out as text
+    Html,
+    /// The message as it was sent, headers included
+    Source,
+}
+
+impl BodyView {
+    /// The view showing what is rendered from `format`.
+    pub fn of(format: BodyFormat) -> Self {
+        match format {
+            BodyFormat::Text => BodyView::Plain,
+            BodyFormat::Html => BodyView::Html,
+            BodyFormat::Mime => BodyView::Source,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            BodyView::Plain => BodyView::Html,
+            BodyView::Html => BodyView::Source,
+            BodyView::Source => BodyView::Plain,
+        }
+    }
+
+    pub fn format(self) -> BodyFormat {
+        match self {
+            BodyView::Plain => BodyFormat::Text,
+            BodyView::Html => BodyFormat::Html,
+            BodyView::Source => BodyFormat::Mime,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BodyView::Plain => "plain text",
+            BodyView::Html => "HTML",
+            BodyView::Source => "source",
+        }
+    }
+}
+
+/// The body format `email` is rendered from: its type's, or HTML when its
+/// plain text part came back empty.
+pub fn body_format(email: &Email) -> BodyFormat {
+    let format = source_format(&email.content_type);
+    let empty_text = email
+        .body
+        .get(BodyFormat::Text)
+        .is_some_and(|text| text.trim().is_empty());
+    if format == BodyFormat::Text && empty_text {
+        BodyFormat::Html
+    } else {
+        format
+    }
+}
+
+/// The type without parameters, e.g. `text/html` for `text/html; charset=utf-8`.
+fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// Shows the body as it is.
+pub struct PlainRenderer;
+
+impl BodyRenderer for PlainRenderer {
+    fn render(&self, body: &str) -> Result<Vec<Spans<'static>>> {
+        Ok(body
+            .lines()
+            .map(|line| Spans::from(line.to_string()))
+            .collect())
+    }
+}
+
+/// Lays HTML out as text: paragraphs apart, lists bulleted or numbered, quotes
+/// marked and links numbered, with their addresses listed at the end.
+pub struct HtmlRenderer;
+
+impl BodyRenderer for HtmlRenderer {
+    fn render(&self, body: &str) -> Result<Vec<Spans<'static>>> {
+        let mut text = HtmlText::default();
+        let mut rest = body;
+        while let Some(start) = rest.find('<') {
+            text.push_text(&rest[..start]);
+            rest = &rest[start..];
+            if rest.starts_with("<!--") {
+                rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+                continue;
+            }
+            let Some(end) = rest.find('>') else {
+                rest = "";
+                break;
+            };
+            text.push_tag(&rest[1..end]);
+            rest = &rest[end + 1..];
+        }
+        text.push_text(rest);
+        Ok(text.finish())
+    }
+}
+
+/// The lines laid out so far from an HTML body.
+#[derive(Default)]
+struct HtmlText {
+    lines: Vec<Spans<'static>>,
+    /// Spans of the line being filled, starting with its indent
+    line: Vec<Span<'static>>,
+    /// Whether the line has text after its indent
+    line_has_text: bool,
+    /// Line ends owed before the next text, 2 for a blank line between blocks
+    breaks: usize,
+    /// Whitespace seen since the last word
+    space: bool,
+    /// Open lists, with the number of the last item of ordered ones
+    lists: Vec<Option<usize>>,
+    /// Bullet or number of a list item, shown before its first text
+    marker: Option<String>,
+    quotes: usize,
+    bold: usize,
+    /// Inside `pre`, where whitespace is kept
+    pre: usize,
+    /// Inside `head`, `script`, `style` or `title`, which aren't shown
+    hidden: usize,
+    /// Address and text of the link being read
+    link: Option<(String, String)>,
+    /// Addresses of the links, numbered from 1 in the text
+    links: Vec<String>,
+}
+
+impl HtmlText {
+    fn push_tag(&mut self, tag: &str) {
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let heading = matches!(name.as_str(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6");
+        match name.as_str() {
+            "head" | "script" | "style" | "title" if closing => {
+                self.hidden = self.hidden.saturating_sub(1)
+            }
+            "head" | "script" | "style" | "title" => self.hidden += 1,
+            "br" => self.breaks += 1,
+            "p" | "table" | "hr" => self.block(2),
+            "div" | "tr" | "dl" | "dt" | "dd" => self.block(1),
+            "td" | "th" => self.space = true,
+            _ if heading => {
+                self.block(2);
+                self.bold = if closing {
+                    self.bold.saturating_sub(1)
+                } else {
+                    self.bold + 1
+                };
+            }
+            "b" | "strong" if closing => self.bold = self.bold.saturating_sub(1),
+            "b" | "strong" => self.bold += 1,
+            "ul" | "ol" if closing => {
+                self.lists.pop();
+                self.block(1);
+            }
+            "ul" => {
+                self.block(1);
+                self.lists.push(None);
+            }
+            "ol" => {
+                self.block(1);
+                self.lists.push(Some(0));
+            }
+            "li" if !closing => {
+                self.block(1);
+                self.marker = Some(match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", number)
+                    }
+                    _ => "• ".to_string(),
+                });
+            }
+            "blockquote" if closing => {
+                self.quotes = self.quotes.saturating_sub(1);
+                self.block(2);
+            }
+            "blockquote" => {
+                self.block(2);
+                self.quotes += 1;
+            }
+            "pre" if closing => {
+                self.pre = self.pre.saturating_sub(1);
+                self.block(2);
+            }
+            "pre" => {
+                self.block(2);
+                self.pre += 1;
+            }
+            "a" if closing => self.end_link(),
+            "a" => self.link = attribute(tag, "href").map(|href| (href, String::new())),
+            _ => {}
+        }
+    }
+
+    fn push_text(&mut self, raw: &str) {
+        if self.hidden > 0 || raw.is_empty() {
+            return;
+        }
+        let text = decode_entities(raw);
+        if let Some((_, link_text)) = &mut self.link {
+            link_text.push_str(&text);
+        }
+        if self.pre > 0 {
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    self.breaks += 1;
+                }
+                if !line.is_empty() {
+                    self.emit(line);
+                }
+            }
+            return;
+        }
+        if text.starts_with(char::is_whitespace) {
+            self.space = true;
+        }
+        for (i, word) in text.split_whitespace().enumerate() {
+            if i > 0 {
+                self.space = true;
+            }
+            self.emit(word);
+        }
+        if text.ends_with(char::is_whitespace) {
+            self.space = true;
+        }
+    }
+
+    /// Numbers the link just read, unless its text is the address already.
+    fn end_link(&mut self) {
+        let Some((href, text)) = self.link.take() else {
+            return;
+        };
+        let text = text.trim();
+        if href.is_empty()
+            || href.starts_with('#')
+            || href == text
+            || href.strip_prefix("mailto:") == Some(text)
+        {
+            return;
+        }
+        self.links.push(href);
+        let spaced = std::mem::replace(&mut self.space, false);
+        let marker = Span::styled(
+            format!("[{}]", self.links.len()),
+            Style::default().fg(Color::DarkGray),
+        );
+        self.emit_span(marker);
+        self.space = spaced;
+    }
+
+    /// Ends the text before the next block, `lines` apart.
+    fn block(&mut self, lines: usize) {
+        self.breaks = self.breaks.max(lines);
+        self.space = false;
+    }
+
+    fn emit(&mut self, text: &str) {
+        let mut style = Style::default();
+        if self.bold > 0 {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if self.link.is_some() {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+        if self.quotes > 0 {
+            style = style.fg(Color::DarkGray);
+        }
+        self.emit_span(Span::styled(text.to_string(), style));
+    }
+
+    fn emit_span(&mut self, span: Span<'static>) {
+        if self.breaks > 0 && (self.line_has_text || !self.lines.is_empty()) {
+            self.end_line();
+            for _ in 1..self.breaks {
+                self.lines.push(Spans::default());
+            }
+        }
+        self.breaks = 0;
+        if !self.line_has_text {
+            self.line.clear();
+            let mut indent = "│ ".repeat(self.quotes);
+            let depth = self.lists.len();
+            match self.marker.take() {
+                Some(marker) => {
+                    indent.push_str(&"  ".repeat(depth.saturating_sub(1)));
+                    indent.push_str(&marker);
+                }
+                None => indent.push_str(&"  ".repeat(depth)),
+            }
+            if !indent.is_empty() {
+                self.line
+                    .push(Span::styled(indent, Style::default().fg(Color::DarkGray)));
+            }
+            self.space = false;
+        } else if self.space {
+            self.line.push(Span::raw(" "));
+        }
+        self.space = false;
+        self.line_has_text = true;
+        match self.line.last_mut() {
+            Some(last) if last.style == span.style => last.content.to_mut().push_str(&span.content),
+            _ => self.line.push(span),
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.lines.push(Spans::from(std::mem::take(&mut self.line)));
+        self.line_has_text = false;
+    }
+
+    fn finish(mut self) -> Vec<Spans<'static>> {
+        if self.line_has_text {
+            self.end_line();
+        }
+        if !self.links.is_empty() {
+            self.lines.push(Spans::default());
+            for (i, href) in self.links.iter().enumerate() {
+                self.lines.push(Spans::from(vec![
+                    Span::styled(
+                        format!("[{}] ", i + 1),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::raw(href.clone()),
+                ]));
+            }
+        }
+        self.lines
+    }
+}
+
+/// The value of attribute `name` in the text of a tag, quoted or not.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_lowercase();
+    let mut from = 0;
+    while let Some(found) = lower[from..].find(name) {
+        let start = from + found;
+        from = start + name.len();
+        let preceded = lower[..start].ends_with(char::is_whitespace);
+        let Some(value) = lower[from..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        if !preceded {
+            continue;
+        }
+        let offset = tag.len() - value.len();
+        let value = tag[offset..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value.split(char::is_whitespace).next().unwrap_or_default(),
+        };
+        return Some(decode_entities(value.trim_end_matches('/')));
+    }
+    None
+}
+
+/// `text` with its character references replaced by the characters.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 8)
+            .map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            "ndash" => Some('–'),
+            "mdash" => Some('—'),
+            "hellip" => Some('…'),
+            "lsquo" => Some('‘'),
+            "rsquo" => Some('’'),
+            "ldquo" => Some('“'),
+            "rdquo" => Some('”'),
+            "copy" => Some('©'),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Styles Markdown headings, lists, quotes and code blocks.
+pub struct MarkdownRenderer;
+
+impl BodyRenderer for MarkdownRenderer {
+    fn render(&self, body: &str) -> Result<Vec<Spans<'static>>> {
+        let mut in_code = false;
+        let mut lines = Vec::new();
+        for line in body.lines() {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+                continue;
+            }
+            let spans = if in_code {
+                Spans::from(Span::styled(
+                    format!("    {}", line),
+                    Style::default().fg(Color::Gray),
+                ))
+            } else if let Some(heading) = line.strip_prefix('#') {
+                Spans::from(Span::styled(
+                    heading.trim_start_matches('#').trim().to_string(),
+                    Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                ))
+            } else if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+                Spans::from(format!("  • {}", item))
+            } else if let Some(quote) = line.strip_prefix('>') {
+                Spans::from(Span::styled(
+                    format!("│ {}", quote.trim_start()),
+                    Style::default().fg(Color::DarkGray),
+                ))
+            } else {
+                Spans::from(line.to_string())
+            };
+            lines.push(spans);
+        }
+        Ok(lines)
+    }
+}
+
+/// Colors the added, removed and hunk lines of a patch.
+pub struct DiffRenderer;
+
+impl BodyRenderer for DiffRenderer {
+    fn render(&self, body: &str) -> Result<Vec<Spans<'static>>> {
+        Ok(body
+            .lines()
+            .map(|line| {
+                let style = if line.starts_with("+++") || line.starts_with("---") {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else if line.starts_with('+') {
+                    Style::default().fg(Color::Green)
+                } else if line.starts_with('-') {
+                    Style::default().fg(Color::Red)
+                } else if line.starts_with("@@") {
+                    Style::default().fg(Color::Cyan)
+                } else {
+                    Style::default()
+                };
+                Spans::from(Span::styled(line.to_string(), style))
+            })
+            .collect())
+    }
+}
+
+/// Summarizes the events of an iCalendar file.
+pub struct CalendarRenderer;
+
+impl BodyRenderer for CalendarRenderer {
+    fn render(&self, body: &str) -> Result<Vec<Spans<'static>>> {
+        // Continuation lines start with a space or tab
+        let unfolded = body
+            .replace("\r\n", "\n")
+            .replace("\n ", "")
+            .replace("\n\t", "");
+        let label_style = Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::BOLD);
+
+        let mut lines = Vec::new();
+        let mut in_event = false;
+        for line in unfolded.lines() {
+            match line.trim() {
+                "BEGIN:VEVENT" => {
+                    in_event = true;
+                    if !lines.is_empty() {
+                        lines.push(Spans::from(""));
+                    }
+                }
+                "END:VEVENT" => in_event = false,
+                line if in_event => {
+                    let Some((key, value)) = line.split_once(':') else {
+                        continue;
+                    };
+                    let label = match key.split(';').next().unwrap_or_default() {
+                        "SUMMARY" => "Event: ",
+                        "DTSTART" => "Start: ",
+                        "DTEND" => "End: ",
+                        "LOCATION" => "Where: ",
+                        "ORGANIZER" => "Organizer: ",
+                        "DESCRIPTION" => "Notes: ",
+                        _ => continue,
+                    };
+                    let value = value
+                        .trim_start_matches("mailto:")
+                        .replace("\\n", " ")
+                        .replace("\\,", ",");
+                    lines.push(Spans::from(vec![
+                        Span::styled(label, label_style),
+                        Span::raw(value),
+                    ]));
+                }
+                _ => {}
+            }
+        }
+        if lines.is_empty() {
+            return PlainRenderer.render(body);
+        }
+        Ok(lines)
+    }
+}
+
+/// Pipes the body through a command from `[renderers]` and shows its output.
+pub struct ExternalRenderer {
+    command: String,
+}
+
+impl BodyRenderer for ExternalRenderer {
+    fn render(&self, body: &str) -> Result<Vec<Spans<'static>>> {
+        let mut parts = self.command.split_whitespace();
+        let Some(program) = parts.next() else {
+            bail!("Empty renderer command");
+        };
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start {}", self.command))?;
+
+        // Written from a thread so a command that prints while reading can't block on us
+        let mut stdin = child.stdin.take().context("No stdin for the renderer")?;
+        let input = body.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output()?;
+        let _ = writer.join();
+        if !output.status.success() {
+            bail!("{} exited with {}", self.command, output.status);
+        }
+        PlainRenderer.render(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renderers_by_type() {
+        let renderers = Renderers::new(&HashMap::new());
+
+        let html = renderers
+            .for_type("text/html; charset=utf-8")
+            .render("<p>Hello&nbsp;<b>team</b></p><p>Q2 &amp; Q3</p>")
+            .unwrap();
+        let html: Vec<String> = html
+            .iter()
+            .map(|spans| spans.0.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        assert_eq!(html, vec!["Hello team", "", "Q2 & Q3"]);
+
+        let calendar = renderers
+            .for_type("text/calendar")
+            .render("BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Sprint\n  review\nLOCATION:Room 4\nEND:VEVENT\nEND:VCALENDAR")
+            .unwrap();
+        assert_eq!(calendar.len(), 2);
+        assert_eq!(calendar[0].0[1].content, "Sprint review");
+
+        assert_eq!(source_format("TEXT/HTML"), BodyFormat::Html);
+        assert_eq!(source_format("text/x-diff"), BodyFormat::Text);
+    }
+
+    #[test]
+    fn test_html_lists_and_links() {
+        let body = "<html><head><style>p { color: red }</style></head><body>\
+            <h1>Release</h1><p>Read the <a href=\"https://example.com/notes\">notes</a> \
+            or mail <a href=\"mailto:team@example.com\">team@example.com</a>.</p>\
+            <ol><li>Build</li><li>Ship\n  <ul><li>Tag &#x2714;</li></ul></li></ol></body></html>";
+        let lines: Vec<String> = HtmlRenderer
+            .render(body)
+            .unwrap()
+            .iter()
+            .map(|spans| spans.0.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Release",
+                "",
+                "Read the notes[1] or mail team@example.com.",
+                "",
+                "1. Build",
+                "2. Ship",
+                "  • Tag ✔",
+                "",
+                "[1] https://example.com/notes",
+            ]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use tui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+
+/// A scrollbar drawn over the right border of a pane, showing which part of
+/// `total` lines or emails is in view.
+pub struct Scrollbar {
+    pub total: usize,
+    pub visible: usize,
+    pub offset: usize,
+    pub style: Style,
+}
+
+impl Widget for Scrollbar {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        // Between the corners of the border
+        let track = area.height.saturating_sub(2) as usize;
+        let Some((start, len)) = thumb(track, self.total, self.visible, self.offset) else {
+            return;
+        };
+        let x = area.right().saturating_sub(1);
+        for row in start..start + len {
+            buf.get_mut(x, area.y + 1 + row as u16)
+                .set_symbol("┃")
+                .set_style(self.style);
+        }
+    }
+}
+
+/// The first cell and length of the thumb in a track `track` cells long, for
+/// `visible` of `total` scrolled down by `offset`, or `None` when all fits.
+fn thumb(track: usize, total: usize, visible: usize, offset: usize) -> Option<(usize, usize)> {
+    if track == 0 || total <= visible {
+        return None;
+    }
+    let len = (track * visible / total).clamp(1, track);
+    let max_offset = total - visible;
+    let start = (track - len) * offset.min(max_offset) / max_offset;
+    Some((start, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumb_follows_the_scroll() {
+        assert_eq!(thumb(10, 5, 10, 0), None);
+        assert_eq!(thumb(10, 40, 10, 0), Some((0, 2)));
+        assert_eq!(thumb(10, 40, 10, 15), Some((4, 2)));
+        assert_eq!(thumb(10, 40, 10, 30), Some((8, 2)));
+        // Thousands of emails still leave a thumb to see
+        assert_eq!(thumb(10, 5000, 10, 5000), Some((9, 1)));
+    }
+}
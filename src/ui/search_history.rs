@@ -0,0 +1,86 @@
+use crate::fuzzy;
+
+/// State of the popup picking a past search with Ctrl-r.
+pub struct HistoryPicker {
+    /// Past searches, newest first
+    pub entries: Vec<String>,
+    pub query: String,
+    /// Position in `matches`
+    pub selected: usize,
+}
+
+impl HistoryPicker {
+    pub fn new(history: &[String]) -> Self {
+        Self {
+            entries: history.iter().rev().cloned().collect(),
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// The searches matching the query fuzzily, best first and newest first
+    /// among equals.
+    pub fn matches(&self) -> Vec<&str> {
+        let mut matches: Vec<(i64, &str)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                fuzzy::score(self.query.trim(), entry).map(|score| (score, entry.as_str()))
+            })
+            .collect();
+        matches.sort_by_key(|(score, _)| -score);
+        matches.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    pub fn selected_entry(&self) -> Option<&str> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    pub fn select_next(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + count - 1) % count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_rank_best_then_newest() {
+        let history: Vec<String> = ["from:alice", "subject:report", "report -draft"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let mut picker = HistoryPicker::new(&history);
+        assert_eq!(
+            picker.matches(),
+            vec!["report -draft", "subject:report", "from:alice"]
+        );
+
+        for c in "sr".chars() {
+            picker.push(c);
+        }
+        assert_eq!(picker.matches(), vec!["subject:report"]);
+        assert_eq!(picker.selected_entry(), Some("subject:report"));
+    }
+}
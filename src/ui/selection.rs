@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use crate::email::Email;
+
+/// Emails tagged with Space, or covered by the range `v` started, which
+/// delete, move, archive and mark read act on together.
+#[derive(Default)]
+pub struct Selection {
+    /// By account and id, so they stay tagged as the list changes around them
+    tagged: HashSet<(String, String)>,
+    /// Position in the list `v` was pressed at, the range running from there
+    /// to the selected email
+    pub anchor: Option<usize>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.tagged.is_empty() && self.anchor.is_none()
+    }
+
+    pub fn toggle(&mut self, email: &Email) {
+        let key = (email.account.clone(), email.id.clone());
+        if !self.tagged.remove(&key) {
+            self.tagged.insert(key);
+        }
+    }
+
+    pub fn tag(&mut self, email: &Email) {
+        self.tagged
+            .insert((email.account.clone(), email.id.clone()));
+    }
+
+    /// Whether the email at `position` in the list is selected, with the
+    /// cursor at `cursor`.
+    pub fn contains(&self, email: &Email, position: usize, cursor: usize) -> bool {
+        let in_range = self
+            .anchor
+            .is_some_and(|anchor| (anchor.min(cursor)..=anchor.max(cursor)).contains(&position));
+        in_range
+            || self
+                .tagged
+                .contains(&(email.account.clone(), email.id.clone()))
+    }
+
+    pub fn clear(&mut self) {
+        self.tagged.clear();
+        self.anchor = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_and_range_select_together() {
+        let emails: Vec<Email> = ["1", "2", "3", "4", "5"]
+            .into_iter()
+            .map(Email::test)
+            .collect();
+        let selected = |selection: &Selection, cursor: usize| -> Vec<&str> {
+            emails
+                .iter()
+                .enumerate()
+                .filter(|(position, email)| selection.contains(email, *position, cursor))
+                .map(|(_, email)| email.id.as_str())
+                .collect()
+        };
+
+        let mut selection = Selection::default();
+        assert!(selection.is_empty());
+        selection.toggle(&emails[0]);
+        selection.toggle(&emails[4]);
+        selection.toggle(&emails[4]);
+        assert_eq!(selected(&selection, 0), vec!["1"]);
+
+        // The range follows the cursor either way from where `v` was pressed
+        selection.anchor = Some(3);
+        assert_eq!(selected(&selection, 2), vec!["1", "3", "4"]);
+        assert_eq!(selected(&selection, 4), vec!["1", "4", "5"]);
+
+        selection.clear();
+        assert!(selection.is_empty());
+        assert!(selected(&selection, 4).is_empty());
+    }
+}
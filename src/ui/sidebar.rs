@@ -0,0 +1,108 @@
+use crate::email::{Folder, INBOX_FOLDER_ID};
+
+/// A folder in the sidebar, standing for the folders of that name in every account.
+#[derive(Debug, PartialEq)]
+pub struct SidebarFolder {
+    pub name: String,
+    /// Unread messages across the accounts
+    pub unread: usize,
+    /// Whether this is the inbox, which syncs instead of loading like other folders
+    pub inbox: bool,
+}
+
+/// State of the folder sidebar next to the email list.
+pub struct Sidebar {
+    pub folders: Vec<SidebarFolder>,
+    pub selected: usize,
+}
+
+impl Sidebar {
+    /// Gathers the folders of every account by name, ignoring case, with the
+    /// inbox first and the others in the order the accounts list them.
+    pub fn new(folders: impl IntoIterator<Item = Folder>) -> Self {
+        let mut merged: Vec<SidebarFolder> = Vec::new();
+        for folder in folders {
+            let inbox = folder.id == INBOX_FOLDER_ID;
+            match merged
+                .iter_mut()
+                .find(|f| f.inbox == inbox && (inbox || f.name.eq_ignore_ascii_case(&folder.name)))
+            {
+                Some(existing) => existing.unread += folder.unread,
+                None => merged.push(SidebarFolder {
+                    name: folder.name,
+                    unread: folder.unread,
+                    inbox,
+                }),
+            }
+        }
+        merged.sort_by_key(|folder| !folder.inbox);
+        Self {
+            folders: merged,
+            selected: 0,
+        }
+    }
+
+    pub fn selected_folder(&self) -> Option<&SidebarFolder> {
+        self.folders.get(self.selected)
+    }
+
+    /// Selects the folder named `name`, or the inbox for `None`.
+    pub fn select(&mut self, name: Option<&str>) {
+        let position = self.folders.iter().position(|folder| match name {
+            Some(name) => !folder.inbox && folder.name.eq_ignore_ascii_case(name),
+            None => folder.inbox,
+        });
+        self.selected = position.unwrap_or(0);
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.folders.is_empty() {
+            self.selected = (self.selected + 1) % self.folders.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.folders.is_empty() {
+            self.selected = (self.selected + self.folders.len() - 1) % self.folders.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(id: &str, name: &str, unread: usize) -> Folder {
+        Folder {
+            id: id.to_string(),
+            name: name.to_string(),
+            unread,
+        }
+    }
+
+    #[test]
+    fn test_sidebar_merges_folders_across_accounts() {
+        let sidebar = Sidebar::new([
+            folder("work-1", "Projects", 2),
+            folder(INBOX_FOLDER_ID, "Inbox", 3),
+            folder("lab-7", "projects", 1),
+            folder(INBOX_FOLDER_ID, "Posteingang", 4),
+        ]);
+
+        assert_eq!(
+            sidebar.folders,
+            vec![
+                SidebarFolder {
+                    name: "Inbox".to_string(),
+                    unread: 7,
+                    inbox: true,
+                },
+                SidebarFolder {
+                    name: "Projects".to_string(),
+                    unread: 3,
+                    inbox: false,
+                },
+            ]
+        );
+    }
+}
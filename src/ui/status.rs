@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Error,
+}
+
+impl Severity {
+    /// How long a message stays in the status bar, longer for errors so they aren't missed.
+    fn timeout(self) -> Duration {
+        match self {
+            Severity::Info => Duration::from_secs(5),
+            Severity::Error => Duration::from_secs(10),
+        }
+    }
+}
+
+pub struct StatusMessage {
+    pub text: String,
+    pub severity: Severity,
+    /// How often the same text was posted while it was shown
+    pub count: usize,
+    posted: Instant,
+}
+
+impl StatusMessage {
+    fn expired(&self, now: Instant) -> bool {
+        now.duration_since(self.posted) >= self.severity.timeout()
+    }
+}
+
+/// Collects the status messages of every part of the app into the one shown in
+/// the status bar. Repeats of the shown text are counted instead of flickering,
+/// and an error isn't replaced by information until it expires.
+#[derive(Default)]
+pub struct StatusBus {
+    current: Option<StatusMessage>,
+}
+
+impl StatusBus {
+    pub fn post(&mut self, severity: Severity, text: String) {
+        self.post_at(severity, text, Instant::now());
+    }
+
+    fn post_at(&mut self, severity: Severity, text: String, now: Instant) {
+        if let Some(current) = self.current.as_mut().filter(|c| !c.expired(now)) {
+            if current.text == text {
+                current.count += 1;
+                current.severity = current.severity.max(severity);
+                current.posted = now;
+                return;
+            }
+            if severity < current.severity {
+                return;
+            }
+        }
+        self.current = Some(StatusMessage {
+            text,
+            severity,
+            count: 1,
+            posted: now,
+        });
+    }
+
+    pub fn current(&self) -> Option<&StatusMessage> {
+        self.current.as_ref()
+    }
+
+    /// Drops the shown message once it has been up for its timeout.
+    pub fn expire(&mut self) {
+        let now = Instant::now();
+        if self.current.as_ref().is_some_and(|c| c.expired(now)) {
+            self.current = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_coalesces_repeats_and_keeps_errors() {
+        let start = Instant::now();
+        let mut bus = StatusBus::default();
+        bus.post_at(Severity::Error, "Failed to sync work".to_string(), start);
+        bus.post_at(Severity::Error, "Failed to sync work".to_string(), start);
+        bus.post_at(Severity::Info, "Synced home".to_string(), start);
+
+        let current = bus.current().unwrap();
+        assert_eq!(current.text, "Failed to sync work");
+        assert_eq!(current.count, 2);
+
+        // Once the error expired, information shows again
+        let later = start + Duration::from_secs(11);
+        bus.post_at(Severity::Info, "Synced home".to_string(), later);
+        assert_eq!(bus.current().unwrap().text, "Synced home");
+        assert_eq!(bus.current().unwrap().severity, Severity::Info);
+    }
+}
@@ -0,0 +1,46 @@
+use super::views::{ActiveView, QuickFilters};
+use super::width;
+use crate::email::Email;
+
+/// Columns the search takes at most in a tab's title.
+const TITLE_SEARCH_WIDTH: usize = 21;
+
+/// The list of a tab in the background: what it shows, the search narrowing it
+/// and the selected email, restored when the tab is switched to.
+#[derive(Default)]
+pub struct Tab {
+    /// The view or folder shown with its mail, or none for the inbox, whose mail
+    /// the tabs showing it share
+    pub view: Option<(ActiveView, Vec<Email>)>,
+    pub search: String,
+    pub filters: QuickFilters,
+    /// Account and id of the listed emails, for server searches, which can't
+    /// be run again locally
+    pub listed: Vec<(String, String)>,
+    pub selected: Option<(String, String)>,
+    pub title: String,
+}
+
+/// A tab's title: the name of its list and the search narrowing it, if any.
+pub fn title(name: &str, search: &str) -> String {
+    let search = search.trim();
+    if search.is_empty() {
+        return name.to_string();
+    }
+    format!("{} /{}", name, width::truncate(search, TITLE_SEARCH_WIDTH))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_shortens_long_searches() {
+        assert_eq!(title("Inbox", " "), "Inbox");
+        assert_eq!(title("Archive", "from:bob"), "Archive /from:bob");
+        assert_eq!(
+            title("Inbox", "subject:quarterly report"),
+            "Inbox /subject:quarterly re…"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use crate::email::{Task, TaskStatus};
+
+pub fn status_label(status: TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::NotStarted => "Not started",
+        TaskStatus::InProgress => "In progress",
+        TaskStatus::WaitingOnOthers => "Waiting",
+        TaskStatus::Deferred => "Deferred",
+        TaskStatus::Completed => "Completed",
+    }
+}
+
+/// State of the task list popup for one account.
+pub struct TaskList {
+    pub account: String,
+    pub tasks: Vec<Task>,
+    pub selected: usize,
+}
+
+impl TaskList {
+    /// Lists open tasks by due date, undated ones after them, and completed tasks last.
+    pub fn new(account: String, mut tasks: Vec<Task>) -> Self {
+        tasks.sort_by_key(|task| {
+            (
+                task.status == TaskStatus::Completed,
+                task.due.is_none(),
+                task.due,
+            )
+        });
+        Self {
+            account,
+            tasks,
+            selected: 0,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.tasks.is_empty() {
+            self.selected = (self.selected + 1) % self.tasks.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.tasks.is_empty() {
+            self.selected = (self.selected + self.tasks.len() - 1) % self.tasks.len();
+        }
+    }
+}
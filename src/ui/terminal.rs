@@ -0,0 +1,74 @@
+use anyhow::Result;
+use crossterm::{
+    cursor::Show,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io;
+use std::panic;
+use std::sync::Once;
+
+use super::action::Input;
+
+/// Switches to raw mode on the alternate screen, capturing the mouse.
+pub fn enter() -> Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    Ok(())
+}
+
+/// Gives the terminal back the way the shell had it.
+pub fn leave() -> Result<()> {
+    disable_raw_mode()?;
+    execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        Show
+    )?;
+    Ok(())
+}
+
+/// Hands the terminal to `program`, which takes over the screen and keyboard,
+/// with `input` suspended until it exits, and takes it back after.
+pub fn hand_over<R>(input: &mut Input, program: impl FnOnce() -> Result<R>) -> Result<R> {
+    input.suspend();
+    let result = leave().and_then(|()| program());
+    let entered = enter();
+    input.spawn();
+    entered?;
+    result
+}
+
+/// Holds the terminal for the TUI and gives it back when dropped, so neither
+/// an error returned early nor a panic leaves the shell in raw mode.
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn enter() -> Result<Self> {
+        install_panic_hook();
+        enter()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = leave();
+    }
+}
+
+/// Restores the terminal before the panic message is printed, as it would be
+/// garbled by raw mode and then wiped with the alternate screen.
+fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let _ = leave();
+            tracing::error!("{}", info);
+            previous(info);
+        }));
+    });
+}
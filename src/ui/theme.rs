@@ -0,0 +1,139 @@
+use tui::style::Color;
+
+use crate::config::{ThemeColor, ThemeConfig, ThemePreset};
+
+/// The colors the view draws with, by what they mark.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub border: Color,
+    pub focus: Color,
+    pub selection: Color,
+    pub unread: Color,
+    pub accent: Color,
+    pub label: Color,
+    pub secondary: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub muted: Color,
+    pub meeting: Color,
+    pub text: Color,
+    pub background: Color,
+    pub status_bar: Color,
+    pub highlight: Color,
+}
+
+impl Theme {
+    /// The preset of `config` with the colors it sets in place of its own.
+    pub fn new(config: &ThemeConfig) -> Self {
+        let mut theme = Self::preset(config.preset);
+        for (color, set) in [
+            (&mut theme.border, config.border),
+            (&mut theme.focus, config.focus),
+            (&mut theme.selection, config.selection),
+            (&mut theme.unread, config.unread),
+            (&mut theme.accent, config.accent),
+            (&mut theme.label, config.label),
+            (&mut theme.secondary, config.secondary),
+            (&mut theme.warning, config.warning),
+            (&mut theme.error, config.error),
+            (&mut theme.muted, config.muted),
+            (&mut theme.meeting, config.meeting),
+            (&mut theme.text, config.text),
+            (&mut theme.background, config.background),
+            (&mut theme.status_bar, config.status_bar),
+            (&mut theme.highlight, config.highlight),
+        ] {
+            if let Some(ThemeColor(set)) = set {
+                *color = set;
+            }
+        }
+        theme
+    }
+
+    pub fn preset(preset: ThemePreset) -> Self {
+        match preset {
+            ThemePreset::Default => Self {
+                border: Color::Reset,
+                focus: Color::Yellow,
+                selection: Color::DarkGray,
+                unread: Color::Cyan,
+                accent: Color::Cyan,
+                label: Color::Green,
+                secondary: Color::Blue,
+                warning: Color::Yellow,
+                error: Color::Red,
+                muted: Color::DarkGray,
+                meeting: Color::Magenta,
+                text: Color::White,
+                background: Color::Black,
+                status_bar: Color::Reset,
+                highlight: Color::Yellow,
+            },
+            ThemePreset::Light => Self {
+                border: Color::Reset,
+                focus: Color::Blue,
+                selection: Color::Gray,
+                unread: Color::Blue,
+                accent: Color::Magenta,
+                label: Color::Green,
+                secondary: Color::Blue,
+                warning: Color::Rgb(181, 137, 0),
+                error: Color::Red,
+                muted: Color::DarkGray,
+                meeting: Color::Magenta,
+                text: Color::Black,
+                background: Color::White,
+                status_bar: Color::Reset,
+                highlight: Color::Rgb(181, 137, 0),
+            },
+            ThemePreset::Gruvbox => Self {
+                border: Color::Rgb(102, 92, 84),
+                focus: Color::Rgb(250, 189, 47),
+                selection: Color::Rgb(80, 73, 69),
+                unread: Color::Rgb(131, 165, 152),
+                accent: Color::Rgb(142, 192, 124),
+                label: Color::Rgb(184, 187, 38),
+                secondary: Color::Rgb(131, 165, 152),
+                warning: Color::Rgb(254, 128, 25),
+                error: Color::Rgb(251, 73, 52),
+                muted: Color::Rgb(146, 131, 116),
+                meeting: Color::Rgb(211, 134, 155),
+                text: Color::Rgb(235, 219, 178),
+                background: Color::Rgb(40, 40, 40),
+                status_bar: Color::Rgb(50, 48, 47),
+                highlight: Color::Rgb(250, 189, 47),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors_set_replace_the_preset() {
+        let parse = |source: &str| -> Result<ThemeConfig, config::ConfigError> {
+            config::Config::builder()
+                .add_source(config::File::from_str(source, config::FileFormat::Toml))
+                .build()?
+                .try_deserialize()
+        };
+        let config = parse(
+            r##"
+            preset = "light"
+            focus = "light-red"
+            selection = "#1d2021"
+            unread = "208"
+            "##,
+        )
+        .unwrap();
+        let theme = Theme::new(&config);
+        assert_eq!(theme.focus, Color::LightRed);
+        assert_eq!(theme.selection, Color::Rgb(0x1d, 0x20, 0x21));
+        assert_eq!(theme.unread, Color::Indexed(208));
+        assert_eq!(theme.text, Color::Black);
+
+        assert!(parse(r#"focus = "mauve""#).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::email::{threading, Email};
+
+/// An email by account and id, which stay the same while indices shift.
+type EmailKey = (String, String);
+
+fn key(email: &Email) -> EmailKey {
+    (email.account.clone(), email.id.clone())
+}
+
+/// An email of a conversation listed after its first one.
+struct Member {
+    key: EmailKey,
+    /// Index into `emails` when grouped, checked against `key` before use
+    index: usize,
+    depth: usize,
+}
+
+/// The conversations of the listed emails, each shown as its first email with
+/// the replies below it while expanded.
+#[derive(Default)]
+pub struct ThreadList {
+    /// Conversations of more than one listed email, by their first email
+    groups: HashMap<EmailKey, Vec<Member>>,
+    /// The first email of the conversation of every other grouped email
+    heads: HashMap<EmailKey, EmailKey>,
+    /// Conversations showing all their emails, by their first email
+    expanded: HashSet<EmailKey>,
+}
+
+impl ThreadList {
+    /// Orders the rows at `indices` into `emails` by conversation, each where its
+    /// first listed email was, leaving out the replies of collapsed conversations.
+    pub fn group(&mut self, emails: &[Email], indices: Vec<usize>) -> Vec<usize> {
+        self.groups.clear();
+        self.heads.clear();
+
+        // Conversations don't cross accounts
+        let mut conversations: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut accounts: Vec<&str> = indices
+            .iter()
+            .map(|&idx| emails[idx].account.as_str())
+            .collect();
+        accounts.sort_unstable();
+        accounts.dedup();
+        for account in accounts {
+            let members: Vec<usize> = indices
+                .iter()
+                .copied()
+                .filter(|&idx| emails[idx].account == account)
+                .collect();
+            let refs: Vec<&Email> = members.iter().map(|&idx| &emails[idx]).collect();
+            for conversation in threading::conversations(&refs) {
+                conversations.push(
+                    conversation
+                        .into_iter()
+                        .map(|(i, depth)| (members[i], depth))
+                        .collect(),
+                );
+            }
+        }
+
+        let position: HashMap<usize, usize> = indices
+            .iter()
+            .enumerate()
+            .map(|(pos, &idx)| (idx, pos))
+            .collect();
+        conversations
+            .sort_by_key(|conversation| conversation.iter().map(|(idx, _)| position[idx]).min());
+
+        let mut rows = Vec::with_capacity(indices.len());
+        for conversation in conversations {
+            let Some(&(head, _)) = conversation.first() else {
+                continue;
+            };
+            rows.push(head);
+            if conversation.len() == 1 {
+                continue;
+            }
+            let head_key = key(&emails[head]);
+            let members: Vec<Member> = conversation[1..]
+                .iter()
+                .map(|&(index, depth)| Member {
+                    key: key(&emails[index]),
+                    index,
+                    // Replies to a message that isn't listed still go below the first one
+                    depth: depth.max(1),
+                })
+                .collect();
+            if self.expanded.contains(&head_key) {
+                rows.extend(members.iter().map(|member| member.index));
+            }
+            for member in &members {
+                self.heads.insert(member.key.clone(), head_key.clone());
+            }
+            self.groups.insert(head_key, members);
+        }
+        rows
+    }
+
+    /// The rows along with the replies hidden in collapsed conversations, in
+    /// the order they were listed before `group`.
+    pub fn ungrouped(&self, emails: &[Email], rows: &[usize]) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(rows.len());
+        for &idx in rows {
+            indices.push(idx);
+            let head = key(&emails[idx]);
+            if self.expanded.contains(&head) {
+                continue;
+            }
+            if let Some(members) = self.groups.get(&head) {
+                indices.extend(
+                    members
+                        .iter()
+                        .filter(|member| resolve(emails, member).is_some())
+                        .map(|member| member.index),
+                );
+            }
+        }
+        indices
+    }
+
+    /// How far `email` is indented below the first email of its conversation.
+    pub fn depth(&self, email: &Email) -> usize {
+        let key = key(email);
+        self.heads
+            .get(&key)
+            .and_then(|head| self.groups.get(head))
+            .and_then(|members| members.iter().find(|member| member.key == key))
+            .map_or(0, |member| member.depth)
+    }
+
+    /// For the first email of a conversation, how many emails it has and how
+    /// many of them are unread.
+    pub fn summary(&self, emails: &[Email], email: &Email) -> Option<(usize, usize)> {
+        let members = self.groups.get(&key(email))?;
+        let unread = std::iter::once(email)
+            .chain(members.iter().filter_map(|member| resolve(emails, member)))
+            .filter(|email| !email.read)
+            .count();
+        Some((members.len() + 1, unread))
+    }
+
+    pub fn is_expanded(&self, email: &Email) -> bool {
+        self.expanded.contains(&key(email))
+    }
+
+    /// Expands or collapses the conversation of the row at `position` in `rows`,
+    /// returning the position of its first email, or `None` for a lone email.
+    pub fn toggle(
+        &mut self,
+        emails: &[Email],
+        rows: &mut Vec<usize>,
+        position: usize,
+    ) -> Option<usize> {
+        let selected = key(emails.get(*rows.get(position)?)?);
+        let head = match self.heads.get(&selected) {
+            Some(head) => head.clone(),
+            None if self.groups.contains_key(&selected) => selected,
+            None => return None,
+        };
+        let members = &self.groups[&head];
+
+        if self.expanded.remove(&head) {
+            let keys: HashSet<&EmailKey> = members.iter().map(|member| &member.key).collect();
+            rows.retain(|&idx| !keys.contains(&key(&emails[idx])));
+        } else {
+            self.expanded.insert(head.clone());
+            let at = rows.iter().position(|&idx| key(&emails[idx]) == head)?;
+            let replies: Vec<usize> = members
+                .iter()
+                .filter(|member| resolve(emails, member).is_some())
+                .map(|member| member.index)
+                .collect();
+            rows.splice(at + 1..at + 1, replies);
+        }
+        rows.iter().position(|&idx| key(&emails[idx]) == head)
+    }
+}
+
+/// The email a member stands for, unless emails moved since it was grouped.
+fn resolve<'a>(emails: &'a [Email], member: &Member) -> Option<&'a Email> {
+    emails
+        .get(member.index)
+        .filter(|email| email.account == member.key.0 && email.id == member.key.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::ThreadIds;
+    use chrono::Duration;
+
+    fn email(n: i64, conversation: &str, read: bool) -> Email {
+        let email = Email::test(&n.to_string());
+        Email {
+            subject: conversation.to_string(),
+            date: email.date + Duration::hours(n),
+            read,
+            thread: ThreadIds {
+                conversation_id: Some(conversation.to_string()),
+                message_id: None,
+                references: Vec::new(),
+            },
+            ..email
+        }
+    }
+
+    #[test]
+    fn test_conversations_collapse_and_expand() {
+        let emails = [
+            email(3, "budget", false),
+            email(2, "offsite", true),
+            email(1, "budget", true),
+            email(4, "budget", false),
+        ];
+        let mut threads = ThreadList::default();
+
+        // Newest first, each conversation where its newest email was
+        let mut rows = threads.group(&emails, vec![3, 0, 1, 2]);
+        assert_eq!(rows, vec![2, 1]);
+        assert_eq!(threads.summary(&emails, &emails[2]), Some((3, 2)));
+        assert_eq!(threads.summary(&emails, &emails[1]), None);
+
+        assert_eq!(threads.toggle(&emails, &mut rows, 0), Some(0));
+        assert_eq!(rows, vec![2, 0, 3, 1]);
+        assert_eq!(threads.depth(&emails[3]), 1);
+        assert_eq!(threads.group(&emails, vec![3, 0, 1, 2]), rows);
+
+        // Collapsing from a reply selects the first email
+        assert_eq!(threads.toggle(&emails, &mut rows, 2), Some(0));
+        assert_eq!(rows, vec![2, 1]);
+        assert_eq!(threads.toggle(&emails, &mut rows, 1), None);
+    }
+}
@@ -1,291 +1,2529 @@
+use chrono::{Duration, Local, Utc};
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::path::PathBuf;
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Span, Spans, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs, Wrap},
     Frame,
 };
+use unicode_width::UnicodeWidthStr;
 
-use super::app::{App, FocusPanel, InputMode};
-use crate::email::EmailClient;
+use super::app::{
+    AddressAction, App, ContentSize, FocusPanel, InputMode, PaneLayout, Startup,
+    FUZZY_SEARCH_PREFIX, LARGE_BODY_BYTES, SERVER_SEARCH_PREFIX,
+};
+use super::compose::{Compose, MessageField, MessageForm};
+use super::date_picker::{Preset, RangeField};
+use super::dates::{self, DateGroup};
+use super::discovery;
+use super::folders::FolderEdit;
+use super::help;
+use super::hints::Hints;
+use super::images::{self, ImageSlot, ImageState};
+use super::links;
+use super::loading::Operation;
+use super::mouse::PaneAreas;
+use super::out_of_office::{self, OofField};
+use super::quotes;
+use super::scrollbar::Scrollbar;
+use super::status::Severity;
+use super::tasks;
+use super::theme::Theme;
+use super::views::ViewSource;
+use super::width;
+use crate::config::{RowField, RowPart, ViewSort};
+use crate::email::{self, Attachment, EmailClient, TaskStatus};
+use crate::outbox::SendState;
+use crate::query::Field;
+
+/// Characters of a line in a large body shown before it is cut off.
+const MAX_LINE_CHARS: usize = 1000;
+
+/// Lines each email takes in the list: subject, sender, date and a gap.
+const LIST_ITEM_HEIGHT: usize = 4;
+
+/// Columns of the folder sidebar, borders included.
+const SIDEBAR_WIDTH: u16 = 24;
+
+/// Most failed accounts listed in the sync health banner before it summarizes the rest.
+const BANNER_MAX_ACCOUNTS: usize = 3;
+/// Below this width the list goes above the content pane instead of beside it.
+const NARROW_WIDTH: u16 = 80;
+/// Below this height stacked panes are shown one at a time.
+const SHORT_HEIGHT: u16 = 20;
 
 pub fn draw<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    app.image_slots.borrow_mut().clear();
+    let banner_height = if app.sync_failures.is_empty() || app.banner_dismissed {
+        0
+    } else {
+        app.sync_failures.len().min(BANNER_MAX_ACCOUNTS) as u16 + 1
+    };
+
+    let tab_titles = app.tab_titles();
+    let workspace_height = if tab_titles.len() > 1 { 1 } else { 0 };
+    let tabs_height = if app.views.is_empty() { 0 } else { 1 };
+
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .constraints(
+            [
+                Constraint::Length(banner_height),
+                Constraint::Length(workspace_height + tabs_height),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
         .split(f.size());
+    let tab_rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(workspace_height),
+                Constraint::Length(tabs_height),
+            ]
+            .as_ref(),
+        )
+        .split(main_chunks[1]);
 
-    let chunks = Layout::default()
+    let sidebar_width = if app.sidebar.is_some() {
+        SIDEBAR_WIDTH
+    } else {
+        0
+    };
+    let panes = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
-        .split(main_chunks[0]);
+        .constraints([Constraint::Length(sidebar_width), Constraint::Min(1)].as_ref())
+        .split(main_chunks[2]);
+    // Narrow terminals stack the panes, and short ones show one at a time
+    let layout = match app.layout {
+        PaneLayout::SideBySide if panes[1].width < NARROW_WIDTH => PaneLayout::Stacked,
+        layout => layout,
+    };
+    let layout = match layout {
+        PaneLayout::Stacked if panes[1].height < SHORT_HEIGHT => PaneLayout::Single,
+        layout => layout,
+    };
+    let direction = match layout {
+        PaneLayout::Stacked => Direction::Vertical,
+        _ => Direction::Horizontal,
+    };
+    let chunks = Layout::default()
+        .direction(direction)
+        .constraints(
+            [
+                Constraint::Percentage(app.list_width),
+                Constraint::Percentage(100 - app.list_width),
+            ]
+            .as_ref(),
+        )
+        .split(panes[1]);
 
-    draw_email_list(f, app, chunks[0]);
-    draw_email_content(f, app, chunks[1]);
-    draw_status_bar(f, app, main_chunks[1]);
+    if banner_height > 0 {
+        draw_sync_banner(f, app, main_chunks[0]);
+    }
+    if workspace_height > 0 {
+        draw_workspace_tabs(f, app, tab_titles, tab_rows[0]);
+    }
+    if tabs_height > 0 {
+        draw_view_tabs(f, app, tab_rows[1]);
+    }
+    if sidebar_width > 0 {
+        draw_sidebar(f, app, panes[0]);
+    }
+    *app.pane_areas.borrow_mut() = PaneAreas {
+        sidebar: (sidebar_width > 0).then_some(panes[0]),
+        ..PaneAreas::default()
+    };
+    match (layout, &app.focus) {
+        (PaneLayout::Single, FocusPanel::EmailContent) => draw_email_content(f, app, panes[1]),
+        (PaneLayout::Single, _) => draw_email_list(f, app, panes[1]),
+        _ => {
+            draw_email_list(f, app, chunks[0]);
+            draw_email_content(f, app, chunks[1]);
+        }
+    }
+    draw_status_bar(f, app, main_chunks[3]);
 
     match app.input_mode {
-        InputMode::Help => draw_help(f),
+        InputMode::Help => draw_help(f, app),
         InputMode::Search => draw_search(f, app),
+        InputMode::AddressMenu => draw_address_menu(f, app),
+        InputMode::Links => draw_links(f, app),
+        InputMode::Confirm => draw_confirmation(f, app),
+        InputMode::SyncHealth => draw_sync_health(f, app),
+        InputMode::SyncError => draw_sync_error(f, app),
+        InputMode::Folders => draw_folder_manager(f, app),
+        InputMode::Recoverable => draw_recoverable_items(f, app),
+        InputMode::WhatsNew => draw_whats_new(f, app),
+        InputMode::OutOfOffice => draw_out_of_office(f, app),
+        InputMode::Directory => draw_directory(f, app),
+        InputMode::Move => draw_move_picker(f, app),
+        InputMode::Compose => draw_compose(f, app),
+        InputMode::Drafts => draw_drafts(f, app),
+        InputMode::Tasks => draw_tasks(f, app),
+        InputMode::Outbox => draw_outbox(f, app),
+        InputMode::Headers => draw_headers(f, app),
+        InputMode::Command => draw_command(f, app),
+        InputMode::Metrics => draw_metrics(f, app),
+        InputMode::Searches => draw_searches(f, app),
+        InputMode::Find => draw_find(f, app),
+        InputMode::SearchHistory => draw_search_history(f, app),
+        InputMode::DateRange => draw_date_range(f, app),
+        InputMode::OutboxEdit => {
+            draw_outbox(f, app);
+            draw_outbox_edit(f, app);
+        }
         _ => {}
     }
 }
 
+fn draw_sync_banner<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>, area: Rect) {
+    let style = Style::default()
+        .fg(app.theme.error)
+        .add_modifier(Modifier::BOLD);
+
+    let mut lines: Vec<Spans> = app
+        .sync_failures
+        .iter()
+        .take(BANNER_MAX_ACCOUNTS)
+        .map(|failure| {
+            Spans::from(vec![
+                Span::styled(format!("⚠ {}: ", failure.account), style),
+                Span::raw(failure.error.as_str()),
+            ])
+        })
+        .collect();
+
+    let hidden = app.sync_failures.len().saturating_sub(BANNER_MAX_ACCOUNTS);
+    let more = if hidden > 0 {
+        format!("{} more failed | ", hidden)
+    } else {
+        String::new()
+    };
+    lines.push(Spans::from(Span::styled(
+        format!("{}Press ! to retry, x to dismiss", more),
+        Style::default().fg(app.theme.warning),
+    )));
+
+    f.render_widget(Paragraph::new(lines), area);
+}
+
+/// The tabs opened with `gn`, numbered for `g1` to `g9`.
+fn draw_workspace_tabs<B: Backend, T: EmailClient>(
+    f: &mut Frame<B>,
+    app: &App<T>,
+    titles: Vec<String>,
+    area: Rect,
+) {
+    let titles = titles
+        .into_iter()
+        .enumerate()
+        .map(|(i, title)| Spans::from(format!("{}:{}", i + 1, title)))
+        .collect();
+    let tabs = Tabs::new(titles).select(app.tab_index).highlight_style(
+        Style::default()
+            .fg(app.theme.focus)
+            .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+    );
+    f.render_widget(tabs, area);
+}
+
+/// Tabs for the inbox and the configured views, numbered by their key.
+fn draw_view_tabs<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>, area: Rect) {
+    let titles = std::iter::once("0 Inbox".to_string())
+        .chain(
+            app.views
+                .iter()
+                .take(9)
+                .enumerate()
+                .map(|(i, view)| format!("{} {}", i + 1, view.name)),
+        )
+        .map(Spans::from)
+        .collect();
+    // A folder from the sidebar has no tab
+    let selected = match app.active_view.as_ref().map(|view| &view.source) {
+        None => 0,
+        Some(ViewSource::Configured(index)) => index + 1,
+        Some(ViewSource::Folder(_)) => usize::MAX,
+    };
+    let tabs = Tabs::new(titles).select(selected).highlight_style(
+        Style::default()
+            .fg(app.theme.focus)
+            .add_modifier(Modifier::BOLD),
+    );
+    f.render_widget(tabs, area);
+}
+
+/// Folders with their unread counts, the one shown highlighted.
+fn draw_sidebar<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>, area: Rect) {
+    let Some(sidebar) = &app.sidebar else {
+        return;
+    };
+    let items: Vec<ListItem> = sidebar
+        .folders
+        .iter()
+        .map(|folder| {
+            let mut spans = vec![Span::raw(folder.name.as_str())];
+            if folder.unread > 0 {
+                spans.push(Span::styled(
+                    format!(" ({})", folder.unread),
+                    Style::default()
+                        .fg(app.theme.unread)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    let block_style = match app.focus {
+        FocusPanel::Sidebar => Style::default().fg(app.theme.focus),
+        _ => Style::default().fg(app.theme.border),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Folders")
+                .borders(Borders::ALL)
+                .style(block_style),
+        )
+        .highlight_style(Style::default().bg(app.theme.selection))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    state.select(Some(sidebar.selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
 fn draw_email_list<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>, area: Rect) {
-    let items: Vec<ListItem> = app
+    // Only the emails that fit are turned into items, keeping the selection on
+    // screen, so frames cost the same however large the mailbox is
+    let item_height = app
+        .row_format
+        .as_ref()
+        .map_or(LIST_ITEM_HEIGHT, |format| format.lines.len());
+    let rows = (area.height.saturating_sub(2) as usize / item_height).max(1);
+    let selected = app.list_state.selected();
+    let offset = selected.map_or(0, |selected| selected.saturating_sub(rows - 1));
+
+    // Sorted by date, the list is split into sections by day, headed by the
+    // section of the first email shown
+    let by_date = matches!(app.sort(), ViewSort::Newest | ViewSort::Oldest);
+    let today = Local::now().date_naive();
+    let mut section = None;
+    let mut items: Vec<ListItem> = Vec::new();
+    // The position of the email of each item, for mouse clicks
+    let mut positions: Vec<Option<usize>> = Vec::new();
+    let mut selected_item = None;
+
+    for (position, email) in app
         .filtered_emails
         .iter()
-        .map(|&idx| &app.emails[idx])
-        .map(|email| {
-            let date = email.date.format("%Y-%m-%d %H:%M").to_string();
-            let content = vec![
-                Spans::from(vec![Span::styled(
+        .enumerate()
+        .skip(offset)
+        .take(rows)
+        .map(|(position, &idx)| (position, &app.emails[idx]))
+    {
+        // Replies stay under their conversation's first email
+        if by_date && app.threads.depth(email) == 0 {
+            let group = DateGroup::of(email.date, today);
+            if section != Some(group) {
+                section = Some(group);
+                positions.push(None);
+                items.push(ListItem::new(Spans::from(Span::styled(
+                    format!("── {} ", group.label()),
+                    Style::default()
+                        .fg(app.theme.warning)
+                        .add_modifier(Modifier::BOLD),
+                ))));
+            }
+        }
+        if selected == Some(position) {
+            selected_item = Some(items.len());
+        }
+        positions.push(Some(position));
+        items.push({
+            let date = if app.relative_dates {
+                dates::relative(email.date, Local::now())
+            } else {
+                app.date_format.format(email.date)
+            };
+            // Unread mail stands out, read mail is dimmed
+            let subject_ranges = app.highlighter.ranges(Field::Subject, &email.subject);
+            let mut subject = if email.read {
+                highlighted(&email.subject, subject_ranges, Style::default(), &app.theme)
+            } else {
+                let mut spans = vec![Span::styled("• ", Style::default().fg(app.theme.unread))];
+                spans.extend(highlighted(
                     &email.subject,
+                    subject_ranges,
                     Style::default().add_modifier(Modifier::BOLD),
-                )]),
-                Spans::from(vec![
-                    Span::styled("From: ", Style::default().fg(Color::Blue)),
-                    Span::raw(&email.sender),
-                ]),
-                Spans::from(vec![
-                    Span::styled("Date: ", Style::default().fg(Color::Blue)),
-                    Span::raw(date),
-                ]),
-                Spans::from(""),
-            ];
-            ListItem::new(content)
-        })
-        .collect();
+                    &app.theme,
+                ));
+                spans
+            };
+            if email.flagged {
+                subject.insert(0, Span::styled("⚑ ", Style::default().fg(app.theme.error)));
+            }
+            if app.selection.contains(email, position, app.selected_index) {
+                subject.insert(
+                    0,
+                    Span::styled(
+                        "✓ ",
+                        Style::default()
+                            .fg(app.theme.highlight)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                );
+            }
+            if !email.attachments.is_empty() {
+                subject.push(Span::styled(" 📎", Style::default().fg(app.theme.accent)));
+            }
+
+            // Conversations show their size on the first email, replies are indented below it
+            let depth = app.threads.depth(email);
+            let indent = "  ".repeat(depth);
+            if depth > 0 {
+                subject.insert(
+                    0,
+                    Span::styled(
+                        format!("{}↳ ", indent),
+                        Style::default().fg(app.theme.muted),
+                    ),
+                );
+            } else if let Some((count, unread)) = app.threads.summary(&app.emails, email) {
+                let marker = if app.threads.is_expanded(email) {
+                    "▾ "
+                } else {
+                    "▸ "
+                };
+                subject.insert(
+                    0,
+                    Span::styled(marker, Style::default().fg(app.theme.accent)),
+                );
+                let summary = match unread {
+                    0 => format!(" ({})", count),
+                    unread => format!(" ({}, {} unread)", count, unread),
+                };
+                subject.push(Span::styled(summary, Style::default().fg(app.theme.accent)));
+            }
+            let indent = if depth > 0 {
+                format!("{}  ", indent)
+            } else {
+                String::new()
+            };
+
+            let from = highlighted(
+                &email.sender,
+                app.highlighter.ranges(Field::Sender, &email.sender),
+                Style::default(),
+                &app.theme,
+            );
+            let content = match &app.row_format {
+                Some(format) => format
+                    .lines
+                    .iter()
+                    .map(|line| {
+                        let mut spans = Vec::new();
+                        for part in line {
+                            match part {
+                                RowPart::Text(text) => spans.push(Span::raw(text.clone())),
+                                RowPart::Field(field, width) => {
+                                    let value = match field {
+                                        RowField::Date => vec![Span::raw(date.clone())],
+                                        RowField::Sender => from.clone(),
+                                        RowField::Subject => subject.clone(),
+                                        RowField::Size => vec![Span::raw(format_size(email.size))],
+                                        RowField::Account => vec![Span::raw(email.account.clone())],
+                                    };
+                                    match width {
+                                        Some(width) => spans.extend(fit_spans(value, *width)),
+                                        None => spans.extend(value),
+                                    }
+                                }
+                            }
+                        }
+                        Spans::from(spans)
+                    })
+                    .collect(),
+                None => {
+                    let mut sender = vec![
+                        Span::raw(indent.clone()),
+                        Span::styled("From: ", Style::default().fg(app.theme.secondary)),
+                    ];
+                    sender.extend(from);
+                    vec![
+                        Spans::from(subject),
+                        Spans::from(sender),
+                        Spans::from(vec![
+                            Span::raw(indent),
+                            Span::styled("Date: ", Style::default().fg(app.theme.secondary)),
+                            Span::raw(date),
+                        ]),
+                        Spans::from(""),
+                    ]
+                }
+            };
+            let style = if email.read {
+                Style::default().add_modifier(Modifier::DIM)
+            } else {
+                Style::default()
+            };
+            ListItem::new(content).style(style)
+        });
+    }
 
+    let (unread, total) = app.list_counts();
+    let mut title = format!(
+        "{} · {} ({} unread / {})",
+        app.list_name(),
+        app.date_range,
+        unread,
+        total
+    );
+    if app.sort() != ViewSort::Newest {
+        title = format!("{} · {}", title, app.sort().label());
+    }
+    let filters = app.quick_filters.labels();
+    if !filters.is_empty() {
+        title = format!("{} · only {}", title, filters.join(", "));
+    }
     let block_style = match app.focus {
-        FocusPanel::EmailList => Style::default().fg(Color::Yellow),
-        _ => Style::default(),
+        FocusPanel::EmailList => Style::default().fg(app.theme.focus),
+        _ => Style::default().fg(app.theme.border),
     };
 
+    let heights: Vec<(usize, Option<usize>)> =
+        items.iter().map(ListItem::height).zip(positions).collect();
+    app.pane_areas
+        .borrow_mut()
+        .record_list(area, &heights, selected_item);
     let list = List::new(items)
         .block(
             Block::default()
-                .title("Emails")
+                .title(title)
                 .borders(Borders::ALL)
                 .style(block_style),
         )
         .highlight_style(
             Style::default()
-                .bg(Color::DarkGray)
+                .bg(app.theme.selection)
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol(">> ");
 
-    // Create a local copy of the list state
-    let mut list_state = app.list_state.clone();
+    // The items start at the offset and include the section headers
+    let mut list_state = ListState::default();
+    list_state.select(selected_item);
     f.render_stateful_widget(list, area, &mut list_state);
+    f.render_widget(
+        Scrollbar {
+            total: app.filtered_emails.len(),
+            visible: rows,
+            offset,
+            style: Style::default().fg(app.theme.accent),
+        },
+        area,
+    );
 }
 
 fn draw_email_content<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>, area: Rect) {
-    let block_style = match app.focus {
-        FocusPanel::EmailContent => Style::default().fg(Color::Yellow),
-        _ => Style::default(),
-    };
+    app.pane_areas.borrow_mut().content = Some(area);
+    // Inside the borders
+    let width = area.width.saturating_sub(2) as usize;
+    let height = area.height.saturating_sub(2) as usize;
+    let mut size = ContentSize::default();
+    let mut scroll = 0;
+    let mut slots = Vec::new();
+    let mut find_rows = Vec::new();
 
     let content = if let Some(email) = app.selected_email() {
+        let label_style = Style::default()
+            .fg(app.theme.label)
+            .add_modifier(Modifier::BOLD);
+        let mut subject = vec![Span::styled("Subject: ", label_style)];
+        subject.extend(highlighted(
+            &email.subject,
+            app.highlighter.ranges(Field::Subject, &email.subject),
+            Style::default().add_modifier(Modifier::BOLD),
+            &app.theme,
+        ));
+        let mut sender = vec![Span::styled("From: ", label_style)];
+        sender.extend(highlighted(
+            &email.sender,
+            app.highlighter.ranges(Field::Sender, &email.sender),
+            Style::default(),
+            &app.theme,
+        ));
         let mut text = Text::from(vec![
+            Spans::from(subject),
+            Spans::from(sender),
             Spans::from(vec![
                 Span::styled(
-                    "Subject: ",
+                    "Date: ",
                     Style::default()
-                        .fg(Color::Green)
+                        .fg(app.theme.label)
                         .add_modifier(Modifier::BOLD),
                 ),
-                Span::styled(
-                    &email.subject,
-                    Style::default().add_modifier(Modifier::BOLD),
-                ),
+                Span::raw(app.date_format.format(email.date)),
             ]),
-            Spans::from(vec![
-                Span::styled(
-                    "From: ",
+        ]);
+
+        for (label, addresses) in [("To: ", &email.to), ("Cc: ", &email.cc)] {
+            if !addresses.is_empty() {
+                text.extend(Text::from(Spans::from(vec![
+                    Span::styled(
+                        label,
+                        Style::default()
+                            .fg(app.theme.label)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(addresses.join(", ")),
+                ])));
+            }
+        }
+        if !email.attachments.is_empty() {
+            let mut spans = vec![Span::styled(
+                "Attachments: ",
+                Style::default()
+                    .fg(app.theme.label)
+                    .add_modifier(Modifier::BOLD),
+            )];
+            for (idx, attachment) in email.attachments.iter().enumerate() {
+                if idx > 0 {
+                    spans.push(Span::raw(", "));
+                }
+                if let Some(label) = app
+                    .hints
+                    .as_ref()
+                    .and_then(|h| h.attachment_label(&attachment.id))
+                {
+                    spans.push(hint_label(label, &app.theme));
+                }
+                spans.push(Span::raw(format!(
+                    "{} ({})",
+                    attachment.name,
+                    format_size(attachment.size)
+                )));
+            }
+            text.extend(Text::from(Spans::from(spans)));
+        }
+        if let Some(headers) = &email.headers {
+            let targets = email::headers::unsubscribe_targets(headers);
+            if !targets.is_empty() {
+                text.extend(Text::from(Spans::from(vec![
+                    Span::styled(
+                        "Unsubscribe: ",
+                        Style::default()
+                            .fg(app.theme.label)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(targets.join(", ")),
+                ])));
+            }
+            for warning in email::headers::phishing_warnings(email, headers) {
+                text.extend(Text::from(Spans::from(Span::styled(
+                    format!("⚠ {}", warning),
                     Style::default()
-                        .fg(Color::Green)
+                        .fg(app.theme.error)
                         .add_modifier(Modifier::BOLD),
-                ),
-                Span::raw(&email.sender),
-            ]),
-            Spans::from(vec![
-                Span::styled(
-                    "Date: ",
+                ))));
+            }
+        }
+        if let Some(meeting) = &email.meeting {
+            let start = meeting.start.with_timezone(&Local);
+            let end = meeting.end.with_timezone(&Local);
+            let when = if start.date_naive() == end.date_naive() {
+                format!(
+                    "{} - {}",
+                    start.format("%a %Y-%m-%d %H:%M"),
+                    end.format("%H:%M")
+                )
+            } else {
+                format!(
+                    "{} - {}",
+                    start.format("%a %Y-%m-%d %H:%M"),
+                    end.format("%a %Y-%m-%d %H:%M")
+                )
+            };
+            let label_style = Style::default()
+                .fg(app.theme.meeting)
+                .add_modifier(Modifier::BOLD);
+            text.extend(Text::from(vec![
+                Spans::from(""),
+                Spans::from(Span::styled(
+                    "Meeting request (i to respond)",
                     Style::default()
-                        .fg(Color::Green)
+                        .fg(app.theme.meeting)
                         .add_modifier(Modifier::BOLD),
-                ),
-                Span::raw(email.date.format("%Y-%m-%d %H:%M:%S").to_string()),
-            ]),
-            Spans::from(""),
-            Spans::from(""),
-        ]);
+                )),
+                Spans::from(vec![Span::styled("When: ", label_style), Span::raw(when)]),
+                Spans::from(vec![
+                    Span::styled("Where: ", label_style),
+                    Span::raw(meeting.location.as_str()),
+                ]),
+                Spans::from(vec![
+                    Span::styled("Organizer: ", label_style),
+                    Span::raw(meeting.organizer.as_str()),
+                ]),
+            ]));
+        }
+        text.extend(Text::from(vec![Spans::from(""), Spans::from("")]));
+
+        // Large bodies only get the lines that fit below the headers, which stay in
+        // place, so log dumps don't stall every frame
+        let body = email.body.text();
+        if body.len() >= LARGE_BODY_BYTES {
+            text.extend(Text::from(vec![
+                Spans::from(Span::styled(
+                    format!(
+                        "Large message ({}) | | to open in a pager",
+                        format_size(body.len() as u64)
+                    ),
+                    Style::default().fg(app.theme.warning),
+                )),
+                Spans::from(""),
+            ]));
+            size = ContentSize {
+                height: height.saturating_sub(wrapped_height(&text, width)),
+                lines: body.lines().count(),
+            };
+            let first = app.body_scroll().min(size.max_scroll());
+            // Only the body scrolls here, a line at a time
+            if let Some(find) = app.find.as_ref().filter(|find| find.is_confirmed()) {
+                find_rows = body
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !find.ranges(line).is_empty())
+                    .map(|(idx, _)| idx)
+                    .collect();
+            }
+            for line in body.lines().skip(first).take(size.height) {
+                let line = matched(Spans::from(truncate_line(line)), app);
+                text.extend(Text::from(hinted(line, app.hints.as_ref(), &app.theme)));
+            }
+            app.content_size.set(size);
+            *app.find_rows.borrow_mut() = find_rows;
+            draw_content_block(f, app, area, text, 0, scroll_title(size, first));
+            f.render_widget(content_scrollbar(app, size, first), area);
+            return;
+        } else if let Some(lines) = app.rendered_lines(email) {
+            let lines = collapse_quotes(app, lines.to_vec());
+            extend_body(app, &mut text, lines.into_iter(), width, &mut find_rows);
+        } else if !email.body.is_loaded(email::BodyFormat::Text) {
+            // Lists only carry envelopes, the body is downloaded when the email is opened
+            if !app.is_loading(Operation::BodyFetch) {
+                text.extend(Text::from(Spans::from(Span::styled(
+                    "Press Enter to load the message",
+                    Style::default().fg(app.theme.muted),
+                ))));
+            }
+        } else {
+            // Split body by newlines and add each line
+            let lines = collapse_quotes(app, body.lines().map(Spans::from).collect());
+            extend_body(app, &mut text, lines.into_iter(), width, &mut find_rows);
+        }
 
-        // Split body by newlines and add each line
-        for line in email.body.lines() {
-            text.extend(Text::from(line));
+        // Image attachments follow the body, over blank lines the terminal
+        // draws them on once the frame is out
+        let max_rows = app.image_rows.min(height.saturating_sub(1) as u16).max(1);
+        let cell = images::cell_size();
+        for attachment in email.attachments.iter().filter(|a| a.is_image()) {
+            text.extend(Text::from(Spans::from("")));
+            match app.image(email, attachment) {
+                Some(ImageState::Ready(image)) if app.graphics.is_some() => {
+                    let (cols, rows) = image.fit(width as u16, max_rows, cell);
+                    slots.push(ImageSlot {
+                        id: image.id,
+                        x: area.x + 1,
+                        y: wrapped_height(&text, width) as u16,
+                        cols,
+                        rows,
+                    });
+                    for _ in 0..rows {
+                        text.extend(Text::from(Spans::from("")));
+                    }
+                }
+                _ => text.extend(Text::from(Spans::from(Span::styled(
+                    format!("[image: {}]", attachment.name),
+                    Style::default().fg(app.theme.muted),
+                )))),
+            }
         }
 
+        size = ContentSize {
+            height,
+            lines: wrapped_height(&text, width),
+        };
+        scroll = app.body_scroll().min(size.max_scroll());
         text
     } else {
         Text::from("No email selected")
     };
+    app.content_size.set(size);
+    *app.find_rows.borrow_mut() = find_rows;
+
+    // Only images wholly in view are drawn, and none under a popup
+    if matches!(app.input_mode, InputMode::Normal | InputMode::EmailView) {
+        let (first, last) = (scroll as u16, (scroll + size.height) as u16);
+        *app.image_slots.borrow_mut() = slots
+            .into_iter()
+            .filter(|slot| slot.y >= first && slot.y + slot.rows <= last)
+            .map(|slot| ImageSlot {
+                y: area.y + 1 + slot.y - first,
+                ..slot
+            })
+            .collect();
+    }
+
+    let mut title = scroll_title(size, scroll);
+    if let Some(view) = app.body_view {
+        title = format!("{} · {}", title, view.label());
+    }
+    draw_content_block(f, app, area, content, scroll, title);
+    f.render_widget(content_scrollbar(app, size, scroll), area);
+}
+
+fn content_scrollbar<T: EmailClient>(app: &App<T>, size: ContentSize, scroll: usize) -> Scrollbar {
+    Scrollbar {
+        total: size.lines,
+        visible: size.height,
+        offset: scroll,
+        style: Style::default().fg(app.theme.accent),
+    }
+}
+
+/// `text` in `style`, with the `ranges` a search matched standing out.
+fn highlighted<'a>(
+    text: &'a str,
+    ranges: Vec<Range<usize>>,
+    style: Style,
+    theme: &Theme,
+) -> Vec<Span<'a>> {
+    let mut spans = Vec::with_capacity(ranges.len() * 2 + 1);
+    let mut position = 0;
+    for range in ranges {
+        if range.start > position {
+            spans.push(Span::styled(&text[position..range.start], style));
+        }
+        spans.push(Span::styled(
+            &text[range.clone()],
+            match_style(style, theme),
+        ));
+        position = range.end;
+    }
+    if position < text.len() || spans.is_empty() {
+        spans.push(Span::styled(&text[position..], style));
+    }
+    spans
+}
+
+/// Adds the body `lines` to `text`, recording the rows of those `/` matches
+/// in `find_rows`.
+fn extend_body<'a, T: EmailClient>(
+    app: &App<T>,
+    text: &mut Text<'a>,
+    lines: impl Iterator<Item = Spans<'a>>,
+    width: usize,
+    find_rows: &mut Vec<usize>,
+) {
+    let find = app.find.as_ref().filter(|find| find.is_confirmed());
+    let mut row = wrapped_height(text, width);
+    for line in lines {
+        let found = find.is_some_and(|find| {
+            line.0
+                .iter()
+                .any(|span| !find.ranges(&span.content).is_empty())
+        });
+        if found {
+            find_rows.push(row);
+        }
+        row += line_height(&line, width);
+        let line = matched(line, app);
+        text.extend(Text::from(hinted(line, app.hints.as_ref(), &app.theme)));
+    }
+}
+
+/// The lines of a body with its quotes collapsed, unless expanded with `Q` or
+/// searched with `/`, whose matches may be in them.
+fn collapse_quotes<'a, T: EmailClient>(app: &App<T>, lines: Vec<Spans<'a>>) -> Vec<Spans<'a>> {
+    if app.quotes_expanded || app.find.is_some() {
+        return lines;
+    }
+    quotes::collapse(lines, Style::default().fg(app.theme.muted))
+}
+
+/// `line` of a body with what `/` found in each span standing out, or else
+/// what the search matched.
+fn matched<'a, T: EmailClient>(line: Spans<'a>, app: &App<T>) -> Spans<'a> {
+    let find = app.find.as_ref().filter(|find| find.is_confirmed());
+    if find.is_none() && app.highlighter.is_empty() {
+        return line;
+    }
+    let mut spans = Vec::with_capacity(line.0.len());
+    for span in line.0 {
+        let ranges = match find {
+            Some(find) => find.ranges(&span.content),
+            None => app.highlighter.ranges(Field::Body, &span.content),
+        };
+        if ranges.is_empty() {
+            spans.push(span);
+            continue;
+        }
+        spans.extend(
+            highlighted(&span.content, ranges, span.style, &app.theme)
+                .into_iter()
+                .map(|part| Span::styled(part.content.into_owned(), part.style)),
+        );
+    }
+    Spans::from(spans)
+}
+
+/// Reversed rather than given a background, so matches still stand out in the
+/// selected row, whose background the list sets over every cell.
+fn match_style(style: Style, theme: &Theme) -> Style {
+    style.fg(theme.highlight).add_modifier(Modifier::REVERSED)
+}
+
+/// `line` with the hint label of each link put before it.
+fn hinted<'a>(line: Spans<'a>, hints: Option<&Hints>, theme: &Theme) -> Spans<'a> {
+    let Some(hints) = hints else {
+        return line;
+    };
+    let mut spans = Vec::with_capacity(line.0.len());
+    for span in line.0 {
+        let found = links::find(&span.content);
+        if found.is_empty() {
+            spans.push(span);
+            continue;
+        }
+        let mut position = 0;
+        for (range, address) in found {
+            let Some(label) = hints.link_label(&address) else {
+                continue;
+            };
+            if range.start > position {
+                spans.push(Span::styled(
+                    span.content[position..range.start].to_string(),
+                    span.style,
+                ));
+            }
+            spans.push(hint_label(label, theme));
+            position = range.start;
+        }
+        spans.push(Span::styled(
+            span.content[position..].to_string(),
+            span.style,
+        ));
+    }
+    Spans::from(spans)
+}
+
+fn hint_label(label: &str, theme: &Theme) -> Span<'static> {
+    Span::styled(
+        label.to_string(),
+        Style::default()
+            .fg(theme.background)
+            .bg(theme.warning)
+            .add_modifier(Modifier::BOLD),
+    )
+}
+
+fn draw_content_block<B: Backend, T: EmailClient>(
+    f: &mut Frame<B>,
+    app: &App<T>,
+    area: Rect,
+    content: Text,
+    scroll: usize,
+    title: String,
+) {
+    let block_style = match app.focus {
+        FocusPanel::EmailContent => Style::default().fg(app.theme.focus),
+        _ => Style::default().fg(app.theme.border),
+    };
+    let title = if app.is_loading(Operation::BodyFetch) {
+        format!(
+            "{} {} {}... (Esc to cancel)",
+            title,
+            spinner_frame(),
+            Operation::BodyFetch.label()
+        )
+    } else {
+        title
+    };
 
     let paragraph = Paragraph::new(content)
         .block(
             Block::default()
-                .title("Content")
+                .title(title)
                 .borders(Borders::ALL)
                 .style(block_style),
         )
-        .wrap(Wrap { trim: true });
+        .wrap(Wrap { trim: true })
+        .scroll((u16::try_from(scroll).unwrap_or(u16::MAX), 0));
 
     f.render_widget(paragraph, area);
 }
 
+/// The content pane title, with how far down the email is scrolled when it
+/// doesn't fit, e.g. `Content 34%`.
+fn scroll_title(size: ContentSize, scroll: usize) -> String {
+    match size.max_scroll() {
+        0 => "Content".to_string(),
+        max => format!("Content {}%", scroll * 100 / max),
+    }
+}
+
+/// Lines `text` takes once wrapped to `width` columns. Wrapping at words can
+/// take a few more, which only costs some blank lines at the end.
+fn wrapped_height(text: &Text, width: usize) -> usize {
+    text.lines.iter().map(|line| line_height(line, width)).sum()
+}
+
+fn line_height(line: &Spans, width: usize) -> usize {
+    line.width().div_ceil(width.max(1)).max(1)
+}
+
 fn draw_status_bar<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>, area: Rect) {
-    let status = if app.is_loading() {
-        // Create a simple spinner animation based on time
-        let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-        let idx = (std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis()
-            / 100) as usize
-            % spinner_chars.len();
-
-        format!("{} Loading emails...", spinner_chars[idx])
-    } else if let Some((msg, _)) = &app.status_message {
-        msg.clone()
+    // Body fetches are shown in the content pane, everything else belongs here
+    let operations: Vec<Operation> = app
+        .loading
+        .active()
+        .into_iter()
+        .filter(|op| *op != Operation::BodyFetch)
+        .collect();
+
+    let status = if let Some(message) = app.status.current() {
+        if message.count > 1 {
+            format!("{} (x{})", message.text, message.count)
+        } else {
+            message.text.clone()
+        }
+    } else if let Some(stage) = app.startup {
+        startup_status(app, stage)
+    } else if !operations.is_empty() {
+        let labels: Vec<&str> = operations.iter().map(|op| op.label()).collect();
+        if operations.contains(&Operation::ListSync) {
+            let progress = sync_progress(app)
+                .map(|progress| format!(" {}", progress))
+                .unwrap_or_default();
+            format!(
+                "{} {}...{} | Esc to cancel",
+                spinner_frame(),
+                labels.join(", "),
+                progress
+            )
+        } else {
+            format!("{} {}...", spinner_frame(), labels.join(", "))
+        }
     } else {
         match app.input_mode {
-            InputMode::Normal => "Normal mode | Press ? for help | q to quit".to_string(),
+            InputMode::Normal if !app.selection.is_empty() => format!(
+                "VISUAL | {} selected | a archive, d delete, m move, I mark read | Esc to clear",
+                app.selected_for_action().len()
+            ),
+            InputMode::Normal => match idle_tip(app) {
+                Some((key, description)) => {
+                    format!(
+                        "Tip: press {} to {} | ? for help | q to quit",
+                        key, description
+                    )
+                }
+                None => "Normal mode | Press ? for help | q to quit".to_string(),
+            },
             InputMode::EmailView => {
-                "Email view mode | Press Esc to return | ? for help".to_string()
+                "Email view mode | j/k to scroll, ↑/↓ for other emails | V plain/HTML/source | Esc to return".to_string()
+            }
+            InputMode::Help => {
+                "Help | j/k or PgDn/PgUp to scroll, g/G to the top or bottom | Any other key to close".to_string()
+            }
+            InputMode::Search => format!(
+                "Search mode | ↑/↓ or Ctrl-r for history | Prefix with {} to search the server, {} to match fuzzily",
+                SERVER_SEARCH_PREFIX, FUZZY_SEARCH_PREFIX
+            ),
+            InputMode::AddressMenu => {
+                "Address actions | j/k to pick an address | Esc to close".to_string()
+            }
+            InputMode::Links => {
+                "Links | Enter or 1-9 to open | y to copy | Esc to close".to_string()
+            }
+            InputMode::Hints => {
+                "Hints | Type a label to open its link or attachment | Esc to cancel".to_string()
+            }
+            InputMode::Confirm => "Confirm | Esc to cancel".to_string(),
+            InputMode::SyncHealth => {
+                "Sync health | Enter to retry an account | Esc to close".to_string()
+            }
+            InputMode::Folders => {
+                "Folders | n new | r rename | d delete | Esc to close".to_string()
+            }
+            InputMode::Recoverable => {
+                "Recoverable items | Enter to restore | Esc to close".to_string()
+            }
+            InputMode::Tasks => "Tasks | j/k to move | Esc to close".to_string(),
+            InputMode::Outbox => {
+                "Outbox | Enter to retry | f to send all | e to edit | d to discard | Esc to close"
+                    .to_string()
+            }
+            InputMode::Headers => "Headers | j/k to scroll | Esc to close".to_string(),
+            InputMode::Command => "Command | Tab to complete | Enter to run | Esc to cancel".to_string(),
+            InputMode::Metrics => "Metrics for this session | Esc to close".to_string(),
+            InputMode::Searches => {
+                "Saved searches | Enter or 1-9 to run | Esc to close".to_string()
+            }
+            InputMode::SearchHistory => {
+                "Search history | Type to narrow | ↑/↓ to pick | Enter to use | Esc to return"
+                    .to_string()
+            }
+            InputMode::DateRange => {
+                if app.date_picker.as_ref().is_some_and(|p| p.field.is_some()) {
+                    "Custom range | Tab for the other day | Enter to fetch | Esc for the presets"
+                        .to_string()
+                } else {
+                    "Date range | j/k to pick | Enter to fetch | Esc to close".to_string()
+                }
+            }
+            InputMode::Find => {
+                "Find in this email | Enter to find | n/N for the next and previous match | Esc to cancel"
+                    .to_string()
+            }
+            InputMode::SyncError => "Sync failed | R to retry | o to work offline".to_string(),
+            InputMode::Sidebar => {
+                "Folders | Enter to open | r to recount | Esc for the list | b to hide".to_string()
+            }
+            InputMode::OutboxEdit => {
+                "Edit | Tab next field | Ctrl-S to save and send | Esc to cancel".to_string()
+            }
+            InputMode::WhatsNew => "What's new | Press any key to continue".to_string(),
+            InputMode::OutOfOffice => {
+                "Automatic replies | Tab next field | Space toggles status | Enter to save | Esc to cancel"
+                    .to_string()
+            }
+            InputMode::Directory => {
+                "Directory | Enter to look up | Up/Down to pick | Tab copies the address | Esc to close"
+                    .to_string()
+            }
+            InputMode::Compose => {
+                "Compose | Tab next field | Up/Down, Tab to pick an address | Ctrl-S send | Ctrl-A attach | Ctrl-X remove attachment | Ctrl-E $EDITOR | Ctrl-D save draft | Ctrl-T signature | Esc discard | F1 help"
+                    .to_string()
+            }
+            InputMode::Drafts => {
+                "Drafts | j/k to move | Enter to edit | d to delete | Esc to close".to_string()
+            }
+            InputMode::Move => {
+                "Move to folder | Type to filter | Up/Down to pick | Enter to move | Esc to cancel"
+                    .to_string()
             }
-            InputMode::Help => "Help mode".to_string(),
-            InputMode::Search => "Search mode".to_string(),
         }
     };
 
-    let status_style = if app.is_loading() {
-        Style::default().fg(Color::Yellow)
-    } else if app.status_message.is_some() {
-        Style::default().fg(Color::Cyan)
+    let status_style = if let Some(message) = app.status.current() {
+        match message.severity {
+            Severity::Info => Style::default().fg(app.theme.accent),
+            Severity::Error => Style::default().fg(app.theme.error),
+        }
+    } else if !operations.is_empty() {
+        Style::default().fg(app.theme.warning)
     } else {
         Style::default()
     };
 
-    let status_bar = Paragraph::new(status).style(status_style);
+    let status_bar = Paragraph::new(status).style(status_style.bg(app.theme.status_bar));
 
     f.render_widget(status_bar, area);
 }
 
-fn draw_help<B: Backend>(f: &mut Frame<B>) {
-    let area = centered_rect(60, 20, f.size());
-
-    let help_text = vec![
-        Spans::from(Span::styled(
-            "Keyboard Shortcuts:",
-            Style::default().add_modifier(Modifier::BOLD),
-        )),
-        Spans::from(""),
-        Spans::from(vec![
-            Span::styled("j/k", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" or "),
-            Span::styled("↑/↓", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - Navigate up/down through emails"),
-        ]),
-        Spans::from(vec![
-            Span::styled("l", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" or "),
-            Span::styled("→", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" or "),
-            Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - View selected email details"),
-        ]),
-        Spans::from(vec![
-            Span::styled("h", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" or "),
-            Span::styled("←", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" or "),
-            Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - Return to email list"),
-        ]),
-        Spans::from(vec![
-            Span::styled("g", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - Go to first email"),
-        ]),
-        Spans::from(vec![
-            Span::styled("G", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - Go to last email"),
-        ]),
-        Spans::from(vec![
-            Span::styled("r", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - Refresh emails"),
-        ]),
-        Spans::from(vec![
-            Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - Quit application"),
-        ]),
-        Spans::from(vec![
-            Span::styled("?", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - Show/hide this help menu"),
-        ]),
-        Spans::from(vec![
-            Span::styled("/", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" - Search emails"),
-        ]),
-        Spans::from(""),
-        Spans::from(Span::styled(
-            "Press any key to close this help window",
-            Style::default().fg(Color::Yellow),
-        )),
-    ];
+/// A keybinding the user hasn't tried yet, rotating over time.
+fn idle_tip<T: EmailClient>(app: &App<T>) -> Option<(char, &'static str)> {
+    if app.state.tips_disabled {
+        return None;
+    }
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    discovery::current_tip(&app.state.used_keys, seconds)
+}
 
-    let help = Paragraph::new(help_text)
-        .block(Block::default().title("Help").borders(Borders::ALL))
-        .style(Style::default().fg(Color::White));
+/// A simple spinner animation frame based on the current time.
+fn spinner_frame() -> char {
+    let spinner_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    let idx = (std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        / 100) as usize
+        % spinner_chars.len();
+    spinner_chars[idx]
+}
 
-    // Create a semi-transparent overlay effect
-    let overlay = Block::default().style(Style::default().bg(Color::Black).fg(Color::White));
-    f.render_widget(overlay, f.size());
+fn draw_help<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(help) = app.help.as_ref() else {
+        return;
+    };
+    let area = centered_rect(70, 80, f.size());
+
+    let help_text: Vec<Spans> = help::rows(help.context)
+        .map(|(keys, description)| {
+            Spans::from(vec![
+                Span::styled(keys, Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" - "),
+                Span::raw(description),
+            ])
+        })
+        .collect();
+
+    // Long descriptions wrap, so the scroll is bounded by the wrapped lines
+    let width = area.width.saturating_sub(2) as usize;
+    let lines: usize = help_text
+        .iter()
+        .map(|spans| line_height(spans, width))
+        .sum();
+    let height = area.height.saturating_sub(2) as usize;
+    help.max_scroll.set(lines.saturating_sub(height));
+    help.height.set(height);
+    let scroll = help.scroll.min(help.max_scroll.get());
+
+    let title = if lines > height {
+        format!(
+            "Help · {} ({}-{} of {}, j/k to scroll)",
+            help.context.label(),
+            scroll + 1,
+            (scroll + height).min(lines),
+            lines
+        )
+    } else {
+        format!("Help · {}", help.context.label())
+    };
+    let help_widget = Paragraph::new(help_text)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .style(Style::default().fg(app.theme.text))
+        .wrap(Wrap { trim: false })
+        .scroll((scroll as u16, 0));
+
+    // Create a semi-transparent overlay effect
+    let overlay =
+        Block::default().style(Style::default().bg(app.theme.background).fg(app.theme.text));
+    f.render_widget(overlay, f.size());
 
     // Render the help dialog on top
-    f.render_widget(help, area);
+    f.render_widget(Clear, area);
+    f.render_widget(help_widget, area);
+    f.render_widget(
+        Scrollbar {
+            total: lines,
+            visible: height,
+            offset: scroll,
+            style: Style::default().fg(app.theme.accent),
+        },
+        area,
+    );
 }
 
 fn draw_search<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
     let area = centered_rect(60, 10, f.size());
 
     let search_text = format!("Search: {}", app.search_input);
-    let cursor_pos = search_text.len();
+    let cursor_pos = search_text.width();
 
+    let title = if app.search_input.is_empty() {
+        "Search Emails".to_string()
+    } else {
+        format!("Search Emails ({} shown)", app.filtered_emails.len())
+    };
     let search_input = Paragraph::new(search_text)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .style(Style::default().fg(app.theme.text));
+
+    // The list stays visible around the dialog, following the query as it is typed
+    f.render_widget(Clear, area);
+    f.render_widget(search_input, area);
+
+    // Show cursor at the end of input
+    f.set_cursor(
+        area.x + cursor_pos as u16 + 1, // +1 for the border
+        area.y + 1,
+    );
+}
+
+fn draw_address_menu<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(email) = app.selected_email() else {
+        return;
+    };
+    let area = centered_rect(50, 40, f.size());
+
+    let mut lines: Vec<Spans> = email
+        .addresses()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, address)| {
+            let blocked = if app.state.is_blocked(address) {
+                " (blocked)"
+            } else {
+                ""
+            };
+            if idx == app.address_menu_index {
+                Spans::from(Span::styled(
+                    format!(">> {}{}", address, blocked),
+                    Style::default()
+                        .bg(app.theme.selection)
+                        .add_modifier(Modifier::BOLD),
+                ))
+            } else {
+                Spans::from(format!("   {}{}", address, blocked))
+            }
+        })
+        .collect();
+
+    lines.push(Spans::from(""));
+    for action in AddressAction::ALL {
+        lines.push(Spans::from(vec![
+            Span::styled(
+                action.key().to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!(" - {}", action.label())),
+        ]));
+    }
+
+    let menu = Paragraph::new(lines)
         .block(
             Block::default()
-                .title("Search Emails")
+                .title("Address Actions")
                 .borders(Borders::ALL),
         )
-        .style(Style::default().fg(Color::White));
+        .style(Style::default().fg(app.theme.text));
 
-    // Create a semi-transparent overlay effect
-    let overlay = Block::default().style(Style::default().bg(Color::Black).fg(Color::White));
-    f.render_widget(overlay, f.size());
+    f.render_widget(Clear, area);
+    f.render_widget(menu, area);
+}
 
-    // Render the search dialog on top
-    f.render_widget(search_input, area);
+fn draw_links<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let area = centered_rect(70, 50, f.size());
+    let items: Vec<ListItem> = app
+        .links
+        .iter()
+        .enumerate()
+        .map(|(idx, link)| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{:>2}. ", idx + 1),
+                    Style::default().fg(app.theme.muted),
+                ),
+                Span::raw(link.as_str()),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("Links ({})", app.links.len()))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.selection)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default();
+    state.select(Some(app.links_index));
 
-    // Show cursor at the end of input
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_searches<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let area = centered_rect(70, 50, f.size());
+    let items: Vec<ListItem> = app
+        .searches
+        .iter()
+        .enumerate()
+        .map(|(idx, search)| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{:>2}. ", idx + 1),
+                    Style::default().fg(app.theme.muted),
+                ),
+                Span::raw(search.name.as_str()),
+                Span::styled(
+                    format!("  {}", search.query),
+                    Style::default().fg(app.theme.muted),
+                ),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("Saved searches ({})", app.searches.len()))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.selection)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default();
+    state.select(Some(app.searches_index));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_confirmation<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(confirmation) = &app.confirmation else {
+        return;
+    };
+    let area = centered_rect(50, 25, f.size());
+
+    let mut lines: Vec<Spans> = confirmation
+        .prompt
+        .lines()
+        .map(|line| {
+            Spans::from(Span::styled(
+                line,
+                Style::default().add_modifier(Modifier::BOLD),
+            ))
+        })
+        .collect();
+    lines.push(Spans::from(""));
+    for (key, label) in confirmation.action.options() {
+        lines.push(Spans::from(vec![
+            Span::styled(
+                key.to_string(),
+                Style::default()
+                    .fg(app.theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!(" - {}", label)),
+        ]));
+    }
+
+    let dialog = Paragraph::new(lines)
+        .block(Block::default().title("Confirm").borders(Borders::ALL))
+        .style(Style::default().fg(app.theme.text))
+        .wrap(Wrap { trim: true });
+
+    f.render_widget(Clear, area);
+    f.render_widget(dialog, area);
+}
+
+fn draw_sync_health<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let area = centered_rect(60, 40, f.size());
+
+    let mut lines = Vec::new();
+    for (idx, failure) in app.sync_failures.iter().enumerate() {
+        let style = if idx == app.sync_health_index {
+            Style::default()
+                .bg(app.theme.selection)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().add_modifier(Modifier::BOLD)
+        };
+        let marker = if idx == app.sync_health_index {
+            ">> "
+        } else {
+            "   "
+        };
+        lines.push(Spans::from(Span::styled(
+            format!("{}{}", marker, failure.account),
+            style,
+        )));
+        lines.push(Spans::from(Span::styled(
+            format!("   {}", failure.error),
+            Style::default().fg(app.theme.error),
+        )));
+    }
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        "Enter/r - Retry selected account | Esc - Close",
+        Style::default().fg(app.theme.warning),
+    )));
+
+    let popup = Paragraph::new(lines)
+        .block(Block::default().title("Sync Health").borders(Borders::ALL))
+        .style(Style::default().fg(app.theme.text))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_sync_error<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(error) = &app.sync_error else {
+        return;
+    };
+    let area = centered_rect(60, 30, f.size());
+
+    let cached = match app.emails.len() {
+        0 => "No cached emails to work with offline".to_string(),
+        count => format!("{} cached emails can still be read offline", count),
+    };
+    let lines = vec![
+        Spans::from(Span::styled(
+            error.as_str(),
+            Style::default().fg(app.theme.error),
+        )),
+        Spans::from(""),
+        Spans::from(cached),
+        Spans::from(""),
+        Spans::from(Span::styled(
+            "R - Retry | o - Work offline | Esc - Close",
+            Style::default().fg(app.theme.warning),
+        )),
+    ];
+
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Sync Failed")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme.error)),
+        )
+        .style(Style::default().fg(app.theme.text))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_folder_manager<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(manager) = &app.folder_manager else {
+        return;
+    };
+    let area = centered_rect(50, 50, f.size());
+    f.render_widget(Clear, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+        .split(area);
+
+    let items: Vec<ListItem> = manager
+        .folders
+        .iter()
+        .map(|folder| ListItem::new(folder.name.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("Folders - {}", manager.account))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.selection)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    let mut state = ListState::default();
+    state.select(Some(manager.selected));
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    let (title, text) = match &manager.edit {
+        Some(FolderEdit::Create) => ("New folder", manager.input.as_str()),
+        Some(FolderEdit::Rename(_)) => ("Rename folder", manager.input.as_str()),
+        None => ("Actions", "n new | r rename | d delete | Esc close"),
+    };
+    let prompt = Paragraph::new(text)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .style(Style::default().fg(app.theme.text));
+    f.render_widget(prompt, chunks[1]);
+
+    if manager.edit.is_some() {
+        f.set_cursor(
+            chunks[1].x + manager.input.width() as u16 + 1,
+            chunks[1].y + 1,
+        );
+    }
+}
+
+fn draw_recoverable_items<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(recoverable) = &app.recoverable else {
+        return;
+    };
+    let area = centered_rect(70, 60, f.size());
+    let now = Utc::now();
+
+    let items: Vec<ListItem> = if recoverable.items.is_empty() {
+        vec![ListItem::new("No recoverable items")]
+    } else {
+        recoverable
+            .items
+            .iter()
+            .map(|item| {
+                let remaining = item.purge_at - now;
+                let age_style = if remaining < Duration::days(2) {
+                    Style::default().fg(app.theme.error)
+                } else {
+                    Style::default().fg(app.theme.secondary)
+                };
+                ListItem::new(vec![
+                    Spans::from(vec![
+                        Span::styled(format!("{:>8} ", format_time_until(remaining)), age_style),
+                        Span::styled(
+                            item.email.subject.as_str(),
+                            Style::default().add_modifier(Modifier::BOLD),
+                        ),
+                    ]),
+                    Spans::from(format!(
+                        "         {} | deleted {}",
+                        item.email.sender,
+                        app.date_format.format(item.deleted_at)
+                    )),
+                ])
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    "Recoverable Items - {} (time until purge)",
+                    recoverable.account
+                ))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.selection)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default();
+    state.select(Some(recoverable.selected));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_tasks<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(task_list) = &app.tasks else {
+        return;
+    };
+    let area = centered_rect(70, 60, f.size());
+    let now = Utc::now();
+
+    let items: Vec<ListItem> = if task_list.tasks.is_empty() {
+        vec![ListItem::new("No tasks")]
+    } else {
+        task_list
+            .tasks
+            .iter()
+            .map(|task| {
+                let completed = task.status == TaskStatus::Completed;
+                let marker = if completed { "[x]" } else { "[ ]" };
+                let subject_style = if completed {
+                    Style::default()
+                        .fg(app.theme.muted)
+                        .add_modifier(Modifier::CROSSED_OUT)
+                } else {
+                    Style::default().add_modifier(Modifier::BOLD)
+                };
+                let (due, due_style) = match task.due {
+                    Some(due) if !completed && due < now => (
+                        format!(
+                            "overdue since {}",
+                            due.with_timezone(&Local).format("%Y-%m-%d")
+                        ),
+                        Style::default().fg(app.theme.error),
+                    ),
+                    Some(due) => (
+                        format!("due {}", due.with_timezone(&Local).format("%Y-%m-%d")),
+                        Style::default().fg(app.theme.secondary),
+                    ),
+                    None => (
+                        "no due date".to_string(),
+                        Style::default().fg(app.theme.muted),
+                    ),
+                };
+                ListItem::new(vec![
+                    Spans::from(vec![
+                        Span::raw(format!("{} ", marker)),
+                        Span::styled(task.subject.as_str(), subject_style),
+                    ]),
+                    Spans::from(vec![
+                        Span::raw(format!("    {} | ", tasks::status_label(task.status))),
+                        Span::styled(due, due_style),
+                    ]),
+                ])
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("Tasks - {}", task_list.account))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.selection)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default();
+    state.select(Some(task_list.selected));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_drafts<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(draft_list) = &app.drafts else {
+        return;
+    };
+    let area = centered_rect(70, 60, f.size());
+
+    let items: Vec<ListItem> = if draft_list.drafts.is_empty() {
+        vec![ListItem::new("No drafts")]
+    } else {
+        draft_list
+            .drafts
+            .iter()
+            .map(|draft| {
+                let subject = if draft.subject.is_empty() {
+                    "(no subject)"
+                } else {
+                    draft.subject.as_str()
+                };
+                let to = if draft.to.is_empty() {
+                    "no recipients yet".to_string()
+                } else {
+                    format!("to {}", draft.to.join(", "))
+                };
+                ListItem::new(vec![
+                    Spans::from(Span::styled(
+                        subject,
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Spans::from(Span::styled(
+                        format!(
+                            "    {} | saved {}",
+                            to,
+                            draft.date.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                        ),
+                        Style::default().fg(app.theme.muted),
+                    )),
+                ])
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("Drafts - {}", draft_list.account))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.selection)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default();
+    state.select(Some(draft_list.selected));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_headers<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(email) = app.selected_email() else {
+        return;
+    };
+    let area = centered_rect(80, 70, f.size());
+
+    let lines: Vec<Spans> = match &email.headers {
+        Some(headers) => headers
+            .iter()
+            .map(|header| {
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{}: ", header.name),
+                        Style::default().fg(app.theme.label),
+                    ),
+                    Span::raw(header.value.as_str()),
+                ])
+            })
+            .collect(),
+        None => vec![Spans::from(format!(
+            "{} Loading headers...",
+            spinner_frame()
+        ))],
+    };
+
+    let popup = Paragraph::new(lines)
+        .block(Block::default().title("Headers").borders(Borders::ALL))
+        .wrap(Wrap { trim: false })
+        .scroll((app.headers_scroll, 0));
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_command<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let area = centered_rect(60, 10, f.size());
+
+    let text = format!(":{}", app.command_input);
+    let cursor_pos = text.width();
+    let prompt = Paragraph::new(text)
+        .block(Block::default().title("Command").borders(Borders::ALL))
+        .style(Style::default().fg(app.theme.text));
+
+    f.render_widget(Clear, area);
+    f.render_widget(prompt, area);
+    f.set_cursor(area.x + cursor_pos as u16 + 1, area.y + 1);
+}
+
+fn draw_find<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let area = centered_rect(60, 10, f.size());
+
+    let input = app.find.as_ref().map_or("", |find| find.input.as_str());
+    let text = format!("/{}", input);
+    let cursor_pos = text.width();
+    let prompt = Paragraph::new(text)
+        .block(
+            Block::default()
+                .title("Find in this email")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(app.theme.text));
+
+    f.render_widget(Clear, area);
+    f.render_widget(prompt, area);
+    f.set_cursor(area.x + cursor_pos as u16 + 1, area.y + 1);
+}
+
+/// Requests, errors and bytes per account and request kind since the app started.
+fn draw_metrics<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let area = centered_rect(80, 70, f.size());
+    let header_style = Style::default().add_modifier(Modifier::BOLD);
+
+    let mut lines = Vec::new();
+    for account in app.accounts.iter() {
+        let metrics = account.client.metrics();
+        let requests: u64 = metrics.values().map(|stats| stats.requests).sum();
+        let received: u64 = metrics.values().map(|stats| stats.bytes_received).sum();
+        let sent: u64 = metrics.values().map(|stats| stats.bytes_sent).sum();
+        lines.push(Spans::from(Span::styled(
+            format!(
+                "{}: {} requests, {} received, {} sent",
+                account.name,
+                requests,
+                format_size(received),
+                format_size(sent)
+            ),
+            Style::default().fg(app.theme.label),
+        )));
+        if metrics.is_empty() {
+            lines.push(Spans::from("  No requests yet"));
+        } else {
+            lines.push(Spans::from(Span::styled(
+                format!(
+                    "  {:<20} {:>8} {:>12} {:>10} {:>10} {:>8}",
+                    "Request", "Count", "Errors", "Received", "Sent", "Avg"
+                ),
+                header_style,
+            )));
+        }
+        for (kind, stats) in &metrics {
+            let row = format!(
+                "  {:<20} {:>8} {:>12} {:>10} {:>10} {:>6}ms",
+                kind,
+                stats.requests,
+                format!("{} ({:.0}%)", stats.errors, stats.error_rate() * 100.0),
+                format_size(stats.bytes_received),
+                format_size(stats.bytes_sent),
+                stats.average_time().as_millis()
+            );
+            let style = if stats.errors > 0 {
+                Style::default().fg(app.theme.error)
+            } else {
+                Style::default()
+            };
+            lines.push(Spans::from(Span::styled(row, style)));
+        }
+        lines.push(Spans::from(""));
+    }
+
+    let popup = Paragraph::new(lines)
+        .block(Block::default().title("Metrics").borders(Borders::ALL))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_outbox<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(view) = &app.outbox_view else {
+        return;
+    };
+    let area = centered_rect(70, 60, f.size());
+
+    let items: Vec<ListItem> = if app.outbox.entries.is_empty() {
+        vec![ListItem::new("The outbox is empty")]
+    } else {
+        app.outbox
+            .entries
+            .iter()
+            .map(|entry| {
+                let state_style = match entry.state {
+                    SendState::Queued => Style::default().fg(app.theme.secondary),
+                    SendState::Sending => Style::default().fg(app.theme.warning),
+                    SendState::Failed { .. } => Style::default().fg(app.theme.error),
+                };
+                let mut lines = vec![Spans::from(vec![
+                    Span::styled(format!("[{}] ", entry.state.label()), state_style),
+                    Span::styled(
+                        entry.message.subject.as_str(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                ])];
+                lines.push(Spans::from(format!(
+                    "    to {} | from {} | queued {}",
+                    entry.message.to.join(", "),
+                    entry.account,
+                    entry
+                        .queued_at
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                )));
+                if let SendState::Failed { error } = &entry.state {
+                    lines.push(Spans::from(Span::styled(
+                        format!("    {}", error),
+                        Style::default().fg(app.theme.error),
+                    )));
+                }
+                ListItem::new(lines)
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(Block::default().title("Outbox").borders(Borders::ALL))
+        .highlight_style(
+            Style::default()
+                .bg(app.theme.selection)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default();
+    state.select(Some(view.selected));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_outbox_edit<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(edit) = app.outbox_view.as_ref().and_then(|view| view.edit.as_ref()) else {
+        return;
+    };
+    let area = centered_rect(70, 60, f.size());
+    draw_message_form(
+        f,
+        &app.theme,
+        area,
+        "Edit Before Sending".to_string(),
+        &edit.form,
+        attachment_labels(edit.attachments(), edit.files()),
+        Vec::new(),
+    );
+}
+
+fn draw_compose<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(compose) = &app.composing else {
+        return;
+    };
+    let area = centered_rect(80, 80, f.size());
+    let title = format!("{} - {}", compose.kind.title(), compose.account);
+    let dim = Style::default().fg(app.theme.muted);
+    let signature = match &compose.signature {
+        Some(signature) if compose.with_signature => std::iter::once("-- ")
+            .chain(signature.lines())
+            .map(|line| Spans::from(Span::styled(line.to_string(), dim)))
+            .collect(),
+        Some(_) => vec![Spans::from(Span::styled(
+            "(signature left out, Ctrl-T adds it back)",
+            dim,
+        ))],
+        None => Vec::new(),
+    };
+    draw_message_form(
+        f,
+        &app.theme,
+        area,
+        title,
+        &compose.form,
+        attachment_labels(&compose.attachments, &compose.files),
+        signature,
+    );
+    draw_completions(f, &app.theme, area, compose);
+    draw_attach_prompt(f, area, compose);
+}
+
+/// Draws the addresses offered for the one being typed, just below it.
+fn draw_completions<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    form_area: Rect,
+    compose: &Compose,
+) {
+    let form = &compose.form;
+    let Some(prefix) = form.address_prefix() else {
+        return;
+    };
+    if compose.completions.is_empty() {
+        return;
+    }
+    let row = MessageField::ALL
+        .iter()
+        .position(|field| *field == form.field)
+        .unwrap_or(0) as u16;
+    let column = format!("{}: ", form.field.label()).width() + form.text(form.field).width()
+        - prefix.width();
+    let width = compose
+        .completions
+        .iter()
+        .map(|address| address.width() as u16 + 2)
+        .max()
+        .unwrap_or(0)
+        .max(20);
+    let x = form_area.x + 1 + column as u16;
+    let y = form_area.y + 2 + row;
+    let area = Rect::new(
+        x.min(form_area.right().saturating_sub(width)),
+        y,
+        width.min(form_area.width),
+        (compose.completions.len() as u16 + 2).min(form_area.bottom().saturating_sub(y)),
+    );
+
+    let items: Vec<ListItem> = compose
+        .completions
+        .iter()
+        .map(|address| ListItem::new(address.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(
+            Style::default()
+                .bg(theme.selection)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default();
+    state.select(Some(compose.completion_index));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// What a message sends along, e.g. "report.pdf (24 KB)" for an attachment
+/// and "notes.txt (1.1 MB)" for a local file, whose size is read now.
+fn attachment_labels(attachments: &[Attachment], files: &[PathBuf]) -> Vec<String> {
+    let forwarded = attachments
+        .iter()
+        .map(|a| format!("{} ({})", a.name, format_size(a.size)));
+    let local = files.iter().map(|path| {
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into(),
+        );
+        match std::fs::metadata(path) {
+            Ok(metadata) => format!("{} ({})", name, format_size(metadata.len())),
+            Err(_) => format!("{} (missing)", name),
+        }
+    });
+    forwarded.chain(local).collect()
+}
+
+/// Draws the path being typed to attach a file over the bottom of the form.
+fn draw_attach_prompt<B: Backend>(f: &mut Frame<B>, form_area: Rect, compose: &Compose) {
+    let Some(path) = &compose.attach_prompt else {
+        return;
+    };
+    let area = Rect::new(
+        form_area.x + 2,
+        form_area.bottom().saturating_sub(4),
+        form_area.width.saturating_sub(4),
+        3.min(form_area.height),
+    );
+    let prompt = Paragraph::new(path.as_str()).block(
+        Block::default()
+            .title("Attach file | Tab completes | Enter adds | Esc cancels")
+            .borders(Borders::ALL),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(prompt, area);
+    f.set_cursor(area.x + 1 + path.width() as u16, area.y + 1);
+}
+
+/// Draws the fields of a message being written, with the cursor in the focused one,
+/// the attachments it sends along above the body and `footer` below it.
+fn draw_message_form<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    area: Rect,
+    title: String,
+    form: &MessageForm,
+    attachments: Vec<String>,
+    footer: Vec<Spans>,
+) {
+    let mut lines = Vec::new();
+    let mut cursor = None;
+    for field in MessageField::ALL {
+        let focused = field == form.field;
+        let label_style = if focused {
+            Style::default()
+                .fg(theme.focus)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.label)
+        };
+        let label = format!("{}: ", field.label());
+        if field == MessageField::Body {
+            if !attachments.is_empty() {
+                lines.push(Spans::from(vec![
+                    Span::styled("Attachments: ", Style::default().fg(theme.label)),
+                    Span::raw(attachments.join(", ")),
+                ]));
+            }
+            lines.push(Spans::from(Span::styled(label, label_style)));
+            let body: Vec<&str> = form.body.split('\n').collect();
+            if focused {
+                let last = body.last().copied().unwrap_or_default();
+                cursor = Some((last.width() as u16, (lines.len() + body.len() - 1) as u16));
+            }
+            lines.extend(body.into_iter().map(Spans::from));
+        } else {
+            let value = form.text(field);
+            if focused {
+                cursor = Some(((label.width() + value.width()) as u16, lines.len() as u16));
+            }
+            lines.push(Spans::from(vec![
+                Span::styled(label, label_style),
+                Span::raw(value),
+            ]));
+        }
+    }
+    if !footer.is_empty() {
+        lines.push(Spans::from(""));
+        lines.extend(footer);
+    }
+
+    let popup = Paragraph::new(lines)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .style(Style::default().fg(theme.text));
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+    if let Some((x, y)) = cursor {
+        f.set_cursor(area.x + x + 1, area.y + y + 1);
+    }
+}
+
+/// `spans` cut or padded with spaces to `width` columns, for the columns of
+/// list rows.
+fn fit_spans(spans: Vec<Span>, width: usize) -> Vec<Span> {
+    let mut fitted = Vec::new();
+    let mut left = width;
+    for span in spans {
+        if left == 0 {
+            break;
+        }
+        let span_width = span.content.width();
+        if span_width <= left {
+            left -= span_width;
+            fitted.push(span);
+        } else {
+            let cut = width::truncate(&span.content, left);
+            left -= cut.width();
+            fitted.push(Span::styled(cut, span.style));
+            break;
+        }
+    }
+    if left > 0 {
+        fitted.push(Span::raw(" ".repeat(left)));
+    }
+    fitted
+}
+
+/// Formats a size in bytes for people, e.g. "512 B", "24 KB" or "1.1 MB".
+/// Cuts `line` off after `MAX_LINE_CHARS` characters.
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// The startup stages done, running and to come, e.g.
+/// `config ✓ → cache ✓ → ⠋ sign-in → sync | Esc to cancel`.
+fn startup_status<T: EmailClient>(app: &App<T>, current: Startup) -> String {
+    let mut stages = vec!["config ✓".to_string()];
+    for stage in Startup::ALL {
+        stages.push(match stage.cmp(&current) {
+            Ordering::Less => format!("{} ✓", stage.label()),
+            Ordering::Equal => match sync_progress(app).filter(|_| stage == Startup::Sync) {
+                Some(progress) => format!("{} {} {}", spinner_frame(), stage.label(), progress),
+                None => format!("{} {}", spinner_frame(), stage.label()),
+            },
+            Ordering::Greater => stage.label().to_string(),
+        });
+    }
+    let mut status = stages.join(" → ");
+    if current >= Startup::SignIn {
+        status.push_str(" | Esc to cancel");
+    }
+    status
+}
+
+/// Pages and messages fetched by the syncs in flight, with a bar once the totals
+/// are known, e.g. `[████░░░░░░] 2/5 pages, 200/480 messages`.
+fn sync_progress<T: EmailClient>(app: &App<T>) -> Option<String> {
+    if app.sync_progress.is_empty() {
+        return None;
+    }
+    let reports = app.sync_progress.iter().map(|(_, progress)| progress);
+    let pages: usize = reports.clone().map(|p| p.pages).sum();
+    let messages: usize = reports.clone().map(|p| p.messages).sum();
+    let total_pages: Option<usize> = reports.clone().map(|p| p.total_pages).sum();
+    let total_messages: Option<usize> = reports.map(|p| p.total_messages).sum();
+
+    Some(match (total_pages, total_messages) {
+        (Some(total_pages), Some(total_messages)) => format!(
+            "{} {}/{} pages, {}/{} messages",
+            progress_bar(messages, total_messages),
+            pages,
+            total_pages,
+            messages,
+            total_messages
+        ),
+        _ => format!("{} pages, {} messages", pages, messages),
+    })
+}
+
+fn progress_bar(done: usize, total: usize) -> String {
+    const WIDTH: usize = 10;
+    let filled = (done * WIDTH)
+        .checked_div(total)
+        .unwrap_or(WIDTH)
+        .min(WIDTH);
+    format!("[{}{}]", "█".repeat(filled), "░".repeat(WIDTH - filled))
+}
+
+fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    if bytes < KB {
+        format!("{} B", bytes)
+    } else if bytes < MB {
+        format!("{} KB", bytes / KB)
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    }
+}
+
+/// Formats a remaining duration compactly, e.g. "13d 4h", "5h" or "<1h".
+fn format_time_until(remaining: Duration) -> String {
+    let hours = remaining.num_hours();
+    if hours < 1 {
+        "<1h".to_string()
+    } else if hours < 24 {
+        format!("{}h", hours)
+    } else {
+        format!("{}d {}h", hours / 24, hours % 24)
+    }
+}
+
+fn draw_whats_new<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let area = centered_rect(60, 60, f.size());
+
+    let mut lines = Vec::new();
+    for release in &app.whats_new {
+        lines.push(Spans::from(Span::styled(
+            format!("Version {}", release.version),
+            Style::default()
+                .fg(app.theme.label)
+                .add_modifier(Modifier::BOLD),
+        )));
+        for note in &release.notes {
+            lines.push(Spans::from(format!("  • {}", note)));
+        }
+        lines.push(Spans::from(""));
+    }
+    lines.push(Spans::from(Span::styled(
+        "Press any key to continue",
+        Style::default().fg(app.theme.warning),
+    )));
+
+    let popup = Paragraph::new(lines)
+        .block(Block::default().title("What's New").borders(Borders::ALL))
+        .style(Style::default().fg(app.theme.text))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_out_of_office<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(form) = &app.oof_form else {
+        return;
+    };
+    let area = centered_rect(60, 50, f.size());
+
+    let mut lines = Vec::new();
+    let mut cursor = None;
+    for field in OofField::ALL {
+        let focused = field == form.field;
+        let label_style = if focused {
+            Style::default()
+                .fg(app.theme.focus)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(app.theme.label)
+        };
+        let value = match form.text(field) {
+            Some(text) => text.to_string(),
+            None => format!("< {} >", out_of_office::state_label(form.state)),
+        };
+        let label = format!("{}: ", field.label());
+        if focused && form.text(field).is_some() {
+            cursor = Some(((label.width() + value.width()) as u16, lines.len() as u16));
+        }
+        lines.push(Spans::from(vec![
+            Span::styled(label, label_style),
+            Span::raw(value),
+        ]));
+    }
+
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!("Automatic Replies - {}", form.account))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(app.theme.text));
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+    if let Some((x, y)) = cursor {
+        f.set_cursor(area.x + x + 1, area.y + y + 1);
+    }
+}
+
+fn draw_directory<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(directory) = &app.directory else {
+        return;
+    };
+    let area = centered_rect(60, 50, f.size());
+
+    let label = "Name or address: ";
+    let mut lines = vec![
+        Spans::from(vec![
+            Span::styled(label, Style::default().fg(app.theme.label)),
+            Span::raw(directory.query.as_str()),
+        ]),
+        Spans::from(""),
+    ];
+    if directory.results.is_empty() && directory.searched {
+        lines.push(Spans::from("No matches in the directory"));
+    }
+    for (idx, entry) in directory.results.iter().enumerate() {
+        let details: Vec<&str> = [entry.job_title.as_str(), entry.department.as_str()]
+            .into_iter()
+            .filter(|detail| !detail.is_empty())
+            .collect();
+        let line = format!("{} <{}>", entry.name, entry.email);
+        let mut spans = if idx == directory.selected {
+            vec![Span::styled(
+                format!(">> {}", line),
+                Style::default()
+                    .bg(app.theme.selection)
+                    .add_modifier(Modifier::BOLD),
+            )]
+        } else {
+            vec![Span::raw(format!("   {}", line))]
+        };
+        if !details.is_empty() {
+            spans.push(Span::styled(
+                format!("  {}", details.join(", ")),
+                Style::default().fg(app.theme.secondary),
+            ));
+        }
+        lines.push(Spans::from(spans));
+    }
+
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!("Directory - {}", directory.account))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(app.theme.text));
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
     f.set_cursor(
-        area.x + cursor_pos as u16 + 1, // +1 for the border
+        area.x + 1 + (label.width() + directory.query.width()) as u16,
         area.y + 1,
     );
 }
 
+fn draw_move_picker<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(picker) = &app.move_picker else {
+        return;
+    };
+    let area = centered_rect(50, 50, f.size());
+
+    let label = "Folder: ";
+    let mut lines = vec![
+        Spans::from(vec![
+            Span::styled(label, Style::default().fg(app.theme.label)),
+            Span::raw(picker.query.as_str()),
+        ]),
+        Spans::from(""),
+    ];
+    let matches = picker.matches();
+    if matches.is_empty() {
+        lines.push(Spans::from("No matching folders"));
+    }
+    for (idx, folder) in matches.iter().enumerate() {
+        lines.push(Spans::from(if idx == picker.selected {
+            Span::styled(
+                format!(">> {}", folder.name),
+                Style::default()
+                    .bg(app.theme.selection)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            Span::raw(format!("   {}", folder.name))
+        }));
+    }
+
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!("Move to - {}", picker.account))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(app.theme.text));
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+    f.set_cursor(
+        area.x + 1 + (label.width() + picker.query.width()) as u16,
+        area.y + 1,
+    );
+}
+
+fn draw_search_history<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(picker) = &app.history_picker else {
+        return;
+    };
+    let area = centered_rect(60, 50, f.size());
+
+    let label = "History: ";
+    let mut lines = vec![
+        Spans::from(vec![
+            Span::styled(label, Style::default().fg(app.theme.label)),
+            Span::raw(picker.query.as_str()),
+        ]),
+        Spans::from(""),
+    ];
+    let matches = picker.matches();
+    if matches.is_empty() {
+        lines.push(Spans::from("No matching searches"));
+    }
+    for (idx, entry) in matches.iter().enumerate() {
+        lines.push(Spans::from(if idx == picker.selected {
+            Span::styled(
+                format!(">> {}", entry),
+                Style::default()
+                    .bg(app.theme.selection)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            Span::raw(format!("   {}", entry))
+        }));
+    }
+
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Past searches")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(app.theme.text));
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+    f.set_cursor(
+        area.x + 1 + (label.width() + picker.query.width()) as u16,
+        area.y + 1,
+    );
+}
+
+fn draw_date_range<B: Backend, T: EmailClient>(f: &mut Frame<B>, app: &App<T>) {
+    let Some(picker) = &app.date_picker else {
+        return;
+    };
+    let area = centered_rect(50, 40, f.size());
+
+    let mut lines = vec![
+        Spans::from(vec![
+            Span::styled("Fetching: ", Style::default().fg(app.theme.label)),
+            Span::raw(app.date_range.to_string()),
+        ]),
+        Spans::from(""),
+    ];
+    for (idx, preset) in Preset::ALL.iter().enumerate() {
+        lines.push(Spans::from(if idx == picker.selected {
+            Span::styled(
+                format!(">> {}", preset.label()),
+                Style::default()
+                    .bg(app.theme.selection)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            Span::raw(format!("   {}", preset.label()))
+        }));
+    }
+    let mut cursor = None;
+    if let Some(editing) = picker.field {
+        lines.push(Spans::from(""));
+        for (field, label, input) in [
+            (RangeField::Start, "From: ", &picker.start),
+            (RangeField::End, "To:   ", &picker.end),
+        ] {
+            let style = if field == editing {
+                cursor = Some((label.width() + input.width(), lines.len()));
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            lines.push(Spans::from(vec![
+                Span::styled(label, Style::default().fg(app.theme.label)),
+                Span::styled(input.as_str(), style),
+            ]));
+        }
+    }
+
+    let popup = Paragraph::new(lines)
+        .block(Block::default().title("Date range").borders(Borders::ALL))
+        .style(Style::default().fg(app.theme.text));
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+    if let Some((x, y)) = cursor {
+        f.set_cursor(area.x + 1 + x as u16, area.y + 1 + y as u16);
+    }
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
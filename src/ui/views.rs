@@ -0,0 +1,65 @@
+use std::cmp::Reverse;
+
+use crate::config::ViewSort;
+use crate::email::Email;
+use crate::query::Query;
+
+/// Where the emails shown instead of the inbox come from.
+#[derive(Clone, PartialEq)]
+pub enum ViewSource {
+    /// The view at this position in `[[views]]`
+    Configured(usize),
+    /// The folders of this name, picked in the sidebar
+    Folder(String),
+}
+
+/// The view being shown instead of the inbox, with its query parsed.
+#[derive(Clone)]
+pub struct ActiveView {
+    pub source: ViewSource,
+    pub query: Query,
+    pub sort: ViewSort,
+}
+
+/// Filters toggled with `F` that narrow whatever the list shows, on top of
+/// the search.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct QuickFilters {
+    pub unread: bool,
+    pub flagged: bool,
+    pub attachments: bool,
+}
+
+impl QuickFilters {
+    pub fn matches(&self, email: &Email) -> bool {
+        (!self.unread || !email.read)
+            && (!self.flagged || email.flagged)
+            && (!self.attachments || !email.attachments.is_empty())
+    }
+
+    /// The filters on, for the list title.
+    pub fn labels(&self) -> Vec<&'static str> {
+        [
+            (self.unread, "unread"),
+            (self.flagged, "flagged"),
+            (self.attachments, "attachments"),
+        ]
+        .into_iter()
+        .filter_map(|(on, label)| on.then_some(label))
+        .collect()
+    }
+}
+
+/// Orders `indices` into `emails` by `sort`.
+pub fn sort(sort: ViewSort, emails: &[Email], indices: &mut [usize]) {
+    match sort {
+        ViewSort::Newest => indices.sort_by_key(|&idx| Reverse(emails[idx].date)),
+        ViewSort::Oldest => indices.sort_by_key(|&idx| emails[idx].date),
+        ViewSort::Sender => indices.sort_by_key(|&idx| emails[idx].sender.to_lowercase()),
+        ViewSort::Subject => indices.sort_by_key(|&idx| emails[idx].thread_subject()),
+        ViewSort::Size => indices.sort_by_key(|&idx| Reverse(emails[idx].size)),
+        ViewSort::Importance => {
+            indices.sort_by_key(|&idx| Reverse((emails[idx].importance, emails[idx].date)))
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// `text` cut to at most `width` columns of the terminal, ending in `…` when
+/// cut. CJK characters and most emoji take two columns.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut used = 0;
+    let mut cut = String::new();
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        // Leaving a column for the ellipsis
+        if used + char_width + 1 > width {
+            break;
+        }
+        used += char_width;
+        cut.push(c);
+    }
+    if width > 0 {
+        cut.push('…');
+    }
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wide_characters_take_two_columns() {
+        assert_eq!(truncate("report", 10), "report");
+        assert_eq!(truncate("quarterly report", 10), "quarterly…");
+        assert_eq!(truncate("会議の議事録", 7), "会議の…");
+        // A wide character that doesn't fit leaves the column unused
+        assert_eq!(truncate("会議の議事録", 6), "会議…");
+        assert_eq!(truncate("🎉🎉🎉 Launch", 6).width(), 5);
+    }
+}